@@ -0,0 +1,484 @@
+//! Aging curves by level and skill, with the delta method.
+//!
+//! Every player season is a value for each metric, with a weight (plate appearances, at bats, batted balls or fastballs). We
+//! match each player season with the same player's next season at the same level, and the change from one to the next is a delta
+//! at the player's age in the first season. The delta at each age is the mean of those changes, weighted by the harmonic mean of
+//! the two weights, and chaining them together gives the curve. The curve is anchored at 0 at the player's peak, the best age for
+//! the metric, so every other age is how far off the peak it is.
+//!
+//! The bands are 95%, from the standard errors of the deltas added up along the chain from the peak.
+//!
+//! The delta method only sees players who played both seasons, and the players who make it to the next season are the ones who
+//! did well, partly by luck, so the curves come out too kind. There are two ways to correct for that:
+//!   * Regressed: each season is regressed to the mean for the level and season before it's compared, with the same number of
+//!     league average trials for every metric, so a lucky first season counts for less.
+//!   * Phantom: a player who doesn't play anywhere the next season gets a phantom season at replacement level, the 20th
+//!     percentile for the level and season, or their own value if they were already worse than that. Players who moved to
+//!     another level didn't wash out, promotions are mostly the best players, so they don't get one.
+//!
+//! The age for a player season is the mean of the age on every pitch, rounded down.
+
+use serde::{Serialize, Deserialize};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use crate::game::PitchResult;
+use crate::linear_weights::{BattingTotals, ratio};
+use crate::pitch_classification::canonical;
+use crate::play_by_play::{Event, PitchTypeCode};
+
+/// The weight a season needs before it's matched with another
+const MIN_PLATE_APPEARANCES: f64 = 50.0;
+const MIN_BATTED_BALLS: f64 = 25.0;
+const MIN_FASTBALLS: f64 = 100.0;
+/// Matched pairs an age needs before we trust its delta
+pub const MIN_PAIRS: u32 = 10;
+const REPLACEMENT_PERCENTILE: f64 = 0.2;
+const Z_95: f64 = 1.96;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum Role {
+    Batter,
+    Pitcher,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum AgingMetric {
+    StrikeoutRate,
+    WalkRate,
+    Iso,
+    ExitVelocity,
+    FastballVelocity,
+    FastballSpinRate,
+}
+
+impl AgingMetric {
+    /// The metrics we build curves for, for each role
+    pub const BATTER: [AgingMetric; 4] = [AgingMetric::StrikeoutRate, AgingMetric::WalkRate, AgingMetric::Iso, AgingMetric::ExitVelocity];
+    pub const PITCHER: [AgingMetric; 4] = [AgingMetric::StrikeoutRate, AgingMetric::WalkRate, AgingMetric::FastballVelocity, AgingMetric::FastballSpinRate];
+
+    pub fn higher_is_better (&self, role: Role) -> bool {
+        !matches!((self, role), (AgingMetric::StrikeoutRate, Role::Batter) | (AgingMetric::WalkRate, Role::Pitcher))
+    }
+
+    fn min_weight (&self) -> f64 {
+        match self {
+            AgingMetric::StrikeoutRate | AgingMetric::WalkRate | AgingMetric::Iso => MIN_PLATE_APPEARANCES,
+            AgingMetric::ExitVelocity => MIN_BATTED_BALLS,
+            AgingMetric::FastballVelocity | AgingMetric::FastballSpinRate => MIN_FASTBALLS,
+        }
+    }
+
+    /// League average trials added to each season for the regressed correction, roughly where each metric is half signal
+    fn regression_weight (&self) -> f64 {
+        match self {
+            AgingMetric::StrikeoutRate => 60.0,
+            AgingMetric::WalkRate => 120.0,
+            AgingMetric::Iso => 160.0,
+            AgingMetric::ExitVelocity => 50.0,
+            AgingMetric::FastballVelocity | AgingMetric::FastballSpinRate => 30.0,
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum SurvivorCorrection {
+    None,
+    Regressed,
+    Phantom,
+}
+
+/// The columns from the play by play output we need for aging curves.
+#[derive(Debug, Clone, Deserialize)]
+pub struct AgingPitch {
+    pub game_pk: u32,
+    pub num_plate_appearance: u8,
+    pub pitch_num_game: u16,
+    pub sport_id: u32,
+    pub game_year: u16,
+    pub batter: u32,
+    pub batter_age: Option<f32>,
+    pub pitcher: u32,
+    pub pitcher_age: Option<f32>,
+    pub pitch_result: PitchResult,
+    pub plate_appearance_result: Option<Event>,
+    pub pitch_type_code: Option<PitchTypeCode>,
+    pub pitch_type_inferred: Option<PitchTypeCode>,
+    pub pitch_speed_start: Option<f64>,
+    pub pitch_spin_rate: Option<f64>,
+    pub hit_data_exit_velocity: Option<f32>,
+}
+
+pub struct AgingData <'a> {
+    pub pitches: &'a [AgingPitch],
+    pub correction: SurvivorCorrection,
+}
+
+/// One age of a curve. The delta is the change from this age to the next, the curve is the distance from the peak at this age.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AgingCurve {
+    pub role: Role,
+    pub metric: AgingMetric,
+    pub sport_id: u32,
+    pub correction: SurvivorCorrection,
+    pub age: u8,
+    pub pairs: u32,
+    pub delta: Option<f32>,
+    pub delta_se: Option<f32>,
+    pub curve: f32,
+    pub curve_low: f32,
+    pub curve_high: f32,
+}
+
+/// A player's season: role, player, sport_id and season
+type SeasonKey = (Role, u32, u32, u16);
+/// Mean delta, its standard error and the pairs at an age
+type AgeDelta = (f64, f64, u32);
+/// Age, the delta at the age, the curve and its standard error
+type CurvePoint = (u8, Option<AgeDelta>, f64, f64);
+
+#[derive(Debug, Default, Clone)]
+struct PlayerSeason {
+    age_sum: f64,
+    age_n: u32,
+    batting: BattingTotals,
+    exit_velocity: (f64, u32),
+    fastball_velocity: (f64, u32),
+    fastball_spin_rate: (f64, u32),
+}
+
+impl PlayerSeason {
+    fn age (&self) -> Option<u8> {
+        if self.age_n == 0 {return None};
+        Some((self.age_sum / self.age_n as f64).floor() as u8)
+    }
+
+    /// (value, weight) for a metric
+    fn value (&self, metric: AgingMetric) -> Option<(f64, f64)> {
+        let b = &self.batting;
+        let (value, weight) = match metric {
+            AgingMetric::StrikeoutRate => (ratio(b.strikeouts as f64, b.plate_appearances as f64), b.plate_appearances as f64),
+            AgingMetric::WalkRate => (ratio(b.walks as f64, b.plate_appearances as f64), b.plate_appearances as f64),
+            AgingMetric::Iso => (ratio((b.doubles + 2 * b.triples + 3 * b.home_runs) as f64, b.at_bats as f64), b.at_bats as f64),
+            AgingMetric::ExitVelocity => (ratio(self.exit_velocity.0, self.exit_velocity.1 as f64), self.exit_velocity.1 as f64),
+            AgingMetric::FastballVelocity => (ratio(self.fastball_velocity.0, self.fastball_velocity.1 as f64), self.fastball_velocity.1 as f64),
+            AgingMetric::FastballSpinRate => (ratio(self.fastball_spin_rate.0, self.fastball_spin_rate.1 as f64), self.fastball_spin_rate.1 as f64),
+        };
+        if weight < metric.min_weight() {None} else {Some((value, weight))}
+    }
+}
+
+fn is_fastball (pitch: &AgingPitch) -> bool {
+    matches!(pitch.pitch_type_code.and_then(canonical).or(pitch.pitch_type_inferred), Some (PitchTypeCode::FF) | Some (PitchTypeCode::SI))
+}
+
+/// Weighted sum, sum of squares and weights of the deltas at an age
+#[derive(Debug, Default, Copy, Clone)]
+struct Deltas {
+    pairs: u32,
+    weight: f64,
+    weight_squared: f64,
+    sum: f64,
+    sum_squares: f64,
+}
+
+impl Deltas {
+    fn add (&mut self, delta: f64, weight: f64) {
+        self.pairs += 1;
+        self.weight += weight;
+        self.weight_squared += weight * weight;
+        self.sum += weight * delta;
+        self.sum_squares += weight * delta * delta;
+    }
+
+    fn mean (&self) -> f64 {
+        ratio(self.sum, self.weight)
+    }
+
+    /// Standard error with the effective sample size of the weights
+    fn se (&self) -> f64 {
+        let mean = self.mean();
+        let variance = (ratio(self.sum_squares, self.weight) - mean * mean).max(0.0);
+        let effective_n = ratio(self.weight * self.weight, self.weight_squared);
+        if effective_n <= 1.0 {return 0.0};
+        (variance / (effective_n - 1.0)).sqrt()
+    }
+}
+
+pub (crate) fn harmonic_mean (a: f64, b: f64) -> f64 {
+    ratio(2.0 * a * b, a + b)
+}
+
+/// Chains the deltas of the longest run of consecutive ages into a curve anchored at the peak
+pub (crate) fn chain (deltas: &BTreeMap<u8, AgeDelta>, higher_is_better: bool) -> Vec<CurvePoint> {
+    // The longest run of consecutive ages
+    let ages: Vec<u8> = deltas.keys().copied().collect();
+    let (mut best, mut start) = ((0, 0), 0);
+    for i in 0 .. ages.len() {
+        if i > 0 && ages[i] != ages[i - 1] + 1 {start = i};
+        if i + 1 - start > best.1 - best.0 {best = (start, i + 1)};
+    }
+    if best.1 == best.0 {return vec![]};
+    let run = &ages[best.0 .. best.1];
+
+    // Level and variance at each age from the first, with the age after the last too
+    let mut levels = vec![(run[0], 0.0, 0.0)];
+    for age in run {
+        let (delta, se, _) = deltas[age];
+        let (_, level, variance) = levels[levels.len() - 1];
+        levels.push((age + 1, level + delta, variance + se * se));
+    }
+
+    let peak = levels.iter()
+        .max_by(|a, b| {
+            let order = a.1.partial_cmp(&b.1).unwrap();
+            if higher_is_better {order} else {order.reverse()}
+        })
+        .copied()
+        .unwrap();
+
+    // The variance between two ages is the sum of the variances of the deltas between them
+    levels.iter()
+        .map(|(age, level, variance)| {
+            let se = (variance - peak.2).abs().sqrt();
+            (*age, deltas.get(age).copied(), level - peak.1, se)
+        })
+        .collect()
+}
+
+impl <'a> From<AgingData<'a>> for Vec<AgingCurve> {
+    fn from (data: AgingData) -> Vec<AgingCurve> {
+
+        // The last pitch of each plate appearance is the one the result happened on
+        let mut plate_appearances: HashMap<(u32, u8), &AgingPitch> = HashMap::new();
+        let mut seasons: HashMap<SeasonKey, PlayerSeason> = HashMap::new();
+
+        for pitch in data.pitches {
+            if pitch.pitch_result == PitchResult::NoPitch {continue};
+            let last = plate_appearances.entry((pitch.game_pk, pitch.num_plate_appearance)).or_insert(pitch);
+            if pitch.pitch_num_game > last.pitch_num_game {*last = pitch};
+
+            let batter = seasons.entry((Role::Batter, pitch.batter, pitch.sport_id, pitch.game_year)).or_default();
+            if let Some(age) = pitch.batter_age {
+                batter.age_sum += age as f64;
+                batter.age_n += 1;
+            }
+
+            let pitcher = seasons.entry((Role::Pitcher, pitch.pitcher, pitch.sport_id, pitch.game_year)).or_default();
+            if let Some(age) = pitch.pitcher_age {
+                pitcher.age_sum += age as f64;
+                pitcher.age_n += 1;
+            }
+            if is_fastball(pitch) {
+                if let Some(speed) = pitch.pitch_speed_start {
+                    pitcher.fastball_velocity.0 += speed;
+                    pitcher.fastball_velocity.1 += 1;
+                }
+                if let Some(spin_rate) = pitch.pitch_spin_rate {
+                    pitcher.fastball_spin_rate.0 += spin_rate;
+                    pitcher.fastball_spin_rate.1 += 1;
+                }
+            }
+        }
+
+        for pitch in plate_appearances.into_values() {
+            let event = match pitch.plate_appearance_result {
+                Some (event) => event,
+                None => continue,
+            };
+            for key in [(Role::Batter, pitch.batter, pitch.sport_id, pitch.game_year), (Role::Pitcher, pitch.pitcher, pitch.sport_id, pitch.game_year)] {
+                let season = seasons.entry(key).or_default();
+                season.batting.add(event);
+                if key.0 == Role::Batter {
+                    if let Some(exit_velocity) = pitch.hit_data_exit_velocity {
+                        season.exit_velocity.0 += exit_velocity as f64;
+                        season.exit_velocity.1 += 1;
+                    }
+                }
+            }
+        }
+
+        // Every season a player appeared in, at any level
+        let played: HashSet<(Role, u32, u16)> = seasons.keys().map(|(role, player, _, season)| (*role, *player, *season)).collect();
+
+        let mut curves = vec![];
+
+        for (role, metrics) in [(Role::Batter, AgingMetric::BATTER), (Role::Pitcher, AgingMetric::PITCHER)] {
+            for metric in metrics {
+                let higher_is_better = metric.higher_is_better(role);
+
+                // Values for the metric, with the level season means and replacement levels
+                let values: HashMap<(u32, u32, u16), (f64, f64, u8)> = seasons.iter()
+                    .filter(|((r, ..), _)| *r == role)
+                    .filter_map(|((_, player, sport_id, season), s)| {
+                        let (value, weight) = s.value(metric)?;
+                        Some(((*player, *sport_id, *season), (value, weight, s.age()?)))
+                    })
+                    .collect();
+
+                let mut levels: HashMap<(u32, u16), Vec<(f64, f64)>> = HashMap::new();
+                for ((_, sport_id, season), (value, weight, _)) in values.iter() {
+                    levels.entry((*sport_id, *season)).or_default().push((*value, *weight));
+                }
+                let levels: HashMap<(u32, u16), (f64, f64)> = levels.into_iter()
+                    .map(|(key, mut values)| {
+                        let weight: f64 = values.iter().map(|(_, w)| w).sum();
+                        let mean = ratio(values.iter().map(|(v, w)| v * w).sum(), weight);
+                        values.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap());
+                        let rank = if higher_is_better {REPLACEMENT_PERCENTILE} else {1.0 - REPLACEMENT_PERCENTILE};
+                        let replacement = values[((values.len() - 1) as f64 * rank).round() as usize].0;
+                        (key, (mean, replacement))
+                    })
+                    .collect();
+
+                let adjusted = |value: f64, weight: f64, sport_id: u32, season: u16| -> f64 {
+                    match (data.correction, levels.get(&(sport_id, season))) {
+                        (SurvivorCorrection::Regressed, Some ((mean, _))) => {
+                            let k = metric.regression_weight();
+                            (value * weight + mean * k) / (weight + k)
+                        },
+                        _ => value,
+                    }
+                };
+
+                let mut deltas: BTreeMap<(u32, u8), Deltas> = BTreeMap::new();
+                for ((player, sport_id, season), (value, weight, age)) in values.iter() {
+                    let before = adjusted(*value, *weight, *sport_id, *season);
+                    match values.get(&(*player, *sport_id, season + 1)) {
+                        Some ((next_value, next_weight, _)) => {
+                            let after = adjusted(*next_value, *next_weight, *sport_id, season + 1);
+                            deltas.entry((*sport_id, *age)).or_default().add(after - before, harmonic_mean(*weight, *next_weight));
+                        },
+                        None if data.correction == SurvivorCorrection::Phantom && !played.contains(&(role, *player, season + 1)) => {
+                            // Only seasons we've seen the next season of the level for, the last season didn't end anyone's career
+                            if let Some((_, replacement)) = levels.get(&(*sport_id, season + 1)) {
+                                let phantom = if higher_is_better {value.min(*replacement)} else {value.max(*replacement)};
+                                deltas.entry((*sport_id, *age)).or_default().add(phantom - value, *weight);
+                            }
+                        },
+                        None => {},
+                    }
+                }
+
+                let mut by_level: BTreeMap<u32, BTreeMap<u8, AgeDelta>> = BTreeMap::new();
+                for ((sport_id, age), d) in deltas {
+                    if d.pairs < MIN_PAIRS {continue};
+                    by_level.entry(sport_id).or_default().insert(age, (d.mean(), d.se(), d.pairs));
+                }
+
+                for (sport_id, deltas) in by_level {
+                    for (age, delta, curve, se) in chain(&deltas, higher_is_better) {
+                        curves.push(AgingCurve {
+                            role,
+                            metric,
+                            sport_id,
+                            correction: data.correction,
+                            age,
+                            pairs: delta.map(|d| d.2).unwrap_or(0),
+                            delta: delta.map(|d| d.0 as f32),
+                            delta_se: delta.map(|d| d.1 as f32),
+                            curve: curve as f32,
+                            curve_low: (curve - Z_95 * se) as f32,
+                            curve_high: (curve + Z_95 * se) as f32,
+                        });
+                    }
+                }
+            }
+        }
+
+        curves
+    }
+}
+
+/// The curves as a lookup, for projections and prospects
+#[derive(Debug, Clone, Default)]
+pub struct AgingCurves {
+    curves: HashMap<(Role, AgingMetric, u32), BTreeMap<u8, f64>>,
+}
+
+impl AgingCurves {
+    /// Only the curves with the given correction
+    pub fn new (curves: &[AgingCurve], correction: SurvivorCorrection) -> Self {
+        let mut lookup: HashMap<(Role, AgingMetric, u32), BTreeMap<u8, f64>> = HashMap::new();
+        for curve in curves.iter().filter(|curve| curve.correction == correction) {
+            lookup.entry((curve.role, curve.metric, curve.sport_id)).or_default().insert(curve.age, curve.curve as f64);
+        }
+        AgingCurves {curves: lookup}
+    }
+
+    /// How much the metric changes from one age to another, holding the ends of the curve flat past the ages we have
+    pub fn change (&self, role: Role, metric: AgingMetric, sport_id: u32, from: u8, to: u8) -> Option<f64> {
+        let curve = self.curves.get(&(role, metric, sport_id))?;
+        let at = |age: u8| -> Option<f64> {
+            let (first, last) = (curve.first_key_value()?, curve.last_key_value()?);
+            Some(*curve.get(&age.clamp(*first.0, *last.0))?)
+        };
+        Some(at(to)? - at(from)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn chain_peaks () {
+        // Up 2 a year to 27, then down 1 a year
+        let deltas: BTreeMap<u8, AgeDelta> = (22 .. 35)
+            .map(|age| (age, (if age < 27 {2.0} else {-1.0}, 0.1, 50)))
+            .collect();
+        let curve = chain(&deltas, true);
+        assert_eq!(curve.len(), 14);
+        let peak = curve.iter().find(|(_, _, level, _)| *level == 0.0).unwrap();
+        assert_eq!(peak.0, 27);
+        let (_, _, level, se) = curve.iter().find(|(age, ..)| *age == 22).unwrap();
+        assert!((level + 10.0).abs() < 1e-9);
+        assert!((se - (5.0 * 0.01f64).sqrt()).abs() < 1e-9);
+
+        let curves = AgingCurves {
+            curves: [((Role::Batter, AgingMetric::Iso, 1), curve.iter().map(|(age, _, level, _)| (*age, *level)).collect())].into_iter().collect(),
+        };
+        assert!((curves.change(Role::Batter, AgingMetric::Iso, 1, 30, 31).unwrap() + 1.0).abs() < 1e-9);
+        assert!((curves.change(Role::Batter, AgingMetric::Iso, 1, 40, 41).unwrap()).abs() < 1e-9);
+    }
+
+    /// A season of fly outs for a 25 year old batter, 26 the year after
+    fn season (game_pk: u32, batter: u32, sport_id: u32, game_year: u16) -> Vec<AgingPitch> {
+        (1 ..= MIN_PLATE_APPEARANCES as u8)
+            .map(|num_plate_appearance| AgingPitch {
+                game_pk,
+                num_plate_appearance,
+                pitch_num_game: num_plate_appearance as u16,
+                sport_id,
+                game_year,
+                batter,
+                batter_age: Some(25.5 + (game_year - 2020) as f32),
+                pitcher: 100,
+                pitcher_age: None,
+                pitch_result: PitchResult::InPlay,
+                plate_appearance_result: Some(Event::FlyOut),
+                pitch_type_code: None,
+                pitch_type_inferred: None,
+                pitch_speed_start: None,
+                pitch_spin_rate: None,
+                hit_data_exit_velocity: None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn phantoms_only_for_players_who_left () {
+        // MIN_PAIRS batters stay at level 11, batter 1 is promoted to level 1 and batter 2 doesn't play again
+        let mut seasons = vec![(1, 11, 2020), (1, 1, 2021), (2, 11, 2020)];
+        for batter in 3 .. 3 + MIN_PAIRS {
+            seasons.extend([(batter, 11, 2020), (batter, 11, 2021)]);
+        }
+        let pitches: Vec<AgingPitch> = seasons.iter().enumerate()
+            .flat_map(|(game_pk, (batter, sport_id, game_year))| season(game_pk as u32, *batter, *sport_id, *game_year))
+            .collect();
+        let curves: Vec<AgingCurve> = AgingData {pitches: &pitches, correction: SurvivorCorrection::Phantom}.into();
+
+        let curve = curves.iter()
+            .find(|c| c.role == Role::Batter && c.metric == AgingMetric::StrikeoutRate && c.sport_id == 11 && c.age == 25)
+            .unwrap();
+        assert_eq!(curve.pairs, MIN_PAIRS + 1);
+    }
+}
@@ -69,6 +69,36 @@ pub struct BoxScore {
   pub home_defense: Defense,
   pub away_defense: Defense,
 
+  // Team level totals, used to validate the game state we reconstruct from the play by play. These are defaulted
+  // so that boxscores cached before we tracked them still load.
+  #[serde(default)]
+  pub home_stats: TeamStats,
+  #[serde(default)]
+  pub away_stats: TeamStats,
+
+}
+
+/// Team level batting totals as reported by the boxscore.
+#[derive(Debug, Copy, Clone, Serialize, Deserialize, Default)]
+pub struct TeamStats {
+  pub runs: Option<u16>,
+  pub hits: Option<u16>,
+  pub walks: Option<u16>,
+  pub strikeouts: Option<u16>,
+}
+
+impl From<Option<TeamStatsDe>> for TeamStats {
+  fn from (stats: Option<TeamStatsDe>) -> TeamStats {
+    match stats {
+      Some (stats) => TeamStats {
+        runs: stats.batting.runs,
+        hits: stats.batting.hits,
+        walks: stats.batting.base_on_balls,
+        strikeouts: stats.batting.strike_outs,
+      },
+      None => TeamStats::default(),
+    }
+  }
 }
 
 #[derive(Debug, Copy, Clone, Serialize, Deserialize)]
@@ -236,6 +266,9 @@ impl From <BoxScoreDe> for BoxScore {
       None => (None, None),
     };
 
+    let home_stats: TeamStats = box_score.teams.home.team_stats.into();
+    let away_stats: TeamStats = box_score.teams.away.team_stats.into();
+
     BoxScore {
       game_weather_temp_f,
       game_weather_temp_c,
@@ -264,6 +297,8 @@ impl From <BoxScoreDe> for BoxScore {
       away_defense,
       home_players,
       away_players,
+      home_stats,
+      away_stats,
     }
   }
 }
@@ -388,6 +423,13 @@ pub (crate) struct TeamData {
   pub (crate) team: Team,
   // #[serde(rename="player")]
   pub (crate) players: Vec<PlayerID>,
+  #[serde(rename="teamStats")]
+  pub (crate) team_stats: Option<TeamStatsDe>,
+}
+
+#[derive(Deserialize, Debug)]
+pub (crate) struct TeamStatsDe {
+  pub (crate) batting: BattingStats,
 }
 
 fn player_id_to_player (players: Vec<PlayerID>) -> Vec<Player> {
@@ -412,6 +454,10 @@ pub struct Player {
   pub id: u32,
   pub position: Pos,
   pub batting_order: Option<u16>,
+  #[serde(default)]
+  pub batters_faced: Option<u16>,
+  #[serde(default)]
+  pub put_outs: Option<u16>,
 }
 
 impl From<PlayerID> for Player {
@@ -422,12 +468,15 @@ impl From<PlayerID> for Player {
       None => None,
     };
 
-    let sp: bool = match player.stats {
-      Some (stats) => match stats.pitching.games_started {
-        Some(games_started) => games_started == 1,
-        None => false,
+    let (sp, batters_faced, put_outs) = match player.stats {
+      Some (stats) => {
+        let sp = match stats.pitching.games_started {
+          Some(games_started) => games_started == 1,
+          None => false,
+        };
+        (sp, stats.pitching.batters_faced, stats.fielding.put_outs)
       },
-      None => false,     
+      None => (false, None, None),     
     };
 
     let pos = match player.all_positions {
@@ -452,6 +501,8 @@ impl From<PlayerID> for Player {
       id: player.person.id,
       position,
       batting_order,
+      batters_faced,
+      put_outs,
     }
 
   }
@@ -556,11 +607,31 @@ impl From<Pos> for String {
 #[serde(rename_all="camelCase")]
 pub (crate) struct Stats {
   pub (crate) pitching: PitchingStats,
+  #[serde(default)]
+  pub (crate) batting: BattingStats,
+  #[serde(default)]
+  pub (crate) fielding: FieldingStats,
 }
 
 #[derive(Deserialize, Debug)]
 #[serde(rename_all="camelCase")]
 pub (crate) struct PitchingStats {
   pub (crate) games_started: Option<u8>,
+  pub (crate) batters_faced: Option<u16>,
+}
+
+#[derive(Deserialize, Debug, Default)]
+#[serde(rename_all="camelCase")]
+pub (crate) struct BattingStats {
+  pub (crate) runs: Option<u16>,
+  pub (crate) hits: Option<u16>,
+  pub (crate) base_on_balls: Option<u16>,
+  pub (crate) strike_outs: Option<u16>,
+}
+
+#[derive(Deserialize, Debug, Default)]
+#[serde(rename_all="camelCase")]
+pub (crate) struct FieldingStats {
+  pub (crate) put_outs: Option<u16>,
 }
 
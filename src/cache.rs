@@ -13,7 +13,8 @@
 //! 
 //! Venues have 3 layers of data. First, there is the unchanging aspect of each venue, such as the location. Second, we have per-season data which will vary for dimension as well as capacity and
 //! possibly the venue name as well. Third, we have the SVG picture of the venue, from which we'll extract the (X,Y) coords of home plate. We'll also want to compute dimension from the play by play data
-//! so that we can show the home run fence distance at 10 different points (0 degrees, 10 degrees ..= 90 degrees).
+//! so that we can show the home run fence distance at 10 different points (0 degrees, 10 degrees ..= 90 degrees). The fences module traces the outfield wall
+//! from the SVG and refines it with the batted balls, and we cache a fence profile for each (venue, season) next to the (X,Y) coords.
 //! 
 //! ## Players
 //! Players have a "current" bio which is easy to query. They also have a lot of changes to their bio, specifically for their height and weight (mostly weight). Height and Weight are both good predictors of exit velo,
@@ -29,6 +30,28 @@ use crate::coaches;
 use crate::players;
 use crate::team;
 use crate::game;
+use crate::validation;
+use crate::data_quality;
+use crate::pitcher_appearance;
+use crate::events;
+use crate::run_expectancy;
+use crate::markov;
+use crate::linear_weights;
+use crate::win_expectancy;
+use crate::park_factors;
+use crate::expected_stats;
+use crate::pitch_classification;
+use crate::pitcher_arsenal;
+use crate::framing;
+use crate::fielding;
+use crate::nathan;
+use crate::tunneling;
+use crate::fences;
+use crate::calibration;
+use crate::aging;
+use crate::mle;
+use crate::projections;
+use crate::stabilization;
 // use crate::defense;
 use serde::Serialize;
 use serde::de::DeserializeOwned;
@@ -43,8 +66,46 @@ const BOXSCORE_JSON: &str = "\\boxscore.json";
 const COACH_JSON: &str = "\\coaches.json";
 const PLAYER_JSON: &str = "\\players.json";
 const TEAMS_JSON: &str = "\\teams.json";
+const RE_288_JSON: &str = "\\re_288.json";
+const RE_288_SIMULATED_JSON: &str = "\\re_288_simulated.json";
+const RE_24_SIMULATED_JSON: &str = "\\re_24_simulated.json";
+const LINEAR_WEIGHTS_JSON: &str = "\\linear_weights.json";
+const WIN_EXPECTANCY_JSON: &str = "\\win_expectancy.json";
+const EXPECTED_OUTCOMES_JSON: &str = "\\expected_outcomes.json";
+const PITCH_CLASSIFIERS_JSON: &str = "\\pitch_classifiers.json";
+const CALLED_STRIKE_JSON: &str = "\\called_strike.json";
+const FIELDING_JSON: &str = "\\fielding.json";
+const VENUE_WALL_POLYLINES_JSON: &str = "\\venue_wall_polylines.json";
+const FENCE_PROFILES_JSON: &str = "\\fence_profiles.json";
+const PIXEL_CALIBRATIONS_JSON: &str = "\\pixel_calibrations.json";
+const AGING_CURVES_JSON: &str = "\\aging_curves.json";
+const MLE_TRANSLATIONS_JSON: &str = "\\mle_translations.json";
 const PLAY_BY_PLAY: &str = r#"S:\OneDrive\Baseball\data\baseball.csv"#;
 const DEFENSE: &str = r#"S:\OneDrive\Baseball\data\defense.csv"#;
+const VALIDATION: &str = r#"S:\OneDrive\Baseball\data\validation.csv"#;
+const DATA_QUALITY: &str = r#"S:\OneDrive\Baseball\data\data_quality.csv"#;
+const QUARANTINE: &str = r#"S:\OneDrive\Baseball\data\quarantine.csv"#;
+const PITCHER_APPEARANCES: &str = r#"S:\OneDrive\Baseball\data\pitcher_appearances.csv"#;
+const EVENTS: &str = r#"S:\OneDrive\Baseball\data\events.csv"#;
+const PARK_FACTORS: &str = r#"S:\OneDrive\Baseball\data\park_factors.csv"#;
+const EXPECTED_STATS: &str = r#"S:\OneDrive\Baseball\data\expected_stats.csv"#;
+const PITCHER_ARSENALS: &str = r#"S:\OneDrive\Baseball\data\pitcher_arsenals.csv"#;
+const ARSENAL_CHANGES: &str = r#"S:\OneDrive\Baseball\data\arsenal_changes.csv"#;
+const CATCHER_FRAMING: &str = r#"S:\OneDrive\Baseball\data\catcher_framing.csv"#;
+const UMPIRE_GAMES: &str = r#"S:\OneDrive\Baseball\data\umpire_games.csv"#;
+const UMPIRE_SEASONS: &str = r#"S:\OneDrive\Baseball\data\umpire_seasons.csv"#;
+const FIELDING: &str = r#"S:\OneDrive\Baseball\data\fielding.csv"#;
+const PITCH_TRAJECTORIES: &str = r#"S:\OneDrive\Baseball\data\pitch_trajectories.csv"#;
+const TUNNEL_PAIRS: &str = r#"S:\OneDrive\Baseball\data\tunnel_pairs.csv"#;
+const PITCHER_TUNNELS: &str = r#"S:\OneDrive\Baseball\data\pitcher_tunnels.csv"#;
+const AGING_CURVES: &str = r#"S:\OneDrive\Baseball\data\aging_curves.csv"#;
+const MLE_FACTORS: &str = r#"S:\OneDrive\Baseball\data\mle_factors.csv"#;
+const MLE_TRANSLATIONS: &str = r#"S:\OneDrive\Baseball\data\mle_translations.csv"#;
+const MLE_LINES: &str = r#"S:\OneDrive\Baseball\data\mle_lines.csv"#;
+const PROJECTIONS: &str = r#"S:\OneDrive\Baseball\data\projections.csv"#;
+const PROJECTION_BACKTEST: &str = r#"S:\OneDrive\Baseball\data\projection_backtest.csv"#;
+const STABILIZATION: &str = r#"S:\OneDrive\Baseball\data\stabilization.csv"#;
+const SHRUNK_RATES: &str = r#"S:\OneDrive\Baseball\data\shrunk_rates.csv"#;
 
 fn cache_folder () -> String {
     format!("{}{}", std::env::current_dir().unwrap().display(), "\\cache" )
@@ -84,6 +145,45 @@ where T: DeserializeOwned,
 }
 
 
+/// Share of rows that can fail to deserialize before load_csv gives up on the file.
+const MAX_BAD_ROW_SHARE: f64 = 0.01;
+
+/// Reads one of our CSV outputs back in. Only the columns in T are deserialized, so we can pull a handful of columns out of
+/// the play by play without loading every Pitch. A few bad rows are skipped and logged, but if more than MAX_BAD_ROW_SHARE of the
+/// file doesn't fit T, something is wrong with the file (usually rows appended under an old schema) and we panic rather than
+/// quietly build on part of it.
+pub fn load_csv <T> (file_name: &str) -> Vec<T>
+where T: DeserializeOwned,
+{
+    let mut reader = match Reader::from_path(file_name) {
+        Ok (reader) => reader,
+        Err (_) => return vec![],
+    };
+
+    let mut rows: Vec<T> = vec![];
+    let mut bad_rows = 0;
+
+    for row in reader.deserialize() {
+        match row {
+            Ok (row) => rows.push(row),
+            Err (err) => {
+                if bad_rows < 5 {println!("Skipping bad row in {}: {}", file_name, err)};
+                bad_rows += 1;
+            },
+        }
+    }
+
+    if bad_rows > 0 {
+        let total = rows.len() + bad_rows;
+        println!("Skipped {} of {} rows in {}", bad_rows, total, file_name);
+        if bad_rows as f64 / total as f64 > MAX_BAD_ROW_SHARE {
+            panic!("Too many bad rows in {} ({} of {}), check that it was written with the current columns", file_name, bad_rows, total);
+        }
+    }
+
+    rows
+}
+
 /// Creates the cache folder if it doesn't exist.
 fn create_folder (path: String) {
     match std::fs::create_dir(path) {
@@ -92,30 +192,320 @@ fn create_folder (path: String) {
     }
 }
 
-pub (crate) fn append_play_by_play (pitches: &Vec<game::Pitch>) {
-
-    // Check if the file exists to determine if we need headers and if we should create a new file
+/// Appends the data to a CSV file, writing the headers only if we're creating the file. The large, flat output tables
+/// (pitch by pitch, discrepancy reports etc.) all go through here.
+///
+/// If the columns of T no longer match the header of the existing file, appending would leave rows that can't be read back.
+/// The game tables are checked and rotated before they're appended to, see rotate_game_tables, so we refuse to write here.
+pub fn append <T> (file_name: &str, data: &Vec<T>)
+where T: Serialize
+{
 
-    // let file_name = format!("{}\\{}", cache_folder(), PLAY_BY_PLAY);
+    if columns_changed(file_name, data) {
+        panic!("The columns in {} have changed, rotate the old file before appending", file_name);
+    }
 
-    let exists = match std::fs::File::open(PLAY_BY_PLAY) {
+    // Check if the file exists to determine if we need headers and if we should create a new file
+    let exists = match std::fs::File::open(file_name) {
         Err (_) => false,
         Ok (_) => true,
     };
 
     let file = match exists {
-        true => std::fs::OpenOptions::new().append(true).open(PLAY_BY_PLAY).unwrap(),
-        false => std::fs::OpenOptions::new().create(true).write(true).open(PLAY_BY_PLAY).unwrap()
+        true => std::fs::OpenOptions::new().append(true).open(file_name).unwrap(),
+        false => std::fs::OpenOptions::new().create(true).write(true).open(file_name).unwrap()
     };
 
     let mut csv_writer = WriterBuilder::new()
                             .has_headers(!exists)
                             .from_writer(file);
 
-    for pitch in pitches {
-        csv_writer.serialize(pitch).unwrap();
+    for row in data {
+        csv_writer.serialize(row).unwrap();
+    };
+
+}
+
+/// True if the file exists and was written with different columns than the data has.
+fn columns_changed <T> (file_name: &str, data: &[T]) -> bool
+where T: Serialize
+{
+    match data.first() {
+        Some (first) => existing_header(file_name).is_some_and(|header| header != csv_header(first)),
+        None => false,
+    }
+}
+
+/// The header line T would be written with.
+fn csv_header <T> (row: &T) -> String
+where T: Serialize
+{
+    let mut csv_writer = WriterBuilder::new()
+                            .has_headers(true)
+                            .from_writer(vec![]);
+    csv_writer.serialize(row).unwrap();
+    let bytes = csv_writer.into_inner().unwrap();
+
+    String::from_utf8_lossy(&bytes).lines().next().unwrap_or("").to_string()
+}
+
+/// The first line of the file, without the line ending.
+fn existing_header (file_name: &str) -> Option<String> {
+    use std::io::BufRead;
+
+    let file = std::fs::File::open(file_name).ok()?;
+    let mut line = String::new();
+    std::io::BufReader::new(file).read_line(&mut line).ok()?;
+
+    Some(line.trim_end_matches(['\r', '\n']).to_string())
+}
+
+/// Moves a CSV written under an old schema out of the way.
+fn rotate (file_name: &str) {
+    let secs = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0);
+    let rotated = match file_name.strip_suffix(".csv") {
+        Some (stem) => format!("{}_{}.csv", stem, secs),
+        None => format!("{}_{}", file_name, secs),
     };
 
+    std::fs::rename(file_name, &rotated).unwrap();
+    println!("Moved {} to {}.", file_name, rotated);
+}
+
+/// The tables written for every game we pull. They're appended to together, so they have to start over together.
+const GAME_TABLES: [&str; 6] = [PLAY_BY_PLAY, PITCHER_APPEARANCES, EVENTS, DATA_QUALITY, QUARANTINE, VALIDATION];
+
+/// True if any of the game tables were written with different columns than this run's rows.
+pub (crate) fn game_tables_changed (
+    pitches: &[game::Pitch],
+    appearances: &[pitcher_appearance::PitcherAppearance],
+    events: &[events::GameEvent],
+    records: &[data_quality::DataQualityRecord],
+    quarantined: &[game::Pitch],
+    discrepancies: &[validation::Discrepancy],
+) -> bool {
+    columns_changed(PLAY_BY_PLAY, pitches)
+        || columns_changed(PITCHER_APPEARANCES, appearances)
+        || columns_changed(EVENTS, events)
+        || columns_changed(DATA_QUALITY, records)
+        || columns_changed(QUARANTINE, quarantined)
+        || columns_changed(VALIDATION, discrepancies)
+}
+
+/// Moves every game table out of the way. The games in them have to be pulled again, so the caller has to clear them from
+/// games processed.
+pub (crate) fn rotate_game_tables () {
+    for file_name in GAME_TABLES {
+        if std::path::Path::new(file_name).exists() {rotate(file_name)};
+    }
+}
+
+/// Writes the data to a CSV file, replacing anything that was there. For the tables we rebuild from scratch, such as park factors.
+pub fn write <T> (file_name: &str, data: &Vec<T>)
+where T: Serialize
+{
+    let mut csv_writer = WriterBuilder::new()
+                            .has_headers(true)
+                            .from_path(file_name)
+                            .unwrap();
+
+    for row in data {
+        csv_writer.serialize(row).unwrap();
+    };
+
+}
+
+pub (crate) fn append_play_by_play (pitches: &Vec<game::Pitch>) {
+
+    // let file_name = format!("{}\\{}", cache_folder(), PLAY_BY_PLAY);
+    append (PLAY_BY_PLAY, pitches);
+
+}
+
+pub (crate) fn append_validation (discrepancies: &Vec<validation::Discrepancy>) {
+    append (VALIDATION, discrepancies);
+}
+
+pub (crate) fn append_data_quality (records: &Vec<data_quality::DataQualityRecord>) {
+    append (DATA_QUALITY, records);
+}
+
+/// Quarantined pitches have the same layout as the play by play, so they can be inspected side by side
+pub (crate) fn append_quarantine (pitches: &Vec<game::Pitch>) {
+    append (QUARANTINE, pitches);
+}
+
+pub (crate) fn append_pitcher_appearances (appearances: &Vec<pitcher_appearance::PitcherAppearance>) {
+    append (PITCHER_APPEARANCES, appearances);
+}
+
+pub (crate) fn append_events (events: &Vec<events::GameEvent>) {
+    append (EVENTS, events);
+}
+
+pub (crate) fn load_re_288_pitches () -> Vec<run_expectancy::RE288Pitch> {
+    load_csv (PLAY_BY_PLAY)
+}
+
+pub (crate) fn cache_re_288 (re_288: &Vec<run_expectancy::RE288Entry>) {
+    cache (RE_288_JSON, re_288.clone());
+}
+
+pub (crate) fn load_re_288 () -> Vec<run_expectancy::RE288Entry> {
+    load (RE_288_JSON)
+}
+
+pub (crate) fn cache_re_simulated (re: &markov::MarkovRunExpectancy) {
+    cache (RE_288_SIMULATED_JSON, re.re_288.clone());
+    cache (RE_24_SIMULATED_JSON, re.re_24.clone());
+}
+
+pub (crate) fn load_re_288_simulated () -> Vec<run_expectancy::RE288Entry> {
+    load (RE_288_SIMULATED_JSON)
+}
+
+pub (crate) fn load_re_24_simulated () -> Vec<markov::RE24Entry> {
+    load (RE_24_SIMULATED_JSON)
+}
+
+pub (crate) fn load_linear_weights_pitches () -> Vec<linear_weights::LinearWeightsPitch> {
+    load_csv (PLAY_BY_PLAY)
+}
+
+pub (crate) fn load_linear_weights_appearances () -> Vec<linear_weights::LinearWeightsAppearance> {
+    load_csv (PITCHER_APPEARANCES)
+}
+
+pub (crate) fn load_appearance_games () -> Vec<pitcher_appearance::AppearanceGame> {
+    load_csv (PITCHER_APPEARANCES)
+}
+
+pub (crate) fn cache_linear_weights (linear_weights: &Vec<linear_weights::LinearWeights>) {
+    cache (LINEAR_WEIGHTS_JSON, linear_weights.clone());
+}
+
+pub (crate) fn load_linear_weights () -> Vec<linear_weights::LinearWeights> {
+    load (LINEAR_WEIGHTS_JSON)
+}
+
+pub (crate) fn load_win_expectancy_pitches () -> Vec<win_expectancy::WinExpectancyPitch> {
+    load_csv (PLAY_BY_PLAY)
+}
+
+pub (crate) fn cache_win_expectancy (win_expectancy: &Vec<win_expectancy::WinExpectancyTable>) {
+    cache (WIN_EXPECTANCY_JSON, win_expectancy.clone());
+}
+
+pub (crate) fn load_win_expectancy () -> Vec<win_expectancy::WinExpectancyTable> {
+    load (WIN_EXPECTANCY_JSON)
+}
+
+pub (crate) fn load_park_factor_pitches () -> Vec<park_factors::ParkFactorPitch> {
+    load_csv (PLAY_BY_PLAY)
+}
+
+pub (crate) fn write_park_factors (park_factors: &Vec<park_factors::ParkFactor>) {
+    write (PARK_FACTORS, park_factors);
+}
+
+pub (crate) fn load_park_factors () -> Vec<park_factors::ParkFactor> {
+    load_csv (PARK_FACTORS)
+}
+
+pub (crate) fn load_expected_stats_pitches () -> Vec<expected_stats::ExpectedStatsPitch> {
+    load_csv (PLAY_BY_PLAY)
+}
+
+pub (crate) fn cache_expected_outcomes (bins: &Vec<expected_stats::ExpectedOutcomeBin>) {
+    cache (EXPECTED_OUTCOMES_JSON, bins.clone());
+}
+
+pub (crate) fn load_expected_outcomes () -> Vec<expected_stats::ExpectedOutcomeBin> {
+    load (EXPECTED_OUTCOMES_JSON)
+}
+
+pub (crate) fn write_expected_stats (players: &Vec<expected_stats::ExpectedStatsPlayer>) {
+    write (EXPECTED_STATS, players);
+}
+
+pub (crate) fn load_classification_pitches () -> Vec<pitch_classification::ClassificationPitch> {
+    load_csv (PLAY_BY_PLAY)
+}
+
+pub (crate) fn cache_pitch_classifiers (classifiers: &Vec<pitch_classification::PitchClassifier>) {
+    cache (PITCH_CLASSIFIERS_JSON, classifiers.clone());
+}
+
+pub (crate) fn load_pitch_classifiers () -> Vec<pitch_classification::PitchClassifier> {
+    load (PITCH_CLASSIFIERS_JSON)
+}
+
+pub (crate) fn load_arsenal_pitches () -> Vec<pitcher_arsenal::ArsenalPitch> {
+    load_csv (PLAY_BY_PLAY)
+}
+
+pub (crate) fn write_pitcher_arsenals (arsenals: &Vec<pitcher_arsenal::PitcherArsenal>) {
+    write (PITCHER_ARSENALS, arsenals);
+}
+
+pub (crate) fn write_arsenal_changes (changes: &Vec<pitcher_arsenal::ArsenalChange>) {
+    write (ARSENAL_CHANGES, changes);
+}
+
+pub (crate) fn load_framing_pitches () -> Vec<framing::FramingPitch> {
+    load_csv (PLAY_BY_PLAY)
+}
+
+pub (crate) fn cache_called_strike_bins (bins: &Vec<framing::CalledStrikeBin>) {
+    cache (CALLED_STRIKE_JSON, bins.clone());
+}
+
+pub (crate) fn load_called_strike_bins () -> Vec<framing::CalledStrikeBin> {
+    load (CALLED_STRIKE_JSON)
+}
+
+pub (crate) fn write_catcher_framing (catchers: &Vec<framing::CatcherFraming>) {
+    write (CATCHER_FRAMING, catchers);
+}
+
+pub (crate) fn write_umpire_games (games: &Vec<framing::UmpireGame>) {
+    write (UMPIRE_GAMES, games);
+}
+
+pub (crate) fn write_umpire_seasons (seasons: &Vec<framing::UmpireSeason>) {
+    write (UMPIRE_SEASONS, seasons);
+}
+
+pub (crate) fn load_fielding_defense () -> Vec<fielding::FieldingDefense> {
+    load_csv (DEFENSE)
+}
+
+pub (crate) fn cache_fielding_bins (bins: &Vec<fielding::FieldingBin>) {
+    cache (FIELDING_JSON, bins.clone());
+}
+
+pub (crate) fn write_fielding (players: &Vec<fielding::FieldingPlayer>) {
+    write (FIELDING, players);
+}
+
+pub (crate) fn load_trajectory_pitches () -> Vec<nathan::TrajectoryPitch> {
+    load_csv (PLAY_BY_PLAY)
+}
+
+pub (crate) fn write_pitch_trajectories (rows: &Vec<nathan::TrajectoryRow>) {
+    write (PITCH_TRAJECTORIES, rows);
+}
+
+pub (crate) fn load_tunnel_pitches () -> Vec<tunneling::TunnelPitch> {
+    load_csv (PLAY_BY_PLAY)
+}
+
+pub (crate) fn write_tunnel_pairs (pairs: &Vec<tunneling::TunnelPair>) {
+    write (TUNNEL_PAIRS, pairs);
+}
+
+pub (crate) fn write_pitcher_tunnels (pitchers: &Vec<tunneling::PitcherTunnel>) {
+    write (PITCHER_TUNNELS, pitchers);
 }
 
 pub (crate) fn cache_teams_data (teams: &Vec<team::TeamData>) {
@@ -171,6 +561,104 @@ pub (crate) fn load_schedule () -> Vec<schedule::GameMetaData> {
     load (SCHEDULE_JSON)
 } 
 
+/// Serialize the outfield walls traced from the venue svgs, in pixels. The walls used to be cached in feet as venue_walls.json,
+/// which is no longer read so that every venue gets traced again.
+pub (crate) fn cache_venue_walls (walls: &Vec<fences::VenueWall>) {
+    cache (VENUE_WALL_POLYLINES_JSON, walls.clone());
+}
+
+pub (crate) fn load_venue_walls () -> Vec<fences::VenueWall> {
+    load (VENUE_WALL_POLYLINES_JSON)
+}
+
+pub (crate) fn cache_fence_profiles (profiles: &Vec<fences::FenceProfile>) {
+    cache (FENCE_PROFILES_JSON, profiles.clone());
+}
+
+pub (crate) fn load_fence_profiles () -> Vec<fences::FenceProfile> {
+    load (FENCE_PROFILES_JSON)
+}
+
+pub (crate) fn load_fence_pitches () -> Vec<fences::FencePitch> {
+    load_csv (PLAY_BY_PLAY)
+}
+
+pub (crate) fn load_calibration_pitches () -> Vec<calibration::CalibrationPitch> {
+    load_csv (PLAY_BY_PLAY)
+}
+
+pub (crate) fn cache_pixel_calibrations (calibrations: &Vec<calibration::PixelCalibration>) {
+    cache (PIXEL_CALIBRATIONS_JSON, calibrations.clone());
+}
+
+pub (crate) fn load_pixel_calibrations () -> Vec<calibration::PixelCalibration> {
+    load (PIXEL_CALIBRATIONS_JSON)
+}
+
+pub (crate) fn load_aging_pitches () -> Vec<aging::AgingPitch> {
+    load_csv (PLAY_BY_PLAY)
+}
+
+pub (crate) fn cache_aging_curves (curves: &Vec<aging::AgingCurve>) {
+    cache (AGING_CURVES_JSON, curves.clone());
+}
+
+pub (crate) fn load_aging_curves () -> Vec<aging::AgingCurve> {
+    load (AGING_CURVES_JSON)
+}
+
+pub (crate) fn write_aging_curves (curves: &Vec<aging::AgingCurve>) {
+    write (AGING_CURVES, curves);
+}
+
+pub (crate) fn load_mle_pitches () -> Vec<mle::MlePitch> {
+    load_csv (PLAY_BY_PLAY)
+}
+
+pub (crate) fn cache_mle_translations (translations: &Vec<mle::MleTranslation>) {
+    cache (MLE_TRANSLATIONS_JSON, translations.clone());
+}
+
+pub (crate) fn load_mle_translations () -> Vec<mle::MleTranslation> {
+    load (MLE_TRANSLATIONS_JSON)
+}
+
+pub (crate) fn write_mle_factors (factors: &Vec<mle::MleFactor>) {
+    write (MLE_FACTORS, factors);
+}
+
+pub (crate) fn write_mle_translations (translations: &Vec<mle::MleTranslation>) {
+    write (MLE_TRANSLATIONS, translations);
+}
+
+pub (crate) fn write_mle_lines (lines: &Vec<mle::MleLine>) {
+    write (MLE_LINES, lines);
+}
+
+pub (crate) fn load_projection_pitches () -> Vec<projections::ProjectionPitch> {
+    load_csv (PLAY_BY_PLAY)
+}
+
+pub (crate) fn write_projections (projections: &Vec<projections::Projection>) {
+    write (PROJECTIONS, projections);
+}
+
+pub (crate) fn write_projection_backtest (scores: &Vec<projections::ProjectionScore>) {
+    write (PROJECTION_BACKTEST, scores);
+}
+
+pub (crate) fn load_stabilization_pitches () -> Vec<stabilization::StabilizationPitch> {
+    load_csv (PLAY_BY_PLAY)
+}
+
+pub (crate) fn write_stabilization (stabilization: &Vec<stabilization::Stabilization>) {
+    write (STABILIZATION, stabilization);
+}
+
+pub (crate) fn write_shrunk_rates (rates: &Vec<stabilization::ShrunkRate>) {
+    write (SHRUNK_RATES, rates);
+}
+
 /// Serialize the venue (x,y) coordinates 
 pub (crate) fn cache_venue_x_y (venues: &Vec<venues::VenueXY>) {
 
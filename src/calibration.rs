@@ -0,0 +1,259 @@
+//! Pixel to feet calibration for the hit coordinates.
+//!
+//! The hit coordinates are pixels on the Gameday picture of the field. We know where home plate is from the venue's svg and
+//! FEET_PER_PIXEL is a rough guess at the scale, but the pictures aren't all drawn to the same scale and they've changed over the
+//! years. So for each venue and season we fit home plate and the scale to the balls we have a tracked distance for:
+//!
+//!   total_distance = feet_per_pixel * |coordinates - home plate|
+//!
+//! by least squares, starting from the svg and FEET_PER_PIXEL. Only fly balls and line drives caught in the park go into the fit,
+//! they're picked up about where they land, so the coordinates and the tracked distance are describing the same spot. After the
+//! first fit we drop the balls more than 3 RMSE off and fit again.
+//!
+//! Venue seasons without enough balls fall back to a fit on every season at the venue, then to the svg and FEET_PER_PIXEL.
+
+use serde::{Serialize, Deserialize};
+use std::collections::{BTreeMap, HashMap};
+use crate::play_by_play::{Event, Trajectory};
+use crate::venues::VenueXY;
+
+/// Balls a venue season needs before we fit it on its own
+pub const MIN_BALLS: usize = 50;
+/// Shorter balls are too close to home plate to tell the scale from the origin
+const MIN_DISTANCE: f32 = 150.0;
+const MAX_ITERATIONS: usize = 25;
+const OUTLIER_RMSE: f64 = 3.0;
+
+/// Hit coordinates in pixels and the tracked distance in feet
+type Ball = (f64, f64, f64);
+
+/// The columns from the play by play output we need for the calibration.
+#[derive(Debug, Clone, Deserialize)]
+pub struct CalibrationPitch {
+    pub venue_id: u32,
+    pub game_year: u16,
+    pub in_play_result: Option<Event>,
+    pub hit_data_trajectory: Option<Trajectory>,
+    pub hit_data_coord_x: Option<f64>,
+    pub hit_data_coord_y: Option<f64>,
+    pub hit_data_total_distance: Option<f32>,
+}
+
+pub struct CalibrationData <'a> {
+    pub pitches: &'a [CalibrationPitch],
+    pub venues_x_y: &'a HashMap<u32, VenueXY>,
+}
+
+/// Home plate in pixels and the scale. A season of None is the fit on every season at the venue.
+#[derive(Debug, Copy, Clone, Serialize, Deserialize)]
+pub struct PixelCalibration {
+    pub venue_id: u32,
+    pub season: Option<u16>,
+    pub home_x: f32,
+    pub home_y: f32,
+    pub feet_per_pixel: f32,
+    pub balls: u32,
+    /// In feet, None when we didn't fit
+    pub rmse: Option<f32>,
+}
+
+impl PixelCalibration {
+    /// The svg's home plate and FEET_PER_PIXEL, for venues we couldn't fit
+    pub fn uncalibrated (venue_id: u32, venue_x_y: Option<&VenueXY>) -> Self {
+        PixelCalibration {
+            venue_id,
+            season: None,
+            home_x: venue_x_y.and_then(|v| v.x).unwrap_or(crate::STADIUM_X),
+            home_y: venue_x_y.and_then(|v| v.y).unwrap_or(crate::STADIUM_Y),
+            feet_per_pixel: crate::FEET_PER_PIXEL,
+            balls: 0,
+            rmse: None,
+        }
+    }
+
+    /// Where the ball was picked up, in feet from home plate. x is toward the 1B side, square to the line to center field, and
+    /// y is toward center field.
+    pub fn landing (&self, x: f64, y: f64) -> (f64, f64) {
+        let scale = self.feet_per_pixel as f64;
+        ((x - self.home_x as f64) * scale, (self.home_y as f64 - y) * scale)
+    }
+
+    pub fn distance (&self, x: f64, y: f64) -> f64 {
+        let (x, y) = self.landing(x, y);
+        (x * x + y * y).sqrt()
+    }
+}
+
+/// The calibration for a game: the venue season, then the venue, then uncalibrated
+pub fn pixel_calibration (calibrations: &HashMap<(u32, Option<u16>), PixelCalibration>, venues_x_y: &HashMap<u32, VenueXY>, venue_id: u32, season: u16) -> PixelCalibration {
+    calibrations.get(&(venue_id, Some(season)))
+        .or(calibrations.get(&(venue_id, None)))
+        .copied()
+        .unwrap_or_else(|| PixelCalibration::uncalibrated(venue_id, venues_x_y.get(&venue_id)))
+}
+
+/// Solves a 3x3 system by Gaussian elimination with partial pivoting. None if it's singular.
+fn solve (mut a: [[f64; 3]; 3], mut b: [f64; 3]) -> Option<[f64; 3]> {
+    for col in 0 .. 3 {
+        let pivot = (col .. 3).max_by(|i, j| a[*i][col].abs().partial_cmp(&a[*j][col].abs()).unwrap())?;
+        if a[pivot][col].abs() < 1e-12 {return None};
+        a.swap(col, pivot);
+        b.swap(col, pivot);
+        let pivot_row = a[col];
+        for row in col + 1 .. 3 {
+            let factor = a[row][col] / pivot_row[col];
+            for (k, value) in a[row].iter_mut().enumerate().skip(col) {
+                *value -= factor * pivot_row[k];
+            }
+            b[row] -= factor * b[col];
+        }
+    }
+    let mut x = [0.0; 3];
+    for row in (0 .. 3).rev() {
+        let sum: f64 = (row + 1 .. 3).map(|k| a[row][k] * x[k]).sum();
+        x[row] = (b[row] - sum) / a[row][row];
+    }
+    Some(x)
+}
+
+/// Gauss-Newton on (home x, home y, feet per pixel) for balls of (x, y, distance). Returns the fit and the RMSE.
+pub (crate) fn fit (balls: &[Ball], start: (f64, f64, f64)) -> Option<((f64, f64, f64), f64)> {
+    if balls.len() < 3 {return None};
+    let (mut x0, mut y0, mut scale) = start;
+
+    for _ in 0 .. MAX_ITERATIONS {
+        let mut jtj = [[0.0; 3]; 3];
+        let mut jtr = [0.0; 3];
+        for (x, y, distance) in balls {
+            let (dx, dy) = (x - x0, y - y0);
+            let pixels = (dx * dx + dy * dy).sqrt();
+            if pixels == 0.0 {continue};
+            let residual = scale * pixels - distance;
+            let j = [-scale * dx / pixels, -scale * dy / pixels, pixels];
+            for r in 0 .. 3 {
+                jtr[r] += j[r] * residual;
+                for c in 0 .. 3 {
+                    jtj[r][c] += j[r] * j[c];
+                }
+            }
+        }
+        let step = solve(jtj, [-jtr[0], -jtr[1], -jtr[2]])?;
+        x0 += step[0];
+        y0 += step[1];
+        scale += step[2];
+        if step[0].abs() < 1e-6 && step[1].abs() < 1e-6 && step[2].abs() < 1e-9 {break};
+    }
+
+    if !scale.is_finite() || scale <= 0.0 {return None};
+    let sse: f64 = balls.iter()
+        .map(|(x, y, distance)| (scale * ((x - x0).powi(2) + (y - y0).powi(2)).sqrt() - distance).powi(2))
+        .sum();
+    Some(((x0, y0, scale), (sse / balls.len() as f64).sqrt()))
+}
+
+/// Fly balls and line drives that were caught. Hits are fielded after they bounce or roll, away from where they landed.
+fn caught (pitch: &CalibrationPitch) -> bool {
+    matches!(pitch.in_play_result, Some (Event::FlyOut) | Some (Event::LineOut) | Some (Event::PopOut) | Some (Event::SacFly) | Some (Event::SacFlyDoublePlay))
+        && matches!(pitch.hit_data_trajectory, Some (Trajectory::FlyBall) | Some (Trajectory::LineDrive))
+}
+
+/// Fits, drops the outliers and fits again
+fn calibrate (venue_id: u32, season: Option<u16>, balls: &[Ball], start: PixelCalibration) -> Option<PixelCalibration> {
+    if balls.len() < MIN_BALLS {return None};
+    let start = (start.home_x as f64, start.home_y as f64, start.feet_per_pixel as f64);
+    let ((x0, y0, scale), rmse) = fit(balls, start)?;
+
+    let kept: Vec<Ball> = balls.iter()
+        .filter(|(x, y, distance)| (scale * ((x - x0).powi(2) + (y - y0).powi(2)).sqrt() - distance).abs() <= OUTLIER_RMSE * rmse)
+        .copied()
+        .collect();
+    if kept.len() < MIN_BALLS {return None};
+    let ((x0, y0, scale), rmse) = fit(&kept, (x0, y0, scale))?;
+
+    Some(PixelCalibration {
+        venue_id,
+        season,
+        home_x: x0 as f32,
+        home_y: y0 as f32,
+        feet_per_pixel: scale as f32,
+        balls: kept.len() as u32,
+        rmse: Some(rmse as f32),
+    })
+}
+
+impl <'a> From<CalibrationData<'a>> for Vec<PixelCalibration> {
+    fn from (data: CalibrationData) -> Vec<PixelCalibration> {
+
+        let mut venue_seasons: BTreeMap<(u32, u16), Vec<Ball>> = BTreeMap::new();
+        for pitch in data.pitches {
+            if !caught(pitch) {continue};
+            if let (Some (x), Some (y), Some (distance)) = (pitch.hit_data_coord_x, pitch.hit_data_coord_y, pitch.hit_data_total_distance) {
+                if distance >= MIN_DISTANCE {
+                    venue_seasons.entry((pitch.venue_id, pitch.game_year)).or_default().push((x, y, distance as f64));
+                }
+            }
+        }
+
+        let mut venues: BTreeMap<u32, Vec<Ball>> = BTreeMap::new();
+        for ((venue_id, _), balls) in venue_seasons.iter() {
+            venues.entry(*venue_id).or_default().extend(balls.iter().copied());
+        }
+
+        let mut calibrations: Vec<PixelCalibration> = vec![];
+        for (venue_id, balls) in venues.iter() {
+            let start = PixelCalibration::uncalibrated(*venue_id, data.venues_x_y.get(venue_id));
+            if let Some(calibration) = calibrate(*venue_id, None, balls, start) {
+                calibrations.push(calibration);
+            }
+        }
+        for ((venue_id, season), balls) in venue_seasons.iter() {
+            let start = PixelCalibration::uncalibrated(*venue_id, data.venues_x_y.get(venue_id));
+            if let Some(calibration) = calibrate(*venue_id, Some(*season), balls, start) {
+                calibrations.push(calibration);
+            }
+        }
+        calibrations
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn recovers_home_plate_and_scale () {
+        // Balls all over the outfield, drawn with home plate at (126, 205) and 2.5 feet per pixel
+        let balls: Vec<Ball> = (0 .. 200)
+            .map(|i| {
+                let angle = ((i % 19) as f64 * 5.0 - 45.0).to_radians();
+                let distance = 160.0 + (i as f64 * 7.3) % 250.0;
+                let pixels = distance / 2.5;
+                (126.0 + pixels * angle.sin(), 205.0 - pixels * angle.cos(), distance)
+            })
+            .collect();
+        let ((x0, y0, scale), rmse) = fit(&balls, (crate::STADIUM_X as f64, crate::STADIUM_Y as f64, crate::FEET_PER_PIXEL as f64)).unwrap();
+        assert!((x0 - 126.0).abs() < 1e-3);
+        assert!((y0 - 205.0).abs() < 1e-3);
+        assert!((scale - 2.5).abs() < 1e-5);
+        assert!(rmse < 1e-3);
+    }
+
+    #[test]
+    fn only_caught_balls () {
+        let pitch = |in_play_result: Event, trajectory: Trajectory| CalibrationPitch {
+            venue_id: 1,
+            game_year: 2023,
+            in_play_result: Some(in_play_result),
+            hit_data_trajectory: Some(trajectory),
+            hit_data_coord_x: Some(100.0),
+            hit_data_coord_y: Some(100.0),
+            hit_data_total_distance: Some(300.0),
+        };
+        assert!(caught(&pitch(Event::FlyOut, Trajectory::FlyBall)));
+        assert!(caught(&pitch(Event::SacFly, Trajectory::FlyBall)));
+        assert!(caught(&pitch(Event::LineOut, Trajectory::LineDrive)));
+        assert!(!caught(&pitch(Event::Double, Trajectory::LineDrive)));
+        assert!(!caught(&pitch(Event::HomeRun, Trajectory::FlyBall)));
+        assert!(!caught(&pitch(Event::GroundOut, Trajectory::GroundBall)));
+    }
+}
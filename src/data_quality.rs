@@ -0,0 +1,200 @@
+//! Data quality checks for the play-by-play state machine in the game module.
+//!
+//! The source data has a small number of records that are clearly garbage: extra pitches after a plate appearance is over,
+//! events duplicated with the same index, runners that disappear from the runner data and counts that can't exist. Rather than
+//! silently emitting the wrong base/out/count state, every impossible transition is classified as a DataQualityIssue and then either:
+//!
+//!   * **Repaired:** We're confident what the correct state is. The fix is applied, the pitches are emitted with data_quality_repaired
+//!     set to true and a DataQualityRecord is written to the audit trail describing what we changed.
+//!   * **Quarantined:** We can't recover the correct state. The whole plate appearance is pulled out of the pitch output and written
+//!     to the quarantine file instead, along with a DataQualityRecord explaining why.
+//!
+//! | Issue              | Detection                                                      | Resolution                      |
+//! |--------------------|----------------------------------------------------------------|---------------------------------|
+//! | DuplicatePlayIndex | Two events in the same plate appearance share an index         | Repaired, later event dropped   |
+//! | ExtraRecord        | A pitch after the count already hit 4 balls or 3 strikes       | Repaired, pitch dropped         |
+//! | BallsWithoutWalk   | Count reaches 4+ balls but the plate appearance isn't a walk   | Quarantined                     |
+//! | OutsDecreased      | The outs reported by the source go down within a half-inning   | Quarantined                     |
+//! | RunnerVanished     | A runner lands on a base occupied by a runner we never moved   | Repaired, old runner removed    |
+//! | BaseValueOverflow  | Base value is greater than 7 (bases loaded)                    | Quarantined, state capped at 7  |
+
+use serde::{Serialize, Deserialize};
+use std::collections::HashMap;
+use crate::play_by_play::{HalfInning, RunnerData};
+
+#[derive(Debug, Serialize, Deserialize, Copy, Clone, PartialEq, Eq)]
+pub enum DataQualityIssue {
+    DuplicatePlayIndex,
+    ExtraRecord,
+    BallsWithoutWalk,
+    OutsDecreased,
+    RunnerVanished,
+    BaseValueOverflow,
+}
+
+#[derive(Debug, Serialize, Deserialize, Copy, Clone, PartialEq, Eq)]
+pub enum Resolution {
+    Repaired,
+    Quarantined,
+}
+
+/// One row of the data quality audit trail.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct DataQualityRecord {
+    pub game_pk: u32,
+    pub half_inning: HalfInning,
+    pub num_inning: u8,
+    pub num_plate_appearance: u8,
+    pub play_index: Option<u8>,
+    pub issue: DataQualityIssue,
+    pub resolution: Resolution,
+    pub description: String,
+}
+
+/// Everything we need to identify the plate appearance an issue came from.
+#[derive(Debug, Copy, Clone)]
+pub (crate) struct PlateAppearanceContext {
+    pub (crate) game_pk: u32,
+    pub (crate) half_inning: HalfInning,
+    pub (crate) num_inning: u8,
+    pub (crate) num_plate_appearance: u8,
+}
+
+impl PlateAppearanceContext {
+    pub (crate) fn record (&self, play_index: Option<u8>, issue: DataQualityIssue, description: String) -> DataQualityRecord {
+        DataQualityRecord {
+            game_pk: self.game_pk,
+            half_inning: self.half_inning,
+            num_inning: self.num_inning,
+            num_plate_appearance: self.num_plate_appearance,
+            play_index,
+            issue,
+            resolution: issue.resolution(),
+            description,
+        }
+    }
+}
+
+impl DataQualityIssue {
+    /// How we handle each type of issue. Anything that leaves us unsure of the true base/out/count state gets quarantined.
+    pub fn resolution (&self) -> Resolution {
+        match self {
+            DataQualityIssue::DuplicatePlayIndex |
+            DataQualityIssue::ExtraRecord |
+            DataQualityIssue::RunnerVanished
+                => Resolution::Repaired,
+            DataQualityIssue::BallsWithoutWalk |
+            DataQualityIssue::OutsDecreased |
+            DataQualityIssue::BaseValueOverflow
+                => Resolution::Quarantined,
+        }
+    }
+}
+
+/// Applies the runner updates for an event to our persistent runner state. If a runner ends up on a base that is still occupied
+/// by a runner who wasn't part of this update, the source data lost track of the original runner. The runner data is the most
+/// recent information we have, so we trust it and remove the old runner, returning them so the caller can record the repair.
+pub (crate) fn update_runner_state (runner_state: &mut HashMap<u32, RunnerData>, runner_data: &HashMap<u32, RunnerData>) -> Vec<RunnerData> {
+
+    for runner in runner_data.values() {
+        runner_state.insert(runner.runner_id, *runner);
+    };
+
+    let occupied: Vec<u8> = runner_data.values()
+        .map(|r| r.end_base_value)
+        .filter(|base| *base > 0)
+        .collect();
+
+    let vanished: Vec<RunnerData> = runner_state.values()
+        .filter(|r| !runner_data.contains_key(&r.runner_id))
+        .filter(|r| occupied.contains(&r.end_base_value))
+        .copied()
+        .collect();
+
+    for runner in &vanished {
+        runner_state.remove(&runner.runner_id);
+    }
+
+    vanished
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::play_by_play::Event;
+
+    /// A runner going from start to end on a single. Base values are 1 for first, 2 for second and 4 for third, 0 is off the bases.
+    fn runner (runner_id: u32, start_base_value: u8, end_base_value: u8) -> RunnerData {
+        RunnerData {
+            runner_id,
+            start_base_value,
+            end_base_value,
+            runs: 0,
+            event: Event::Single,
+            rbi: false,
+            earned: false,
+            play_index: 0,
+            outs: 0,
+            fielded_by_pos: None,
+            fielded_by_id: None,
+        }
+    }
+
+    fn runners (runners: &[RunnerData]) -> HashMap<u32, RunnerData> {
+        runners.iter().map(|r| (r.runner_id, *r)).collect()
+    }
+
+    #[test]
+    fn record_carries_the_plate_appearance () {
+        let context = PlateAppearanceContext {game_pk: 1, half_inning: HalfInning::Bottom, num_inning: 3, num_plate_appearance: 20};
+        let record = context.record(Some(2), DataQualityIssue::OutsDecreased, "Outs went from 2 to 1".to_string());
+
+        assert_eq!(record.game_pk, 1);
+        assert_eq!(record.half_inning, HalfInning::Bottom);
+        assert_eq!(record.num_inning, 3);
+        assert_eq!(record.num_plate_appearance, 20);
+        assert_eq!(record.play_index, Some(2));
+        assert_eq!(record.issue, DataQualityIssue::OutsDecreased);
+        assert_eq!(record.resolution, Resolution::Quarantined);
+    }
+
+    #[test]
+    fn runners_that_move_are_kept () {
+        let mut runner_state = runners(&[runner(1, 0, 1)]);
+        let vanished = update_runner_state(&mut runner_state, &runners(&[runner(1, 1, 2), runner(2, 0, 1)]));
+
+        assert!(vanished.is_empty());
+        assert_eq!(runner_state.len(), 2);
+        assert_eq!(runner_state[&1].end_base_value, 2);
+        assert_eq!(runner_state[&2].end_base_value, 1);
+    }
+
+    #[test]
+    fn runners_left_alone_are_kept () {
+        let mut runner_state = runners(&[runner(1, 0, 4)]);
+        let vanished = update_runner_state(&mut runner_state, &runners(&[runner(2, 0, 1)]));
+
+        assert!(vanished.is_empty());
+        assert_eq!(runner_state[&1].end_base_value, 4);
+    }
+
+    #[test]
+    fn runner_on_a_taken_base_vanishes () {
+        let mut runner_state = runners(&[runner(1, 0, 2)]);
+        let vanished = update_runner_state(&mut runner_state, &runners(&[runner(2, 0, 2)]));
+
+        assert_eq!(vanished.len(), 1);
+        assert_eq!(vanished[0].runner_id, 1);
+        assert!(!runner_state.contains_key(&1));
+        assert!(runner_state.contains_key(&2));
+    }
+
+    #[test]
+    fn off_the_bases_is_not_a_base () {
+        let mut runner_state = runners(&[runner(1, 4, 0)]);
+        let vanished = update_runner_state(&mut runner_state, &runners(&[runner(2, 0, 0)]));
+
+        assert!(vanished.is_empty());
+        assert_eq!(runner_state.len(), 2);
+    }
+}
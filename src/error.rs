@@ -6,6 +6,7 @@
 //! 
 //! **Source Data Issues:**\
 //!     * **Extra Records / Garbage Records:** Certain source data are clearly junk as they produce counts (balls and strikes) that are clearly wrong.
+//!         These are detected, repaired or quarantined in the data_quality module.
 //!     * **Missing Metadata:** Some games do not have any boxscore data, or are missing other metadata such as coaches, venue etc.
 //!     * 
 //! 
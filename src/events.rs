@@ -0,0 +1,296 @@
+//! Everything that happens in a game that isn't a pitch: pickoffs, step-offs, balks, substitutions, pitch-clock violations
+//! and replay reviews. The game module only keeps pitches, so these events are collected into their own table with enough
+//! context (plate appearance, count, timing) to join them back to the pitches around them.
+//!
+//! Pitch-clock violations are recorded as pitches with an automatic ball or strike code. We keep those in the pitch table as well,
+//! but also add them here, classified by who committed the violation. The classification is based on the description, since the
+//! codes have changed over the years as the rules have been tested in the minor leagues.
+
+use serde::{Serialize, Deserialize};
+use std::collections::HashMap;
+use crate::play_by_play::{Code, Event, HalfInning, PlayEvent, PlayEventType, ReviewDetails};
+use crate::players::Player;
+
+#[derive(Debug, Serialize, Deserialize, Copy, Clone, PartialEq, Eq)]
+pub enum GameEventType {
+    Action,
+    Pickoff,
+    NoPitch,
+    Stepoff,
+    AutomaticCall,
+    Review,
+}
+
+#[derive(Debug, Serialize, Deserialize, Copy, Clone, PartialEq, Eq)]
+pub enum TimerViolation {
+    Pitcher,
+    Batter,
+    Catcher,
+    Disengagement,
+    DefensiveShift,
+    Unknown,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct GameEvent {
+    pub game_pk: u32,
+    pub half_inning: HalfInning,
+    pub num_inning: u8,
+    pub num_plate_appearance: u8,
+    pub play_index: Option<u8>,
+    pub play_id: Option<String>,
+    pub start_time: Option<String>,
+
+    pub event_type: GameEventType,
+    pub event: Option<Event>,
+    pub code: Option<Code>,
+    pub description: String,
+
+    pub pitcher: u32,
+    pub batter: u32,
+    pub base: Option<u8>,
+    pub player_id: Option<u32>,
+    pub player_name: Option<String>,
+
+    pub balls: Option<u8>,
+    pub strikes: Option<u8>,
+    pub outs: Option<u8>,
+    ///Pickoff attempts and step-offs so far in the plate appearance, including this one
+    pub disengagement_num_plate_appearance: u8,
+
+    pub has_review: bool,
+    pub review_type: Option<String>,
+    pub review_overturned: Option<bool>,
+    pub review_challenge_team_id: Option<u32>,
+
+    pub timer_violation: Option<TimerViolation>,
+}
+
+/// The plate appearance an event belongs to.
+pub (crate) struct EventContext <'e> {
+    pub (crate) game_pk: u32,
+    pub (crate) half_inning: HalfInning,
+    pub (crate) num_inning: u8,
+    pub (crate) num_plate_appearance: u8,
+    pub (crate) pitcher: u32,
+    pub (crate) batter: u32,
+    pub (crate) players: &'e HashMap<u32, Player>,
+}
+
+impl <'e> EventContext <'e> {
+
+    /// Builds the event row for a play event. Regular pitches return None, unless they were reviewed.
+    pub (crate) fn event (&self, event: &PlayEvent, disengagement_num_plate_appearance: u8) -> Option<GameEvent> {
+
+        let automatic_call = match event.details.code {
+            Some (code) => is_automatic_call(code),
+            None => false,
+        };
+
+        let event_type = match event.play_event_type {
+            PlayEventType::Action => GameEventType::Action,
+            PlayEventType::Pickoff => GameEventType::Pickoff,
+            PlayEventType::NoPitch => GameEventType::NoPitch,
+            PlayEventType::Stepoff => GameEventType::Stepoff,
+            PlayEventType::Pitch if automatic_call => GameEventType::AutomaticCall,
+            PlayEventType::Pitch if event.review_details.is_some() => GameEventType::Review,
+            PlayEventType::Pitch => return None,
+        };
+
+        let description = event.details.description.clone().unwrap_or_default();
+        let player_id = event.player.as_ref().map(|player| player.id);
+
+        let player_name = match player_id {
+            Some (id) => self.players.get(&id).map(|player| player.name.clone()),
+            None => None,
+        };
+
+        let (review_type, review_overturned, review_challenge_team_id) = review(event.review_details.as_ref());
+
+        Some (
+            GameEvent {
+                game_pk: self.game_pk,
+                half_inning: self.half_inning,
+                num_inning: self.num_inning,
+                num_plate_appearance: self.num_plate_appearance,
+                play_index: Some(event.index),
+                play_id: event.play_id.clone(),
+                start_time: event.start_time.clone(),
+                event_type,
+                event: event.details.event,
+                code: event.details.code,
+                timer_violation: timer_violation(&description, automatic_call),
+                description,
+                pitcher: self.pitcher,
+                batter: self.batter,
+                base: event.base,
+                player_id,
+                player_name,
+                balls: event.count.balls,
+                strikes: event.count.strikes,
+                outs: event.count.outs,
+                disengagement_num_plate_appearance,
+                has_review: event.details.has_review,
+                review_type,
+                review_overturned,
+                review_challenge_team_id,
+            }
+        )
+    }
+
+    /// Builds the event row for a review of the whole plate appearance, which isn't attached to any single play event.
+    pub (crate) fn review (&self, review_details: &ReviewDetails, description: String) -> GameEvent {
+
+        let (review_type, review_overturned, review_challenge_team_id) = review(Some(review_details));
+
+        GameEvent {
+            game_pk: self.game_pk,
+            half_inning: self.half_inning,
+            num_inning: self.num_inning,
+            num_plate_appearance: self.num_plate_appearance,
+            play_index: None,
+            play_id: None,
+            start_time: None,
+            event_type: GameEventType::Review,
+            event: None,
+            code: None,
+            description,
+            pitcher: self.pitcher,
+            batter: self.batter,
+            base: None,
+            player_id: None,
+            player_name: None,
+            balls: None,
+            strikes: None,
+            outs: None,
+            disengagement_num_plate_appearance: 0,
+            has_review: true,
+            review_type,
+            review_overturned,
+            review_challenge_team_id,
+            timer_violation: None,
+        }
+    }
+}
+
+fn review (review_details: Option<&ReviewDetails>) -> (Option<String>, Option<bool>, Option<u32>) {
+    match review_details {
+        Some (review) => (review.review_type.clone(), review.is_overturned, review.challenge_team_id),
+        None => (None, None, None),
+    }
+}
+
+/// Automatic balls and strikes are the pitch clock (and shift) enforcement codes, not real pitches.
+pub (crate) fn is_automatic_call (code: Code) -> bool {
+    matches!(code, Code::A | Code::AB | Code::AC | Code::V | Code::VB | Code::VC | Code::VP | Code::VS)
+}
+
+/// Pickoff attempts and step-offs both count against the pitcher's disengagement limit.
+pub (crate) fn is_disengagement (play_event_type: PlayEventType) -> bool {
+    matches!(play_event_type, PlayEventType::Pickoff | PlayEventType::Stepoff)
+}
+
+fn timer_violation (description: &str, automatic_call: bool) -> Option<TimerViolation> {
+    let description = description.to_lowercase();
+
+    if !automatic_call && !description.contains("violation") {return None};
+
+         if description.contains("disengagement") {Some(TimerViolation::Disengagement)}
+    else if description.contains("shift") {Some(TimerViolation::DefensiveShift)}
+    else if description.contains("pitcher") {Some(TimerViolation::Pitcher)}
+    else if description.contains("batter") {Some(TimerViolation::Batter)}
+    else if description.contains("catcher") {Some(TimerViolation::Catcher)}
+    else {Some(TimerViolation::Unknown)}
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A play event as the source sends it, with only the fields we read
+    fn play_event (play_event_type: &str, code: Option<&str>, description: &str, review: bool) -> PlayEvent {
+        let code = code.map(|code| format!(r#""code": "{}","#, code)).unwrap_or_default();
+        let review_details = if review {r#", "reviewDetails": {"isOverturned": true, "reviewType": "MJ", "challengeTeamId": 147}"#} else {""};
+        serde_json::from_str(&format!(
+            r#"{{"details": {{{} "description": "{}", "hasReview": {}}}, "count": {{"balls": 1, "strikes": 0, "outs": 1}}, "isPitch": false, "index": 2, "type": "{}"{}}}"#,
+            code, description, review, play_event_type, review_details,
+        )).unwrap()
+    }
+
+    fn event (play_event: &PlayEvent) -> Option<GameEvent> {
+        let players = HashMap::new();
+        let context = EventContext {game_pk: 1, half_inning: HalfInning::Top, num_inning: 1, num_plate_appearance: 1, pitcher: 10, batter: 20, players: &players};
+        context.event(play_event, 0)
+    }
+
+    #[test]
+    fn automatic_calls () {
+        for code in [Code::A, Code::AB, Code::AC, Code::V, Code::VB, Code::VC, Code::VP, Code::VS] {
+            assert!(is_automatic_call(code));
+        }
+        for code in [Code::B, Code::C, Code::P, Code::I, Code::N, Code::PO] {
+            assert!(!is_automatic_call(code));
+        }
+    }
+
+    #[test]
+    fn timer_violations_by_who_committed_them () {
+        assert_eq!(timer_violation("Pitch Clock Violation - Pitcher", false), Some(TimerViolation::Pitcher));
+        assert_eq!(timer_violation("Pitch clock violation - batter", false), Some(TimerViolation::Batter));
+        assert_eq!(timer_violation("Automatic Ball - Catcher", true), Some(TimerViolation::Catcher));
+        assert_eq!(timer_violation("Pitcher Disengagement Violation", false), Some(TimerViolation::Disengagement));
+        assert_eq!(timer_violation("Defensive Shift Violation", false), Some(TimerViolation::DefensiveShift));
+        assert_eq!(timer_violation("Automatic Ball", true), Some(TimerViolation::Unknown));
+    }
+
+    #[test]
+    fn no_timer_violation_without_a_violation () {
+        assert_eq!(timer_violation("Pickoff Attempt 1B", false), None);
+        assert_eq!(timer_violation("Pitcher Step Off", false), None);
+    }
+
+    #[test]
+    fn event_types () {
+        let event_type = |play_event: PlayEvent| event(&play_event).map(|e| e.event_type);
+
+        assert_eq!(event_type(play_event("action", None, "Mound Visit", false)), Some(GameEventType::Action));
+        assert_eq!(event_type(play_event("pickoff", Some("1"), "Pickoff Attempt 1B", false)), Some(GameEventType::Pickoff));
+        assert_eq!(event_type(play_event("no_pitch", Some("N"), "No Pitch", false)), Some(GameEventType::NoPitch));
+        assert_eq!(event_type(play_event("stepoff", None, "Pitcher Step Off", false)), Some(GameEventType::Stepoff));
+        assert_eq!(event_type(play_event("pitch", Some("VP"), "Automatic Ball - Pitcher Pitch Timer Violation", false)), Some(GameEventType::AutomaticCall));
+        assert_eq!(event_type(play_event("pitch", Some("C"), "Called Strike", true)), Some(GameEventType::Review));
+        assert_eq!(event_type(play_event("pitch", Some("C"), "Called Strike", false)), None);
+    }
+
+    #[test]
+    fn automatic_calls_are_timer_violations () {
+        let game_event = event(&play_event("pitch", Some("AC"), "Automatic Strike - Batter Timer Violation", false)).unwrap();
+
+        assert_eq!(game_event.timer_violation, Some(TimerViolation::Batter));
+        assert_eq!(game_event.code, Some(Code::AC));
+        assert_eq!((game_event.balls, game_event.strikes, game_event.outs), (Some(1), Some(0), Some(1)));
+    }
+
+    #[test]
+    fn reviews () {
+        let game_event = event(&play_event("pitch", Some("C"), "Called Strike", true)).unwrap();
+
+        assert!(game_event.has_review);
+        assert_eq!(game_event.review_type.as_deref(), Some("MJ"));
+        assert_eq!(game_event.review_overturned, Some(true));
+        assert_eq!(game_event.review_challenge_team_id, Some(147));
+        assert_eq!(game_event.timer_violation, None);
+    }
+
+    #[test]
+    fn plate_appearance_reviews () {
+        let players = HashMap::new();
+        let context = EventContext {game_pk: 1, half_inning: HalfInning::Top, num_inning: 1, num_plate_appearance: 1, pitcher: 10, batter: 20, players: &players};
+        let review_details = ReviewDetails {is_overturned: Some(false), review_type: Some("MA".to_string()), challenge_team_id: None};
+        let game_event = context.review(&review_details, "Out at first".to_string());
+
+        assert_eq!(game_event.event_type, GameEventType::Review);
+        assert_eq!(game_event.play_index, None);
+        assert_eq!(game_event.review_overturned, Some(false));
+    }
+}
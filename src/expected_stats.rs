@@ -0,0 +1,560 @@
+//! Expected outcomes for batted balls (xBA, xSLG and xwOBA), fit for each sport_id and season from our own data.
+//!
+//! The model is binned smoothing. Every batted ball with an exit velocity and launch angle goes into a bin of EXIT_VELOCITY_BIN mph
+//! by LAUNCH_ANGLE_BIN degrees, split by direction (pull, center or opposite field), and each bin keeps the rate of singles,
+//! doubles, triples and home runs. Bins with fewer than MIN_SAMPLE batted balls are pooled with their neighbours, going out
+//! one bin at a time up to MAX_RADIUS bins, the same idea as the neighbours in the run_expectancy module.
+//!
+//! Most minor league parks don't have tracking data, so there's a second set of bins by trajectory and contact quality (and
+//! direction), which the stringers record everywhere. A batted ball without an exit velocity and launch angle uses those bins,
+//! and one without either uses the league rates. We don't have sprint speed, so there's no adjustment for fast runners.
+//!
+//! xwOBA uses the wOBA weights from the linear_weights module for the league and season. At the player level:
+//!   * xBA and xSLG are the expected hits and total bases over at bats. Strikeouts count as 0.
+//!   * xwOBA uses actual walks and hit by pitches, and the expected value of every batted ball, over the wOBA denominator.
+
+use serde::{Serialize, Deserialize};
+use std::collections::{BTreeMap, HashMap};
+use crate::linear_weights::{BattingTotals, WobaWeights, ratio};
+use crate::play_by_play::{Event, Hardness, SideCode, Trajectory};
+
+/// Bins with fewer batted balls than this get pooled with their neighbours
+const MIN_SAMPLE: u32 = 30;
+const EXIT_VELOCITY_BIN: f32 = 2.0;
+const LAUNCH_ANGLE_BIN: f32 = 2.0;
+/// How far out, in bins, we'll go to pool a sparse bin
+const MAX_RADIUS: i16 = 3;
+
+#[derive(Debug, Serialize, Deserialize, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum Direction {
+    Pull,
+    Center,
+    Opposite,
+}
+
+impl Direction {
+    /// Spray angle runs from 0 on the left field line to 90 on the right field line, so a right handed batter pulls the ball
+    /// to the low angles.
+    pub fn from_spray_angle (spray_angle: f64, batter_bats: SideCode) -> Option<Direction> {
+        let field = if spray_angle < 30.0 {0} else if spray_angle <= 60.0 {1} else {2};
+        match (batter_bats, field) {
+            (_, 1) => Some(Direction::Center),
+            (SideCode::R, 0) | (SideCode::L, 2) => Some(Direction::Pull),
+            (SideCode::R, 2) | (SideCode::L, 0) => Some(Direction::Opposite),
+            _ => None,
+        }
+    }
+}
+
+/// The columns from the play by play output we need to fit the model and aggregate by player.
+#[derive(Debug, Clone, Deserialize)]
+pub struct ExpectedStatsPitch {
+    pub game_pk: u32,
+    pub sport_id: u32,
+    pub game_year: u16,
+    pub num_plate_appearance: u8,
+    pub pitch_num_game: u16,
+    pub batter: u32,
+    pub pitcher: u32,
+    pub batter_bats: SideCode,
+    pub in_play: u8,
+    pub plate_appearance_result: Option<Event>,
+    pub hit_data_exit_velocity: Option<f32>,
+    pub hit_data_launch_angle: Option<f32>,
+    pub hit_data_spray_angle: Option<f64>,
+    pub hit_data_trajectory: Option<Trajectory>,
+    pub hit_data_contact_quality: Option<Hardness>,
+}
+
+/// Everything we might know about a batted ball
+#[derive(Debug, Copy, Clone)]
+pub struct BattedBall {
+    pub exit_velocity: Option<f32>,
+    pub launch_angle: Option<f32>,
+    pub spray_angle: Option<f64>,
+    pub batter_bats: SideCode,
+    pub trajectory: Option<Trajectory>,
+    pub hardness: Option<Hardness>,
+}
+
+impl From<&ExpectedStatsPitch> for BattedBall {
+    fn from (pitch: &ExpectedStatsPitch) -> BattedBall {
+        BattedBall {
+            exit_velocity: pitch.hit_data_exit_velocity,
+            launch_angle: pitch.hit_data_launch_angle,
+            spray_angle: pitch.hit_data_spray_angle,
+            batter_bats: pitch.batter_bats,
+            trajectory: pitch.hit_data_trajectory,
+            hardness: pitch.hit_data_contact_quality,
+        }
+    }
+}
+
+impl BattedBall {
+    fn direction (&self) -> Option<Direction> {
+        Direction::from_spray_angle(self.spray_angle?, self.batter_bats)
+    }
+
+    fn exit_velocity_launch_angle (&self) -> Option<(i16, i16)> {
+        match (self.exit_velocity, self.launch_angle) {
+            (Some (exit_velocity), Some (launch_angle)) => Some(
+                ((exit_velocity / EXIT_VELOCITY_BIN).floor() as i16, (launch_angle / LAUNCH_ANGLE_BIN).floor() as i16)
+            ),
+            _ => None,
+        }
+    }
+}
+
+/// One bin of a fitted model. Exit velocity/launch angle bins have the bins set, batted ball type bins have the trajectory set,
+/// and the league rates have neither. A bin with no direction is pooled across all directions.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ExpectedOutcomeBin {
+    pub sport_id: u32,
+    pub season: u16,
+    pub exit_velocity_bin: Option<i16>,
+    pub launch_angle_bin: Option<i16>,
+    pub trajectory: Option<Trajectory>,
+    pub hardness: Option<Hardness>,
+    pub direction: Option<Direction>,
+    /// Batted balls in the bin itself, before any pooling
+    pub sample_size: u32,
+    pub smoothed: bool,
+    pub single: f32,
+    pub double: f32,
+    pub triple: f32,
+    pub home_run: f32,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord)]
+pub enum PlayerRole {
+    Batter,
+    Pitcher,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ExpectedStatsPlayer {
+    pub player_id: u32,
+    pub role: PlayerRole,
+    pub sport_id: u32,
+    pub season: u16,
+    pub plate_appearances: u32,
+    pub at_bats: u32,
+    pub batted_balls: u32,
+    pub ba: f32,
+    pub xba: f32,
+    pub slg: f32,
+    pub xslg: f32,
+    pub woba: f32,
+    pub xwoba: f32,
+}
+
+/// The chance a batted ball is each kind of hit
+#[derive(Debug, Default, Copy, Clone)]
+pub struct Probabilities {
+    pub single: f32,
+    pub double: f32,
+    pub triple: f32,
+    pub home_run: f32,
+}
+
+/// Expected values for a single batted ball
+#[derive(Debug, Copy, Clone)]
+pub struct ExpectedStats {
+    pub xba: f32,
+    pub xslg: f32,
+    pub xwoba: f32,
+}
+
+/// A fitted model for one sport_id and season, ready for lookups.
+#[derive(Debug, Clone)]
+pub struct ExpectedOutcomes {
+    exit_velocity_launch_angle: HashMap<(i16, i16, Option<Direction>), Probabilities>,
+    batted_ball_type: HashMap<(Trajectory, Option<Hardness>, Option<Direction>), Probabilities>,
+    league: Probabilities,
+    woba_weights: WobaWeights,
+}
+
+impl ExpectedOutcomes {
+
+    pub fn new (bins: &[ExpectedOutcomeBin], woba_weights: WobaWeights) -> ExpectedOutcomes {
+        let mut model = ExpectedOutcomes {
+            exit_velocity_launch_angle: HashMap::new(),
+            batted_ball_type: HashMap::new(),
+            league: Probabilities::default(),
+            woba_weights,
+        };
+
+        for bin in bins {
+            let probabilities = Probabilities {single: bin.single, double: bin.double, triple: bin.triple, home_run: bin.home_run};
+            match (bin.exit_velocity_bin, bin.launch_angle_bin, bin.trajectory) {
+                (Some (exit_velocity), Some (launch_angle), _) => {model.exit_velocity_launch_angle.insert((exit_velocity, launch_angle, bin.direction), probabilities);},
+                (_, _, Some (trajectory)) => {model.batted_ball_type.insert((trajectory, bin.hardness, bin.direction), probabilities);},
+                _ => {model.league = probabilities;},
+            }
+        }
+
+        model
+    }
+
+    pub fn probabilities (&self, batted_ball: &BattedBall) -> Probabilities {
+        let direction = batted_ball.direction();
+
+        if let Some((exit_velocity, launch_angle)) = batted_ball.exit_velocity_launch_angle() {
+            let probabilities = self.exit_velocity_launch_angle.get(&(exit_velocity, launch_angle, direction))
+                .or(self.exit_velocity_launch_angle.get(&(exit_velocity, launch_angle, None)));
+            if let Some(probabilities) = probabilities {return *probabilities};
+        }
+
+        if let Some(trajectory) = batted_ball.trajectory {
+            let probabilities = self.batted_ball_type.get(&(trajectory, batted_ball.hardness, direction))
+                .or(self.batted_ball_type.get(&(trajectory, batted_ball.hardness, None)))
+                .or(self.batted_ball_type.get(&(trajectory, None, None)));
+            if let Some(probabilities) = probabilities {return *probabilities};
+        }
+
+        self.league
+    }
+
+    pub fn expected (&self, batted_ball: &BattedBall) -> ExpectedStats {
+        let p = self.probabilities(batted_ball);
+        let w = self.woba_weights;
+        ExpectedStats {
+            xba: p.single + p.double + p.triple + p.home_run,
+            xslg: p.single + 2.0 * p.double + 3.0 * p.triple + 4.0 * p.home_run,
+            xwoba: w.single * p.single + w.double * p.double + w.triple * p.triple + w.home_run * p.home_run,
+        }
+    }
+}
+
+/// Batted balls and hits of each kind
+#[derive(Debug, Default, Copy, Clone)]
+struct Counts {
+    n: u32,
+    single: u32,
+    double: u32,
+    triple: u32,
+    home_run: u32,
+}
+
+impl Counts {
+    fn add (&mut self, result: Option<Event>) {
+        self.n += 1;
+        match result {
+            Some (Event::Single) => self.single += 1,
+            Some (Event::Double) => self.double += 1,
+            Some (Event::Triple) => self.triple += 1,
+            Some (Event::HomeRun) => self.home_run += 1,
+            _ => {},
+        }
+    }
+
+    fn pool (self, other: Counts) -> Counts {
+        Counts {
+            n: self.n + other.n,
+            single: self.single + other.single,
+            double: self.double + other.double,
+            triple: self.triple + other.triple,
+            home_run: self.home_run + other.home_run,
+        }
+    }
+
+    fn probabilities (&self) -> Probabilities {
+        let n = self.n.max(1) as f32;
+        Probabilities {
+            single: self.single as f32 / n,
+            double: self.double as f32 / n,
+            triple: self.triple as f32 / n,
+            home_run: self.home_run as f32 / n,
+        }
+    }
+}
+
+pub struct ExpectedStatsData <'e> {
+    pub pitches: &'e [ExpectedStatsPitch],
+    /// wOBA weights by (sport_id, season). Leagues and seasons without any use DEFAULT_WOBA_WEIGHTS.
+    pub woba_weights: &'e HashMap<(u32, u16), WobaWeights>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct ExpectedStatsOutput {
+    pub bins: Vec<ExpectedOutcomeBin>,
+    pub players: Vec<ExpectedStatsPlayer>,
+}
+
+type Context = (u32, u16);
+
+#[derive(Default)]
+struct ContextCounts {
+    exit_velocity_launch_angle: HashMap<(i16, i16, Option<Direction>), Counts>,
+    batted_ball_type: HashMap<(Trajectory, Option<Hardness>, Option<Direction>), Counts>,
+    league: Counts,
+}
+
+fn bin (sport_id: u32, season: u16, sample_size: u32, smoothed: bool, counts: Counts) -> ExpectedOutcomeBin {
+    let p = counts.probabilities();
+    ExpectedOutcomeBin {
+        sport_id,
+        season,
+        exit_velocity_bin: None,
+        launch_angle_bin: None,
+        trajectory: None,
+        hardness: None,
+        direction: None,
+        sample_size,
+        smoothed,
+        single: p.single,
+        double: p.double,
+        triple: p.triple,
+        home_run: p.home_run,
+    }
+}
+
+/// Pools the bin with its neighbours, one ring at a time, until there are MIN_SAMPLE batted balls
+fn smooth (counts: &HashMap<(i16, i16, Option<Direction>), Counts>, key: (i16, i16, Option<Direction>)) -> (Counts, bool) {
+    let (exit_velocity, launch_angle, direction) = key;
+    let mut pooled = counts[&key];
+    if pooled.n >= MIN_SAMPLE {return (pooled, false)};
+
+    for radius in 1 ..= MAX_RADIUS {
+        for dx in -radius ..= radius {
+            for dy in -radius ..= radius {
+                // Only the new ring, the inside has already been pooled
+                if dx.abs() != radius && dy.abs() != radius {continue};
+                if let Some(neighbour) = counts.get(&(exit_velocity + dx, launch_angle + dy, direction)) {
+                    pooled = pooled.pool(*neighbour);
+                }
+            }
+        }
+        if pooled.n >= MIN_SAMPLE {break};
+    }
+    (pooled, true)
+}
+
+impl ContextCounts {
+    fn bins (&self, sport_id: u32, season: u16) -> Vec<ExpectedOutcomeBin> {
+        let mut bins = vec![bin(sport_id, season, self.league.n, false, self.league)];
+
+        for key in self.exit_velocity_launch_angle.keys() {
+            let (pooled, smoothed) = smooth(&self.exit_velocity_launch_angle, *key);
+            let (exit_velocity, launch_angle, direction) = *key;
+            bins.push(ExpectedOutcomeBin {
+                exit_velocity_bin: Some(exit_velocity),
+                launch_angle_bin: Some(launch_angle),
+                direction,
+                ..bin(sport_id, season, self.exit_velocity_launch_angle[key].n, smoothed, pooled)
+            });
+        }
+
+        // Sparse batted ball types fall back on the same type with less detail, and the model does the same at lookup, so
+        // we just leave them out.
+        for ((trajectory, hardness, direction), counts) in self.batted_ball_type.iter() {
+            let coarsest = hardness.is_none() && direction.is_none();
+            if counts.n < MIN_SAMPLE && !coarsest {continue};
+            bins.push(ExpectedOutcomeBin {
+                trajectory: Some(*trajectory),
+                hardness: *hardness,
+                direction: *direction,
+                ..bin(sport_id, season, counts.n, false, *counts)
+            });
+        }
+
+        // The counts are in hash maps, so sort to always write the bins in the same order
+        bins.sort_by_key(|b| (b.exit_velocity_bin, b.launch_angle_bin, b.trajectory.map(|t| t as u8), b.hardness.map(|h| h as u8), b.direction.map(|d| d as u8)));
+        bins
+    }
+}
+
+/// Player totals while we aggregate
+#[derive(Debug, Default, Copy, Clone)]
+struct PlayerTotals {
+    totals: BattingTotals,
+    batted_balls: u32,
+    xba: f64,
+    xslg: f64,
+    xwobacon: f64,
+}
+
+impl <'e> From<ExpectedStatsData<'e>> for ExpectedStatsOutput {
+    fn from (data: ExpectedStatsData) -> ExpectedStatsOutput {
+
+        // The last pitch of each plate appearance has the result, and the batted ball if there was one
+        let mut plate_appearances: HashMap<(u32, u8), &ExpectedStatsPitch> = HashMap::new();
+        for pitch in data.pitches {
+            let last = plate_appearances.entry((pitch.game_pk, pitch.num_plate_appearance)).or_insert(pitch);
+            if pitch.pitch_num_game > last.pitch_num_game {*last = pitch};
+        }
+
+        // Fit
+        let mut counts: BTreeMap<Context, ContextCounts> = BTreeMap::new();
+        for pitch in plate_appearances.values().filter(|pitch| pitch.in_play == 1) {
+            let context = counts.entry((pitch.sport_id, pitch.game_year)).or_default();
+            let batted_ball = BattedBall::from(*pitch);
+            let direction = batted_ball.direction();
+            let mut directions = vec![None];
+            if direction.is_some() {directions.push(direction)};
+
+            context.league.add(pitch.plate_appearance_result);
+
+            if let Some((exit_velocity, launch_angle)) = batted_ball.exit_velocity_launch_angle() {
+                for direction in directions.iter() {
+                    context.exit_velocity_launch_angle.entry((exit_velocity, launch_angle, *direction)).or_default().add(pitch.plate_appearance_result);
+                }
+            }
+
+            if let Some(trajectory) = batted_ball.trajectory {
+                let mut keys: Vec<(Trajectory, Option<Hardness>, Option<Direction>)> = directions.iter().map(|direction| (trajectory, batted_ball.hardness, *direction)).collect();
+                if batted_ball.hardness.is_some() {keys.push((trajectory, None, None))};
+                for key in keys {
+                    context.batted_ball_type.entry(key).or_default().add(pitch.plate_appearance_result);
+                }
+            }
+        }
+
+        let mut bins: Vec<ExpectedOutcomeBin> = vec![];
+        let mut models: HashMap<Context, ExpectedOutcomes> = HashMap::new();
+        for ((sport_id, season), context) in counts.iter() {
+            let context_bins = context.bins(*sport_id, *season);
+            let woba_weights = *data.woba_weights.get(&(*sport_id, *season)).unwrap_or(&crate::linear_weights::DEFAULT_WOBA_WEIGHTS);
+            models.insert((*sport_id, *season), ExpectedOutcomes::new(&context_bins, woba_weights));
+            bins.extend(context_bins);
+        }
+
+        // Aggregate by player
+        let mut players: BTreeMap<(u32, PlayerRole, u32, u16), PlayerTotals> = BTreeMap::new();
+        for pitch in plate_appearances.values() {
+            let event = match pitch.plate_appearance_result {
+                Some (event) => event,
+                None => continue,
+            };
+
+            let expected = match (pitch.in_play, models.get(&(pitch.sport_id, pitch.game_year))) {
+                (1, Some (model)) => Some(model.expected(&BattedBall::from(*pitch))),
+                _ => None,
+            };
+
+            for (player_id, role) in [(pitch.batter, PlayerRole::Batter), (pitch.pitcher, PlayerRole::Pitcher)] {
+                let player = players.entry((player_id, role, pitch.sport_id, pitch.game_year)).or_default();
+                player.totals.add(event);
+
+                if let Some(expected) = expected {
+                    // Sac bunts aren't in the wOBA denominator, and sac flies aren't at bats
+                    if matches!(event, Event::SacBunt | Event::SacrificeBuntDoublePlay) {continue};
+                    player.batted_balls += 1;
+                    player.xwobacon += expected.xwoba as f64;
+                    if matches!(event, Event::SacFly | Event::SacFlyDoublePlay) {continue};
+                    player.xba += expected.xba as f64;
+                    player.xslg += expected.xslg as f64;
+                }
+            }
+        }
+
+        let players = players.into_iter()
+            .map(|((player_id, role, sport_id, season), player)| {
+                let w = *data.woba_weights.get(&(sport_id, season)).unwrap_or(&crate::linear_weights::DEFAULT_WOBA_WEIGHTS);
+                let totals = player.totals;
+                let at_bats = totals.at_bats as f64;
+                let total_bases = (totals.singles + 2 * totals.doubles + 3 * totals.triples + 4 * totals.home_runs) as f64;
+                let xwoba = w.walk as f64 * totals.walks as f64 + w.hit_by_pitch as f64 * totals.hit_by_pitch as f64 + player.xwobacon;
+
+                ExpectedStatsPlayer {
+                    player_id,
+                    role,
+                    sport_id,
+                    season,
+                    plate_appearances: totals.plate_appearances,
+                    at_bats: totals.at_bats,
+                    batted_balls: player.batted_balls,
+                    ba: ratio(totals.hits as f64, at_bats) as f32,
+                    xba: ratio(player.xba, at_bats) as f32,
+                    slg: ratio(total_bases, at_bats) as f32,
+                    xslg: ratio(player.xslg, at_bats) as f32,
+                    woba: w.woba(&totals) as f32,
+                    xwoba: ratio(xwoba, totals.denominator()) as f32,
+                }
+            })
+            .collect();
+
+        ExpectedStatsOutput {
+            bins,
+            players,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const WEIGHTS: WobaWeights = WobaWeights {walk: 0.5, hit_by_pitch: 0.5, single: 1.0, double: 1.5, triple: 2.0, home_run: 2.5};
+
+    /// A one pitch plate appearance. Batted balls are all 100.5 mph at 20.5 degrees up the middle, bin (50, 10).
+    fn plate_appearance (num_plate_appearance: u8, result: Event) -> ExpectedStatsPitch {
+        let in_play = !matches!(result, Event::Walk | Event::StrikeOut);
+        ExpectedStatsPitch {
+            game_pk: 1,
+            sport_id: 1,
+            game_year: 2021,
+            num_plate_appearance,
+            pitch_num_game: num_plate_appearance as u16,
+            batter: 10,
+            pitcher: 20,
+            batter_bats: SideCode::R,
+            in_play: in_play as u8,
+            plate_appearance_result: Some(result),
+            hit_data_exit_velocity: in_play.then_some(100.5),
+            hit_data_launch_angle: in_play.then_some(20.5),
+            hit_data_spray_angle: in_play.then_some(45.0),
+            hit_data_trajectory: None,
+            hit_data_contact_quality: None,
+        }
+    }
+
+    fn expected_stats () -> ExpectedStatsOutput {
+        let pitches = vec![
+            plate_appearance(1, Event::Single),
+            plate_appearance(2, Event::FlyOut),
+            plate_appearance(3, Event::Double),
+            plate_appearance(4, Event::FlyOut),
+            plate_appearance(5, Event::Walk),
+            plate_appearance(6, Event::StrikeOut),
+        ];
+        let woba_weights = HashMap::from([((1, 2021), WEIGHTS)]);
+        ExpectedStatsData {pitches: &pitches, woba_weights: &woba_weights}.into()
+    }
+
+    #[test]
+    fn bins_in_order () {
+        let bins = expected_stats().bins;
+        let keys: Vec<_> = bins.iter().map(|b| (b.exit_velocity_bin, b.launch_angle_bin, b.direction)).collect();
+
+        // League rates, then the exit velocity/launch angle bin over all directions and up the middle
+        assert_eq!(keys, vec![(None, None, None), (Some(50), Some(10), None), (Some(50), Some(10), Some(Direction::Center))]);
+    }
+
+    #[test]
+    fn binned_rates () {
+        // 4 batted balls, a single and a double. Too few to stand alone, but there's nothing around them to pool with.
+        let bins = expected_stats().bins;
+        let bin = &bins[1];
+
+        assert_eq!(bin.sample_size, 4);
+        assert!(bin.smoothed);
+        assert_eq!((bin.single, bin.double, bin.triple, bin.home_run), (0.25, 0.25, 0.0, 0.0));
+    }
+
+    #[test]
+    fn player_xba_and_xwoba () {
+        let output = expected_stats();
+        let batter = output.players.iter().find(|p| p.player_id == 10 && p.role == PlayerRole::Batter).unwrap();
+
+        // Each batted ball is 0.5 xBA and 0.25 * 1.0 + 0.25 * 1.5 = 0.625 xwOBA. 5 at bats with the strikeout, and the walk is
+        // worth 0.5 in the 6 plate appearance denominator.
+        assert_eq!(batter.at_bats, 5);
+        assert_eq!(batter.batted_balls, 4);
+        assert_eq!(batter.xba, 0.4);
+        assert_eq!(batter.xslg, 0.6);
+        assert_eq!(batter.xwoba, 0.5);
+        assert_eq!(batter.ba, 0.4);
+        assert_eq!(batter.woba, 0.5);
+    }
+}
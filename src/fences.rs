@@ -0,0 +1,394 @@
+//! Outfield fence profiles: the distance and height of the wall every 10 degrees of spray angle, by venue and season.
+//!
+//! There are three sources for the distance, in the order we trust them:
+//!   * What the batted balls say. In each 10 degree slice of the field, the distance that best splits the home runs from the
+//!     fly balls that stayed in the park. We only use it when there are enough of both in the slice.
+//!   * The outfield wall traced from the Gameday svg. The wall is the polyline that spans the field and sits farthest from home
+//!     plate, converted from pixels with the venue season's calibration, see the calibration module.
+//!   * The distances the venue lists, down the lines, to the alleys and to center.
+//!
+//! The height comes from the balls that got to the wall. We fly each one with the flight module, stretched to land where it was
+//! measured, and take the height at the wall that best splits the home runs from the balls that hit the wall. Without enough of
+//! those, the wall is DEFAULT_FENCE_HEIGHT.
+
+use serde::{Serialize, Deserialize};
+use std::collections::{BTreeMap, HashMap};
+use crate::flight::{simulate, Fence, DEFAULT_FENCE_HEIGHT, NEUTRAL};
+use crate::venues::{Venue, VenueXY};
+use crate::calibration::{PixelCalibration, pixel_calibration};
+
+/// The spray angles we profile the fence at, 0 down the 3B line to 90 down the 1B line
+pub const SPRAY_ANGLES: [f32; 10] = [0.0, 10.0, 20.0, 30.0, 40.0, 50.0, 60.0, 70.0, 80.0, 90.0];
+/// Each profile point takes the batted balls within this many degrees
+const SLICE: f64 = 5.0;
+/// Only balls in the air this high are fly balls that might reach the wall
+const MIN_LAUNCH_ANGLE: f32 = 15.0;
+/// Home runs and balls in the park a slice needs before we trust the distance it gives us
+const MIN_HOME_RUNS: usize = 10;
+const MIN_IN_PARK: usize = 10;
+/// Balls that reached the wall and stayed in, before we trust the height
+const MIN_OFF_WALL: usize = 5;
+/// A polyline has to cover this many degrees of the field to be the wall
+const MIN_WALL_SPAN: f32 = 60.0;
+const MIN_FENCE_HEIGHT: f32 = 3.0;
+const MAX_FENCE_HEIGHT: f32 = 40.0;
+
+/// The outfield wall from the venue's svg, in pixels. Empty if the venue has no svg or we couldn't find the wall.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct VenueWall {
+    pub id: u32,
+    pub points: Vec<(f32, f32)>,
+}
+
+/// Spray angle and distance from home plate of a pixel, in the play by play convention
+pub (crate) fn spray_angle (home: (f32, f32), point: (f32, f32)) -> (f32, f32) {
+    let (dx, dy) = (point.0 - home.0, home.1 - point.1);
+    let distance = (dx * dx + dy * dy).sqrt();
+    if distance == 0.0 {return (45.0, 0.0)};
+    (45.0 + dx.atan2(dy).to_degrees(), distance)
+}
+
+impl VenueWall {
+    /// Picks the wall out of the svg polylines, with home plate from VenueXY
+    pub fn new (venue: &VenueXY, polylines: &[Vec<(f32, f32)>]) -> Self {
+        let home = match (venue.x, venue.y) {
+            (Some (x), Some (y)) => (x, y),
+            _ => return VenueWall {id: venue.id, points: vec![]},
+        };
+
+        let wall = polylines.iter()
+            .map(|polyline| (polyline, fair_points(home, 1.0, polyline)))
+            .filter(|(_, points)| points.len() >= 2 && points[points.len() - 1].0 - points[0].0 >= MIN_WALL_SPAN)
+            .max_by(|a, b| median(&a.1).partial_cmp(&median(&b.1)).unwrap())
+            .map(|(polyline, _)| polyline.clone())
+            .unwrap_or_default();
+
+        VenueWall {id: venue.id, points: wall}
+    }
+
+    /// Distance to the wall in feet at a spray angle, None outside the part of the field the polyline covers
+    pub fn distance (&self, spray_angle: f32, calibration: &PixelCalibration) -> Option<f32> {
+        let points = fair_points((calibration.home_x, calibration.home_y), calibration.feet_per_pixel, &self.points);
+        let i = points.iter().position(|point| point.0 >= spray_angle)?;
+        if i == 0 {
+            return if points[0].0 - spray_angle <= SLICE as f32 {Some(points[0].1)} else {None};
+        }
+        let (a, b) = (points[i - 1], points[i]);
+        let share = if b.0 > a.0 {(spray_angle - a.0) / (b.0 - a.0)} else {1.0};
+        Some(a.1 + (b.1 - a.1) * share)
+    }
+}
+
+/// (spray angle, distance) of the points in and just around fair territory, in order of spray angle
+fn fair_points (home: (f32, f32), scale: f32, polyline: &[(f32, f32)]) -> Vec<(f32, f32)> {
+    let mut points: Vec<(f32, f32)> = polyline.iter()
+        .map(|point| spray_angle(home, *point))
+        .filter(|(angle, _)| (-5.0 ..= 95.0).contains(angle))
+        .map(|(angle, distance)| (angle, distance * scale))
+        .collect();
+    points.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap());
+    points
+}
+
+fn median (points: &[(f32, f32)]) -> f32 {
+    let mut distances: Vec<f32> = points.iter().map(|point| point.1).collect();
+    distances.sort_by(|a, b| a.partial_cmp(b).unwrap());
+    distances[distances.len() / 2]
+}
+
+/// The columns from the play by play output we need for fence profiles.
+#[derive(Debug, Clone, Deserialize)]
+pub struct FencePitch {
+    pub venue_id: u32,
+    pub game_year: u16,
+    pub in_play_hr: Option<u8>,
+    pub hit_data_exit_velocity: Option<f32>,
+    pub hit_data_launch_angle: Option<f32>,
+    pub hit_data_total_distance: Option<f32>,
+    pub hit_data_spray_angle: Option<f64>,
+}
+
+pub struct FenceData <'a> {
+    pub pitches: &'a [FencePitch],
+    pub walls: &'a [VenueWall],
+    /// By (venue_id, season)
+    pub venues: &'a HashMap<(u32, u16), Venue>,
+    pub venues_x_y: &'a HashMap<u32, VenueXY>,
+    pub calibrations: &'a HashMap<(u32, Option<u16>), PixelCalibration>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FencePoint {
+    pub spray_angle: f32,
+    pub distance: f32,
+    pub height: f32,
+    pub observed_distance: Option<f32>,
+    pub svg_distance: Option<f32>,
+    pub listed_distance: Option<f32>,
+    pub observed_height: Option<f32>,
+    pub home_runs: u32,
+    pub in_park: u32,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FenceProfile {
+    pub venue_id: u32,
+    pub season: u16,
+    pub points: Vec<FencePoint>,
+}
+
+impl From<&FenceProfile> for Fence {
+    fn from (profile: &FenceProfile) -> Fence {
+        Fence {
+            points: profile.points.iter()
+                .map(|point| (point.spray_angle as f64, point.distance as f64, point.height as f64))
+                .collect(),
+        }
+    }
+}
+
+/// The threshold that misclassifies the fewest of (value, above), halfway between the values either side of it
+pub (crate) fn split (values: &mut [(f64, bool)]) -> Option<f64> {
+    if values.is_empty() {return None};
+    values.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap());
+    // Errors with the threshold under everything: every value that isn't above
+    let mut errors = values.iter().filter(|(_, above)| !above).count();
+    let (mut best, mut best_errors) = (values[0].0, errors);
+    for i in 0 .. values.len() {
+        if values[i].1 {errors += 1} else {errors -= 1};
+        if errors < best_errors {
+            best_errors = errors;
+            best = match values.get(i + 1) {
+                Some (next) => (values[i].0 + next.0) / 2.0,
+                None => values[i].0,
+            };
+        }
+    }
+    Some(best)
+}
+
+#[derive(Debug, Default, Clone)]
+struct Slice <'a> {
+    balls: Vec<&'a FencePitch>,
+}
+
+impl <'a> Slice<'a> {
+    fn observed_distance (&self) -> (Option<f64>, u32, u32) {
+        let mut values: Vec<(f64, bool)> = self.balls.iter()
+            .map(|ball| (ball.hit_data_total_distance.unwrap() as f64, ball.in_play_hr == Some(1)))
+            .collect();
+        let home_runs = values.iter().filter(|(_, hr)| *hr).count();
+        let in_park = values.len() - home_runs;
+        let distance = if home_runs >= MIN_HOME_RUNS && in_park >= MIN_IN_PARK {split(&mut values)} else {None};
+        (distance, home_runs as u32, in_park as u32)
+    }
+
+    /// The wall height that best splits the balls that got to the wall, by how high each one was when it got there
+    fn observed_height (&self, wall: f64) -> Option<f64> {
+        let mut values: Vec<(f64, bool)> = self.balls.iter()
+            .filter(|ball| ball.hit_data_total_distance.unwrap() as f64 >= wall)
+            .filter_map(|ball| {
+                let measured = ball.hit_data_total_distance.unwrap() as f64;
+                let flight = simulate(ball.hit_data_exit_velocity? as f64, ball.hit_data_launch_angle? as f64, ball.hit_data_spray_angle?, &NEUTRAL)?;
+                // Stretch the simulated flight to land where the ball was measured
+                let height = flight.height_at(wall * flight.carry / measured)?;
+                Some((height, ball.in_play_hr == Some(1)))
+            })
+            .collect();
+        let off_wall = values.iter().filter(|(_, hr)| !hr).count();
+        if off_wall < MIN_OFF_WALL {return None};
+        split(&mut values)
+    }
+}
+
+impl <'a> From<FenceData<'a>> for Vec<FenceProfile> {
+    fn from (data: FenceData) -> Vec<FenceProfile> {
+
+        let walls: HashMap<u32, &VenueWall> = data.walls.iter().map(|wall| (wall.id, wall)).collect();
+
+        let mut slices: BTreeMap<(u32, u16), Vec<Slice>> = BTreeMap::new();
+        for (venue_id, season) in data.venues.keys() {
+            slices.insert((*venue_id, *season), vec![Slice::default(); SPRAY_ANGLES.len()]);
+        }
+
+        for pitch in data.pitches {
+            let spray_angle = match (pitch.hit_data_total_distance, pitch.hit_data_spray_angle, pitch.hit_data_launch_angle) {
+                (Some (distance), Some (spray_angle), Some (launch_angle)) if launch_angle >= MIN_LAUNCH_ANGLE && distance > 0.0 => spray_angle,
+                _ => continue,
+            };
+            let venue = slices.entry((pitch.venue_id, pitch.game_year)).or_insert_with(|| vec![Slice::default(); SPRAY_ANGLES.len()]);
+            for (i, angle) in SPRAY_ANGLES.iter().enumerate() {
+                if (spray_angle - *angle as f64).abs() <= SLICE {
+                    venue[i].balls.push(pitch);
+                }
+            }
+        }
+
+        slices.into_iter()
+            .filter_map(|((venue_id, season), slices)| {
+                let listed = data.venues.get(&(venue_id, season)).and_then(Fence::from_venue);
+                let wall = walls.get(&venue_id);
+                let calibration = pixel_calibration(data.calibrations, data.venues_x_y, venue_id, season);
+
+                let points: Vec<FencePoint> = SPRAY_ANGLES.iter().zip(slices.iter())
+                    .map(|(spray_angle, slice)| {
+                        let (observed_distance, home_runs, in_park) = slice.observed_distance();
+                        let svg_distance = wall.and_then(|wall| wall.distance(*spray_angle, &calibration));
+                        let listed_distance = listed.as_ref().map(|fence| fence.at(*spray_angle as f64).0 as f32);
+                        let distance = observed_distance.map(|d| d as f32).or(svg_distance).or(listed_distance);
+                        let observed_height = distance
+                            .and_then(|distance| slice.observed_height(distance as f64))
+                            .map(|height| (height as f32).clamp(MIN_FENCE_HEIGHT, MAX_FENCE_HEIGHT));
+                        FencePoint {
+                            spray_angle: *spray_angle,
+                            distance: distance.unwrap_or(0.0),
+                            height: observed_height.unwrap_or(DEFAULT_FENCE_HEIGHT as f32),
+                            observed_distance: observed_distance.map(|d| d as f32),
+                            svg_distance,
+                            listed_distance,
+                            observed_height,
+                            home_runs,
+                            in_park,
+                        }
+                    })
+                    .collect();
+
+                // A profile is only worth keeping if we know where the whole wall is
+                if points.iter().any(|point| point.distance <= 0.0) {return None};
+                Some(FenceProfile {venue_id, season, points})
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn split_and_wall () {
+        let mut values = vec![(300.0, false), (350.0, false), (390.0, false), (410.0, true), (395.0, true), (430.0, true), (420.0, false)];
+        assert_eq!(split(&mut values), Some(392.5));
+
+        let venue = VenueXY {id: 1, x: Some(125.0), y: Some(200.0)};
+        let polylines = vec![
+            // The baselines
+            vec![(95.0, 170.0), (125.0, 200.0), (155.0, 170.0)],
+            // The wall, 120 pixels out
+            (0 ..= 18).map(|i| {
+                let angle = (i as f32 * 5.0 - 45.0).to_radians();
+                (125.0 + 120.0 * angle.sin(), 200.0 - 120.0 * angle.cos())
+            }).collect(),
+        ];
+        let wall = VenueWall::new(&venue, &polylines);
+        let calibration = PixelCalibration::uncalibrated(1, Some(&venue));
+        let distance = wall.distance(45.0, &calibration).unwrap();
+        assert!((distance - 120.0 * crate::FEET_PER_PIXEL).abs() < 0.01);
+        assert!((wall.distance(0.0, &calibration).unwrap() - distance).abs() < 0.01);
+    }
+
+    fn fly_ball (spray_angle: f64, distance: f32, home_run: bool) -> FencePitch {
+        FencePitch {
+            venue_id: 1,
+            game_year: 2023,
+            in_play_hr: Some(home_run as u8),
+            hit_data_exit_velocity: Some(100.0),
+            hit_data_launch_angle: Some(30.0),
+            hit_data_total_distance: Some(distance),
+            hit_data_spray_angle: Some(spray_angle),
+        }
+    }
+
+    fn fly_balls (n: usize, spray_angle: f64, distance: f32, home_run: bool) -> Vec<FencePitch> {
+        vec![fly_ball(spray_angle, distance, home_run); n]
+    }
+
+    #[test]
+    fn distance_sources_in_order () {
+        let pitches: Vec<FencePitch> = [
+            // Enough of both to trust the split at 0 degrees
+            fly_balls(MIN_HOME_RUNS, 0.0, 380.0, true),
+            fly_balls(MIN_IN_PARK, 0.0, 320.0, false),
+            // One short of enough in the park, then one short of enough home runs
+            fly_balls(MIN_HOME_RUNS, 80.0, 380.0, true),
+            fly_balls(MIN_IN_PARK - 1, 80.0, 320.0, false),
+            fly_balls(MIN_HOME_RUNS - 1, 90.0, 380.0, true),
+            fly_balls(MIN_IN_PARK, 90.0, 320.0, false),
+            // Too low to reach the wall
+            vec![FencePitch {hit_data_launch_angle: Some(5.0), ..fly_ball(40.0, 380.0, true)}],
+        ].concat();
+
+        // The svg wall is 100 pixels out from 24 to 64 degrees
+        let venue_x_y = VenueXY {id: 1, x: Some(125.0), y: Some(200.0)};
+        let wall = VenueWall {
+            id: 1,
+            points: (5 ..= 13).map(|i| {
+                let angle = (i as f32 * 5.0 - 46.0).to_radians();
+                (125.0 + 100.0 * angle.sin(), 200.0 - 100.0 * angle.cos())
+            }).collect(),
+        };
+        let listed = Venue {
+            venue_left_line: Some(330), venue_left: Some(330), venue_left_center: Some(330), venue_center: Some(330),
+            venue_right_center: Some(330), venue_right: Some(330), venue_right_line: Some(330),
+            ..Venue::default()
+        };
+        // Venue 2 has nothing, so we can't place its wall
+        let venues = HashMap::from([((1, 2023), listed), ((2, 2023), Venue::default())]);
+        let venues_x_y = HashMap::from([(1, venue_x_y)]);
+
+        let profiles: Vec<FenceProfile> = FenceData {
+            pitches: &pitches,
+            walls: &[wall],
+            venues: &venues,
+            venues_x_y: &venues_x_y,
+            calibrations: &HashMap::new(),
+        }.into();
+        assert_eq!(profiles.len(), 1);
+        assert_eq!(profiles[0].venue_id, 1);
+        let points = &profiles[0].points;
+
+        // Observed over everything else. None of the home runs reached the wall, so it's the default height.
+        assert_eq!((points[0].distance, points[0].observed_distance, points[0].svg_distance, points[0].listed_distance), (350.0, Some(350.0), None, Some(330.0)));
+        assert_eq!((points[0].home_runs, points[0].in_park), (MIN_HOME_RUNS as u32, MIN_IN_PARK as u32));
+        assert_eq!((points[0].height, points[0].observed_height), (DEFAULT_FENCE_HEIGHT as f32, None));
+        // The svg where it covers the field, then the listed distances
+        for point in points[1 ..].iter() {
+            match point.svg_distance {
+                Some (svg_distance) => {
+                    assert_eq!(point.distance, svg_distance);
+                    assert!((svg_distance - 100.0 * crate::FEET_PER_PIXEL).abs() < 1.0);
+                },
+                None => assert_eq!(point.distance, 330.0),
+            }
+        }
+        let svg: Vec<f32> = points.iter().filter(|point| point.svg_distance.is_some()).map(|point| point.spray_angle).collect();
+        assert_eq!(svg, vec![20.0, 30.0, 40.0, 50.0, 60.0]);
+        assert_eq!((points[4].home_runs, points[4].in_park), (0, 0));
+
+        assert_eq!((points[8].observed_distance, points[8].home_runs, points[8].in_park), (None, MIN_HOME_RUNS as u32, MIN_IN_PARK as u32 - 1));
+        assert_eq!((points[9].observed_distance, points[9].home_runs, points[9].in_park), (None, MIN_HOME_RUNS as u32 - 1, MIN_IN_PARK as u32));
+    }
+
+    #[test]
+    fn height_splits_the_balls_at_the_wall () {
+        let wall = 330.0;
+        let off_wall = FencePitch {hit_data_exit_velocity: Some(95.0), hit_data_launch_angle: Some(25.0), ..fly_ball(45.0, 340.0, false)};
+        let home_run = FencePitch {hit_data_exit_velocity: Some(105.0), ..fly_ball(45.0, 400.0, true)};
+        let short = fly_ball(45.0, 300.0, false);
+
+        // How high each one was at the wall, with its flight stretched to land where it was measured
+        let height = |ball: &FencePitch| {
+            let flight = simulate(ball.hit_data_exit_velocity.unwrap() as f64, ball.hit_data_launch_angle.unwrap() as f64, 45.0, &NEUTRAL).unwrap();
+            flight.height_at(wall * flight.carry / ball.hit_data_total_distance.unwrap() as f64).unwrap()
+        };
+
+        let mut balls = vec![&short];
+        balls.extend(vec![&off_wall; MIN_OFF_WALL]);
+        balls.extend(vec![&home_run; 3]);
+        assert_eq!(Slice {balls: balls.clone()}.observed_height(wall), Some((height(&off_wall) + height(&home_run)) / 2.0));
+
+        // The short ball never got to the wall, so it doesn't make up for a missing ball off the wall
+        balls.remove(1);
+        assert_eq!(Slice {balls}.observed_height(wall), None);
+    }
+}
@@ -0,0 +1,392 @@
+//! Outs above expected for fielders.
+//!
+//! Built on the defense table, which lays each batted ball out as one row per fielder. A batted ball is charged to the fielder who
+//! fielded it (fielded_by_id), so we keep the one row per ball where the fielder is the one who fielded it, at the position they were
+//! playing. Balls nobody fielded and home runs aren't opportunities for anyone.
+//!
+//! The out probability for a batted ball comes from binned out rates on:
+//!   * trajectory
+//!   * spray angle, 0 is the third base line and 90 the first base line
+//!   * tracked distance. The hit coordinates mark where the ball was fielded, which gives the outcome away (a ball fielded 300 feet out
+//!     on the ground is a hit), so we use the total distance instead
+//!   * hang time, a proxy from exit velocity and launch angle. It's the flight time with no drag, so it overstates the real hang time,
+//!     but it ranks the batted balls in the same order. Ground balls have no hang time.
+//!
+//! Like the called strike model, every level is shrunk towards the level above it with RE288_MIN_SAMPLE pseudo-counts. The batted
+//! ball levels go trajectory -> + spray angle -> + distance -> + hang time over all of our data, then by sport_id and then season.
+//! Errors are not outs, so they count against the fielder.
+//!
+//! Outs above expected are converted to runs with the linear weights for the sport_id and season: the difference between the
+//! average non-out on the fielded balls and an out.
+
+use serde::{Serialize, Deserialize};
+use std::collections::{BTreeMap, HashMap};
+use crate::boxscore::Pos;
+use crate::linear_weights::LinearWeights;
+use crate::play_by_play::{Event, Trajectory};
+use crate::run_expectancy::RE288_MIN_SAMPLE;
+use crate::schedule::GameDate;
+
+const SPRAY_ANGLE_BIN: f64 = 5.0;
+/// Feet
+const DISTANCE_BIN: f64 = 15.0;
+/// Seconds
+const HANG_TIME_BIN: f64 = 0.5;
+/// Height of contact in feet, for the hang time
+const CONTACT_HEIGHT: f64 = 3.0;
+const GRAVITY: f64 = 32.174;
+const MPH_TO_FEET_PER_SECOND: f64 = 5280.0 / 3600.0;
+/// Runs per out above expected when we don't have linear weights for the season
+const DEFAULT_RUNS_PER_OUT: f64 = 0.8;
+
+/// The columns from the defense table we need for fielding.
+#[derive(Debug, Clone, Deserialize)]
+pub struct FieldingDefense {
+    pub game_date: String,
+    pub sport_id: u32,
+    pub fielder: u32,
+    pub fielder_name: String,
+    pub position: Pos,
+    pub fielded_by_id: Option<u32>,
+    pub in_play_result: Option<Event>,
+    pub hit_data_trajectory: Option<Trajectory>,
+    pub hit_data_exit_velocity: Option<f32>,
+    pub hit_data_launch_angle: Option<f32>,
+    pub hit_data_spray_angle: Option<f64>,
+    pub hit_data_total_distance: Option<f64>,
+}
+
+/// A batted ball, as the model sees it. Distance is in feet.
+#[derive(Debug, Copy, Clone)]
+pub struct FieldedBall {
+    pub sport_id: u32,
+    pub season: u16,
+    pub trajectory: Trajectory,
+    pub spray_angle: Option<f64>,
+    pub distance: Option<f64>,
+    pub exit_velocity: Option<f64>,
+    pub launch_angle: Option<f64>,
+}
+
+impl FieldedBall {
+    fn from_defense (row: &FieldingDefense, season: u16) -> Option<FieldedBall> {
+        Some(FieldedBall {
+            sport_id: row.sport_id,
+            season,
+            trajectory: row.hit_data_trajectory?,
+            spray_angle: row.hit_data_spray_angle,
+            distance: row.hit_data_total_distance,
+            exit_velocity: row.hit_data_exit_velocity.map(|ev| ev as f64),
+            launch_angle: row.hit_data_launch_angle.map(|la| la as f64),
+        })
+    }
+
+    /// Flight time with no drag, from contact height back down to the ground
+    pub fn hang_time (&self) -> Option<f64> {
+        let (exit_velocity, launch_angle) = (self.exit_velocity?, self.launch_angle?);
+        if launch_angle <= 0.0 {return Some(0.0)};
+        let vertical = exit_velocity * MPH_TO_FEET_PER_SECOND * launch_angle.to_radians().sin();
+        Some((vertical + (vertical * vertical + 2.0 * GRAVITY * CONTACT_HEIGHT).sqrt()) / GRAVITY)
+    }
+}
+
+/// Raw counts for one bin at one level of the model. Anything the level pools over is None.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FieldingBin {
+    pub sport_id: Option<u32>,
+    pub season: Option<u16>,
+    pub trajectory: Trajectory,
+    pub spray_angle_bin: Option<i16>,
+    pub distance_bin: Option<i16>,
+    pub hang_time_bin: Option<i16>,
+    pub batted_balls: u32,
+    pub outs: u32,
+}
+
+type BinKey = (Option<u32>, Option<u16>, Trajectory, Option<i16>, Option<i16>, Option<i16>);
+
+/// Every level of the model a batted ball belongs to, from the most pooled to the least. A level whose feature is missing is
+/// left out, along with every level after it.
+fn levels (ball: &FieldedBall) -> Vec<BinKey> {
+    let spray_angle = ball.spray_angle.map(|s| (s / SPRAY_ANGLE_BIN).floor() as i16);
+    let distance = ball.distance.map(|d| (d / DISTANCE_BIN).floor() as i16);
+    let hang_time = ball.hang_time().map(|h| (h / HANG_TIME_BIN).floor() as i16);
+    let trajectory = ball.trajectory;
+
+    let features = [spray_angle, distance, hang_time];
+    let mut key = [None; 3];
+    let mut levels = vec![(None, None, trajectory, None, None, None)];
+    for (index, feature) in features.iter().enumerate().take_while(|(_, f)| f.is_some()) {
+        key[index] = *feature;
+        levels.push((None, None, trajectory, key[0], key[1], key[2]));
+    }
+    let [spray_angle, distance, hang_time] = key;
+    levels.push((Some(ball.sport_id), None, trajectory, spray_angle, distance, hang_time));
+    levels.push((Some(ball.sport_id), Some(ball.season), trajectory, spray_angle, distance, hang_time));
+    levels
+}
+
+/// The fitted out probability model, ready for lookups.
+#[derive(Debug, Clone, Default)]
+pub struct FieldingModel {
+    bins: HashMap<BinKey, (u32, u32)>,
+    league: f64,
+}
+
+impl FieldingModel {
+    pub fn new (bins: &[FieldingBin]) -> FieldingModel {
+        let bins: HashMap<BinKey, (u32, u32)> = bins.iter()
+            .map(|b| ((b.sport_id, b.season, b.trajectory, b.spray_angle_bin, b.distance_bin, b.hang_time_bin), (b.batted_balls, b.outs)))
+            .collect();
+
+        // The trajectory bins over all of our data hold every batted ball exactly once
+        let (batted_balls, outs) = bins.iter()
+            .filter(|(k, _)| k.0.is_none() && k.3.is_none())
+            .fold((0u32, 0u32), |acc, (_, (n, o))| (acc.0 + n, acc.1 + o));
+
+        FieldingModel {
+            bins,
+            league: if batted_balls == 0 {0.7} else {outs as f64 / batted_balls as f64},
+        }
+    }
+
+    /// Probability the batted ball is turned into an out
+    pub fn probability (&self, ball: &FieldedBall) -> f64 {
+        let k = RE288_MIN_SAMPLE as f64;
+        let mut probability = self.league;
+        for key in levels(ball).iter() {
+            if let Some((batted_balls, outs)) = self.bins.get(key) {
+                probability = (*outs as f64 + k * probability) / (*batted_balls as f64 + k);
+            }
+        }
+        probability
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FieldingPlayer {
+    pub fielder: u32,
+    pub fielder_name: String,
+    pub position: Pos,
+    pub sport_id: u32,
+    pub season: u16,
+    pub opportunities: u32,
+    pub outs: u32,
+    pub expected_outs: f32,
+    pub outs_above_expected: f32,
+    pub runs_per_out: f32,
+    pub runs: f32,
+}
+
+pub struct FieldingData <'f> {
+    pub defense: &'f [FieldingDefense],
+    pub linear_weights: &'f HashMap<(u32, u16), LinearWeights>,
+}
+
+pub struct FieldingOutput {
+    pub bins: Vec<FieldingBin>,
+    pub players: Vec<FieldingPlayer>,
+}
+
+/// (fielder_name, position, opportunities, outs, expected_outs)
+type PlayerTotals = (String, Pos, u32, u32, f64);
+
+fn is_out (event: Option<Event>) -> bool {
+    matches!(event,
+        Some (Event::FieldOut) | Some (Event::FlyOut) | Some (Event::GroundOut) | Some (Event::LineOut) | Some (Event::PopOut) |
+        Some (Event::ForceOut) | Some (Event::DoublePlay) | Some (Event::GroundedIntoDoublePlay) | Some (Event::TriplePlay) |
+        Some (Event::FieldersChoice) | Some (Event::SacFly) | Some (Event::SacFlyDoublePlay) | Some (Event::SacBunt) |
+        Some (Event::SacrificeBuntDoublePlay) | Some (Event::BuntGroundOut) | Some (Event::BuntPopOut)
+    )
+}
+
+/// Run value of a non-out over an out. Errors are valued as singles.
+fn runs_per_out (weights: Option<&LinearWeights>, non_outs: &HashMap<Event, u32>) -> f64 {
+    let weights = match weights {
+        Some (weights) => weights,
+        None => return DEFAULT_RUNS_PER_OUT,
+    };
+    let (total, n) = non_outs.iter().fold((0f64, 0u32), |(total, n), (event, count)| {
+        let value = match event {
+            Event::Double => weights.run_value_double,
+            Event::Triple => weights.run_value_triple,
+            _ => weights.run_value_single,
+        };
+        (total + value as f64 * *count as f64, n + count)
+    });
+    if n == 0 {return DEFAULT_RUNS_PER_OUT};
+    total / n as f64 - weights.run_value_out as f64
+}
+
+impl <'f> From<FieldingData<'f>> for FieldingOutput {
+    fn from (data: FieldingData) -> FieldingOutput {
+
+        // Only the row for the fielder who fielded the ball, so each batted ball is counted once
+        let balls: Vec<(&FieldingDefense, FieldedBall, bool)> = data.defense.iter()
+            .filter(|row| row.fielded_by_id == Some(row.fielder))
+            .filter(|row| row.in_play_result != Some(Event::HomeRun))
+            .filter_map(|row| {
+                let season = GameDate::from(row.game_date.clone()).year;
+                FieldedBall::from_defense(row, season).map(|b| (row, b, is_out(row.in_play_result)))
+            })
+            .collect();
+
+        let mut counts: HashMap<BinKey, (u32, u32)> = HashMap::new();
+        let mut non_outs: HashMap<(u32, u16), HashMap<Event, u32>> = HashMap::new();
+        for (row, ball, out) in balls.iter() {
+            for key in levels(ball) {
+                let (batted_balls, outs) = counts.entry(key).or_default();
+                *batted_balls += 1;
+                if *out {*outs += 1};
+            }
+            if let (false, Some (event)) = (out, row.in_play_result) {
+                *non_outs.entry((ball.sport_id, ball.season)).or_default().entry(event).or_default() += 1;
+            }
+        }
+
+        let mut bins: Vec<FieldingBin> = counts.into_iter()
+            .map(|((sport_id, season, trajectory, spray_angle_bin, distance_bin, hang_time_bin), (batted_balls, outs))| FieldingBin {
+                sport_id, season, trajectory, spray_angle_bin, distance_bin, hang_time_bin, batted_balls, outs,
+            })
+            .collect();
+        // Sorted so the output is always in the same order
+        bins.sort_by_key(|b| (b.sport_id, b.season, b.trajectory as u8, b.spray_angle_bin, b.distance_bin, b.hang_time_bin));
+        let model = FieldingModel::new(&bins);
+
+        let empty = HashMap::new();
+        let mut players: BTreeMap<(u32, u32, u16, u8), PlayerTotals> = BTreeMap::new();
+        for (row, ball, out) in balls.iter() {
+            let (_, _, opportunities, outs, expected_outs) = players
+                .entry((row.fielder, ball.sport_id, ball.season, row.position as u8))
+                .or_insert((row.fielder_name.clone(), row.position, 0, 0, 0.0));
+            *opportunities += 1;
+            if *out {*outs += 1};
+            *expected_outs += model.probability(ball);
+        }
+
+        let players: Vec<FieldingPlayer> = players.into_iter()
+            .map(|((fielder, sport_id, season, _), (fielder_name, position, opportunities, outs, expected_outs))| {
+                let runs_per_out = runs_per_out(
+                    data.linear_weights.get(&(sport_id, season)),
+                    non_outs.get(&(sport_id, season)).unwrap_or(&empty),
+                );
+                let outs_above_expected = outs as f64 - expected_outs;
+                FieldingPlayer {
+                    fielder,
+                    fielder_name,
+                    position,
+                    sport_id,
+                    season,
+                    opportunities,
+                    outs,
+                    expected_outs: expected_outs as f32,
+                    outs_above_expected: outs_above_expected as f32,
+                    runs_per_out: runs_per_out as f32,
+                    runs: (outs_above_expected * runs_per_out) as f32,
+                }
+            })
+            .collect();
+
+        FieldingOutput {
+            bins,
+            players,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn row (fielder: u32, fielded_by_id: u32, position: Pos, in_play_result: Event) -> FieldingDefense {
+        FieldingDefense {
+            game_date: "2021-06-01".to_string(),
+            sport_id: 1,
+            fielder,
+            fielder_name: "".to_string(),
+            position,
+            fielded_by_id: Some(fielded_by_id),
+            in_play_result: Some(in_play_result),
+            hit_data_trajectory: Some(Trajectory::FlyBall),
+            hit_data_exit_velocity: Some(95.0),
+            hit_data_launch_angle: Some(28.0),
+            hit_data_spray_angle: Some(45.0),
+            hit_data_total_distance: Some(330.0),
+        }
+    }
+
+    fn fielding (defense: &[FieldingDefense]) -> FieldingOutput {
+        FieldingData {defense, linear_weights: &HashMap::new()}.into()
+    }
+
+    #[test]
+    fn ball_goes_to_the_fielder_who_fielded_it () {
+        // One fly ball to center, with the shortstop's row from the same play
+        let output = fielding(&[
+            row(6, 8, Pos::ShortStop, Event::FlyOut),
+            row(8, 8, Pos::CenterField, Event::FlyOut),
+        ]);
+
+        assert_eq!(output.players.len(), 1);
+        assert_eq!(output.players[0].fielder, 8);
+        assert_eq!(output.players[0].position, Pos::CenterField);
+        assert_eq!(output.players[0].season, 2021);
+        assert_eq!(output.players[0].opportunities, 1);
+    }
+
+    #[test]
+    fn outs_above_expected_against_the_bin_rate () {
+        // The same fly ball twice, caught once. Every bin is 1 out in 2, so each ball is expected to be caught half the time.
+        let output = fielding(&[
+            row(1, 1, Pos::CenterField, Event::FlyOut),
+            row(2, 2, Pos::CenterField, Event::Single),
+        ]);
+        let player = |id: u32| output.players.iter().find(|p| p.fielder == id).unwrap();
+
+        assert_eq!(player(1).expected_outs, 0.5);
+        assert_eq!(player(1).outs_above_expected, 0.5);
+        assert_eq!(player(2).outs_above_expected, -0.5);
+        assert_eq!(player(1).runs, (0.5 * DEFAULT_RUNS_PER_OUT) as f32);
+    }
+
+    #[test]
+    fn one_bin_per_level () {
+        // Trajectory, + spray angle, + distance, + hang time, then by sport_id and season
+        let output = fielding(&[row(1, 1, Pos::CenterField, Event::FlyOut)]);
+        assert_eq!(output.bins.len(), 6);
+    }
+
+    #[test]
+    fn levels_stop_at_a_missing_feature () {
+        let mut no_distance = row(1, 1, Pos::CenterField, Event::FlyOut);
+        no_distance.hit_data_total_distance = None;
+        let output = fielding(&[no_distance]);
+
+        // Trajectory, + spray angle, by sport_id and by season, none of them with a distance or hang time bin
+        assert_eq!(output.bins.len(), 4);
+        assert!(output.bins.iter().all(|b| b.distance_bin.is_none() && b.hang_time_bin.is_none()));
+    }
+
+    #[test]
+    fn home_runs_are_not_opportunities () {
+        let output = fielding(&[row(8, 8, Pos::CenterField, Event::HomeRun)]);
+        assert!(output.players.is_empty());
+        assert!(output.bins.is_empty());
+    }
+
+    #[test]
+    fn hang_time () {
+        let mut ball = FieldedBall::from_defense(&row(1, 1, Pos::CenterField, Event::FlyOut), 2021).unwrap();
+
+        ball.launch_angle = Some(-5.0);
+        assert_eq!(ball.hang_time(), Some(0.0));
+
+        // Straight up at 10 mph: 14.67 ft/s up from 3 feet, (v + sqrt(v^2 + 2 g h)) / g
+        ball.launch_angle = Some(90.0);
+        ball.exit_velocity = Some(10.0);
+        assert!((ball.hang_time().unwrap() - 1.0838).abs() < 1e-4);
+
+        ball.exit_velocity = None;
+        assert_eq!(ball.hang_time(), None);
+    }
+}
@@ -0,0 +1,297 @@
+//! Batted ball flight: a point mass with drag and Magnus lift, stepped through the air from contact to the ground.
+//!
+//! The drag and lift coefficients are the ones Dr. Nathan's trajectory calculator uses, and the lift model is the same one the
+//! nathan module inverts for the pitches: CL = 1 / (2.32 + 0.4 / S), with S the spin factor. We don't get spin on batted balls,
+//! so backspin is a rough fit on launch angle, pointed straight back along the initial direction of the ball, and decays with time.
+//!
+//! Air density comes from the temperature, the elevation of the park and the humidity. The boxscore doesn't report humidity, so
+//! we use 50% unless told otherwise. The boxscore reports the wind relative to the field (out to center, left to right and so
+//! on), so the orientation of the park is already baked in and we only need to turn it into a vector along the field. Calm,
+//! indoor and varying wind count as no wind.
+//!
+//! Coordinates are in the field's frame: x is toward the right field side, square to the line from home to center, y is toward
+//! center field and z is up. Spray angles are the play by play convention, 0 down the 3B line and 90 down the 1B line.
+
+use crate::boxscore::WindDirection;
+use crate::venues::Venue;
+
+/// Neutral conditions, 70 degrees at sea level with no wind
+pub const NEUTRAL: Conditions = Conditions {
+    temp_f: 70.0,
+    elevation_ft: 0.0,
+    relative_humidity: 0.5,
+    wind_speed_mph: 0.0,
+    wind_direction: None,
+};
+
+/// Balls hit lower than this aren't simulated, the flight is too short for the conditions to matter
+pub const MIN_LAUNCH_ANGLE: f64 = 10.0;
+/// Fence height when we don't know better, in feet
+pub const DEFAULT_FENCE_HEIGHT: f64 = 8.0;
+
+const MASS: f64 = 0.145;
+const RADIUS: f64 = 0.0366;
+const G: f64 = 9.80665;
+const CD0: f64 = 0.3008;
+/// Extra drag per 1000 rpm of spin
+const CD_SPIN: f64 = 0.0292;
+/// Spin decay time constant, in seconds
+const TAU: f64 = 25.0;
+/// Height of contact, in meters
+const CONTACT_HEIGHT: f64 = 0.914;
+const DT: f64 = 0.005;
+const MAX_TIME: f64 = 15.0;
+
+const FEET_PER_METER: f64 = 3.28084;
+const MPS_PER_MPH: f64 = 0.44704;
+
+#[derive(Debug, Copy, Clone)]
+pub struct Conditions {
+    pub temp_f: f64,
+    pub elevation_ft: f64,
+    pub relative_humidity: f64,
+    pub wind_speed_mph: f64,
+    pub wind_direction: Option<WindDirection>,
+}
+
+impl Conditions {
+    /// Game conditions from the boxscore, falling back to neutral for anything missing
+    pub fn new (temp_f: Option<f32>, elevation_ft: Option<f32>, wind_speed_mph: Option<u8>, wind_direction: Option<WindDirection>) -> Self {
+        Conditions {
+            temp_f: temp_f.map(|t| t as f64).unwrap_or(NEUTRAL.temp_f),
+            elevation_ft: elevation_ft.map(|e| e as f64).unwrap_or(NEUTRAL.elevation_ft),
+            relative_humidity: NEUTRAL.relative_humidity,
+            wind_speed_mph: wind_speed_mph.map(|w| w as f64).unwrap_or(NEUTRAL.wind_speed_mph),
+            wind_direction,
+        }
+    }
+
+    /// kg/m^3, dry air and water vapour as ideal gases at the standard atmosphere pressure for the elevation
+    pub fn air_density (&self) -> f64 {
+        let temp_c = (self.temp_f - 32.0) * 5.0 / 9.0;
+        let temp_k = temp_c + 273.15;
+        let pressure = 101_325.0 * (1.0 - 2.25577e-5 * self.elevation_ft / FEET_PER_METER).powf(5.25588);
+        let saturation = 610.78 * 10f64.powf(7.5 * temp_c / (temp_c + 237.3));
+        let vapour = self.relative_humidity * saturation;
+        (pressure - vapour) / (287.058 * temp_k) + vapour / (461.495 * temp_k)
+    }
+
+    /// Where the wind is blowing to in the field's frame, in m/s
+    pub fn wind (&self) -> (f64, f64) {
+        let direction: Option<(f64, f64)> = match self.wind_direction {
+            Some (WindDirection::OutToCF) => Some((0.0, 1.0)),
+            Some (WindDirection::InFromCF) => Some((0.0, -1.0)),
+            Some (WindDirection::OutToLF) => Some(horizontal(-30.0)),
+            Some (WindDirection::InFromLF) => Some(horizontal(150.0)),
+            Some (WindDirection::OutToRF) => Some(horizontal(30.0)),
+            Some (WindDirection::InFromRF) => Some(horizontal(-150.0)),
+            Some (WindDirection::LTOR) => Some((1.0, 0.0)),
+            Some (WindDirection::RTOL) => Some((-1.0, 0.0)),
+            _ => None,
+        };
+        match direction {
+            Some ((x, y)) => (x * self.wind_speed_mph * MPS_PER_MPH, y * self.wind_speed_mph * MPS_PER_MPH),
+            None => (0.0, 0.0),
+        }
+    }
+}
+
+/// Unit vector along the ground, degrees from the line to center field, positive toward right field
+fn horizontal (degrees: f64) -> (f64, f64) {
+    let radians = degrees.to_radians();
+    (radians.sin(), radians.cos())
+}
+
+/// Backspin in rpm, a rough fit on launch angle
+fn backspin (launch_angle: f64) -> f64 {
+    (1000.0 + 50.0 * launch_angle).clamp(500.0, 3500.0)
+}
+
+/// The simulated flight, sampled every step, in feet from home plate
+#[derive(Debug, Clone)]
+pub struct Flight {
+    /// (x, y, z) in feet
+    pub points: Vec<(f64, f64, f64)>,
+    /// Horizontal distance from home plate where the ball lands, in feet
+    pub carry: f64,
+    pub hang_time: f64,
+}
+
+/// Exit velocity in mph, launch and spray angles in degrees. None for balls under MIN_LAUNCH_ANGLE.
+pub fn simulate (exit_velocity: f64, launch_angle: f64, spray_angle: f64, conditions: &Conditions) -> Option<Flight> {
+    if launch_angle < MIN_LAUNCH_ANGLE || exit_velocity <= 0.0 {return None};
+
+    let k = conditions.air_density() * std::f64::consts::PI * RADIUS * RADIUS / (2.0 * MASS);
+    let (wind_x, wind_y) = conditions.wind();
+    let (dx, dy) = horizontal(spray_angle - 45.0);
+    // Backspin, the axis is square to the direction of the ball, so the lift is up and back
+    let axis = (dy, -dx, 0.0);
+    let omega_0 = backspin(launch_angle) * std::f64::consts::PI / 30.0;
+
+    let acceleration = |v: (f64, f64, f64), t: f64| -> (f64, f64, f64) {
+        let relative = (v.0 - wind_x, v.1 - wind_y, v.2);
+        let speed = (relative.0 * relative.0 + relative.1 * relative.1 + relative.2 * relative.2).sqrt();
+        if speed == 0.0 {return (0.0, 0.0, -G)};
+        let omega = omega_0 * (-t / TAU).exp();
+        let s = RADIUS * omega / speed;
+        let cd = CD0 + CD_SPIN * omega * 30.0 / std::f64::consts::PI / 1000.0;
+        let cl = 1.0 / (2.32 + 0.4 / s);
+        // axis x relative velocity
+        let cross = (
+            axis.1 * relative.2 - axis.2 * relative.1,
+            axis.2 * relative.0 - axis.0 * relative.2,
+            axis.0 * relative.1 - axis.1 * relative.0,
+        );
+        (
+            -k * cd * speed * relative.0 + k * cl * speed * cross.0,
+            -k * cd * speed * relative.1 + k * cl * speed * cross.1,
+            -k * cd * speed * relative.2 + k * cl * speed * cross.2 - G,
+        )
+    };
+
+    let speed = exit_velocity * MPS_PER_MPH;
+    let (sin_la, cos_la) = launch_angle.to_radians().sin_cos();
+    let mut v = (speed * cos_la * dx, speed * cos_la * dy, speed * sin_la);
+    let mut p = (0.0, 0.0, CONTACT_HEIGHT);
+    let mut t = 0.0;
+    let mut points = vec![feet(p)];
+
+    // Fourth order Runge-Kutta, the acceleration doesn't depend on the position
+    while t < MAX_TIME {
+        let a1 = acceleration(v, t);
+        let v2 = add(v, a1, DT / 2.0);
+        let a2 = acceleration(v2, t + DT / 2.0);
+        let v3 = add(v, a2, DT / 2.0);
+        let a3 = acceleration(v3, t + DT / 2.0);
+        let v4 = add(v, a3, DT);
+        let a4 = acceleration(v4, t + DT);
+
+        let next_p = (
+            p.0 + DT / 6.0 * (v.0 + 2.0 * v2.0 + 2.0 * v3.0 + v4.0),
+            p.1 + DT / 6.0 * (v.1 + 2.0 * v2.1 + 2.0 * v3.1 + v4.1),
+            p.2 + DT / 6.0 * (v.2 + 2.0 * v2.2 + 2.0 * v3.2 + v4.2),
+        );
+        v = (
+            v.0 + DT / 6.0 * (a1.0 + 2.0 * a2.0 + 2.0 * a3.0 + a4.0),
+            v.1 + DT / 6.0 * (a1.1 + 2.0 * a2.1 + 2.0 * a3.1 + a4.1),
+            v.2 + DT / 6.0 * (a1.2 + 2.0 * a2.2 + 2.0 * a3.2 + a4.2),
+        );
+
+        if next_p.2 <= 0.0 {
+            // Land the ball between the last two steps
+            let share = p.2 / (p.2 - next_p.2);
+            let landing = (p.0 + (next_p.0 - p.0) * share, p.1 + (next_p.1 - p.1) * share, 0.0);
+            points.push(feet(landing));
+            let carry = (landing.0 * landing.0 + landing.1 * landing.1).sqrt() * FEET_PER_METER;
+            return Some(Flight {points, carry, hang_time: t + DT * share});
+        }
+
+        p = next_p;
+        t += DT;
+        points.push(feet(p));
+    }
+    None
+}
+
+fn add (v: (f64, f64, f64), a: (f64, f64, f64), dt: f64) -> (f64, f64, f64) {
+    (v.0 + a.0 * dt, v.1 + a.1 * dt, v.2 + a.2 * dt)
+}
+
+fn feet (p: (f64, f64, f64)) -> (f64, f64, f64) {
+    (p.0 * FEET_PER_METER, p.1 * FEET_PER_METER, p.2 * FEET_PER_METER)
+}
+
+/// Spray angle, distance and height of the wall, in order of spray angle
+#[derive(Debug, Clone)]
+pub struct Fence {
+    pub points: Vec<(f64, f64, f64)>,
+}
+
+impl Fence {
+    /// From the listed distances down the lines, to the alleys and to center, spaced every 15 degrees, with the default height.
+    /// None if the venue doesn't list at least the lines and center.
+    pub fn from_venue (venue: &Venue) -> Option<Fence> {
+        let distances = [
+            venue.venue_left_line,
+            venue.venue_left,
+            venue.venue_left_center,
+            venue.venue_center,
+            venue.venue_right_center,
+            venue.venue_right,
+            venue.venue_right_line,
+        ];
+        if distances[0].is_none() || distances[3].is_none() || distances[6].is_none() {return None};
+        let points = distances.iter().enumerate()
+            .filter_map(|(i, distance)| distance.map(|distance| (i as f64 * 15.0, distance as f64, DEFAULT_FENCE_HEIGHT)))
+            .collect();
+        Some(Fence {points})
+    }
+
+    /// Distance and height of the wall at a spray angle, linear between the points
+    pub fn at (&self, spray_angle: f64) -> (f64, f64) {
+        let first = self.points[0];
+        let last = self.points[self.points.len() - 1];
+        if spray_angle <= first.0 {return (first.1, first.2)};
+        if spray_angle >= last.0 {return (last.1, last.2)};
+        let i = self.points.iter().position(|point| point.0 >= spray_angle).unwrap_or(self.points.len() - 1).max(1);
+        let (a, b) = (self.points[i - 1], self.points[i]);
+        let share = (spray_angle - a.0) / (b.0 - a.0);
+        (a.1 + (b.1 - a.1) * share, a.2 + (b.2 - a.2) * share)
+    }
+}
+
+impl Flight {
+    /// Height of the ball, in feet, when it's this far from home plate on the way out. None if it lands short.
+    pub fn height_at (&self, distance: f64) -> Option<f64> {
+        let horizontal = |p: &(f64, f64, f64)| (p.0 * p.0 + p.1 * p.1).sqrt();
+        self.points.windows(2)
+            .find(|window| horizontal(&window[1]) >= distance)
+            .map(|window| {
+                let (a, b) = (horizontal(&window[0]), horizontal(&window[1]));
+                let share = if b > a {((distance - a) / (b - a)).clamp(0.0, 1.0)} else {1.0};
+                window[0].2 + (window[1].2 - window[0].2) * share
+            })
+    }
+
+    /// Whether the ball is over the wall when it gets to it. Fair balls only, anything outside the lines is never a home run.
+    pub fn clears (&self, fence: &Fence) -> bool {
+        for window in self.points.windows(2) {
+            let (a, b) = (window[0], window[1]);
+            let distance = (b.0 * b.0 + b.1 * b.1).sqrt();
+            let spray_angle = 45.0 + b.0.atan2(b.1).to_degrees();
+            if !(0.0 ..= 90.0).contains(&spray_angle) {return false};
+            let (wall, height) = fence.at(spray_angle);
+            if distance >= wall {
+                let previous = (a.0 * a.0 + a.1 * a.1).sqrt();
+                let share = if distance > previous {(wall - previous) / (distance - previous)} else {1.0};
+                return a.2 + (b.2 - a.2) * share.clamp(0.0, 1.0) > height;
+            }
+        }
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn conditions_change_carry () {
+        let neutral = simulate(103.0, 28.0, 45.0, &NEUTRAL).unwrap().carry;
+        assert!(neutral > 370.0 && neutral < 450.0, "{}", neutral);
+
+        let denver = Conditions {elevation_ft: 5200.0, ..NEUTRAL};
+        assert!(simulate(103.0, 28.0, 45.0, &denver).unwrap().carry > neutral + 15.0);
+
+        let cold = Conditions {temp_f: 40.0, ..NEUTRAL};
+        assert!(simulate(103.0, 28.0, 45.0, &cold).unwrap().carry < neutral);
+
+        let out = Conditions {wind_speed_mph: 10.0, wind_direction: Some(WindDirection::OutToCF), ..NEUTRAL};
+        let carry = simulate(103.0, 28.0, 45.0, &out).unwrap().carry;
+        assert!(carry > neutral);
+
+        let fence = Fence {points: vec![(0.0, 330.0, 8.0), (45.0, 400.0, 8.0), (90.0, 330.0, 8.0)]};
+        assert!(simulate(103.0, 28.0, 10.0, &NEUTRAL).unwrap().clears(&fence));
+        assert!(!simulate(85.0, 28.0, 45.0, &NEUTRAL).unwrap().clears(&fence));
+    }
+}
@@ -0,0 +1,637 @@
+//! Called strike probability, catcher framing and umpire accuracy.
+//!
+//! The called strike model is fit on taken pitches, balls and called strikes, from the play by play output. Pitchouts and intentional
+//! balls are thrown wide on purpose, so they're left out of the model, framing and the umpire numbers. The location is
+//! normalized by the batter's zone: the horizontal location is flipped so that positive is always away from the batter, and the
+//! height is measured from the middle of the batter's zone in half-zones, so the top and bottom of every batter's zone are 1 and -1.
+//! Locations are binned, and each bin is split by sport_id, season, batter side and count.
+//!
+//! Bins get sparse quickly, so every level of the model is shrunk towards the level above it with RE288_MIN_SAMPLE pseudo-counts:
+//! (sport_id, season, batter side, count) -> (sport_id, season, batter side) -> (sport_id, season) -> sport_id -> all of our data
+//! -> the rulebook zone. The cached bins hold the raw counts at every level, so the shrinkage is redone at lookup. The game module
+//! uses the cached model for the pitch_called_strike_probability on every pitch with a location.
+//!
+//! With the probabilities in hand:
+//!   * Framing is called strikes above expected for the catcher, and framing runs weight each call by the run value of a strike over
+//!     a ball in that count. The run values come from the re_288_val on each pitch, so walks and strikeouts are baked in. We don't
+//!     control for the pitcher or umpire.
+//!   * Umpire accuracy is the share of calls that match the rulebook zone, with the edge of the ball touching the zone a strike.
+//!   * Umpire consistency is the share of calls that match the umpire's own zone for the game. Their zone is the league zone shifted
+//!     on the logit scale until the expected strikes match the strikes they called, so an umpire with a big but steady zone is
+//!     consistent, even if they aren't accurate.
+
+use serde::{Serialize, Deserialize};
+use std::collections::{BTreeMap, HashMap};
+use crate::game::PitchResult;
+use crate::linear_weights::ratio;
+use crate::pitcher_arsenal::{in_zone, ZONE_HALF_WIDTH};
+use crate::play_by_play::{PitchTypeCode, SideCode};
+use crate::run_expectancy::RE288_MIN_SAMPLE;
+
+/// Bin sizes, feet for the horizontal location and half-zones for the height
+const X_BIN: f64 = 0.1;
+const Z_BIN: f64 = 0.1;
+/// Pitches further out than this are lumped into the edge bins
+const MAX_X: f64 = 2.5;
+const MAX_Z: f64 = 3.0;
+/// Used when a pitch has a missing or broken zone
+const DEFAULT_ZONE_TOP: f64 = 3.5;
+const DEFAULT_ZONE_BOTTOM: f64 = 1.5;
+/// Run value of a strike over a ball, for counts we have no data for
+const DEFAULT_STRIKE_VALUE: f64 = 0.125;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum CountGroup {
+    /// More strikes than balls
+    Ahead,
+    Even,
+    Behind,
+}
+
+impl CountGroup {
+    pub fn new (balls: u8, strikes: u8) -> CountGroup {
+        match strikes.cmp(&balls) {
+            std::cmp::Ordering::Greater => CountGroup::Ahead,
+            std::cmp::Ordering::Equal => CountGroup::Even,
+            std::cmp::Ordering::Less => CountGroup::Behind,
+        }
+    }
+}
+
+/// The columns from the play by play output we need for framing and umpires.
+#[derive(Debug, Clone, Deserialize)]
+pub struct FramingPitch {
+    pub game_pk: u32,
+    pub game_date: String,
+    pub sport_id: u32,
+    pub game_year: u16,
+    pub catcher_id: Option<u32>,
+    pub catcher_name: Option<String>,
+    pub hp_umpire_id: Option<u32>,
+    pub hp_umpire_name: Option<String>,
+    pub hp_umpire_age: Option<f32>,
+    pub hp_umpire_height: Option<u8>,
+    pub batter_bats: SideCode,
+    pub balls_start: u8,
+    pub strikes_start: u8,
+    pub pitch_result: PitchResult,
+    pub pitch_type_code: Option<PitchTypeCode>,
+    pub strike_zone_top: f32,
+    pub strike_zone_bottom: f32,
+    pub pitch_p_x: Option<f64>,
+    pub pitch_p_z: Option<f64>,
+    pub re_288_val: f32,
+}
+
+/// A taken pitch, as the model sees it
+#[derive(Debug, Copy, Clone)]
+pub struct CalledPitch {
+    pub sport_id: u32,
+    pub season: u16,
+    pub batter_bats: SideCode,
+    pub balls: u8,
+    pub strikes: u8,
+    pub p_x: f64,
+    pub p_z: f64,
+    pub strike_zone_top: f64,
+    pub strike_zone_bottom: f64,
+}
+
+impl CalledPitch {
+    fn zone (&self) -> (f64, f64) {
+        match self.strike_zone_top - self.strike_zone_bottom > 0.5 {
+            true => (self.strike_zone_top, self.strike_zone_bottom),
+            false => (DEFAULT_ZONE_TOP, DEFAULT_ZONE_BOTTOM),
+        }
+    }
+
+    /// (away from the batter in feet, height from the middle of the zone in half-zones)
+    fn normalized (&self) -> (f64, f64) {
+        let (top, bottom) = self.zone();
+        let x = if self.batter_bats == SideCode::L {-self.p_x} else {self.p_x};
+        (x, (self.p_z - (top + bottom) / 2.0) / ((top - bottom) / 2.0))
+    }
+
+    fn bin (&self) -> (i16, i16) {
+        let (x, z) = self.normalized();
+        ((x.clamp(-MAX_X, MAX_X - X_BIN) / X_BIN).floor() as i16, (z.clamp(-MAX_Z, MAX_Z - Z_BIN) / Z_BIN).floor() as i16)
+    }
+
+    fn in_zone (&self) -> bool {
+        let (top, bottom) = self.zone();
+        in_zone(self.p_x, self.p_z, top, bottom)
+    }
+
+    fn from_pitch (pitch: &FramingPitch) -> Option<CalledPitch> {
+        Some(CalledPitch {
+            sport_id: pitch.sport_id,
+            season: pitch.game_year,
+            batter_bats: pitch.batter_bats,
+            balls: pitch.balls_start,
+            strikes: pitch.strikes_start,
+            p_x: pitch.pitch_p_x?,
+            p_z: pitch.pitch_p_z?,
+            strike_zone_top: pitch.strike_zone_top as f64,
+            strike_zone_bottom: pitch.strike_zone_bottom as f64,
+        })
+    }
+}
+
+/// Raw counts for one location bin at one level of the model. The levels leave the fields they pool over as None.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CalledStrikeBin {
+    pub sport_id: Option<u32>,
+    pub season: Option<u16>,
+    pub batter_bats: Option<SideCode>,
+    pub count: Option<CountGroup>,
+    pub x_bin: i16,
+    pub z_bin: i16,
+    pub pitches: u32,
+    pub called_strikes: u32,
+}
+
+type BinKey = (Option<u32>, Option<u16>, Option<SideCode>, Option<CountGroup>, i16, i16);
+/// ((sport_id, season) or None for all of our data, balls, strikes, called strike)
+type ValueKey = (Option<(u32, u16)>, u8, u8, bool);
+
+/// Every level of the model a pitch belongs to, from the most pooled to the least
+fn levels (pitch: &CalledPitch) -> [BinKey; 5] {
+    let (x_bin, z_bin) = pitch.bin();
+    let (sport_id, season, side, count) = (Some(pitch.sport_id), Some(pitch.season), Some(pitch.batter_bats), Some(CountGroup::new(pitch.balls, pitch.strikes)));
+    [
+        (None, None, None, None, x_bin, z_bin),
+        (sport_id, None, None, None, x_bin, z_bin),
+        (sport_id, season, None, None, x_bin, z_bin),
+        (sport_id, season, side, None, x_bin, z_bin),
+        (sport_id, season, side, count, x_bin, z_bin),
+    ]
+}
+
+/// The fitted called strike model, ready for lookups.
+#[derive(Debug, Clone, Default)]
+pub struct CalledStrikeModel {
+    bins: HashMap<BinKey, (u32, u32)>,
+}
+
+impl CalledStrikeModel {
+    pub fn new (bins: &[CalledStrikeBin]) -> CalledStrikeModel {
+        CalledStrikeModel {
+            bins: bins.iter()
+                .map(|b| ((b.sport_id, b.season, b.batter_bats, b.count, b.x_bin, b.z_bin), (b.pitches, b.called_strikes)))
+                .collect(),
+        }
+    }
+
+    /// Probability the pitch is called a strike if the batter takes it
+    pub fn probability (&self, pitch: &CalledPitch) -> f64 {
+        let (x_bin, z_bin) = pitch.bin();
+        let centre = ((x_bin as f64 + 0.5) * X_BIN, (z_bin as f64 + 0.5) * Z_BIN);
+        let mut probability = if centre.0.abs() <= ZONE_HALF_WIDTH && centre.1.abs() <= 1.0 {1.0} else {0.0};
+
+        let k = RE288_MIN_SAMPLE as f64;
+        for key in levels(pitch).iter() {
+            if let Some((pitches, called_strikes)) = self.bins.get(key) {
+                probability = (*called_strikes as f64 + k * probability) / (*pitches as f64 + k);
+            }
+        }
+        probability
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CatcherFraming {
+    pub catcher_id: u32,
+    pub catcher_name: String,
+    pub sport_id: u32,
+    pub season: u16,
+    pub called_pitches: u32,
+    pub called_strikes: u32,
+    pub expected_called_strikes: f32,
+    pub strikes_above_expected: f32,
+    pub framing_runs: f32,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UmpireGame {
+    pub game_pk: u32,
+    pub game_date: String,
+    pub sport_id: u32,
+    pub season: u16,
+    pub hp_umpire_id: u32,
+    pub hp_umpire_name: String,
+    pub called_pitches: u32,
+    pub called_strikes: u32,
+    pub expected_called_strikes: f32,
+    pub strikes_above_expected: f32,
+    /// Runs the calls above expected took away from the batters
+    pub strikes_above_expected_runs: f32,
+    pub correct_calls: u32,
+    pub accuracy: f32,
+    pub consistency: f32,
+    /// How far the umpire's zone was shifted from the league's, on the logit scale. Positive is a bigger zone.
+    pub zone_shift: f32,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UmpireSeason {
+    pub hp_umpire_id: u32,
+    pub hp_umpire_name: String,
+    pub sport_id: u32,
+    pub season: u16,
+    pub hp_umpire_age: Option<f32>,
+    pub hp_umpire_height: Option<u8>,
+    pub games: u32,
+    pub called_pitches: u32,
+    pub called_strikes: u32,
+    pub expected_called_strikes: f32,
+    pub strikes_above_expected: f32,
+    pub strikes_above_expected_runs: f32,
+    pub accuracy: f32,
+    /// Consistent calls over called pitches, across all the umpire's games
+    pub consistency: f32,
+    /// Standard deviation of the game zone shifts, how much the zone moves from game to game
+    pub zone_shift_sd: Option<f32>,
+}
+
+pub struct FramingData <'f> {
+    pub pitches: &'f [FramingPitch],
+}
+
+pub struct FramingOutput {
+    pub bins: Vec<CalledStrikeBin>,
+    pub catchers: Vec<CatcherFraming>,
+    pub umpire_games: Vec<UmpireGame>,
+    pub umpire_seasons: Vec<UmpireSeason>,
+}
+
+/// A taken pitch with everything we need after the fit
+struct Call <'f> {
+    pitch: &'f FramingPitch,
+    strike: bool,
+    probability: f64,
+    strike_value: f64,
+    in_zone: bool,
+}
+
+type UmpireSeasonTotals <'f> = (&'f FramingPitch, CallTotals, Vec<f64>);
+
+/// Balls and called strikes, other than pitchouts and intentional balls
+fn taken (pitch: &FramingPitch) -> bool {
+    matches!(pitch.pitch_result, PitchResult::Ball | PitchResult::CalledStrike)
+        && !matches!(pitch.pitch_type_code, Some (PitchTypeCode::PO) | Some (PitchTypeCode::IN))
+}
+
+fn logit (p: f64) -> f64 {
+    let p = p.clamp(1e-6, 1.0 - 1e-6);
+    (p / (1.0 - p)).ln()
+}
+
+fn logistic (x: f64) -> f64 {
+    1.0 / (1.0 + (-x).exp())
+}
+
+/// The logit shift that makes the expected strikes match the called strikes, by bisection
+fn zone_shift (calls: &[&Call]) -> f64 {
+    let strikes = calls.iter().filter(|c| c.strike).count() as f64;
+    let expected = |shift: f64| calls.iter().map(|c| logistic(logit(c.probability) + shift)).sum::<f64>();
+    let (mut low, mut high) = (-5.0, 5.0);
+    for _ in 0 .. 50 {
+        let mid = (low + high) / 2.0;
+        if expected(mid) < strikes {low = mid} else {high = mid};
+    }
+    (low + high) / 2.0
+}
+
+#[derive(Debug, Default, Copy, Clone)]
+struct CallTotals {
+    called_pitches: u32,
+    called_strikes: u32,
+    expected_called_strikes: f64,
+    runs: f64,
+    correct_calls: u32,
+    consistent_calls: u32,
+}
+
+impl CallTotals {
+    fn add (&mut self, call: &Call) {
+        self.called_pitches += 1;
+        if call.strike {self.called_strikes += 1};
+        self.expected_called_strikes += call.probability;
+        self.runs += (call.strike as u8 as f64 - call.probability) * call.strike_value;
+        if call.strike == call.in_zone {self.correct_calls += 1};
+    }
+
+    fn strikes_above_expected (&self) -> f32 {
+        (self.called_strikes as f64 - self.expected_called_strikes) as f32
+    }
+}
+
+impl <'f> From<FramingData<'f>> for FramingOutput {
+    fn from (data: FramingData) -> FramingOutput {
+
+        let taken: Vec<(&FramingPitch, CalledPitch)> = data.pitches.iter()
+            .filter(|p| taken(p))
+            .filter_map(|p| CalledPitch::from_pitch(p).map(|c| (p, c)))
+            .collect();
+
+        let mut counts: HashMap<BinKey, (u32, u32)> = HashMap::new();
+        for (pitch, called) in taken.iter() {
+            for key in levels(called).iter() {
+                let (pitches, called_strikes) = counts.entry(*key).or_default();
+                *pitches += 1;
+                if pitch.pitch_result == PitchResult::CalledStrike {*called_strikes += 1};
+            }
+        }
+
+        // Run value of a strike over a ball, from the average re_288_val of each in the same count
+        let mut values: HashMap<ValueKey, (f64, u32)> = HashMap::new();
+        for (pitch, _) in taken.iter() {
+            let strike = pitch.pitch_result == PitchResult::CalledStrike;
+            for context in [Some((pitch.sport_id, pitch.game_year)), None] {
+                let (sum, n) = values.entry((context, pitch.balls_start, pitch.strikes_start, strike)).or_default();
+                *sum += pitch.re_288_val as f64;
+                *n += 1;
+            }
+        }
+        let strike_value = |context: Option<(u32, u16)>, balls: u8, strikes: u8| -> Option<f64> {
+            match (values.get(&(context, balls, strikes, false)), values.get(&(context, balls, strikes, true))) {
+                (Some ((ball, n_ball)), Some ((strike, n_strike))) if *n_ball.min(n_strike) >= RE288_MIN_SAMPLE =>
+                    Some(ball / *n_ball as f64 - strike / *n_strike as f64),
+                _ => None,
+            }
+        };
+
+        let mut bins: Vec<CalledStrikeBin> = counts.into_iter()
+            .map(|((sport_id, season, batter_bats, count, x_bin, z_bin), (pitches, called_strikes))| CalledStrikeBin {
+                sport_id, season, batter_bats, count, x_bin, z_bin, pitches, called_strikes,
+            })
+            .collect();
+        // Sorted so the output is always in the same order
+        bins.sort_by_key(|b| (b.sport_id, b.season, b.batter_bats.map(|s| s as u8), b.count, b.x_bin, b.z_bin));
+        let model = CalledStrikeModel::new(&bins);
+
+        let calls: Vec<Call> = taken.iter()
+            .map(|(pitch, called)| Call {
+                pitch,
+                strike: pitch.pitch_result == PitchResult::CalledStrike,
+                probability: model.probability(called),
+                strike_value: strike_value(Some((pitch.sport_id, pitch.game_year)), pitch.balls_start, pitch.strikes_start)
+                    .or(strike_value(None, pitch.balls_start, pitch.strikes_start))
+                    .unwrap_or(DEFAULT_STRIKE_VALUE),
+                in_zone: called.in_zone(),
+            })
+            .collect();
+
+        let mut catchers: BTreeMap<(u32, u32, u16), (String, CallTotals)> = BTreeMap::new();
+        let mut games: BTreeMap<(u32, u32), Vec<&Call>> = BTreeMap::new();
+        for call in calls.iter() {
+            if let Some(catcher_id) = call.pitch.catcher_id {
+                let (_, totals) = catchers.entry((catcher_id, call.pitch.sport_id, call.pitch.game_year))
+                    .or_insert((call.pitch.catcher_name.clone().unwrap_or_default(), CallTotals::default()));
+                totals.add(call);
+            }
+            if let Some(hp_umpire_id) = call.pitch.hp_umpire_id {
+                games.entry((call.pitch.game_pk, hp_umpire_id)).or_default().push(call);
+            }
+        }
+
+        let catchers: Vec<CatcherFraming> = catchers.into_iter()
+            .map(|((catcher_id, sport_id, season), (catcher_name, t))| CatcherFraming {
+                catcher_id,
+                catcher_name,
+                sport_id,
+                season,
+                called_pitches: t.called_pitches,
+                called_strikes: t.called_strikes,
+                expected_called_strikes: t.expected_called_strikes as f32,
+                strikes_above_expected: t.strikes_above_expected(),
+                framing_runs: t.runs as f32,
+            })
+            .collect();
+
+        // The first pitch, for the umpire's bio, the season totals and the game zone shifts
+        let mut seasons: BTreeMap<(u32, u32, u16), UmpireSeasonTotals> = BTreeMap::new();
+        let umpire_games: Vec<UmpireGame> = games.into_iter()
+            .map(|((game_pk, hp_umpire_id), calls)| {
+                let first = calls[0].pitch;
+                let shift = zone_shift(&calls);
+                let mut t = CallTotals::default();
+                for call in calls.iter() {
+                    t.add(call);
+                    if call.strike == (logistic(logit(call.probability) + shift) >= 0.5) {t.consistent_calls += 1};
+                }
+
+                let (_, season, shifts) = seasons.entry((hp_umpire_id, first.sport_id, first.game_year))
+                    .or_insert((first, CallTotals::default(), vec![]));
+                season.called_pitches += t.called_pitches;
+                season.called_strikes += t.called_strikes;
+                season.expected_called_strikes += t.expected_called_strikes;
+                season.runs += t.runs;
+                season.correct_calls += t.correct_calls;
+                season.consistent_calls += t.consistent_calls;
+                shifts.push(shift);
+
+                UmpireGame {
+                    game_pk,
+                    game_date: first.game_date.clone(),
+                    sport_id: first.sport_id,
+                    season: first.game_year,
+                    hp_umpire_id,
+                    hp_umpire_name: first.hp_umpire_name.clone().unwrap_or_default(),
+                    called_pitches: t.called_pitches,
+                    called_strikes: t.called_strikes,
+                    expected_called_strikes: t.expected_called_strikes as f32,
+                    strikes_above_expected: t.strikes_above_expected(),
+                    strikes_above_expected_runs: t.runs as f32,
+                    correct_calls: t.correct_calls,
+                    accuracy: ratio(t.correct_calls as f64, t.called_pitches as f64) as f32,
+                    consistency: ratio(t.consistent_calls as f64, t.called_pitches as f64) as f32,
+                    zone_shift: shift as f32,
+                }
+            })
+            .collect();
+
+        let umpire_seasons: Vec<UmpireSeason> = seasons.into_iter()
+            .map(|((hp_umpire_id, sport_id, season), (first, t, shifts))| {
+                let n = shifts.len() as f64;
+                let mean = shifts.iter().sum::<f64>() / n;
+                let zone_shift_sd = match shifts.len() {
+                    0 | 1 => None,
+                    _ => Some((shifts.iter().map(|s| (s - mean).powi(2)).sum::<f64>() / (n - 1.0)).sqrt() as f32),
+                };
+                UmpireSeason {
+                    hp_umpire_id,
+                    hp_umpire_name: first.hp_umpire_name.clone().unwrap_or_default(),
+                    sport_id,
+                    season,
+                    hp_umpire_age: first.hp_umpire_age,
+                    hp_umpire_height: first.hp_umpire_height,
+                    games: shifts.len() as u32,
+                    called_pitches: t.called_pitches,
+                    called_strikes: t.called_strikes,
+                    expected_called_strikes: t.expected_called_strikes as f32,
+                    strikes_above_expected: t.strikes_above_expected(),
+                    strikes_above_expected_runs: t.runs as f32,
+                    accuracy: ratio(t.correct_calls as f64, t.called_pitches as f64) as f32,
+                    consistency: ratio(t.consistent_calls as f64, t.called_pitches as f64) as f32,
+                    zone_shift_sd,
+                }
+            })
+            .collect();
+
+        FramingOutput {
+            bins,
+            catchers,
+            umpire_games,
+            umpire_seasons,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pitcher_arsenal::BALL_RADIUS;
+
+    /// A 0-0 pitch to a right handed batter with a 3.5 to 1.5 foot zone
+    fn pitch (catcher_id: u32, p_x: f64, p_z: f64, strike: bool) -> FramingPitch {
+        FramingPitch {
+            game_pk: 1,
+            game_date: "2021-05-01".to_string(),
+            sport_id: 1,
+            game_year: 2021,
+            catcher_id: Some(catcher_id),
+            catcher_name: None,
+            hp_umpire_id: Some(9),
+            hp_umpire_name: None,
+            hp_umpire_age: Some(45.0),
+            hp_umpire_height: Some(72),
+            batter_bats: SideCode::R,
+            balls_start: 0,
+            strikes_start: 0,
+            pitch_result: if strike {PitchResult::CalledStrike} else {PitchResult::Ball},
+            pitch_type_code: Some(PitchTypeCode::FF),
+            strike_zone_top: 3.5,
+            strike_zone_bottom: 1.5,
+            pitch_p_x: Some(p_x),
+            pitch_p_z: Some(p_z),
+            re_288_val: 0.0,
+        }
+    }
+
+    fn called (p_x: f64, p_z: f64) -> CalledPitch {
+        CalledPitch::from_pitch(&pitch(1, p_x, p_z, false)).unwrap()
+    }
+
+    #[test]
+    fn count_groups () {
+        assert_eq!(CountGroup::new(0, 2), CountGroup::Ahead);
+        assert_eq!(CountGroup::new(1, 1), CountGroup::Even);
+        assert_eq!(CountGroup::new(3, 1), CountGroup::Behind);
+    }
+
+    #[test]
+    fn location_normalized_to_the_batter () {
+        assert_eq!(called(0.5, 3.5).normalized(), (0.5, 1.0));
+        assert_eq!(CalledPitch {batter_bats: SideCode::L, ..called(0.5, 1.5)}.normalized(), (-0.5, -1.0));
+    }
+
+    #[test]
+    fn broken_zones_use_the_default () {
+        let broken = CalledPitch {strike_zone_top: 2.0, strike_zone_bottom: 2.0, ..called(0.0, DEFAULT_ZONE_TOP)};
+        assert_eq!(broken.normalized(), (0.0, 1.0));
+    }
+
+    #[test]
+    fn bins () {
+        assert_eq!(called(0.25, 2.5).bin(), (2, 0));
+        // Way outside is lumped in with the edge
+        assert_eq!(called(-10.0, 2.5).bin(), (-25, 0));
+    }
+
+    #[test]
+    fn no_data_is_the_rulebook_zone () {
+        let model = CalledStrikeModel::default();
+        assert_eq!(model.probability(&called(0.0, 2.5)), 1.0);
+        assert_eq!(model.probability(&called(1.5, 2.5)), 0.0);
+    }
+
+    #[test]
+    fn bins_shrink_towards_the_level_above () {
+        // RE288_MIN_SAMPLE balls down the middle over all of our data, so the rulebook strike is pulled halfway to a ball
+        let (x_bin, z_bin) = called(0.0, 2.5).bin();
+        let bin = CalledStrikeBin {sport_id: None, season: None, batter_bats: None, count: None, x_bin, z_bin, pitches: RE288_MIN_SAMPLE, called_strikes: 0};
+        let model = CalledStrikeModel::new(&[bin]);
+
+        assert_eq!(model.probability(&called(0.0, 2.5)), 0.5);
+    }
+
+    #[test]
+    fn catcher_strikes_above_expected () {
+        // Two pitches down the middle, catcher 1 gets the strike and catcher 2 doesn't. Every level has 1 strike in 2 pitches,
+        // shrunk from the rulebook strike.
+        let pitches = vec![pitch(1, 0.0, 2.5, true), pitch(2, 0.0, 2.5, false)];
+        let output: FramingOutput = FramingData {pitches: &pitches}.into();
+        let catcher = |id: u32| output.catchers.iter().find(|c| c.catcher_id == id).unwrap();
+
+        let k = RE288_MIN_SAMPLE as f64;
+        let p = (0 .. 5).fold(1.0, |p, _| (1.0 + k * p) / (2.0 + k));
+
+        assert_eq!(catcher(1).expected_called_strikes, p as f32);
+        assert_eq!(catcher(1).strikes_above_expected, (1.0 - p) as f32);
+        assert_eq!(catcher(2).strikes_above_expected, -p as f32);
+        // Not enough pitches for our own run values
+        assert_eq!(catcher(1).framing_runs, ((1.0 - p) * DEFAULT_STRIKE_VALUE) as f32);
+    }
+
+    #[test]
+    fn umpire_accuracy_against_the_rulebook () {
+        // Right on both down the middle and way outside, wrong on a ball down the middle and a strike way outside
+        let pitches = vec![
+            pitch(1, 0.0, 2.5, true),
+            pitch(1, 2.0, 2.5, false),
+            pitch(1, 0.0, 2.5, false),
+            pitch(1, 2.0, 2.5, true),
+        ];
+        let output: FramingOutput = FramingData {pitches: &pitches}.into();
+
+        assert_eq!(output.umpire_games.len(), 1);
+        assert_eq!(output.umpire_games[0].correct_calls, 2);
+        assert_eq!(output.umpire_games[0].accuracy, 0.5);
+    }
+
+    #[test]
+    fn pitchouts_and_intentional_balls_are_not_calls () {
+        let pitches = vec![
+            pitch(1, 0.0, 2.5, true),
+            FramingPitch {pitch_type_code: Some(PitchTypeCode::PO), ..pitch(1, 0.0, 2.5, false)},
+            FramingPitch {pitch_type_code: Some(PitchTypeCode::IN), ..pitch(1, 2.0, 2.5, false)},
+            FramingPitch {pitch_type_code: None, ..pitch(1, 2.0, 2.5, false)},
+        ];
+        let output: FramingOutput = FramingData {pitches: &pitches}.into();
+
+        assert_eq!(output.catchers[0].called_pitches, 2);
+        assert_eq!(output.umpire_games[0].correct_calls, 2);
+        assert_eq!(output.umpire_games[0].accuracy, 1.0);
+    }
+
+    #[test]
+    fn edge_of_the_ball_is_a_strike () {
+        assert!(called(ZONE_HALF_WIDTH, 2.5).in_zone());
+        assert!(called(0.0, 3.5 + BALL_RADIUS).in_zone());
+        assert!(!called(ZONE_HALF_WIDTH + 0.01, 2.5).in_zone());
+    }
+
+    #[test]
+    fn zone_shift_matches_called_strikes () {
+        // Every pitch a coin flip and half of them called strikes, so the umpire's zone is the league's
+        let (strike, ball) = (pitch(1, 0.0, 2.5, true), pitch(1, 0.0, 2.5, false));
+        let calls = [
+            Call {pitch: &strike, strike: true, probability: 0.5, strike_value: 0.0, in_zone: true},
+            Call {pitch: &ball, strike: false, probability: 0.5, strike_value: 0.0, in_zone: true},
+        ];
+        let calls: Vec<&Call> = calls.iter().collect();
+
+        assert!(zone_shift(&calls).abs() < 1e-9);
+    }
+}
@@ -14,8 +14,16 @@ use crate::coaches::CoachData;
 use crate::team::{TeamData, Team};
 use crate::metadata::MetaData;
 use crate::date::Date;
+use crate::data_quality::{DataQualityRecord, DataQualityIssue, PlateAppearanceContext, update_runner_state};
+use crate::pitcher_appearance::{PitcherAppearance, AppearanceTracker, PlateAppearanceStart};
+use crate::events::{GameEvent, EventContext, is_automatic_call, is_disengagement};
+use crate::win_expectancy::WinExpectancyState;
+use crate::expected_stats::BattedBall;
+use crate::pitch_classification::PitchFeatures;
+use crate::framing::CalledPitch;
+use crate::flight::Conditions;
 use serde::{Serialize, Deserialize};
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use core::f64::consts::*;
 
 
@@ -25,12 +33,117 @@ pub (crate) struct GameData <'m> {
     pub (crate) game_pk: u32,
 }
 
+/// Everything we build from walking through a single game. Plate appearances that fail the data quality checks
+/// end up in quarantined instead of pitches, and every repair or quarantine is recorded in data_quality.
+#[derive(Debug, Default)]
+pub struct GameTables {
+    pub pitches: Vec<Pitch>,
+    pub quarantined: Vec<Pitch>,
+    pub data_quality: Vec<DataQualityRecord>,
+    pub pitcher_appearances: Vec<PitcherAppearance>,
+    pub events: Vec<GameEvent>,
+}
+
 #[derive(Debug, Serialize, Deserialize, Copy, Clone)]
 pub enum PitcherSPRP {
     SP,
     RP,
 }
 
+/// What happened on the pitch, collapsed from the pitch call codes.
+#[derive(Debug, Serialize, Deserialize, Copy, Clone, PartialEq, Eq)]
+pub enum PitchResult {
+    Ball,
+    HitByPitch,
+    CalledStrike,
+    SwingingStrike,
+    Foul,
+    FoulTip,
+    InPlay,
+    NoPitch,
+}
+
+/// The parts of a pitch we carry forward so the next pitch can be put in context.
+#[derive(Debug, Copy, Clone)]
+struct PreviousPitch {
+    type_code: Option<PitchTypeCode>,
+    speed: Option<f64>,
+    p_x: Option<f64>,
+    p_z: Option<f64>,
+    break_vertical_induced: Option<f64>,
+    break_horizontal: Option<f64>,
+    result: PitchResult,
+}
+
+fn delta (current: Option<f64>, previous: Option<f64>) -> Option<f64> {
+    Some (current? - previous?)
+}
+
+/// Sequencing context for the pitches in a game: the previous pitch in the plate appearance and in each pitcher's outing, and how
+/// many of each pitch type the batter has seen in the plate appearance. Only real pitches move it along, no pitches, pickoffs
+/// and pitch clock automatic calls don't.
+#[derive(Debug, Default)]
+struct Sequencing {
+    outing: HashMap<u32, PreviousPitch>,
+    plate_appearance: Option<PreviousPitch>,
+    pitch_type_counts: HashMap<PitchTypeCode, u8>,
+}
+
+/// What a pitch knows about the pitches before it
+#[derive(Debug, Copy, Clone)]
+struct SequenceContext {
+    plate_appearance: Option<PreviousPitch>,
+    outing: Option<PreviousPitch>,
+    pitch_type_num_plate_appearance: Option<u8>,
+    first_pitch: bool,
+}
+
+impl Sequencing {
+    fn new_plate_appearance (&mut self) {
+        self.plate_appearance = None;
+        self.pitch_type_counts.clear();
+    }
+
+    fn pitch (&mut self, pitcher: u32, pitch: PreviousPitch, real_pitch: bool) -> SequenceContext {
+        let context = SequenceContext {
+            plate_appearance: self.plate_appearance,
+            outing: self.outing.get(&pitcher).copied(),
+            pitch_type_num_plate_appearance: None,
+            first_pitch: real_pitch && self.plate_appearance.is_none(),
+        };
+        if !real_pitch {return context};
+
+        self.outing.insert(pitcher, pitch);
+        self.plate_appearance = Some(pitch);
+        let pitch_type_num_plate_appearance = pitch.type_code.map(|code| {
+            let count = self.pitch_type_counts.entry(code).or_insert(0);
+            *count += 1;
+            *count
+        });
+
+        SequenceContext {pitch_type_num_plate_appearance, ..context}
+    }
+}
+
+/// (two_strikes, full_count) for the count the pitch was thrown in
+fn count_flags (balls: u8, strikes: u8) -> (bool, bool) {
+    (strikes == 2, balls == 3 && strikes == 2)
+}
+
+impl SequenceContext {
+    /// Change in (speed, induced vertical break, horizontal break) from the previous pitch in the plate appearance
+    fn deltas (&self, pitch: &PreviousPitch) -> (Option<f64>, Option<f64>, Option<f64>) {
+        match self.plate_appearance {
+            Some (previous) => (
+                delta(pitch.speed, previous.speed),
+                delta(pitch.break_vertical_induced, previous.break_vertical_induced),
+                delta(pitch.break_horizontal, previous.break_horizontal),
+            ),
+            None => (None, None, None),
+        }
+    }
+}
+
 
 /// Pitch is the final serializable struct that we'll export from this module. It will flatten all the at-bat level
 /// data for easy use. This is intentionally de-normalized for ease of use. 
@@ -147,6 +260,28 @@ pub struct Pitch {
     ///Did the pitch have a pickoff play right before it?
     pub preceded_by_pickoff: bool,
 
+    //Sequencing context. The previous pitch in the plate appearance and in the pitcher's outing are None for the first pitch of each.
+    pub pitch_result: PitchResult,
+    pub prev_pitch_plate_appearance_type_code: Option<PitchTypeCode>,
+    pub prev_pitch_plate_appearance_speed: Option<f64>,
+    pub prev_pitch_plate_appearance_p_x: Option<f64>,
+    pub prev_pitch_plate_appearance_p_z: Option<f64>,
+    pub prev_pitch_plate_appearance_result: Option<PitchResult>,
+    pub prev_pitch_outing_type_code: Option<PitchTypeCode>,
+    pub prev_pitch_outing_speed: Option<f64>,
+    pub prev_pitch_outing_p_x: Option<f64>,
+    pub prev_pitch_outing_p_z: Option<f64>,
+    pub prev_pitch_outing_result: Option<PitchResult>,
+    ///Change from the previous pitch in the plate appearance
+    pub pitch_speed_delta: Option<f64>,
+    pub pitch_break_vertical_induced_delta: Option<f64>,
+    pub pitch_break_horizontal_delta: Option<f64>,
+    ///How many pitches of this type the batter has seen in the plate appearance, including this one
+    pub pitch_type_num_plate_appearance: Option<u8>,
+    pub first_pitch: bool,
+    pub two_strikes: bool,
+    pub full_count: bool,
+
     //Is it possible to turn a double play on a ground ball? 
     pub double_play_opportunity: bool,
 
@@ -165,6 +300,14 @@ pub struct Pitch {
     pub re_288_start: f32,
     pub re_288_end: f32,
     pub re_288_val: f32,
+
+    // The score going into the pitch, and win expectancy. WE is the home team's, WPA is the batting team's. See the win_expectancy module.
+    pub home_score_start: u8,
+    pub away_score_start: u8,
+    pub win_expectancy_start: Option<f32>,
+    pub win_expectancy_end: Option<f32>,
+    pub win_probability_added: Option<f32>,
+    pub leverage_index: Option<f32>,
   
     //pitch-specific data, options for MLB-level data
     pub in_play: u8,
@@ -203,6 +346,11 @@ pub struct Pitch {
     pub pitch_z0: Option<f64>,
     pub pitch_type_code: Option<PitchTypeCode>,
     pub pitch_type_desc: Option<PitchTypeDescription>,
+    // Our own pitch type from the pitcher's arsenal model, see the pitch_classification module
+    pub pitch_type_inferred: Option<PitchTypeCode>,
+    pub pitch_type_inferred_confidence: Option<f32>,
+    // Probability the pitch is called a strike if it's taken, see the framing module
+    pub pitch_called_strike_probability: Option<f32>,
 
     pub xr: Option<f64>,
     pub yr: Option<f64>,
@@ -231,6 +379,19 @@ pub struct Pitch {
     pub ivb: Option<f64>,
     pub hb: Option<f64>,
     pub cd: Option<f64>,
+    // Where the pitch is at the decision point, 23.8 ft from the plate
+    pub decision_t: Option<f64>,
+    pub decision_x: Option<f64>,
+    pub decision_z: Option<f64>,
+    // Spin from the movement against the measured spin, a big gap between the two axes is seam-shifted wake
+    pub cl: Option<f64>,
+    pub transverse_spin: Option<f64>,
+    pub spin_efficiency: Option<f64>,
+    pub spin_axis_inferred: Option<f64>,
+    pub spin_axis_inferred_clock: Option<String>,
+    pub spin_axis_observed_clock: Option<String>,
+    pub spin_axis_deviation: Option<f64>,
+    pub seam_shifted_wake: Option<bool>,
 
     //1B, 2B, 3B, HR, strikeout, walk for easy summing in analytical tools
     pub in_play_result: Option<Event>,
@@ -257,6 +418,19 @@ pub struct Pitch {
     pub hit_data_spray_angle: Option<f64>,
     //distance calculated from spray chart
     pub hit_data_calc_distance: Option<f64>,
+    //distance and landing spot in feet, x toward 1B and y toward CF, see the calibration module
+    pub hit_data_calc_distance_ft: Option<f64>,
+    pub hit_data_landing_x_ft: Option<f64>,
+    pub hit_data_landing_y_ft: Option<f64>,
+    // Expected outcomes for the batted ball, see the expected_stats module
+    pub hit_data_xba: Option<f32>,
+    pub hit_data_xslg: Option<f32>,
+    pub hit_data_xwoba: Option<f32>,
+    // Simulated carry, see the flight module. The home run counts are out of the parks that hosted the level that season.
+    pub hit_data_carry_distance: Option<f32>,
+    pub hit_data_neutral_distance: Option<f32>,
+    pub hit_data_hr_parks: Option<u8>,
+    pub hit_data_hr_parks_total: Option<u8>,
 
 
 
@@ -284,6 +458,10 @@ pub struct Pitch {
     pub game_year: u16,
     pub game_month: u8,
     pub game_status: AbstractGameState,
+    /// Did the box score rebuilt from this game's pitches tie out with the boxscore endpoint? See the validation module.
+    pub game_box_score_validated: bool,
+    /// Was any of the state in this plate appearance repaired by the data quality checks? See the data_quality module.
+    pub data_quality_repaired: bool,
     
     // Venue Metadata
     pub venue_id: u32,
@@ -308,6 +486,11 @@ pub struct Pitch {
     pub venue_retrosheet_id: String,
     pub venue_latitude: Option<f32>,
     pub venue_longitude: Option<f32>,
+    pub venue_elevation: Option<f32>,
+    /// Park factors for the batter's side, see the park_factors module
+    pub venue_park_factor_runs: Option<f32>,
+    pub venue_park_factor_home_runs: Option<f32>,
+    pub venue_park_factor_home_runs_batted_ball: Option<f32>,
 
     pub league_name: String,
 
@@ -394,15 +577,18 @@ fn get_ump (id: Option<u32>, game_date: GameDate, ump_map: &HashMap<u32, Player>
 }
 
 
-///Convert all the data about the game into a vector of pitches
-impl <'m> From <GameData<'m>> for Vec<Pitch> {
+///Convert all the data about the game into a vector of pitches, along with the data quality audit trail
+impl <'m> From <GameData<'m>> for GameTables {
 
-    fn from (data: GameData) -> Vec<Pitch> {
+    fn from (data: GameData) -> GameTables {
 
         let plays = data.pitch_data;
 
         // 300 should be around the size of each game. This will minimize allocations
         let mut pitches: Vec<Pitch> = Vec::with_capacity(300);
+        let mut quarantined: Vec<Pitch> = vec![];
+        let mut data_quality: Vec<DataQualityRecord> = vec![];
+        let mut events: Vec<GameEvent> = vec![];
         let game_pk = data.game_pk;
         
         //we start with the schedule, so we can safely unwrap here
@@ -413,7 +599,7 @@ impl <'m> From <GameData<'m>> for Vec<Pitch> {
         // don't have a boxscore, we'll be missing a LOT of critical information, such as the defense.
         // At some point, we may want to include games with no boxscore data, but for now we'll exclude them.
         // We handle all other possible missing metadata through Default impls, or explicitly in the code below.
-        if !data.meta_data.boxscore.contains_key(&game_pk) {return vec![]};
+        if !data.meta_data.boxscore.contains_key(&game_pk) {return GameTables::default()};
         let box_meta = data.meta_data.boxscore.get(&game_pk).unwrap();
 
         // Handle the case where we don't have venue metadata
@@ -470,10 +656,17 @@ impl <'m> From <GameData<'m>> for Vec<Pitch> {
         let sport_id = sched_meta.sport_id;
         let sport_details = crate::sports::get_sport(sport_id);
 
+        let re_288_table = data.meta_data.re_288_table(sched_meta.game_venue_id, sport_id, year);
+        let win_expectancy = data.meta_data.win_expectancy.get(&(sport_id, year));
+        let expected_outcomes = data.meta_data.expected_outcomes.get(&(sport_id, year));
+        let fences = data.meta_data.fences.get(&(sport_id, year));
+        let conditions = Conditions::new(box_meta.game_weather_temp_f, venue_meta.venue_elevation, box_meta.game_wind_speed_mph, box_meta.game_wind_direction);
+
         let (venue_home_plate_x, venue_home_plate_y) = match venue_x_y {
             Some (venue) => (venue.x.unwrap_or(crate::STADIUM_X), venue.y.unwrap_or(crate::STADIUM_Y)),
             None => (crate::STADIUM_X, crate::STADIUM_Y),
         };
+        let pixel_calibration = data.meta_data.pixel_calibration(sched_meta.game_venue_id, year);
 
         // Set the initial half-inning state that we check against
         let mut previous_half_inning = HalfInning::Top;
@@ -495,6 +688,18 @@ impl <'m> From <GameData<'m>> for Vec<Pitch> {
         // clear at the end of each half-inning. 
         let mut runner_state: HashMap<u32, RunnerData> = HashMap::new();
 
+        // The outs the source data reports with each event. These should never go down within a half-inning.
+        let mut reported_outs: Option<u8> = None;
+
+        // Each pitcher's game line is built up as we go
+        let mut appearance_tracker = AppearanceTracker::new(game_pk, sched_meta.game_date, sport_id, &data.meta_data.pitcher_appearance_dates);
+
+        // (home, away) score at the start of each plate appearance
+        let mut score = (0u8, 0u8);
+
+        // The previous pitches in the plate appearance and each pitcher's outing
+        let mut sequencing = Sequencing::default();
+
         for plate_app in plays {
             // Set the initial state for the half inning if the half inning has changed since the last plate appearance
             
@@ -511,6 +716,36 @@ impl <'m> From <GameData<'m>> for Vec<Pitch> {
             let pitcher = plate_app.matchup.pitcher_id;
             let pitcher_throws = plate_app.matchup.pitcher_pitch_hand_code;
             let pitcher_throws_desc = plate_app.matchup.pitcher_pitch_hand_desc;
+            let pitch_classifier = data.meta_data.pitch_classifiers.get(&pitcher);
+
+            // We buffer all the pitches for the plate appearance, so that we can quarantine the whole thing if it fails
+            // any of the data quality checks.
+            let mut plate_app_pitches: Vec<Pitch> = vec![];
+            let mut plate_app_repaired = false;
+            let mut plate_app_quarantined = false;
+            let mut event_indices: HashSet<u8> = HashSet::new();
+
+            sequencing.new_plate_appearance();
+
+            let event_context = EventContext {
+                game_pk,
+                half_inning,
+                num_inning,
+                num_plate_appearance,
+                pitcher,
+                batter,
+                players: &player_meta,
+            };
+            let mut disengagements = 0u8;
+
+            let plate_app_context = PlateAppearanceContext {
+                game_pk,
+                half_inning,
+                num_inning,
+                num_plate_appearance,
+            };
+
+            let plate_app_walk = matches!(plate_app.result.plate_appearance_result, Some(Event::Walk) | Some(Event::IntentionalWalk));
 
             // Update the pitcher_num_plate_appearance (home, away) tuple
             match half_inning {
@@ -538,6 +773,8 @@ impl <'m> From <GameData<'m>> for Vec<Pitch> {
                 outs_end = 0;
                 pitch_num_inning = 0;
                 runner_state.drain();
+                reported_outs = None;
+                appearance_tracker.new_half_inning();
             }
             
             // Each plate appearance may only update a subset of active runners. We'll need to keep a
@@ -650,6 +887,36 @@ impl <'m> From <GameData<'m>> for Vec<Pitch> {
                 HalfInning::Bottom => (away_team.id, away_team.clone().team_city_name, away_parent_team.id, away_parent_team.clone().team_city_name),
             };
 
+            let park_factor = data.meta_data.park_factor(sched_meta.game_venue_id, sport_id, year, batter_bats);
+
+            let pitcher_lead = match half_inning {
+                HalfInning::Top => score.0 as i16 - score.1 as i16,
+                HalfInning::Bottom => score.1 as i16 - score.0 as i16,
+            };
+
+            appearance_tracker.start_plate_appearance(
+                PlateAppearanceStart {
+                    pitcher,
+                    pitcher_name: pitcher_details.name.clone(),
+                    pitcher_team_id,
+                    half_inning,
+                    num_inning,
+                    outs: outs_start,
+                    base_value: base_value_start,
+                    runners_on: runner_state.values().filter(|r| r.end_base_value > 0).map(|r| r.runner_id).collect(),
+                    lead: pitcher_lead,
+                    result: plate_app.result.plate_appearance_result,
+                }
+            );
+
+            // The score after the plate appearance becomes the start score for the next one
+            let score_end = match (plate_app.result.home_score, plate_app.result.away_score) {
+                (Some(home), Some(away)) => (home, away),
+                _ => score,
+            };
+            // The running score within the plate appearance, so each pitch knows the score going into it
+            let mut event_score = score;
+
             let mut re_288_batter_responsible = true;
 
             //Some plays don't have any events, but have runner events. We'll update those here in that case
@@ -667,8 +934,12 @@ impl <'m> From <GameData<'m>> for Vec<Pitch> {
                 // We update our runner state with the new runner data. This will overwrite the old values, but more
                 // importantly, it will keep all the old values. For outs, we'll use just the runner_data, for base value
                 // we'll use our runner_state.
-                for runner in runner_data.values() {
-                    runner_state.insert(runner.runner_id, *runner);
+                for runner in update_runner_state(&mut runner_state, &runner_data) {
+                    data_quality.push(plate_app_context.record(
+                        None, DataQualityIssue::RunnerVanished,
+                        format!("Runner {} was left on base value {} and never moved", runner.runner_id, runner.end_base_value),
+                    ));
+                    plate_app_repaired = true;
                 };
 
 
@@ -681,10 +952,42 @@ impl <'m> From <GameData<'m>> for Vec<Pitch> {
                 .filter(|v| v.play_index == -1)
                 .map (|r| r.outs)
                 .sum::<u8>();
+
+                appearance_tracker.runners(half_inning, runner_data.values(), num_inning, outs_end);
             }
 
             for event in plate_app.play_events {
 
+                // Duplicated events share the same index. We keep the first one and drop the rest, since the runner data
+                // can only be matched to one of them.
+                if !event_indices.insert(event.index) {
+                    data_quality.push(plate_app_context.record(
+                        Some(event.index), DataQualityIssue::DuplicatePlayIndex,
+                        format!("Dropped duplicate event with play index {}", event.index),
+                    ));
+                    plate_app_repaired = true;
+                    continue;
+                }
+
+                // If the source has the outs going down without a new half-inning, we can't trust any of the state
+                if let Some(outs) = event.count.outs {
+                    if let Some(previous_outs) = reported_outs {
+                        if outs < previous_outs {
+                            data_quality.push(plate_app_context.record(
+                                Some(event.index), DataQualityIssue::OutsDecreased,
+                                format!("Outs went from {} to {} within the half-inning", previous_outs, outs),
+                            ));
+                            plate_app_quarantined = true;
+                        }
+                    }
+                    reported_outs = Some(outs);
+                }
+
+                // Everything that isn't a regular pitch also goes into the event table
+                if is_disengagement(event.play_event_type) {disengagements += 1;}
+                if let Some(game_event) = event_context.event(&event, disengagements) {
+                    events.push(game_event);
+                }
 
                 // First, we need to de-duplicate the runner data. We'll take the last record for each runner
                 // and assume that it's the correct one. This may or may not be accurate. Based on the source code in std,
@@ -698,8 +1001,12 @@ impl <'m> From <GameData<'m>> for Vec<Pitch> {
                 // We update our runner state with the new runner data. This will overwrite the old values, but more
                 // importantly, it will keep all the old values. For outs, we'll use just the runner_data, for base value
                 // we'll use our runner_state.
-                for runner in runner_data.values() {
-                    runner_state.insert(runner.runner_id, *runner);
+                for runner in update_runner_state(&mut runner_state, &runner_data) {
+                    data_quality.push(plate_app_context.record(
+                        Some(event.index), DataQualityIssue::RunnerVanished,
+                        format!("Runner {} was left on base value {} and never moved", runner.runner_id, runner.end_base_value),
+                    ));
+                    plate_app_repaired = true;
                 };
 
                 let (fielded_by_id, fielded_by_pos) = plate_app.runners.clone().into_iter()
@@ -734,6 +1041,14 @@ impl <'m> From <GameData<'m>> for Vec<Pitch> {
                                                 .map (|r| r.outs)
                                                 .sum::<u8>();
 
+                appearance_tracker.runners(half_inning, runner_data.values(), num_inning, outs_end);
+
+
+                let score_start = event_score;
+                match half_inning {
+                    HalfInning::Top => {event_score.1 = event_score.1.saturating_add(runs_scored);},
+                    HalfInning::Bottom => {event_score.0 = event_score.0.saturating_add(runs_scored);},
+                }
 
                 // if runner_data.len() > 0 {
                 //     if runner_data[0].play_index == -1 {base_value_end = runner_data[0].end_base_value}
@@ -823,6 +1138,19 @@ impl <'m> From <GameData<'m>> for Vec<Pitch> {
                         // Do Nothing Here
                     }
                     PlayEventType::Pitch => {
+
+                        // Once the count has hit 4 balls or 3 strikes the plate appearance is over, so any more pitches are extra
+                        // records. We drop the pitch, but keep any base/out changes the runner data attached to it.
+                        if balls_start >= 4 || strikes_start >= 3 {
+                            data_quality.push(plate_app_context.record(
+                                Some(event.index), DataQualityIssue::ExtraRecord,
+                                format!("Dropped pitch after the count reached {}-{}", balls_start, strikes_start),
+                            ));
+                            plate_app_repaired = true;
+                            outs_start = outs_end;
+                            base_value_start = base_value_end;
+                            continue;
+                        }
                         
                         pitch_num_game += 1;
                         pitch_num_plate_appearance += 1;
@@ -876,19 +1204,23 @@ impl <'m> From <GameData<'m>> for Vec<Pitch> {
                         let mut swing = 0;
                         let mut swing_and_miss = None;
                         let mut foul = 0;
+                        let mut pitch_result = PitchResult::NoPitch;
+                        let code = event.details.code.unwrap();
 
-                        match event.details.code.unwrap() {
+                        match code {
                             // Ball or Ball in Dirt or Hit Batter
                             Code::BD | Code::B | Code::P | Code::I | Code::H | Code::VS | Code::VC | Code::VB | Code::VP | Code::V  => {
                                 // balls_end = balls_start + 1;
                                 
                                 swing = 0;
+                                pitch_result = if matches!(code, Code::H) {PitchResult::HitByPitch} else {PitchResult::Ball};
                             },
                             
                             // Called Strike or Automating strike
                             Code::C | Code::A | Code::AC | Code::AB => {
                                 // strikes_end = strikes_start +1;
                                 swing = 0;
+                                pitch_result = PitchResult::CalledStrike;
                             },
                             
                             //Swinging Strike or Swinging Strike Blocked or missed bunt
@@ -896,6 +1228,7 @@ impl <'m> From <GameData<'m>> for Vec<Pitch> {
                                 // strikes_end = strikes_start + 1;
                                 swing = 1;
                                 swing_and_miss = Some(1);
+                                pitch_result = PitchResult::SwingingStrike;
                             },
 
                             //Foul Ball
@@ -904,6 +1237,7 @@ impl <'m> From <GameData<'m>> for Vec<Pitch> {
                                 // if strikes_start < 2 {strikes_end = strikes_start + 1};
                                 swing = 1;
                                 swing_and_miss = Some(0);
+                                pitch_result = PitchResult::Foul;
                             },
                             // Foul Tip
                             Code::T | Code::O=> {
@@ -911,29 +1245,46 @@ impl <'m> From <GameData<'m>> for Vec<Pitch> {
                                 // if strikes_start < 2 {strikes_end = strikes_start + 1};
                                 swing = 1;
                                 swing_and_miss = Some(1);
+                                pitch_result = PitchResult::FoulTip;
                             },
 
                             //In Play
                             Code::D | Code::E | Code::X | Code::J| Code::Y  | Code::Z => {
                                 swing = 1;
                                 swing_and_miss = Some (0);
+                                pitch_result = PitchResult::InPlay;
                             },
 
                             // No Pitch
-                            Code::N | Code::PSO | Code::PO => {
-
-                            }
+                            Code::N | Code::PSO | Code::PO => {}
 
                             // All other cases, such as POs we PANIC as we want to classify them all
                             // _ => {panic!("Missing logic for Code")}
                         };
+
+                        appearance_tracker.pitch(half_inning, code, pitch_result);
                         
-                        // There are in some cases extra pitches, so we always correct to the count given
-                        // in the data. This *might* lead to double counting of walks and strikeouts, if this pitch
-                        // is duplicated in the data. Should check for this at some point. 
-                        let balls_end = event.count.balls.unwrap();
+                        // We always correct to the count given in the data. Duplicated and extra pitches have already been
+                        // dropped above, so we won't double count walks and strikeouts.
+                        let mut balls_end = event.count.balls.unwrap();
                         let strikes_end = event.count.strikes.unwrap();
 
+                        if balls_end >= 4 && !plate_app_walk {
+                            data_quality.push(plate_app_context.record(
+                                Some(event.index), DataQualityIssue::BallsWithoutWalk,
+                                format!("Count reached {} balls, but the plate appearance ended in {:?}", balls_end, plate_app.result.plate_appearance_result),
+                            ));
+                            plate_app_quarantined = true;
+                        }
+                        else if balls_end > 4 {
+                            data_quality.push(plate_app_context.record(
+                                Some(event.index), DataQualityIssue::ExtraRecord,
+                                format!("Count jumped to {} balls on a walk, capped at 4", balls_end),
+                            ));
+                            plate_app_repaired = true;
+                            balls_end = 4;
+                        }
+
                         if strikes_end == 3 {strikeout = 1;};
                         if balls_end == 4 {walk = 1};
 
@@ -1002,20 +1353,72 @@ impl <'m> From <GameData<'m>> for Vec<Pitch> {
                             (_, _) => (None, None),
                         };
 
+                        // The same spot in feet, with the calibrated home plate and scale for the venue
+                        let (hit_data_landing_x_ft, hit_data_landing_y_ft) = match (hit_data_coord_x, hit_data_coord_y) {
+                            (Some (x), Some (y)) => {
+                                let (x, y) = pixel_calibration.landing(x, y);
+                                (Some(x), Some(y))
+                            },
+                            _ => (None, None),
+                        };
+                        let hit_data_calc_distance_ft = hit_data_landing_x_ft.zip(hit_data_landing_y_ft).map(|(x, y)| (x * x + y * y).sqrt());
+
                         let double_play_opportunity = {
                             outs_start < 2 &&
                             (base_value_start == 1 || base_value_start == 3 || base_value_start ==5 || base_value_start == 7)
                         };
 
-                        // Due to challenges with the Runner data, we hard-code a hack here that forces them to be a max of 7. This affects only
-                        // a small subset of records and will need to be fixed at some point.
-                        if base_value_end > 7 {base_value_end = 7};
+                        // Vanished runners are repaired when we update the runner state, so anything over 7 means we have more runners than
+                        // bases and no way to know who is really on. We cap the state so the rest of the half-inning is usable, but quarantine
+                        // the plate appearance. The start value was already checked on the previous pitch.
+                        if base_value_end > 7 {
+                            data_quality.push(plate_app_context.record(
+                                Some(event.index), DataQualityIssue::BaseValueOverflow,
+                                format!("Base value of {} with runners {:?}", base_value_end, runner_state.keys().collect::<Vec<&u32>>()),
+                            ));
+                            plate_app_quarantined = true;
+                            base_value_end = 7;
+                        };
                         if base_value_start > 7 {base_value_start = 7};
 
-                        let re_288_start = data.meta_data.re_288_default.get(&(balls_start, strikes_start, base_value_start, outs_start)).unwrap_or(&0f32);
-                        let re_288_end = if outs_end == 3 {&0f32} else {data.meta_data.re_288_default.get(&(balls_end % 4, strikes_end % 3, base_value_end, outs_end % 3)).unwrap_or(&0f32)};
+                        let re_288_start = re_288_table.get(&(balls_start, strikes_start, base_value_start, outs_start)).unwrap_or(&0f32);
+                        // A count of 4 balls or 3 strikes ends the plate appearance, so the end state is a fresh count for the next batter
+                        let re_288_end = if outs_end == 3 {&0f32} else {re_288_table.get(&(balls_end % 4, strikes_end % 3, base_value_end, outs_end % 3)).unwrap_or(&0f32)};
                         let re_288_val = re_288_end - re_288_start + runs_scored as f32;
 
+                        let (win_expectancy_start, win_expectancy_end, win_probability_added, leverage_index) = match win_expectancy {
+                            Some (we) => {
+                                let we_state_start = WinExpectancyState {
+                                    num_inning,
+                                    half_inning,
+                                    balls: balls_start,
+                                    strikes: strikes_start,
+                                    base_value: base_value_start,
+                                    outs: outs_start,
+                                    home_lead: score_start.0 as i16 - score_start.1 as i16,
+                                };
+                                // The next batter starts with a fresh count
+                                let plate_app_over = matches!(pitch_result, PitchResult::InPlay | PitchResult::HitByPitch) || balls_end >= 4 || strikes_end >= 3;
+                                let we_state_end = WinExpectancyState {
+                                    balls: if plate_app_over {0} else {balls_end},
+                                    strikes: if plate_app_over {0} else {strikes_end},
+                                    base_value: base_value_end,
+                                    outs: outs_end,
+                                    home_lead: event_score.0 as i16 - event_score.1 as i16,
+                                    ..we_state_start
+                                };
+
+                                let we_start = we.win_expectancy(we_state_start);
+                                let we_end = we.win_expectancy(we_state_end);
+                                let wpa = match half_inning {
+                                    HalfInning::Top => we_start - we_end,
+                                    HalfInning::Bottom => we_end - we_start,
+                                };
+                                (Some(we_start), Some(we_end), Some(wpa), Some(we.leverage_index(we_state_start)))
+                            },
+                            None => (None, None, None, None),
+                        };
+
                         
                         let (pitcher_sp_rp_half, pitcher_num_plate_appearance, pitcher_num_pitch) = match half_inning {
                             HalfInning::Top => (pitcher_sp_rp.0, pitcher_num_plate_appearance_game.0, pitcher_num_pitch_game.0),
@@ -1039,9 +1442,9 @@ impl <'m> From <GameData<'m>> for Vec<Pitch> {
                             &&  pitch_data.coordinates.p_z.is_some()
                             &&  pitch_data.extension.is_some();
 
-                        let nathan_data : crate::nathan::Nathan = if enough_pitch_data {
+                        let nathan_input = if enough_pitch_data {
                         
-                            crate::nathan::Data {
+                            Some(crate::nathan::Data {
                                 extension: pitch_data.extension.expect("Missing a variable for pitch details"),
                                 vy0: pitch_data.coordinates.v_y0.expect("Missing a variable for pitch details"),
                                 vx0: pitch_data.coordinates.v_x0.expect("Missing a variable for pitch details"),
@@ -1054,12 +1457,41 @@ impl <'m> From <GameData<'m>> for Vec<Pitch> {
                                 x0: pitch_data.coordinates.x0.expect("Missing a variable for pitch details"),
                                 y0: pitch_data.coordinates.y0.expect("Missing a variable for pitch details"),
                                 z0: pitch_data.coordinates.z0.expect("Missing a variable for pitch details"),
-                            }.into()}
+                            })}
                             else {
-                                crate::nathan::Nathan::default() 
+                                None
                             }                           
                             ;
 
+                        let nathan_data : crate::nathan::Nathan = nathan_input.map(|input| input.into()).unwrap_or_default();
+                        let spin = nathan_data.spin(pitch_spin_rate, pitch_spin_direction);
+                        let decision_point = nathan_input.and_then(|input| crate::nathan::PitchTrajectory::from(&input).at_decision_point(crate::nathan::DECISION_POINT));
+
+                        let pitch_type_inferred = pitch_classifier.and_then(|classifier| classifier.classify(&PitchFeatures {
+                            speed: pitch_data.start_speed,
+                            induced_vertical_break: nathan_data.ivb.or(pitch_break_vertical_induced),
+                            horizontal_break: nathan_data.hb.or(pitch_break_horizontal),
+                            spin_rate: pitch_spin_rate,
+                            spin_direction: pitch_spin_direction,
+                            release_side: nathan_data.xr,
+                            release_height: nathan_data.zr,
+                        }));
+
+                        let pitch_called_strike_probability = match (pitch_data.coordinates.p_x, pitch_data.coordinates.p_z) {
+                            (Some (p_x), Some (p_z)) => Some(data.meta_data.called_strike.probability(&CalledPitch {
+                                sport_id,
+                                season: year,
+                                batter_bats,
+                                balls: balls_start,
+                                strikes: strikes_start,
+                                p_x,
+                                p_z,
+                                strike_zone_top: pitch_data.strike_zone_top as f64,
+                                strike_zone_bottom: pitch_data.strike_zone_bottom as f64,
+                            }) as f32),
+                            _ => None,
+                        };
+
                         let plate_appearance_description = plate_app.result.plate_appearance_result_description.clone().unwrap_or_default();
                         
                         let imputed_hit_trajectory: Option<Trajectory> = if event.details.is_in_play.unwrap() {
@@ -1080,6 +1512,40 @@ impl <'m> From <GameData<'m>> for Vec<Pitch> {
                             _ => hit_data_trajectory,
                         };
 
+                        let expected = match (event.details.is_in_play.unwrap(), expected_outcomes) {
+                            (true, Some (model)) => Some(model.expected(&BattedBall {
+                                exit_velocity: hit_data_exit_velocity,
+                                launch_angle: hit_data_launch_angle,
+                                spray_angle: hit_data_spray_angle,
+                                batter_bats,
+                                trajectory: hit_data_trajectory,
+                                hardness: hit_data_contact_quality,
+                            })),
+                            _ => None,
+                        };
+
+                        // Carry under the game's conditions and under neutral ones. The neutral distance moves the measured distance
+                        // by the difference between the two when we have it, so it keeps whatever the simulation gets wrong about the ball.
+                        let flight = match (event.details.is_in_play.unwrap(), hit_data_exit_velocity, hit_data_launch_angle, hit_data_spray_angle) {
+                            (true, Some (exit_velocity), Some (launch_angle), Some (spray_angle)) => {
+                                crate::flight::simulate(exit_velocity as f64, launch_angle as f64, spray_angle, &conditions)
+                                    .zip(crate::flight::simulate(exit_velocity as f64, launch_angle as f64, spray_angle, &crate::flight::NEUTRAL))
+                            },
+                            _ => None,
+                        };
+                        let hit_data_carry_distance = flight.as_ref().map(|(game, _)| game.carry as f32);
+                        let hit_data_neutral_distance = flight.as_ref().map(|(game, neutral)| match hit_data_total_distance {
+                            Some (distance) => distance + (neutral.carry - game.carry) as f32,
+                            None => neutral.carry as f32,
+                        });
+                        let (hit_data_hr_parks, hit_data_hr_parks_total) = match (flight.as_ref(), fences) {
+                            (Some ((game, _)), Some (fences)) => (
+                                Some(fences.iter().filter(|(_, fence)| game.clears(fence)).count() as u8),
+                                Some(fences.len() as u8),
+                            ),
+                            _ => (None, None),
+                        };
+
                         let bunt_description = if event.details.is_in_play.unwrap() {
                             Some ( event.details.description.clone().unwrap_or_default().contains("bunt"))
                         } else {None};
@@ -1089,7 +1555,22 @@ impl <'m> From <GameData<'m>> for Vec<Pitch> {
                             None => bunt_description,
                         };
 
-                        pitches.push(
+                        let current_pitch = PreviousPitch {
+                            type_code: pitch_type_code,
+                            speed: pitch_data.start_speed,
+                            p_x: pitch_data.coordinates.p_x,
+                            p_z: pitch_data.coordinates.p_z,
+                            break_vertical_induced: pitch_break_vertical_induced,
+                            break_horizontal: pitch_break_horizontal,
+                            result: pitch_result,
+                        };
+
+                        let real_pitch = pitch_result != PitchResult::NoPitch && !is_automatic_call(code);
+                        let sequence = sequencing.pitch(pitcher, current_pitch, real_pitch);
+                        let (pitch_speed_delta, pitch_break_vertical_induced_delta, pitch_break_horizontal_delta) = sequence.deltas(&current_pitch);
+                        let (two_strikes, full_count) = count_flags(balls_start, strikes_start);
+
+                        plate_app_pitches.push(
                             Pitch {
                                 
                                 play_id: event.play_id.unwrap_or_default(),
@@ -1157,6 +1638,10 @@ impl <'m> From <GameData<'m>> for Vec<Pitch> {
                                 venue_retrosheet_id: venue_meta.venue_retrosheet_id.clone(),
                                 venue_latitude: venue_meta.venue_latitude,
                                 venue_longitude: venue_meta.venue_longitude,
+                                venue_elevation: venue_meta.venue_elevation,
+                                venue_park_factor_runs: park_factor.map(|pf| pf.runs),
+                                venue_park_factor_home_runs: park_factor.map(|pf| pf.home_runs),
+                                venue_park_factor_home_runs_batted_ball: park_factor.and_then(|pf| pf.home_runs_batted_ball),
                                 
                                 pitcher,
                                 pitcher_throws,
@@ -1227,6 +1712,25 @@ impl <'m> From <GameData<'m>> for Vec<Pitch> {
                                 pitch_num_inning,    
                                 pitch_num_game,
                                 preceded_by_pickoff,
+
+                                pitch_result,
+                                prev_pitch_plate_appearance_type_code: sequence.plate_appearance.and_then(|p| p.type_code),
+                                prev_pitch_plate_appearance_speed: sequence.plate_appearance.and_then(|p| p.speed),
+                                prev_pitch_plate_appearance_p_x: sequence.plate_appearance.and_then(|p| p.p_x),
+                                prev_pitch_plate_appearance_p_z: sequence.plate_appearance.and_then(|p| p.p_z),
+                                prev_pitch_plate_appearance_result: sequence.plate_appearance.map(|p| p.result),
+                                prev_pitch_outing_type_code: sequence.outing.and_then(|p| p.type_code),
+                                prev_pitch_outing_speed: sequence.outing.and_then(|p| p.speed),
+                                prev_pitch_outing_p_x: sequence.outing.and_then(|p| p.p_x),
+                                prev_pitch_outing_p_z: sequence.outing.and_then(|p| p.p_z),
+                                prev_pitch_outing_result: sequence.outing.map(|p| p.result),
+                                pitch_speed_delta,
+                                pitch_break_vertical_induced_delta,
+                                pitch_break_horizontal_delta,
+                                pitch_type_num_plate_appearance: sequence.pitch_type_num_plate_appearance,
+                                first_pitch: sequence.first_pitch,
+                                two_strikes,
+                                full_count,
                                 balls_start,
                                 balls_end,
                                 strikes_start,
@@ -1240,6 +1744,12 @@ impl <'m> From <GameData<'m>> for Vec<Pitch> {
                                 re_288_start: *re_288_start,
                                 re_288_end: *re_288_end,
                                 re_288_val,
+                                home_score_start: score_start.0,
+                                away_score_start: score_start.1,
+                                win_expectancy_start,
+                                win_expectancy_end,
+                                win_probability_added,
+                                leverage_index,
                                 swing,
                                 foul,
                                 swing_and_miss,
@@ -1276,6 +1786,9 @@ impl <'m> From <GameData<'m>> for Vec<Pitch> {
                                 pitch_z0: pitch_data.coordinates.z0,
                                 pitch_type_code,
                                 pitch_type_desc,
+                                pitch_type_inferred: pitch_type_inferred.map(|(pitch_type, _)| pitch_type),
+                                pitch_type_inferred_confidence: pitch_type_inferred.map(|(_, confidence)| confidence),
+                                pitch_called_strike_probability,
                                 
                                 in_play_1b,
                                 in_play_2b,
@@ -1295,6 +1808,16 @@ impl <'m> From <GameData<'m>> for Vec<Pitch> {
                                 hit_data_total_distance, 
                                 hit_data_spray_angle,
                                 hit_data_calc_distance,
+                                hit_data_calc_distance_ft,
+                                hit_data_landing_x_ft,
+                                hit_data_landing_y_ft,
+                                hit_data_xba: expected.map(|x| x.xba),
+                                hit_data_xslg: expected.map(|x| x.xslg),
+                                hit_data_xwoba: expected.map(|x| x.xwoba),
+                                hit_data_carry_distance,
+                                hit_data_neutral_distance,
+                                hit_data_hr_parks,
+                                hit_data_hr_parks_total,
 
                                 fielded_by_id,
                                 fielded_by_name,
@@ -1308,6 +1831,10 @@ impl <'m> From <GameData<'m>> for Vec<Pitch> {
                                 game_year: sched_meta.game_date.year,
                                 game_month: sched_meta.game_date.month,
                                 game_status: sched_meta.game_status,
+                                // We can only validate once the whole game has been walked, this gets set in get_data
+                                game_box_score_validated: false,
+                                // Set once we know whether the whole plate appearance needed repairs
+                                data_quality_repaired: false,
                                 game_weather_condition: box_meta.game_weather_condition,
                                 game_weather_temp_c: box_meta.game_weather_temp_c,
                                 game_weather_temp_f: box_meta.game_weather_temp_f,
@@ -1347,11 +1874,22 @@ impl <'m> From <GameData<'m>> for Vec<Pitch> {
                                 ivb: nathan_data.ivb,
                                 hb: nathan_data.hb,
                                 cd: nathan_data.cd,
+                                decision_t: decision_point.map(|point| point.t),
+                                decision_x: decision_point.map(|point| point.x),
+                                decision_z: decision_point.map(|point| point.z),
+                                cl: nathan_data.cl,
+                                transverse_spin: spin.transverse_spin,
+                                spin_efficiency: spin.spin_efficiency,
+                                spin_axis_inferred: spin.spin_axis_inferred,
+                                spin_axis_inferred_clock: spin.spin_axis_inferred_clock,
+                                spin_axis_observed_clock: spin.spin_axis_observed_clock,
+                                spin_axis_deviation: spin.spin_axis_deviation,
+                                seam_shifted_wake: spin.seam_shifted_wake,
                                 
 
                             }
                         );
-                        // The pitches.push() function ends here
+                        // The plate_app_pitches.push() function ends here
                         // If we've pushed a pitch, we can reset the preceded_by_pickoff flag
                         preceded_by_pickoff = false;
 
@@ -1366,7 +1904,267 @@ impl <'m> From <GameData<'m>> for Vec<Pitch> {
                     }
                 }
             }
+
+            if let Some(review_details) = &plate_app.review_details {
+                let description = plate_app.result.plate_appearance_result_description.clone().unwrap_or_default();
+                events.push(event_context.review(review_details, description));
+            }
+
+            for pitch in plate_app_pitches.iter_mut() {
+                pitch.data_quality_repaired = plate_app_repaired;
+            }
+
+            if plate_app_quarantined {
+                quarantined.extend(plate_app_pitches);
+            }
+            else {
+                pitches.extend(plate_app_pitches);
+            }
+
+            score = score_end;
+        }
+
+        GameTables {
+            pitches,
+            quarantined,
+            data_quality,
+            pitcher_appearances: appearance_tracker.finish(),
+            events,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data_quality::Resolution;
+
+    fn pitch (type_code: PitchTypeCode, speed: f64) -> PreviousPitch {
+        PreviousPitch {
+            type_code: Some(type_code),
+            speed: Some(speed),
+            p_x: Some(0.0),
+            p_z: Some(2.5),
+            break_vertical_induced: Some(15.0),
+            break_horizontal: None,
+            result: PitchResult::CalledStrike,
         }
-        pitches
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn previous_pitch_in_the_plate_appearance () {
+        let mut sequencing = Sequencing::default();
+        let first = sequencing.pitch(1, pitch(PitchTypeCode::FF, 95.0), true);
+        let second = sequencing.pitch(1, pitch(PitchTypeCode::SL, 85.0), true);
+
+        assert!(first.plate_appearance.is_none());
+        assert!(first.first_pitch);
+        assert_eq!(second.plate_appearance.and_then(|p| p.type_code), Some(PitchTypeCode::FF));
+        assert!(!second.first_pitch);
+    }
+
+    #[test]
+    fn previous_pitch_across_the_outing () {
+        let mut sequencing = Sequencing::default();
+        sequencing.pitch(1, pitch(PitchTypeCode::FF, 95.0), true);
+        sequencing.new_plate_appearance();
+        let next_batter = sequencing.pitch(1, pitch(PitchTypeCode::SL, 85.0), true);
+        let reliever = sequencing.pitch(2, pitch(PitchTypeCode::CU, 78.0), true);
+
+        assert!(next_batter.plate_appearance.is_none());
+        assert!(next_batter.first_pitch);
+        assert_eq!(next_batter.outing.and_then(|p| p.type_code), Some(PitchTypeCode::FF));
+        assert!(reliever.outing.is_none());
+    }
+
+    #[test]
+    fn deltas_from_the_previous_pitch () {
+        let mut sequencing = Sequencing::default();
+        let fastball = pitch(PitchTypeCode::FF, 95.0);
+        let slider = PreviousPitch {break_vertical_induced: Some(2.0), ..pitch(PitchTypeCode::SL, 85.0)};
+
+        assert_eq!(sequencing.pitch(1, fastball, true).deltas(&fastball), (None, None, None));
+        assert_eq!(sequencing.pitch(1, slider, true).deltas(&slider), (Some(-10.0), Some(-13.0), None));
+    }
+
+    #[test]
+    fn pitch_types_counted_in_the_plate_appearance () {
+        let mut sequencing = Sequencing::default();
+        let mut counts: Vec<Option<u8>> = [PitchTypeCode::FF, PitchTypeCode::SL, PitchTypeCode::FF].into_iter()
+            .map(|code| sequencing.pitch(1, pitch(code, 90.0), true).pitch_type_num_plate_appearance)
+            .collect();
+        sequencing.new_plate_appearance();
+        counts.push(sequencing.pitch(1, pitch(PitchTypeCode::FF, 90.0), true).pitch_type_num_plate_appearance);
+
+        assert_eq!(counts, vec![Some(1), Some(1), Some(2), Some(1)]);
+    }
+
+    #[test]
+    fn only_real_pitches_move_the_sequence () {
+        // An automatic ball before the first pitch, then another between pitches
+        let mut sequencing = Sequencing::default();
+        let automatic = PreviousPitch {type_code: None, speed: None, result: PitchResult::Ball, ..pitch(PitchTypeCode::FF, 0.0)};
+
+        let before = sequencing.pitch(1, automatic, false);
+        let first = sequencing.pitch(1, pitch(PitchTypeCode::FF, 95.0), true);
+        let between = sequencing.pitch(1, automatic, false);
+        let second = sequencing.pitch(1, pitch(PitchTypeCode::FF, 94.0), true);
+
+        assert!(!before.first_pitch);
+        assert!(first.first_pitch);
+        assert!(first.plate_appearance.is_none() && first.outing.is_none());
+        assert_eq!(between.plate_appearance.and_then(|p| p.speed), Some(95.0));
+        assert_eq!(between.pitch_type_num_plate_appearance, None);
+        assert_eq!(second.plate_appearance.and_then(|p| p.speed), Some(95.0));
+        assert_eq!(second.outing.and_then(|p| p.speed), Some(95.0));
+        assert_eq!(second.pitch_type_num_plate_appearance, Some(2));
+    }
+
+    #[test]
+    fn count_flags_for_the_count_thrown_in () {
+        assert_eq!(count_flags(0, 0), (false, false));
+        assert_eq!(count_flags(1, 2), (true, false));
+        assert_eq!(count_flags(3, 2), (true, true));
+        assert_eq!(count_flags(3, 1), (false, false));
+    }
+
+    /// A 2023 regular season game with nothing but its schedule and boxscore, so the play by play is all that matters
+    fn meta_data () -> MetaData {
+        let schedule: GameMetaData = serde_json::from_str(
+            r#"{"game_type": "R", "game_type_desc": "RegularSeason", "game_pk": 1, "game_date": {"year": 2023, "month": 6, "day": 1},
+                "game_venue_id": 15, "game_url_play_by_play": "", "game_url_boxscore": "", "coaches_home_url": "", "coaches_away_url": "",
+                "game_status": "Final", "sport_id": 1}"#
+        ).unwrap();
+        let boxscore: crate::boxscore::BoxScore = serde_json::from_str(
+            r#"{"home_team_id": 1, "away_team_id": 2, "home_sport_id": 1, "away_sport_id": 1, "home_parent_team_id": 1, "away_parent_team_id": 2,
+                "home_players": [], "away_players": [], "home_defense": {}, "away_defense": {}}"#
+        ).unwrap();
+        MetaData {schedule: HashMap::from([(1, schedule)]), boxscore: HashMap::from([(1, boxscore)]), ..MetaData::default()}
+    }
+
+    /// A pitch as the source sends it, with the count and outs after the pitch
+    fn pitch_event (index: u8, code: &str, balls: u8, strikes: u8, outs: u8) -> String {
+        format!(
+            r#"{{"details": {{"code": "{}", "isInPlay": {}, "hasReview": false}}, "count": {{"balls": {}, "strikes": {}, "outs": {}}},
+                "isPitch": true, "index": {}, "type": "pitch", "pitchData": {{"strikeZoneTop": 3.5, "strikeZoneBottom": 1.5, "coordinates": {{}}}}}}"#,
+            code, code == "X", balls, strikes, outs, index,
+        )
+    }
+
+    /// A runner who reached `end` on the event with `play_index`
+    fn runner_event (runner_id: u32, end: &str, play_index: u8) -> String {
+        format!(
+            r#"{{"movement": {{"start": null, "end": "{}", "isOut": false}},
+                "details": {{"runner": {{"id": {}}}, "event": "Walk", "eventType": "walk", "rbi": false, "earned": false, "playIndex": {}}}}}"#,
+            end, runner_id, play_index,
+        )
+    }
+
+    /// A plate appearance in the top of the 1st. The batter is 100 plus the index.
+    fn plate_appearance (index: u8, result: &str, events: &[String], runners: &[String]) -> AllPlays {
+        serde_json::from_str(&format!(
+            r#"{{"result": {{"event": "{}", "homeScore": 0, "awayScore": 0}}, "about": {{"atBatIndex": {}, "halfInning": "top", "inning": 1}},
+                "matchup": {{"batter": {{"id": {}}}, "pitcher": {{"id": 20}}, "batSide": {{"code": "R"}}, "pitchHand": {{"code": "R"}}}},
+                "runners": [{}], "playEvents": [{}]}}"#,
+            result, index, 100 + index as u32, runners.join(", "), events.join(", "),
+        )).unwrap()
+    }
+
+    /// Four balls, with the outs the source reports
+    fn four_balls (outs: u8) -> Vec<String> {
+        (1 ..= 4).map(|balls| pitch_event(balls - 1, "B", balls, 0, outs)).collect()
+    }
+
+    fn game (plays: Vec<AllPlays>) -> GameTables {
+        GameTables::from(GameData {pitch_data: plays, meta_data: &meta_data(), game_pk: 1})
+    }
+
+    fn issues (tables: &GameTables) -> Vec<(Option<u8>, DataQualityIssue, Resolution)> {
+        tables.data_quality.iter().map(|record| (record.play_index, record.issue, record.resolution)).collect()
+    }
+
+    #[test]
+    fn clean_plate_appearances_have_no_issues () {
+        let tables = game(vec![plate_appearance(0, "Walk", &four_balls(0), &[])]);
+
+        assert!(tables.data_quality.is_empty());
+        assert_eq!(tables.pitches.len(), 4);
+        assert!(tables.pitches.iter().all(|pitch| !pitch.data_quality_repaired));
+        assert!(tables.quarantined.is_empty());
+    }
+
+    #[test]
+    fn duplicate_play_indices_are_dropped () {
+        let mut events = four_balls(0);
+        events.insert(1, pitch_event(0, "B", 1, 0, 0));
+        let tables = game(vec![plate_appearance(0, "Walk", &events, &[])]);
+
+        assert_eq!(issues(&tables), vec![(Some(0), DataQualityIssue::DuplicatePlayIndex, Resolution::Repaired)]);
+        assert_eq!(tables.pitches.iter().map(|pitch| pitch.balls_end).collect::<Vec<u8>>(), vec![1, 2, 3, 4]);
+        assert!(tables.pitches.iter().all(|pitch| pitch.data_quality_repaired));
+        assert!(tables.quarantined.is_empty());
+    }
+
+    #[test]
+    fn pitches_after_ball_four_are_dropped () {
+        let mut events = four_balls(0);
+        events.push(pitch_event(4, "B", 4, 0, 0));
+        let tables = game(vec![plate_appearance(0, "Walk", &events, &[])]);
+
+        assert_eq!(issues(&tables), vec![(Some(4), DataQualityIssue::ExtraRecord, Resolution::Repaired)]);
+        assert_eq!(tables.pitches.len(), 4);
+        assert_eq!(tables.pitches.iter().map(|pitch| pitch.walk).sum::<u8>(), 1);
+        assert!(tables.pitches.iter().all(|pitch| pitch.data_quality_repaired));
+    }
+
+    #[test]
+    fn walks_past_four_balls_are_capped () {
+        let events = vec![pitch_event(0, "B", 1, 0, 0), pitch_event(1, "B", 2, 0, 0), pitch_event(2, "B", 3, 0, 0), pitch_event(3, "B", 5, 0, 0)];
+        let tables = game(vec![plate_appearance(0, "Walk", &events, &[])]);
+
+        assert_eq!(issues(&tables), vec![(Some(3), DataQualityIssue::ExtraRecord, Resolution::Repaired)]);
+        assert_eq!(tables.pitches[3].balls_end, 4);
+        assert_eq!(tables.pitches[3].walk, 1);
+        assert!(tables.quarantined.is_empty());
+    }
+
+    #[test]
+    fn balls_without_a_walk_quarantine_the_plate_appearance () {
+        let tables = game(vec![
+            plate_appearance(0, "Single", &four_balls(0), &[]),
+            plate_appearance(1, "Walk", &four_balls(0), &[]),
+        ]);
+
+        assert_eq!(issues(&tables), vec![(Some(3), DataQualityIssue::BallsWithoutWalk, Resolution::Quarantined)]);
+        // Every pitch of the bad plate appearance, not just the one that failed
+        assert_eq!(tables.quarantined.len(), 4);
+        assert!(tables.quarantined.iter().all(|pitch| pitch.num_plate_appearance == 1));
+        assert_eq!(tables.pitches.len(), 4);
+        assert!(tables.pitches.iter().all(|pitch| pitch.num_plate_appearance == 2));
+    }
+
+    #[test]
+    fn outs_going_down_quarantine_the_plate_appearance () {
+        let tables = game(vec![
+            plate_appearance(0, "Walk", &four_balls(1), &[]),
+            plate_appearance(1, "Walk", &four_balls(0), &[]),
+        ]);
+
+        assert_eq!(issues(&tables), vec![(Some(0), DataQualityIssue::OutsDecreased, Resolution::Quarantined)]);
+        assert!(tables.pitches.iter().all(|pitch| pitch.num_plate_appearance == 1));
+        assert_eq!(tables.quarantined.len(), 4);
+        assert!(tables.quarantined.iter().all(|pitch| pitch.num_plate_appearance == 2));
+    }
+
+    #[test]
+    fn more_runners_than_bases_quarantine_the_plate_appearance () {
+        // Two runners end up on first on ball four, and neither of them was already on base
+        let runners = [runner_event(100, "1B", 3), runner_event(101, "2B", 3), runner_event(102, "3B", 3), runner_event(103, "1B", 3)];
+        let tables = game(vec![plate_appearance(0, "Walk", &four_balls(0), &runners)]);
+
+        assert_eq!(issues(&tables), vec![(Some(3), DataQualityIssue::BaseValueOverflow, Resolution::Quarantined)]);
+        assert!(tables.pitches.is_empty());
+        assert_eq!(tables.quarantined.len(), 4);
+        assert_eq!(tables.quarantined[3].base_value_end, 7);
+    }
+}
@@ -15,11 +15,35 @@ use crate::schedule::{YearRange, GameMetaData, Schedule, SeasonSportStatus, Seas
 use crate::sports;
 use crate::team::{TeamData, TeamJson};
 
-use crate::venues::{VenueXY, Venues, VenueData};
-use crate::game::{Pitch, GameData};
+use crate::venues::{VenueXY, Venues, VenueData, Venue};
+use crate::game::{Pitch, GameData, GameTables};
+use crate::data_quality::DataQualityRecord;
+use crate::pitcher_appearance::PitcherAppearance;
+use crate::events::GameEvent;
+use crate::run_expectancy::{RE288Data, RE288Entry, RE288Pitch};
+use crate::markov::{MarkovData, MarkovRunExpectancy, RE24Entry};
+use crate::linear_weights::{LinearWeights, LinearWeightsData};
+use crate::win_expectancy::{WinExpectancyData, WinExpectancyTable};
+use crate::park_factors::{ParkFactor, ParkFactorData};
+use crate::expected_stats::{ExpectedStatsData, ExpectedStatsOutput};
+use crate::linear_weights::WobaWeights;
+use crate::pitch_classification::{ClassificationData, PitchClassifier};
+use crate::pitcher_arsenal::{ArsenalData, PitcherArsenal, season_changes};
+use crate::framing::{FramingData, FramingOutput};
+use crate::fielding::{FieldingData, FieldingOutput};
+use crate::nathan::{PolylineData, TrajectoryRow, POLYLINE_POINTS};
+use crate::tunneling::{TunnelingData, TunnelingOutput};
+use crate::fences::{FenceData, FenceProfile, VenueWall};
+use crate::calibration::{CalibrationData, PixelCalibration};
+use crate::aging::{AgingCurve, AgingData, SurvivorCorrection};
+use crate::mle::{MleData, MleOutput};
+use crate::aging::AgingCurves;
+use crate::projections::{ProjectionData, ProjectionOutput};
+use crate::stabilization::{StabilizationData, StabilizationOutput};
+use crate::validation::{ValidationData, ValidationReport, Discrepancy};
 
 use rayon::prelude::*;
-use std::collections::BTreeSet;
+use std::collections::{BTreeSet, HashMap};
 use serde::{Serialize, Deserialize};
 use reqwest::blocking::*;
 
@@ -55,6 +79,9 @@ pub fn get_play_by_play (schedule: Vec<GameMetaData>, meta_data: &MetaData) {
     struct GamesProcessed {
         good: BTreeSet<u32>,
         bad: BTreeSet<u32>,
+        // Games that were processed, but whose pitches don't tie out with the boxscore. These are a subset of the good games.
+        #[serde(default)]
+        failed_validation: BTreeSet<u32>,
     }
 
     let games_processed_str = format!("{}{}", cache_folder(), "\\games_processed.json" );
@@ -64,11 +91,13 @@ pub fn get_play_by_play (schedule: Vec<GameMetaData>, meta_data: &MetaData) {
         GamesProcessed {
             good: BTreeSet::new(),
             bad: BTreeSet::new(),
+            failed_validation: BTreeSet::new(),
         }
     );
 
     let mut good_games: BTreeSet<u32> = games_processed.good;
     let mut bad_games: BTreeSet<u32> = games_processed.bad;
+    let mut failed_validation_games: BTreeSet<u32> = games_processed.failed_validation;
     // let mut bad_games: BTreeSet<u32> = BTreeSet::new();
 
     //For some reason, we have duplicate game_pks in our schedule, so we make it a set to get rid of that problem.
@@ -85,7 +114,7 @@ pub fn get_play_by_play (schedule: Vec<GameMetaData>, meta_data: &MetaData) {
     let requested_games: BTreeSet<u32> = pbp_urls.iter().map(|game| game.0).collect();
     dbg!(requested_games.len());
 
-    let result: Vec<Pitch> = pbp_urls.into_par_iter()
+    let games: Vec<(GameTables, Option<ValidationReport>)> = pbp_urls.into_par_iter()
         // .inspect(|data| println!("{}", &data.1))
         .map (|data| (data.0, get(data.1).unwrap().text().unwrap_or("".to_string())))
         .filter(|data| data.1.contains("allPlays"))
@@ -96,13 +125,54 @@ pub fn get_play_by_play (schedule: Vec<GameMetaData>, meta_data: &MetaData) {
                 meta_data: &meta_data,
                 game_pk: data.0,
             };
-            let pitches: Vec<Pitch> = game_data.into();
-            pitches
+            let mut game_tables: GameTables = game_data.into();
+            let pitches = &mut game_tables.pitches;
+
+            // Roll the pitches back up into a box score and check them against the boxscore endpoint. Games without
+            // any pitches were never built, so there is nothing to validate.
+            let report: Option<ValidationReport> = match (pitches.len(), meta_data.boxscore.get(&data.0)) {
+                (0, _) | (_, None) => None,
+                (_, Some (box_score)) => Some (
+                    ValidationData {
+                        game_pk: data.0,
+                        pitches,
+                        box_score,
+                    }.into()
+                ),
+            };
+
+            if let Some(report) = &report {
+                let validated = report.passed();
+                for pitch in pitches.iter_mut() {
+                    pitch.game_box_score_validated = validated;
+                }
+            }
+
+            (game_tables, report)
         })
-        .flatten()
         .collect()
         ;
 
+    let mut result: Vec<Pitch> = Vec::new();
+    let mut quarantined: Vec<Pitch> = Vec::new();
+    let mut data_quality: Vec<DataQualityRecord> = Vec::new();
+    let mut pitcher_appearances: Vec<PitcherAppearance> = Vec::new();
+    let mut events: Vec<GameEvent> = Vec::new();
+    let mut discrepancies: Vec<Discrepancy> = Vec::new();
+
+    for (game_tables, report) in games {
+        result.extend(game_tables.pitches);
+        quarantined.extend(game_tables.quarantined);
+        data_quality.extend(game_tables.data_quality);
+        pitcher_appearances.extend(game_tables.pitcher_appearances);
+        events.extend(game_tables.events);
+        if let Some(report) = report {
+            if !report.passed() {failed_validation_games.insert(report.game_pk);};
+            discrepancies.extend(report.discrepancies);
+            discrepancies.extend(report.put_out_discrepancies);
+        }
+    }
+
     let games_returned: BTreeSet<u32> = result.iter().map(|game|game.game_pk).collect();
     let games_missed: BTreeSet<u32> = requested_games.into_iter().filter(|game| !games_returned.contains(game)).collect();
 
@@ -110,15 +180,27 @@ pub fn get_play_by_play (schedule: Vec<GameMetaData>, meta_data: &MetaData) {
     dbg!(games_missed.len());
     dbg!(games_returned.len());
 
+    // New columns in any of the game tables mean starting them over. The games already in the old files have to be pulled
+    // again, so only this run's games stay in the ledger.
+    if crate::cache::game_tables_changed(&result, &pitcher_appearances, &events, &data_quality, &quarantined, &discrepancies) {
+        crate::cache::rotate_game_tables();
+        good_games.clear();
+        failed_validation_games.retain(|game| games_returned.contains(game));
+        println!("The game tables have new columns, cleared games processed so every game is pulled again.");
+    }
+
     good_games.extend(games_returned);
     bad_games.extend(games_missed);
 
 
     let num_games_processed = good_games.len();
 
+    let num_games_failed_validation = failed_validation_games.len();
+
     let games_processed = GamesProcessed {
         good: good_games,
         bad: bad_games,
+        failed_validation: failed_validation_games,
     };
 
     let json = serde_json::to_string(&games_processed).unwrap();
@@ -128,8 +210,395 @@ pub fn get_play_by_play (schedule: Vec<GameMetaData>, meta_data: &MetaData) {
     crate::cache::append_play_by_play(&result);
     println!("Added {} records.", result.len());
 
-    println!("Processed {} total games.", num_games_processed);
+    println!("Writing pitcher appearances to CSV...");
+    crate::cache::append_pitcher_appearances(&pitcher_appearances);
+    println!("Added {} pitcher appearances.", pitcher_appearances.len());
+
+    println!("Writing non-pitch events to CSV...");
+    crate::cache::append_events(&events);
+    println!("Added {} events.", events.len());
+
+    println!("Writing data quality audit trail and quarantined pitches to CSV...");
+    crate::cache::append_data_quality(&data_quality);
+    crate::cache::append_quarantine(&quarantined);
+    println!("Found {} data quality issues, quarantined {} pitches.", data_quality.len(), quarantined.len());
+
+    println!("Writing box score validation report to CSV...");
+    crate::cache::append_validation(&discrepancies);
+    println!("Found {} discrepancies.", discrepancies.len());
+
+    println!("Processed {} total games, {} failed box score validation.", num_games_processed, num_games_failed_validation);
+
+}
+
+/// Builds RE288 tables for every sport_id and season in our play by play output and caches them. Set by_venue to also build
+/// a table for each venue. The tables are picked up the next time the metadata is loaded, so any play by play generated after
+/// this will use them for re_288_val.
+pub fn build_run_expectancy (by_venue: bool) {
+
+    println!("Loading pitches to build RE288 tables...");
+    let pitches: Vec<RE288Pitch> = load_re_288_pitches();
+    println!("Loaded {} pitches.", pitches.len());
+
+    let re_288: Vec<RE288Entry> = RE288Data {
+        pitches: &pitches,
+        by_venue,
+    }.into();
+
+    cache_re_288(&re_288);
+    println!("Built {} RE288 tables.", re_288.len() / 288);
+}
+
+/// Builds RE288 and RE24 tables for every sport_id and season by simulation (Method 2) and caches them next to the averaged tables
+/// so the two can be compared. The same seed always gives the same tables.
+pub fn build_run_expectancy_simulated (iterations: u32, seed: u64) {
+
+    println!("Loading pitches to simulate RE288 tables...");
+    let pitches: Vec<RE288Pitch> = load_re_288_pitches();
+    println!("Loaded {} pitches.", pitches.len());
+
+    let re: MarkovRunExpectancy = MarkovData {
+        pitches: &pitches,
+        iterations,
+        seed,
+    }.into();
+
+    cache_re_simulated(&re);
+    println!("Simulated {} RE288 tables.", re.re_288.len() / 288);
+}
+
+/// Builds linear weights, wOBA weights and the league constants for every sport_id and season and caches them next to the RE
+/// tables. Run values come from the 0-0 count of the cached RE288 league tables, so run build_run_expectancy first. Build the
+/// pitcher appearances for a season before this if you want its FIP constant.
+pub fn build_linear_weights () {
+
+    println!("Loading pitches to build linear weights...");
+    let pitches = load_linear_weights_pitches();
+    let appearances = load_linear_weights_appearances();
+    println!("Loaded {} pitches and {} pitcher appearances.", pitches.len(), appearances.len());
+
+    let re_24: Vec<RE24Entry> = load_re_288().into_iter()
+        .filter(|re| re.venue_id.is_none() && re.balls == 0 && re.strikes == 0)
+        .map(|re| RE24Entry {
+            sport_id: re.sport_id,
+            season: re.season,
+            base_value: re.base_value,
+            outs: re.outs,
+            run_expectancy: re.run_expectancy,
+        })
+        .collect();
+
+    let linear_weights: Vec<LinearWeights> = LinearWeightsData {
+        pitches: &pitches,
+        appearances: &appearances,
+        re_24: &re_24,
+    }.into();
+
+    cache_linear_weights(&linear_weights);
+    println!("Built linear weights for {} league seasons.", linear_weights.len());
+}
+
+/// Builds win expectancy tables for every sport_id and season in our play by play output and caches them. Like the RE288 tables,
+/// they're picked up the next time the metadata is loaded, so only play by play generated after this will have WE, WPA and LI.
+pub fn build_win_expectancy () {
+
+    println!("Loading pitches to build win expectancy tables...");
+    let pitches = load_win_expectancy_pitches();
+    println!("Loaded {} pitches.", pitches.len());
+
+    let win_expectancy: Vec<WinExpectancyTable> = WinExpectancyData {
+        pitches: &pitches,
+    }.into();
+
+    cache_win_expectancy(&win_expectancy);
+    println!("Built {} win expectancy tables.", win_expectancy.len());
+}
+
+/// Builds park factors for every venue, sport_id and season in our play by play output and writes them to the park factors table,
+/// replacing the old one. Play by play generated after this picks up the factors for the batter's side.
+pub fn build_park_factors () {
+
+    println!("Loading pitches to build park factors...");
+    let pitches = load_park_factor_pitches();
+    println!("Loaded {} pitches.", pitches.len());
+
+    let park_factors: Vec<ParkFactor> = ParkFactorData {
+        pitches: &pitches,
+    }.into();
+
+    write_park_factors(&park_factors);
+    println!("Built {} park factors.", park_factors.len());
+}
+
+/// Fits the expected outcome model for every sport_id and season in our play by play output and caches it, then writes xBA, xSLG
+/// and xwOBA for every batter and pitcher to the expected stats table. xwOBA uses the cached linear weights, so run
+/// build_linear_weights first.
+pub fn build_expected_stats () {
+
+    println!("Loading pitches to fit expected outcomes...");
+    let pitches = load_expected_stats_pitches();
+    println!("Loaded {} pitches.", pitches.len());
+
+    let woba_weights: HashMap<(u32, u16), WobaWeights> = load_linear_weights().iter()
+        .map(|lw| ((lw.sport_id, lw.season), lw.into()))
+        .collect();
+
+    let expected: ExpectedStatsOutput = ExpectedStatsData {
+        pitches: &pitches,
+        woba_weights: &woba_weights,
+    }.into();
+
+    cache_expected_outcomes(&expected.bins);
+    write_expected_stats(&expected.players);
+    println!("Fit {} expected outcome bins, {} player seasons.", expected.bins.len(), expected.players.len());
+}
+
+/// Fits a pitch classifier for every pitcher with enough pitches and caches them. Games pick up the classifiers through the
+/// metadata and write the inferred pitch types.
+pub fn build_pitch_classifiers () {
+
+    println!("Loading pitches to classify...");
+    let pitches = load_classification_pitches();
+    println!("Loaded {} pitches.", pitches.len());
+
+    let classifiers: Vec<PitchClassifier> = ClassificationData {pitches: &pitches}.into();
+
+    cache_pitch_classifiers(&classifiers);
+    println!("Fit pitch classifiers for {} pitchers.", classifiers.len());
+}
+
+/// Builds every pitcher's arsenal by season and pitch type, along with the season over season changes. Run this after the
+/// play by play has the inferred pitch types, so untagged pitches are included.
+pub fn build_pitcher_arsenals () {
+
+    println!("Loading pitches for arsenals...");
+    let pitches = load_arsenal_pitches();
+    println!("Loaded {} pitches.", pitches.len());
+
+    let arsenals: Vec<PitcherArsenal> = ArsenalData {pitches: &pitches}.into();
+    let changes = season_changes(&arsenals);
+
+    write_pitcher_arsenals(&arsenals);
+    write_arsenal_changes(&changes);
+    println!("Built {} arsenal rows, flagged {} changes.", arsenals.len(), changes.len());
+}
+
+/// Fits the called strike model and builds catcher framing and umpire accuracy off of it.
+pub fn build_framing () {
+
+    println!("Loading pitches for the called strike model...");
+    let pitches = load_framing_pitches();
+    println!("Loaded {} pitches.", pitches.len());
+
+    let framing: FramingOutput = FramingData {pitches: &pitches}.into();
+
+    cache_called_strike_bins(&framing.bins);
+    write_catcher_framing(&framing.catchers);
+    write_umpire_games(&framing.umpire_games);
+    write_umpire_seasons(&framing.umpire_seasons);
+    println!("Fit {} called strike bins, {} catcher seasons, {} umpire games.", framing.bins.len(), framing.catchers.len(), framing.umpire_games.len());
+}
+
+/// Fits the out probability model for batted balls and credits each fielder with their outs above expected. Reads the defense table
+/// (defense.csv), one row per fielder for every ball in play.
+pub fn build_fielding () {
+
+    println!("Loading the defense table for fielding...");
+    let defense = load_fielding_defense();
+    println!("Loaded {} defense rows.", defense.len());
+
+    let linear_weights: HashMap<(u32, u16), LinearWeights> = load_linear_weights().into_iter()
+        .map(|lw| ((lw.sport_id, lw.season), lw))
+        .collect();
+
+    let fielding: FieldingOutput = FieldingData {
+        defense: &defense,
+        linear_weights: &linear_weights,
+    }.into();
+
+    cache_fielding_bins(&fielding.bins);
+    write_fielding(&fielding.players);
+    println!("Fit {} fielding bins, {} fielder seasons.", fielding.bins.len(), fielding.players.len());
+}
+
+/// Samples every tracked pitch from release to the plate and writes out the polylines.
+pub fn build_pitch_trajectories () {
+
+    println!("Loading pitches for trajectories...");
+    let pitches = load_trajectory_pitches();
+    println!("Loaded {} pitches.", pitches.len());
+
+    let rows: Vec<TrajectoryRow> = PolylineData {
+        pitches: &pitches,
+        points: POLYLINE_POINTS,
+    }.into();
+
+    write_pitch_trajectories(&rows);
+    println!("Wrote {} trajectory points.", rows.len());
+}
+
+/// Compares each pitch with the one before it in the plate appearance and sums up the tunnels for each pitcher and pitch type pair.
+pub fn build_tunneling () {
+
+    println!("Loading pitches for tunneling...");
+    let pitches = load_tunnel_pitches();
+    println!("Loaded {} pitches.", pitches.len());
+
+    let tunneling: TunnelingOutput = TunnelingData {
+        pitches: &pitches,
+    }.into();
+
+    write_tunnel_pairs(&tunneling.pairs);
+    write_pitcher_tunnels(&tunneling.pitchers);
+    println!("Compared {} pitch pairs, {} pitcher pitch type pairs.", tunneling.pairs.len(), tunneling.pitchers.len());
+}
+
+/// Fits home plate and the pixel scale of the hit coordinates for every venue and season from the tracked distances.
+pub fn build_pixel_calibrations () {
+
+    let venues_x_y: HashMap<u32, VenueXY> = load_venue_x_y().into_iter()
+        .map(|v| (v.id, v))
+        .collect();
+
+    println!("Loading batted balls for the pixel calibration...");
+    let pitches = load_calibration_pitches();
+    println!("Loaded {} pitches.", pitches.len());
+
+    let calibrations: Vec<PixelCalibration> = CalibrationData {
+        pitches: &pitches,
+        venues_x_y: &venues_x_y,
+    }.into();
+
+    cache_pixel_calibrations(&calibrations);
+    println!("Calibrated {} venues and venue seasons.", calibrations.len());
+}
+
+/// Builds the aging curves for every level and skill, once for each survivor bias correction so they can be compared.
+pub fn build_aging_curves () {
+
+    println!("Loading pitches for aging curves...");
+    let pitches = load_aging_pitches();
+    println!("Loaded {} pitches.", pitches.len());
+
+    let curves: Vec<AgingCurve> = [SurvivorCorrection::None, SurvivorCorrection::Regressed, SurvivorCorrection::Phantom].into_par_iter()
+        .flat_map(|correction| {
+            let curves: Vec<AgingCurve> = AgingData {
+                pitches: &pitches,
+                correction,
+            }.into();
+            curves
+        })
+        .collect();
+
+    cache_aging_curves(&curves);
+    write_aging_curves(&curves);
+    println!("Built {} aging curve points.", curves.len());
+}
+
+/// Links the levels through the players who played at two of them in a season, chains the factors up to MLB and translates every
+/// minor league line.
+pub fn build_mles () {
+
+    println!("Loading pitches for minor league equivalencies...");
+    let pitches = load_mle_pitches();
+    println!("Loaded {} pitches.", pitches.len());
+
+    let mles: MleOutput = MleData {
+        pitches: &pitches,
+    }.into();
+
+    write_mle_factors(&mles.factors);
+    cache_mle_translations(&mles.translations);
+    write_mle_translations(&mles.translations);
+    write_mle_lines(&mles.lines);
+    println!("Built {} level factors, {} translations to MLB and {} translated lines.", mles.factors.len(), mles.translations.len(), mles.lines.len());
+}
+
+/// Projects every player at every level for each season from the three before it, through the season after our last one, and
+/// backtests the projections against the seasons we have. Run build_aging_curves first for the age adjustment.
+pub fn build_projections () {
+
+    let aging_curves = AgingCurves::new(&load_aging_curves(), crate::projections::AGING_CORRECTION);
+
+    println!("Loading pitches for projections...");
+    let pitches = load_projection_pitches();
+    println!("Loaded {} pitches.", pitches.len());
+
+    let projections: ProjectionOutput = ProjectionData {
+        pitches: &pitches,
+        aging_curves: &aging_curves,
+    }.into();
+
+    write_projections(&projections.projections);
+    write_projection_backtest(&projections.backtest);
+    println!("Wrote {} projections and {} backtest scores.", projections.projections.len(), projections.backtest.len());
+}
+
+/// Measures how reliable each rate stat is at every level and season, then shrinks every batter, pitcher, catcher and umpire's
+/// rates toward the league. xwOBA uses the cached linear weights, so run build_linear_weights and build_expected_stats first.
+pub fn build_stabilization () {
+
+    println!("Loading pitches for stabilization...");
+    let pitches = load_stabilization_pitches();
+    println!("Loaded {} pitches.", pitches.len());
+
+    let woba_weights: HashMap<(u32, u16), WobaWeights> = load_linear_weights().iter()
+        .map(|lw| ((lw.sport_id, lw.season), lw.into()))
+        .collect();
+
+    let stabilization: StabilizationOutput = StabilizationData {
+        pitches: &pitches,
+        woba_weights: &woba_weights,
+    }.into();
+
+    write_stabilization(&stabilization.stabilization);
+    write_shrunk_rates(&stabilization.rates);
+    println!("Measured {} metric seasons and shrunk {} rates.", stabilization.stabilization.len(), stabilization.rates.len());
+}
+
+/// Traces the outfield wall of any venue we haven't seen from its svg, then profiles every park's fence for each season with the
+/// batted balls hit there. Run build_pixel_calibrations first, the walls are converted to feet with the calibrations.
+pub fn build_fence_profiles () {
+
+    let venues: HashMap<(u32, u16), Venue> = load_venue().into_iter()
+        .map(|v| ((v.venue.id, v.year), v.venue))
+        .collect();
+
+    let venues_x_y: HashMap<u32, VenueXY> = load_venue_x_y().into_iter()
+        .map(|v| (v.id, v))
+        .collect();
+    let calibrations: HashMap<(u32, Option<u16>), PixelCalibration> = load_pixel_calibrations().into_iter()
+        .map(|pc| ((pc.venue_id, pc.season), pc))
+        .collect();
+
+    let mut walls = load_venue_walls();
+    let traced: BTreeSet<u32> = walls.iter().map(|wall| wall.id).collect();
+    // Venues whose svg we couldn't fetch are left out of the cache, so they're tried again on the next run
+    let mut new_walls: Vec<VenueWall> = venues_x_y.values()
+        .filter(|venue| !traced.contains(&venue.id))
+        .filter_map(|venue| Some(VenueWall::new(venue, &crate::venues::get_svg_polylines(venue.id)?)))
+        .collect();
+    new_walls.sort_by_key(|wall| wall.id);
+    if !new_walls.is_empty() {
+        println!("Traced {} new outfield walls.", new_walls.len());
+        walls.extend(new_walls);
+        cache_venue_walls(&walls);
+    }
+
+    println!("Loading batted balls for fence profiles...");
+    let pitches = load_fence_pitches();
+    println!("Loaded {} pitches.", pitches.len());
+
+    let profiles: Vec<FenceProfile> = FenceData {
+        pitches: &pitches,
+        walls: &walls,
+        venues: &venues,
+        venues_x_y: &venues_x_y,
+        calibrations: &calibrations,
+    }.into();
 
+    cache_fence_profiles(&profiles);
+    println!("Profiled {} venue seasons.", profiles.len());
 }
 
 /// Feed the get_data function a list of years and sport_ids and get back all the data for those combos. BOSS will cache anything it already has data for and try to fill in any missing pieces 
@@ -179,6 +648,16 @@ pub fn get_meta_data(years: Vec<u16>, sport_ids: Vec<u32>) -> VecMetaDataInputs
         // feed_data: feed_live_data,
         teams: teams_data,
         players: player_data,
+        re_288: load_re_288(),
+        linear_weights: load_linear_weights(),
+        win_expectancy: load_win_expectancy(),
+        park_factors: load_park_factors(),
+        expected_outcomes: load_expected_outcomes(),
+        pitch_classifiers: load_pitch_classifiers(),
+        called_strike: load_called_strike_bins(),
+        fence_profiles: load_fence_profiles(),
+        pixel_calibrations: load_pixel_calibrations(),
+        pitcher_appearances: load_appearance_games(),
     }
 
     
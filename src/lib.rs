@@ -64,6 +64,7 @@ pub const STADIUM_X: f32 = 125.2;
 #[allow(unused)]
 pub const STADIUM_Y: f32 = 203.5;
 
-///Default number for converting pixels into feet. We'll update this variable after we analyze the data some more.
+///Default number for converting pixels into feet, for venues we don't have a fit for. The calibration module fits the scale and home plate
+///for each venue and season from the tracked distances.
 #[allow(unused)]
 pub const FEET_PER_PIXEL: f32 = 2.75;
\ No newline at end of file
@@ -0,0 +1,466 @@
+//! Linear weights, wOBA weights and the league constants (wOBA scale, league wOBA, FIP constant) for each sport_id and season.
+//!
+//! The run value of an event is the average of RE24 end - RE24 start + runs scored over every plate appearance that ended in that
+//! event. The base/out state at the start is the state before the final pitch, so stolen bases and other running events earlier in
+//! the plate appearance aren't credited to the batter. We take the RE24 tables from the 0-0 count of our RE288 tables, so the
+//! run values are in the run environment of that league and season.
+//!
+//! The wOBA weights follow the usual recipe:
+//!   * Each weight is the run value of the event minus the run value of an out, since an out is what the batter was trying to avoid.
+//!   * The wOBA scale puts wOBA on the same scale as OBP: wOBA scale = league OBP / league wOBA before scaling.
+//!   * Intentional walks are left out of both wOBA and OBP.
+//!
+//! The FIP constant is the league ERA less league FIP before the constant. Earned runs and innings come from the pitcher
+//! appearances table, so the constant is only there for seasons we've built appearances for.
+//!
+//! Minor league seasons and small samples can leave an event with only a handful of plate appearances. When an event has fewer
+//! than RE288_MIN_SAMPLE occurrences in a season we use its run value across every season for the sport_id, and then across all
+//! of our data.
+
+use serde::{Serialize, Deserialize};
+use std::collections::{BTreeMap, HashMap};
+use crate::markov::RE24Entry;
+use crate::play_by_play::Event;
+use crate::run_expectancy::{RE288_DEFAULT, RE288_MIN_SAMPLE};
+use crate::schedule::GameDate;
+
+/// The columns from the play by play output we need for linear weights.
+#[derive(Debug, Clone, Deserialize)]
+pub struct LinearWeightsPitch {
+    pub game_pk: u32,
+    pub sport_id: u32,
+    pub game_year: u16,
+    pub num_plate_appearance: u8,
+    pub pitch_num_game: u16,
+    pub base_value_start: u8,
+    pub outs_start: u8,
+    pub base_value_end: u8,
+    pub outs_end: u8,
+    pub runs_scored: u8,
+    pub plate_appearance_result: Option<Event>,
+}
+
+/// The columns from the pitcher appearances output we need for league ERA.
+#[derive(Debug, Clone, Deserialize)]
+pub struct LinearWeightsAppearance {
+    pub sport_id: u32,
+    pub game_date: String,
+    pub outs_recorded: u16,
+    pub earned_runs: u16,
+}
+
+pub struct LinearWeightsData <'l> {
+    pub pitches: &'l [LinearWeightsPitch],
+    pub appearances: &'l [LinearWeightsAppearance],
+    /// RE24 tables by sport_id and season. Any season without one uses the default table.
+    pub re_24: &'l [RE24Entry],
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LinearWeights {
+    pub sport_id: u32,
+    pub season: u16,
+    pub plate_appearances: u32,
+
+    /// Run values, relative to the average plate appearance
+    pub run_value_walk: f32,
+    pub run_value_hit_by_pitch: f32,
+    pub run_value_single: f32,
+    pub run_value_double: f32,
+    pub run_value_triple: f32,
+    pub run_value_home_run: f32,
+    pub run_value_out: f32,
+
+    pub woba_walk: f32,
+    pub woba_hit_by_pitch: f32,
+    pub woba_single: f32,
+    pub woba_double: f32,
+    pub woba_triple: f32,
+    pub woba_home_run: f32,
+    pub woba_scale: f32,
+
+    pub league_obp: f32,
+    pub league_woba: f32,
+    pub league_runs_per_plate_appearance: f32,
+    pub league_era: Option<f32>,
+    pub fip_constant: Option<f32>,
+}
+
+/// Just the wOBA weights, for anything that needs to calculate wOBA
+#[derive(Debug, Copy, Clone)]
+pub struct WobaWeights {
+    pub walk: f32,
+    pub hit_by_pitch: f32,
+    pub single: f32,
+    pub double: f32,
+    pub triple: f32,
+    pub home_run: f32,
+}
+
+/// Roughly the MLB weights of the last few seasons, for leagues and seasons we haven't built linear weights for
+pub const DEFAULT_WOBA_WEIGHTS: WobaWeights = WobaWeights {
+    walk: 0.69,
+    hit_by_pitch: 0.72,
+    single: 0.88,
+    double: 1.25,
+    triple: 1.58,
+    home_run: 2.03,
+};
+
+impl From<&LinearWeights> for WobaWeights {
+    fn from (linear_weights: &LinearWeights) -> WobaWeights {
+        WobaWeights {
+            walk: linear_weights.woba_walk,
+            hit_by_pitch: linear_weights.woba_hit_by_pitch,
+            single: linear_weights.woba_single,
+            double: linear_weights.woba_double,
+            triple: linear_weights.woba_triple,
+            home_run: linear_weights.woba_home_run,
+        }
+    }
+}
+
+impl WobaWeights {
+    /// wOBA for a set of batting totals
+    pub (crate) fn woba (&self, totals: &BattingTotals) -> f64 {
+        let numerator = self.walk as f64 * totals.walks as f64
+            + self.hit_by_pitch as f64 * totals.hit_by_pitch as f64
+            + self.single as f64 * totals.singles as f64
+            + self.double as f64 * totals.doubles as f64
+            + self.triple as f64 * totals.triples as f64
+            + self.home_run as f64 * totals.home_runs as f64;
+        ratio(numerator, totals.denominator())
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+enum WeightedEvent {
+    Walk,
+    HitByPitch,
+    Single,
+    Double,
+    Triple,
+    HomeRun,
+    Out,
+}
+
+impl WeightedEvent {
+    fn from_event (event: Event) -> Option<WeightedEvent> {
+        match event {
+            Event::Walk => Some(WeightedEvent::Walk),
+            Event::HitByPitch => Some(WeightedEvent::HitByPitch),
+            Event::Single => Some(WeightedEvent::Single),
+            Event::Double => Some(WeightedEvent::Double),
+            Event::Triple => Some(WeightedEvent::Triple),
+            Event::HomeRun => Some(WeightedEvent::HomeRun),
+            Event::BatterInterference | Event::BuntGroundOut | Event::BuntPopOut | Event::DoublePlay | Event::FieldOut |
+            Event::FlyOut | Event::ForceOut | Event::GroundOut | Event::GroundedIntoDoublePlay | Event::LineOut | Event::PopOut |
+            Event::SacBunt | Event::SacFly | Event::SacFlyDoublePlay | Event::SacrificeBuntDoublePlay | Event::StrikeOut |
+            Event::TriplePlay => Some(WeightedEvent::Out),
+            _ => None,
+        }
+    }
+}
+
+/// Sum of run values and the number of plate appearances
+#[derive(Debug, Default, Copy, Clone)]
+struct RunValue {
+    runs: f64,
+    n: u32,
+}
+
+impl RunValue {
+    fn add (&mut self, runs: f64) {
+        self.runs += runs;
+        self.n += 1;
+    }
+}
+
+/// Counting stats for OBP, wOBA and FIP. Walks are unintentional walks.
+#[derive(Debug, Default, Copy, Clone)]
+pub (crate) struct BattingTotals {
+    pub (crate) plate_appearances: u32,
+    pub (crate) at_bats: u32,
+    pub (crate) hits: u32,
+    pub (crate) walks: u32,
+    pub (crate) hit_by_pitch: u32,
+    pub (crate) sac_flies: u32,
+    pub (crate) singles: u32,
+    pub (crate) doubles: u32,
+    pub (crate) triples: u32,
+    pub (crate) home_runs: u32,
+    pub (crate) strikeouts: u32,
+    pub (crate) runs: u32,
+}
+
+impl BattingTotals {
+    pub (crate) fn add (&mut self, event: Event) {
+        self.plate_appearances += 1;
+        match event {
+            Event::Walk => self.walks += 1,
+            Event::HitByPitch => self.hit_by_pitch += 1,
+            Event::SacFly | Event::SacFlyDoublePlay => self.sac_flies += 1,
+            Event::IntentionalWalk | Event::SacBunt | Event::SacrificeBuntDoublePlay | Event::CatcherInterference => {},
+            _ => self.at_bats += 1,
+        }
+        match event {
+            Event::Single => {self.hits += 1; self.singles += 1;},
+            Event::Double => {self.hits += 1; self.doubles += 1;},
+            Event::Triple => {self.hits += 1; self.triples += 1;},
+            Event::HomeRun => {self.hits += 1; self.home_runs += 1;},
+            Event::StrikeOut => self.strikeouts += 1,
+            _ => {},
+        }
+    }
+
+    /// Plate appearances that count for OBP and wOBA, without intentional walks, sac bunts and interference
+    pub (crate) fn denominator (&self) -> f64 {
+        (self.at_bats + self.walks + self.hit_by_pitch + self.sac_flies) as f64
+    }
+}
+
+type Context = (u32, u16);
+
+impl <'l> From<LinearWeightsData<'l>> for Vec<LinearWeights> {
+    fn from (data: LinearWeightsData) -> Vec<LinearWeights> {
+
+        let mut re_24: HashMap<Context, HashMap<(u8, u8), f32>> = HashMap::new();
+        for re in data.re_24 {
+            re_24.entry((re.sport_id, re.season)).or_default().insert((re.base_value, re.outs), re.run_expectancy);
+        }
+        let default: HashMap<(u8, u8), f32> = RE288_DEFAULT.iter()
+            .filter(|re| re.balls == 0 && re.strikes == 0)
+            .map(|re| ((re.base_value, re.outs), re.run_expectancy))
+            .collect();
+
+        // The last pitch of each plate appearance is the one the result happened on
+        let mut plate_appearances: HashMap<(u32, u8), &LinearWeightsPitch> = HashMap::new();
+        for pitch in data.pitches {
+            let last = plate_appearances.entry((pitch.game_pk, pitch.num_plate_appearance)).or_insert(pitch);
+            if pitch.pitch_num_game > last.pitch_num_game {*last = pitch};
+        }
+
+        let mut season_values: BTreeMap<Context, HashMap<WeightedEvent, RunValue>> = BTreeMap::new();
+        let mut sport_values: HashMap<u32, HashMap<WeightedEvent, RunValue>> = HashMap::new();
+        let mut all_values: HashMap<WeightedEvent, RunValue> = HashMap::new();
+        let mut totals: HashMap<Context, BattingTotals> = HashMap::new();
+
+        for pitch in data.pitches {
+            totals.entry((pitch.sport_id, pitch.game_year)).or_default().runs += pitch.runs_scored as u32;
+        }
+
+        for pitch in plate_appearances.into_values() {
+            let event = match pitch.plate_appearance_result {
+                Some (event) => event,
+                None => continue,
+            };
+            if pitch.base_value_start > 7 || pitch.outs_start > 2 || pitch.base_value_end > 7 || pitch.outs_end > 3 {continue};
+
+            let context = (pitch.sport_id, pitch.game_year);
+            totals.entry(context).or_default().add(event);
+
+            let weighted_event = match WeightedEvent::from_event(event) {
+                Some (weighted_event) => weighted_event,
+                None => continue,
+            };
+
+            let table = re_24.get(&context).unwrap_or(&default);
+            let re_start = *table.get(&(pitch.base_value_start, pitch.outs_start)).unwrap_or(&0f32);
+            let re_end = if pitch.outs_end == 3 {0f32} else {*table.get(&(pitch.base_value_end, pitch.outs_end)).unwrap_or(&0f32)};
+            let run_value = (re_end - re_start) as f64 + pitch.runs_scored as f64;
+
+            season_values.entry(context).or_default().entry(weighted_event).or_default().add(run_value);
+            sport_values.entry(pitch.sport_id).or_default().entry(weighted_event).or_default().add(run_value);
+            all_values.entry(weighted_event).or_default().add(run_value);
+        }
+
+        let mut pitching: HashMap<Context, (u32, u32)> = HashMap::new();
+        for appearance in data.appearances {
+            let season = GameDate::from(appearance.game_date.clone()).year;
+            let (outs, earned_runs) = pitching.entry((appearance.sport_id, season)).or_default();
+            *outs += appearance.outs_recorded as u32;
+            *earned_runs += appearance.earned_runs as u32;
+        }
+
+        season_values.into_iter()
+            .map(|((sport_id, season), values)| {
+                let run_value = |event: WeightedEvent| -> f64 {
+                    [values.get(&event), sport_values[&sport_id].get(&event), all_values.get(&event)].into_iter()
+                        .flatten()
+                        .find(|value| value.n >= RE288_MIN_SAMPLE)
+                        .or(all_values.get(&event))
+                        .map(|value| value.runs / value.n as f64)
+                        .unwrap_or(0f64)
+                };
+
+                let out = run_value(WeightedEvent::Out);
+                let weight = |event: WeightedEvent| run_value(event) - out;
+                let totals = totals[&(sport_id, season)];
+
+                let league_obp = ratio((totals.hits + totals.walks + totals.hit_by_pitch) as f64, totals.denominator());
+                let league_woba_raw = ratio(
+                    weight(WeightedEvent::Walk) * totals.walks as f64 +
+                    weight(WeightedEvent::HitByPitch) * totals.hit_by_pitch as f64 +
+                    weight(WeightedEvent::Single) * totals.singles as f64 +
+                    weight(WeightedEvent::Double) * totals.doubles as f64 +
+                    weight(WeightedEvent::Triple) * totals.triples as f64 +
+                    weight(WeightedEvent::HomeRun) * totals.home_runs as f64,
+                    totals.denominator(),
+                );
+                let woba_scale = ratio(league_obp, league_woba_raw);
+
+                let (league_era, fip_constant) = match pitching.get(&(sport_id, season)) {
+                    Some ((outs, earned_runs)) if *outs > 0 => {
+                        let innings = *outs as f64 / 3.0;
+                        let league_era = 9.0 * *earned_runs as f64 / innings;
+                        let league_fip = (13.0 * totals.home_runs as f64 + 3.0 * (totals.walks + totals.hit_by_pitch) as f64 - 2.0 * totals.strikeouts as f64) / innings;
+                        (Some(league_era as f32), Some((league_era - league_fip) as f32))
+                    },
+                    _ => (None, None),
+                };
+
+                LinearWeights {
+                    sport_id,
+                    season,
+                    plate_appearances: totals.plate_appearances,
+                    run_value_walk: run_value(WeightedEvent::Walk) as f32,
+                    run_value_hit_by_pitch: run_value(WeightedEvent::HitByPitch) as f32,
+                    run_value_single: run_value(WeightedEvent::Single) as f32,
+                    run_value_double: run_value(WeightedEvent::Double) as f32,
+                    run_value_triple: run_value(WeightedEvent::Triple) as f32,
+                    run_value_home_run: run_value(WeightedEvent::HomeRun) as f32,
+                    run_value_out: out as f32,
+                    woba_walk: (weight(WeightedEvent::Walk) * woba_scale) as f32,
+                    woba_hit_by_pitch: (weight(WeightedEvent::HitByPitch) * woba_scale) as f32,
+                    woba_single: (weight(WeightedEvent::Single) * woba_scale) as f32,
+                    woba_double: (weight(WeightedEvent::Double) * woba_scale) as f32,
+                    woba_triple: (weight(WeightedEvent::Triple) * woba_scale) as f32,
+                    woba_home_run: (weight(WeightedEvent::HomeRun) * woba_scale) as f32,
+                    woba_scale: woba_scale as f32,
+                    league_obp: league_obp as f32,
+                    league_woba: (league_woba_raw * woba_scale) as f32,
+                    league_runs_per_plate_appearance: ratio(totals.runs as f64, totals.plate_appearances as f64) as f32,
+                    league_era,
+                    fip_constant,
+                }
+            })
+            .collect()
+    }
+}
+
+pub (crate) fn ratio (numerator: f64, denominator: f64) -> f64 {
+    if denominator == 0.0 {0.0} else {numerator / denominator}
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A one pitch plate appearance with the bases empty and nobody out, that leaves them that way
+    fn pitch (game_pk: u32, result: Event) -> LinearWeightsPitch {
+        LinearWeightsPitch {
+            game_pk,
+            sport_id: 11,
+            game_year: 2019,
+            num_plate_appearance: 1,
+            pitch_num_game: 1,
+            base_value_start: 0,
+            outs_start: 0,
+            base_value_end: 0,
+            outs_end: 0,
+            runs_scored: 0,
+            plate_appearance_result: Some(result),
+        }
+    }
+
+    fn single (game_pk: u32) -> LinearWeightsPitch {
+        LinearWeightsPitch {base_value_end: 1, ..pitch(game_pk, Event::Single)}
+    }
+
+    fn home_run (game_pk: u32) -> LinearWeightsPitch {
+        LinearWeightsPitch {runs_scored: 1, ..pitch(game_pk, Event::HomeRun)}
+    }
+
+    fn out (game_pk: u32, result: Event) -> LinearWeightsPitch {
+        LinearWeightsPitch {outs_end: 1, ..pitch(game_pk, result)}
+    }
+
+    /// Only the states the tests use, anything else is 0
+    fn re_24 (season: u16) -> Vec<RE24Entry> {
+        [(0, 0, 0.5), (1, 0, 0.75), (0, 1, 0.25), (0, 2, 0.125)].into_iter()
+            .map(|(base_value, outs, run_expectancy)| RE24Entry {sport_id: 11, season, base_value, outs, run_expectancy})
+            .collect()
+    }
+
+    fn weights (pitches: &[LinearWeightsPitch], appearances: &[LinearWeightsAppearance]) -> Vec<LinearWeights> {
+        let mut re_24_tables = re_24(2019);
+        re_24_tables.extend(re_24(2020));
+        LinearWeightsData {pitches, appearances, re_24: &re_24_tables}.into()
+    }
+
+    #[test]
+    fn run_value_is_the_change_in_run_expectancy_plus_runs () {
+        let weights = weights(&[single(1), home_run(2), out(3, Event::GroundOut)], &[]);
+
+        assert_eq!(weights[0].run_value_single, 0.25);
+        assert_eq!(weights[0].run_value_home_run, 1.0);
+        assert_eq!(weights[0].run_value_out, -0.25);
+    }
+
+    #[test]
+    fn third_out_leaves_no_run_expectancy () {
+        let strikeout = LinearWeightsPitch {outs_start: 2, outs_end: 3, ..pitch(1, Event::StrikeOut)};
+        let weights = weights(&[strikeout], &[]);
+        assert_eq!(weights[0].run_value_out, -0.125);
+    }
+
+    #[test]
+    fn result_comes_from_the_last_pitch () {
+        // The first pitch of the plate appearance is still filled in with the bases empty
+        let first = LinearWeightsPitch {base_value_end: 0, ..single(1)};
+        let last = LinearWeightsPitch {pitch_num_game: 2, ..single(1)};
+        let weights = weights(&[last, first], &[]);
+
+        assert_eq!(weights[0].plate_appearances, 1);
+        assert_eq!(weights[0].run_value_single, 0.25);
+    }
+
+    #[test]
+    fn sparse_seasons_use_the_sport () {
+        // 2019 has RE288_MIN_SAMPLE singles worth 0.25. 2020 has one, worth 1.25 with a run in, so it pools with 2019.
+        let mut pitches: Vec<LinearWeightsPitch> = (0 .. RE288_MIN_SAMPLE).map(single).collect();
+        pitches.push(LinearWeightsPitch {game_year: 2020, runs_scored: 1, ..single(100)});
+        let weights = weights(&pitches, &[]);
+
+        assert_eq!(weights[0].run_value_single, 0.25);
+        assert_eq!(weights[1].season, 2020);
+        assert_eq!(weights[1].run_value_single, (8.75f64 / 31.0) as f32);
+    }
+
+    #[test]
+    fn woba_scaled_to_obp () {
+        let weights = weights(&[single(1), home_run(2), out(3, Event::GroundOut), out(4, Event::StrikeOut)], &[]);
+
+        assert_eq!(weights[0].league_obp, 0.5);
+        // The out is worth -0.25, so a single is 0.5 and a home run 1.25 over an out. (0.5 + 1.25) / 4 scales up to 0.5.
+        assert_eq!(weights[0].woba_scale, (0.5f64 / (1.75 / 4.0)) as f32);
+        assert_eq!(weights[0].league_woba, 0.5);
+    }
+
+    #[test]
+    fn fip_constant () {
+        let appearance = LinearWeightsAppearance {sport_id: 11, game_date: "2019-05-01".to_string(), outs_recorded: 27, earned_runs: 4};
+        let weights = weights(&[single(1), home_run(2), out(3, Event::StrikeOut)], &[appearance]);
+
+        // 4 earned runs in 9 innings, and (13 * 1 home run - 2 * 1 strikeout) / 9 innings of FIP
+        assert_eq!(weights[0].league_era, Some(4.0));
+        assert_eq!(weights[0].fip_constant, Some((4.0f64 - 11.0 / 9.0) as f32));
+    }
+
+    #[test]
+    fn no_fip_constant_without_appearances () {
+        let weights = weights(&[single(1)], &[]);
+        assert!(weights[0].league_era.is_none());
+        assert!(weights[0].fip_constant.is_none());
+    }
+}
@@ -19,6 +19,28 @@ mod run_expectancy;
 mod team;
 mod get_data;
 mod nathan;
+mod validation;
+mod data_quality;
+mod pitcher_appearance;
+mod events;
+mod rng;
+mod markov;
+mod linear_weights;
+mod win_expectancy;
+mod park_factors;
+mod expected_stats;
+mod pitch_classification;
+mod pitcher_arsenal;
+mod framing;
+mod fielding;
+mod tunneling;
+mod flight;
+mod fences;
+mod calibration;
+mod aging;
+mod mle;
+mod projections;
+mod stabilization;
 
 pub (crate) const BASE_URL: &'static str = "https://statsapi.mlb.com/api/v1/";
 pub (crate) const BASE_URL_V11: &'static str = "https://statsapi.mlb.com/api/v1.1/";
@@ -30,7 +52,8 @@ pub const STADIUM_X: f32 = 125.2;
 /// If we don't have an svg file for a particular venue_id - we'll fill in the y value with this constant
 pub const STADIUM_Y: f32 = 203.5;
 
-///Default number for converting pixels into feet. We'll update this variable after we analyze the data some more.
+///Default number for converting pixels into feet, for venues we don't have a fit for. The calibration module fits the scale and home plate
+///for each venue and season from the tracked distances.
 #[allow(unused)]
 pub const FEET_PER_PIXEL: f32 = 2.75;
 
@@ -0,0 +1,323 @@
+//! Method 2 from the run_expectancy module: run expectancy by simulation.
+//!
+//! We treat the half-inning as a Markov chain over the 288 ball/strike/base/out states, with 3 outs as the absorbing state. The
+//! transition probabilities, along with the runs that score on each transition, are estimated from the pitch data for each sport_id
+//! and season. The next state is the start state of the next pitch in the half-inning, so steals, pickoffs and other non-pitch
+//! events between pitches are baked into the transitions. Each state's run expectancy is the average runs scored over `iterations`
+//! simulated innings starting from that state.
+//!
+//! Minor league seasons can be sparse, so when a state has fewer than RE288_MIN_SAMPLE observed transitions we blend in the
+//! transitions for the same state from every season of that sport_id, and then from all of our data. A state with n observed
+//! transitions uses its own season's transitions n / RE288_MIN_SAMPLE of the time. The RE24 table is just the 0-0 count
+//! from the RE288 table.
+//!
+//! The RNG is seeded, and each (sport_id, season, state) gets its own stream, so the output is the same for the same seed
+//! no matter how rayon schedules the work.
+
+use serde::{Serialize, Deserialize};
+use std::collections::{BTreeSet, HashMap};
+use rayon::prelude::*;
+use crate::rng::Rng;
+use crate::run_expectancy::{RE288Pitch, RE288Entry, RE288_DEFAULT, RE288_MIN_SAMPLE, State, all_states, complete_half_innings, valid_state};
+
+/// Cap on transitions in a single simulated inning, so bad data with a loop in it can't hang the simulation.
+const MAX_TRANSITIONS: u32 = 1_000;
+
+pub struct MarkovData <'m> {
+    pub pitches: &'m [RE288Pitch],
+    pub iterations: u32,
+    pub seed: u64,
+}
+
+/// RE24: The run expectancy for the 24 base/out states at the start of a plate appearance
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RE24Entry {
+    pub sport_id: u32,
+    pub season: u16,
+    pub base_value: u8,
+    pub outs: u8,
+    pub run_expectancy: f32,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct MarkovRunExpectancy {
+    pub re_288: Vec<RE288Entry>,
+    pub re_24: Vec<RE24Entry>,
+}
+
+/// Observed transitions out of a single state. None is the end of the inning.
+#[derive(Debug, Default)]
+struct Transitions {
+    outcomes: Vec<(Option<State>, u8)>,
+    // Running total of the counts for each outcome, for sampling
+    cumulative: Vec<u32>,
+    total: u32,
+}
+
+impl Transitions {
+    fn from_counts (counts: HashMap<(Option<State>, u8), u32>) -> Transitions {
+        // Sort so that the sampling order doesn't depend on the HashMap
+        let mut counts: Vec<((Option<State>, u8), u32)> = counts.into_iter().collect();
+        counts.sort();
+
+        let mut transitions = Transitions::default();
+        for (outcome, count) in counts {
+            transitions.total += count;
+            transitions.outcomes.push(outcome);
+            transitions.cumulative.push(transitions.total);
+        }
+        transitions
+    }
+
+    fn sample (&self, rng: &mut Rng) -> (Option<State>, u8) {
+        let draw = rng.below(self.total as u64) as u32;
+        let index = self.cumulative.partition_point(|c| *c <= draw);
+        self.outcomes[index]
+    }
+}
+
+type Table = HashMap<State, Transitions>;
+type Counts = HashMap<State, HashMap<(Option<State>, u8), u32>>;
+
+fn add (counts: &mut Counts, state: State, outcome: (Option<State>, u8)) {
+    *counts.entry(state).or_default().entry(outcome).or_insert(0) += 1;
+}
+
+fn table (counts: Counts) -> Table {
+    counts.into_iter().map(|(state, c)| (state, Transitions::from_counts(c))).collect()
+}
+
+/// The transition tables for one (sport_id, season), along with the tables we fall back on.
+struct Chain <'c> {
+    season: &'c Table,
+    sport: &'c Table,
+    all: &'c Table,
+    default: &'c HashMap<State, f32>,
+}
+
+impl <'c> Chain <'c> {
+
+    fn season_sample_size (&self, state: State) -> u32 {
+        self.season.get(&state).map(|t| t.total).unwrap_or(0)
+    }
+
+    fn sample (&self, state: State, rng: &mut Rng) -> Option<(Option<State>, u8)> {
+        for (table, last) in [(self.season, false), (self.sport, false), (self.all, true)] {
+            let n = table.get(&state).map(|t| t.total).unwrap_or(0);
+            let use_table = match last {
+                false => n >= RE288_MIN_SAMPLE || (rng.next_f64() < n as f64 / RE288_MIN_SAMPLE as f64),
+                true => n > 0,
+            };
+            if use_table {return Some(table[&state].sample(rng))};
+        }
+        None
+    }
+
+    /// Runs scored from the state until the end of the inning. If we wander into a state nobody has ever seen, we stop
+    /// and use the default run expectancy for the rest of the inning.
+    fn simulate_inning (&self, start: State, rng: &mut Rng) -> f64 {
+        let mut state = start;
+        let mut runs = 0f64;
+
+        for _ in 0 .. MAX_TRANSITIONS {
+            match self.sample(state, rng) {
+                Some ((Some (next), scored)) => {runs += scored as f64; state = next;},
+                Some ((None, scored)) => return runs + scored as f64,
+                None => return runs + *self.default.get(&state).unwrap_or(&0f32) as f64,
+            }
+        }
+        runs
+    }
+}
+
+impl <'m> From<MarkovData<'m>> for MarkovRunExpectancy {
+    fn from (data: MarkovData) -> MarkovRunExpectancy {
+
+        let mut season_counts: HashMap<(u32, u16), Counts> = HashMap::new();
+        let mut sport_counts: HashMap<u32, Counts> = HashMap::new();
+        let mut all_counts: Counts = HashMap::new();
+
+        for pitches in complete_half_innings(data.pitches) {
+            for (index, pitch) in pitches.iter().enumerate() {
+                let state = (pitch.balls_start, pitch.strikes_start, pitch.base_value_start, pitch.outs_start);
+                if !valid_state(state) {continue};
+
+                let next = pitches.get(index + 1).map(|next| (next.balls_start, next.strikes_start, next.base_value_start, next.outs_start));
+                // A transition into an impossible state is garbage, we'd rather not learn from it
+                if let Some(next) = next {
+                    if !valid_state(next) {continue};
+                }
+
+                let outcome = (next, pitch.runs_scored);
+                add(season_counts.entry((pitch.sport_id, pitch.game_year)).or_default(), state, outcome);
+                add(sport_counts.entry(pitch.sport_id).or_default(), state, outcome);
+                add(&mut all_counts, state, outcome);
+            }
+        }
+
+        let contexts: BTreeSet<(u32, u16)> = season_counts.keys().copied().collect();
+        let season_tables: HashMap<(u32, u16), Table> = season_counts.into_iter().map(|(k, c)| (k, table(c))).collect();
+        let sport_tables: HashMap<u32, Table> = sport_counts.into_iter().map(|(k, c)| (k, table(c))).collect();
+        let all_table: Table = table(all_counts);
+
+        let default: HashMap<State, f32> = RE288_DEFAULT.iter()
+            .map(|re| ((re.balls, re.strikes, re.base_value, re.outs), re.run_expectancy))
+            .collect();
+
+        let rng = Rng::new(data.seed);
+        let iterations = data.iterations.max(1);
+
+        let re_288: Vec<RE288Entry> = contexts.into_iter()
+            .flat_map(|context| all_states().into_iter().map(move |state| (context, state)))
+            .collect::<Vec<((u32, u16), State)>>()
+            .into_par_iter()
+            .map(|((sport_id, season), state)| {
+                let chain = Chain {
+                    season: &season_tables[&(sport_id, season)],
+                    sport: &sport_tables[&sport_id],
+                    all: &all_table,
+                    default: &default,
+                };
+
+                let (balls, strikes, base_value, outs) = state;
+                let stream = ((sport_id as u64) << 32) | ((season as u64) << 16) | ((balls as u64) << 12) | ((strikes as u64) << 8) | ((base_value as u64) << 4) | outs as u64;
+                let mut rng = rng.fork(stream);
+
+                let total_runs: f64 = (0 .. iterations).map(|_| chain.simulate_inning(state, &mut rng)).sum();
+                let sample_size = chain.season_sample_size(state);
+
+                RE288Entry {
+                    sport_id,
+                    season,
+                    venue_id: None,
+                    balls,
+                    strikes,
+                    base_value,
+                    outs,
+                    run_expectancy: (total_runs / iterations as f64) as f32,
+                    sample_size,
+                    smoothed: sample_size < RE288_MIN_SAMPLE,
+                }
+            })
+            .collect();
+
+        let re_24: Vec<RE24Entry> = re_288.iter()
+            .filter(|re| re.balls == 0 && re.strikes == 0)
+            .map(|re| RE24Entry {
+                sport_id: re.sport_id,
+                season: re.season,
+                base_value: re.base_value,
+                outs: re.outs,
+                run_expectancy: re.run_expectancy,
+            })
+            .collect();
+
+        MarkovRunExpectancy {
+            re_288,
+            re_24,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::play_by_play::HalfInning;
+
+    fn pitch (game_pk: u32, game_year: u16, pitch_num_game: u16, base_value_start: u8, outs_start: u8, outs_end: u8, runs_scored: u8) -> RE288Pitch {
+        RE288Pitch {
+            game_pk,
+            sport_id: 11,
+            game_year,
+            venue_id: 1,
+            half_inning: HalfInning::Top,
+            num_inning: 1,
+            pitch_num_game,
+            balls_start: 0,
+            strikes_start: 0,
+            base_value_start,
+            outs_start,
+            outs_end,
+            runs_scored,
+        }
+    }
+
+    /// RE288_MIN_SAMPLE innings in 2019 that all go: leadoff single, 2 runs score on an out, then two first pitch outs. Every
+    /// state has one way out of it, so the chain is deterministic.
+    fn innings () -> Vec<RE288Pitch> {
+        (0 .. RE288_MIN_SAMPLE).flat_map(|game| vec![
+            pitch(game, 2019, 1, 0, 0, 0, 0),
+            pitch(game, 2019, 2, 1, 0, 1, 2),
+            pitch(game, 2019, 3, 0, 1, 2, 0),
+            pitch(game, 2019, 4, 0, 2, 3, 0),
+        ]).collect()
+    }
+
+    fn re_288 (re: &MarkovRunExpectancy, season: u16, balls: u8, strikes: u8, base_value: u8, outs: u8) -> &RE288Entry {
+        re.re_288.iter()
+            .find(|r| r.season == season && r.balls == balls && r.strikes == strikes && r.base_value == base_value && r.outs == outs)
+            .unwrap()
+    }
+
+    #[test]
+    fn runs_to_end_of_inning () {
+        let re: MarkovRunExpectancy = MarkovData {pitches: &innings(), iterations: 10, seed: 7}.into();
+
+        assert_eq!(re_288(&re, 2019, 0, 0, 0, 0).run_expectancy, 2.0);
+        assert_eq!(re_288(&re, 2019, 0, 0, 1, 0).run_expectancy, 2.0);
+        assert_eq!(re_288(&re, 2019, 0, 0, 0, 1).run_expectancy, 0.0);
+    }
+
+    #[test]
+    fn re_24_is_the_0_0_count () {
+        let re: MarkovRunExpectancy = MarkovData {pitches: &innings(), iterations: 10, seed: 7}.into();
+
+        assert_eq!(re.re_24.len(), 24);
+        for entry in re.re_24.iter() {
+            assert_eq!(entry.run_expectancy, re_288(&re, 2019, 0, 0, entry.base_value, entry.outs).run_expectancy);
+        }
+    }
+
+    #[test]
+    fn sample_size () {
+        let re: MarkovRunExpectancy = MarkovData {pitches: &innings(), iterations: 10, seed: 7}.into();
+
+        assert_eq!(re_288(&re, 2019, 0, 0, 0, 0).sample_size, RE288_MIN_SAMPLE);
+        assert!(!re_288(&re, 2019, 0, 0, 0, 0).smoothed);
+        assert_eq!(re_288(&re, 2019, 1, 0, 0, 0).sample_size, 0);
+        assert!(re_288(&re, 2019, 1, 0, 0, 0).smoothed);
+    }
+
+    #[test]
+    fn unseen_states_use_the_default_table () {
+        let re: MarkovRunExpectancy = MarkovData {pitches: &innings(), iterations: 1, seed: 7}.into();
+        assert_eq!(re_288(&re, 2019, 3, 2, 7, 2).run_expectancy, 0.992);
+    }
+
+    #[test]
+    fn empty_season_states_use_the_sport () {
+        // 2020 only has one inning, and it never had a runner on first with nobody out
+        let mut pitches = innings();
+        pitches.extend(vec![pitch(100, 2020, 1, 0, 1, 2, 0), pitch(100, 2020, 2, 0, 2, 3, 0)]);
+        let re: MarkovRunExpectancy = MarkovData {pitches: &pitches, iterations: 10, seed: 7}.into();
+
+        assert_eq!(re_288(&re, 2020, 0, 0, 1, 0).sample_size, 0);
+        assert_eq!(re_288(&re, 2020, 0, 0, 1, 0).run_expectancy, 2.0);
+    }
+
+    #[test]
+    fn same_seed_same_answer () {
+        // Half the leadoff hitters make an out instead, so the simulation has something to draw
+        let mut pitches = innings();
+        for game in 100 .. 100 + RE288_MIN_SAMPLE {
+            pitches.extend(vec![pitch(game, 2019, 1, 0, 0, 1, 0), pitch(game, 2019, 2, 0, 1, 2, 0), pitch(game, 2019, 3, 0, 2, 3, 0)]);
+        }
+
+        let first: MarkovRunExpectancy = MarkovData {pitches: &pitches, iterations: 500, seed: 42}.into();
+        let second: MarkovRunExpectancy = MarkovData {pitches: &pitches, iterations: 500, seed: 42}.into();
+
+        for (a, b) in first.re_288.iter().zip(second.re_288.iter()) {
+            assert_eq!(a.run_expectancy, b.run_expectancy);
+        }
+    }
+}
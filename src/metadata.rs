@@ -6,13 +6,26 @@
 /// 
 
 
-use std::collections::HashMap;
-use crate::schedule::GameMetaData;
+use std::collections::{BTreeSet, HashMap};
+use crate::schedule::{GameMetaData, GameDate};
 use crate::boxscore::{BoxScore, BoxScoreData};
 use crate::venues::{Venue, VenueXY, VenueData};
 use crate::coaches::CoachData;
 use crate::team::{Team, TeamData};
 use crate::players::Player;
+use crate::run_expectancy::RE288Entry;
+use crate::linear_weights::LinearWeights;
+use crate::win_expectancy::{WinExpectancy, WinExpectancyTable};
+use crate::park_factors::ParkFactor;
+use crate::expected_stats::{ExpectedOutcomes, ExpectedOutcomeBin};
+use crate::linear_weights::DEFAULT_WOBA_WEIGHTS;
+use crate::pitch_classification::PitchClassifier;
+use crate::framing::{CalledStrikeBin, CalledStrikeModel};
+use crate::flight::Fence;
+use crate::fences::FenceProfile;
+use crate::calibration::{PixelCalibration, pixel_calibration};
+use crate::play_by_play::SideCode;
+use crate::pitcher_appearance::AppearanceGame;
 
 
 
@@ -26,6 +39,16 @@ pub struct VecMetaDataInputs {
     // pub coaches:    Vec<CoachData>,
     pub teams:      Vec<TeamData>,
     pub players:    Vec<Player>,
+    pub re_288:     Vec<RE288Entry>,
+    pub linear_weights: Vec<LinearWeights>,
+    pub win_expectancy: Vec<WinExpectancyTable>,
+    pub park_factors: Vec<ParkFactor>,
+    pub expected_outcomes: Vec<ExpectedOutcomeBin>,
+    pub pitch_classifiers: Vec<PitchClassifier>,
+    pub called_strike: Vec<CalledStrikeBin>,
+    pub fence_profiles: Vec<FenceProfile>,
+    pub pixel_calibrations: Vec<PixelCalibration>,
+    pub pitcher_appearances: Vec<AppearanceGame>,
 }
 
 ///u32
@@ -35,6 +58,7 @@ type ID = u32;
 ///u16
 type Year = u16;
 
+#[derive(Default)]
 pub struct MetaData {
     pub schedule:       HashMap<GamePK,             GameMetaData>,
     pub boxscore:       HashMap<GamePK,             BoxScore>,
@@ -44,6 +68,47 @@ pub struct MetaData {
     pub teams:          HashMap<(ID, Year),         Team>,
     pub players:        HashMap<ID,                 Player>,
     pub re_288_default: HashMap<(u8, u8, u8, u8),   f32>,
+    /// RE288 tables we've built from our own data, by (sport_id, season) and (venue_id, sport_id, season)
+    pub re_288:         HashMap<(ID, Year),         HashMap<(u8, u8, u8, u8), f32>>,
+    pub re_288_venue:   HashMap<(ID, ID, Year),     HashMap<(u8, u8, u8, u8), f32>>,
+    /// Every (game_date, game_pk) each pitcher appeared in, sorted. Used to calculate days of rest.
+    pub pitcher_appearance_dates: HashMap<ID,     Vec<(GameDate, GamePK)>>,
+    /// Linear weights, wOBA weights and league constants by (sport_id, season)
+    pub linear_weights: HashMap<(ID, Year),     LinearWeights>,
+    /// Win expectancy tables by (sport_id, season). Leagues and seasons without one get no WE, WPA or LI.
+    pub win_expectancy: HashMap<(ID, Year),     WinExpectancy>,
+    /// Park factors by (venue_id, sport_id, season, batter_bats). None is all batters.
+    pub park_factors:   HashMap<(ID, ID, Year, Option<SideCode>), ParkFactor>,
+    /// Expected outcome models for batted balls by (sport_id, season)
+    pub expected_outcomes: HashMap<(ID, Year),  ExpectedOutcomes>,
+    /// Each pitcher's arsenal model, for the inferred pitch types
+    pub pitch_classifiers: HashMap<ID,          PitchClassifier>,
+    /// Called strike model, one for every level since the levels pool into each other
+    pub called_strike:  CalledStrikeModel,
+    /// The fences of every park that hosted a game, by (sport_id, season), for the would be home run counts
+    pub fences:         HashMap<(ID, Year),         Vec<(ID, Fence)>>,
+    /// Hit coordinate calibrations by (venue_id, season), None is every season at the venue
+    pub pixel_calibrations: HashMap<(ID, Option<Year>), PixelCalibration>,
+}
+
+impl MetaData {
+    /// The RE288 table for a game: the venue table if we built one, then the league table for the season, then the default.
+    pub fn re_288_table (&self, venue_id: u32, sport_id: u32, year: u16) -> &HashMap<(u8, u8, u8, u8), f32> {
+        self.re_288_venue.get(&(venue_id, sport_id, year))
+            .or(self.re_288.get(&(sport_id, year)))
+            .unwrap_or(&self.re_288_default)
+    }
+
+    /// Home plate and the scale for the hit coordinates at a venue: the season's fit, the venue's, then the svg and FEET_PER_PIXEL.
+    pub fn pixel_calibration (&self, venue_id: u32, year: u16) -> PixelCalibration {
+        pixel_calibration(&self.pixel_calibrations, &self.venue_x_y, venue_id, year)
+    }
+
+    /// The park factors for the batter's side, falling back to the factors for all batters
+    pub fn park_factor (&self, venue_id: u32, sport_id: u32, year: u16, batter_bats: SideCode) -> Option<&ParkFactor> {
+        self.park_factors.get(&(venue_id, sport_id, year, Some(batter_bats)))
+            .or(self.park_factors.get(&(venue_id, sport_id, year, None)))
+    }
 }
 
 // Converts all metadata into Hashmaps that the play by play data can use.
@@ -109,6 +174,105 @@ impl From<VecMetaDataInputs> for MetaData {
             .collect()
             ;
 
+        let mut re_288: HashMap<(u32, u16), HashMap<(u8, u8, u8, u8), f32>> = HashMap::new();
+        let mut re_288_venue: HashMap<(u32, u32, u16), HashMap<(u8, u8, u8, u8), f32>> = HashMap::new();
+        for re in meta.re_288.iter() {
+            let table = match re.venue_id {
+                Some (venue_id) => re_288_venue.entry((venue_id, re.sport_id, re.season)).or_default(),
+                None => re_288.entry((re.sport_id, re.season)).or_default(),
+            };
+            table.insert((re.balls, re.strikes, re.base_value, re.outs), re.run_expectancy);
+        }
+
+        // Appearances come from the pitcher appearances table, every game we've walked. Boxscores fill in the games we haven't
+        // walked yet: a pitcher appeared in the game if they faced at least one batter. Every pitcher on the roster is in the
+        // boxscore, so we can't just use the position, and boxscores cached before we pulled batters faced don't have it.
+        let mut appearance_games: BTreeSet<(u32, u32)> = meta.pitcher_appearances.iter()
+            .map(|appearance| (appearance.pitcher, appearance.game_pk))
+            .collect();
+        for (game_pk, box_score) in boxscore.iter() {
+            for player in box_score.home_players.iter().chain(box_score.away_players.iter()) {
+                if player.batters_faced.unwrap_or(0) > 0 {
+                    appearance_games.insert((player.id, *game_pk));
+                }
+            }
+        }
+        let mut pitcher_appearance_dates: HashMap<u32, Vec<(GameDate, u32)>> = HashMap::new();
+        for (pitcher, game_pk) in appearance_games {
+            if let Some(game) = schedule.get(&game_pk) {
+                pitcher_appearance_dates.entry(pitcher).or_default().push((game.game_date, game_pk));
+            }
+        }
+        for dates in pitcher_appearance_dates.values_mut() {
+            dates.sort();
+        }
+
+        let linear_weights: HashMap<(u32, u16), LinearWeights> = meta.linear_weights
+            .iter()
+            .map (|lw| ((lw.sport_id, lw.season), lw.clone()))
+            .collect()
+            ;
+
+        let win_expectancy: HashMap<(u32, u16), WinExpectancy> = meta.win_expectancy
+            .iter()
+            .map (|we| ((we.sport_id, we.season), we.into()))
+            .collect()
+            ;
+
+        let park_factors: HashMap<(u32, u32, u16, Option<SideCode>), ParkFactor> = meta.park_factors
+            .iter()
+            .map (|pf| ((pf.venue_id, pf.sport_id, pf.season, pf.batter_bats), pf.clone()))
+            .collect()
+            ;
+
+        let mut expected_outcome_bins: HashMap<(u32, u16), Vec<ExpectedOutcomeBin>> = HashMap::new();
+        for bin in meta.expected_outcomes.iter() {
+            expected_outcome_bins.entry((bin.sport_id, bin.season)).or_default().push(bin.clone());
+        }
+        let expected_outcomes: HashMap<(u32, u16), ExpectedOutcomes> = expected_outcome_bins
+            .into_iter()
+            .map (|(key, bins)| {
+                let woba_weights = linear_weights.get(&key).map(|lw| lw.into()).unwrap_or(DEFAULT_WOBA_WEIGHTS);
+                (key, ExpectedOutcomes::new(&bins, woba_weights))
+            })
+            .collect()
+            ;
+
+        let pitch_classifiers: HashMap<u32, PitchClassifier> = meta.pitch_classifiers
+            .iter()
+            .map (|pc| (pc.pitcher, pc.clone()))
+            .collect()
+            ;
+
+        let called_strike = CalledStrikeModel::new(&meta.called_strike);
+
+        let pixel_calibrations: HashMap<(u32, Option<u16>), PixelCalibration> = meta.pixel_calibrations
+            .iter()
+            .map (|pc| ((pc.venue_id, pc.season), *pc))
+            .collect()
+            ;
+
+        let mut parks: BTreeSet<(u32, u16, u32)> = BTreeSet::new();
+        for game in meta.schedule.iter() {
+            parks.insert((game.sport_id, game.game_date.year, game.game_venue_id));
+        }
+        // The fence profiles when we have them, the listed distances when we don't
+        let fence_profiles: HashMap<(u32, u16), &FenceProfile> = meta.fence_profiles
+            .iter()
+            .map (|fp| ((fp.venue_id, fp.season), fp))
+            .collect()
+            ;
+        let mut fences: HashMap<(u32, u16), Vec<(u32, Fence)>> = HashMap::new();
+        for (sport_id, year, venue_id) in parks {
+            let fence = match fence_profiles.get(&(venue_id, year)) {
+                Some (profile) => Some(Fence::from(*profile)),
+                None => venue.get(&(venue_id, year)).and_then(Fence::from_venue),
+            };
+            if let Some(fence) = fence {
+                fences.entry((sport_id, year)).or_default().push((venue_id, fence));
+            }
+        }
+
         MetaData {
             schedule,
             boxscore,
@@ -118,6 +282,17 @@ impl From<VecMetaDataInputs> for MetaData {
             teams,
             players,
             re_288_default,
+            re_288,
+            re_288_venue,
+            pitcher_appearance_dates,
+            linear_weights,
+            win_expectancy,
+            park_factors,
+            expected_outcomes,
+            pitch_classifiers,
+            called_strike,
+            fences,
+            pixel_calibrations,
         }
     }
 }
\ No newline at end of file
@@ -0,0 +1,582 @@
+//! Minor league equivalencies: what a minor league line would have looked like in MLB.
+//!
+//! Players who play at two levels in the same season are the link between the levels. For every pair of affiliated levels in a
+//! season we pool those players and take the ratio of their rates at the higher level to their rates at the lower one, for each
+//! component:
+//!   * K% and BB%, per plate appearance
+//!   * HR/FB, home runs per fly ball, with the home runs counting as fly balls
+//!   * BABIP
+//!   * Exit velocity, which is a difference in mph rather than a ratio
+//!
+//! Each player's two lines are weighted by the harmonic mean of the component's denominator at the two levels, so a player with
+//! 600 PA in AAA and 20 in MLB counts for little. Level pairs with too few players in a season fall back to the pair pooled over
+//! every season.
+//!
+//! The factors are then chained up to MLB, one level at a time by level_of_play_rank, skipping over a level when there's no link
+//! to it, and the chained factors translate every minor league line. Batters and pitchers get their own factors, a pitcher's line
+//! is what they allowed.
+
+use serde::{Serialize, Deserialize};
+use std::collections::{BTreeMap, HashMap};
+use crate::aging::Role;
+use crate::game::PitchResult;
+use crate::linear_weights::{BattingTotals, ratio};
+use crate::play_by_play::{Event, Trajectory};
+use crate::sports::{MLB, SPORTS};
+
+/// Plate appearances (or batters faced) a player needs at both levels to link them
+const MIN_PLATE_APPEARANCES: u32 = 50;
+/// Linked players a level pair needs in a season before we use the season on its own
+pub const MIN_PLAYERS: u32 = 10;
+const MLB_SPORT_ID: u32 = 1;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum MleComponent {
+    StrikeoutRate,
+    WalkRate,
+    HomeRunPerFlyBall,
+    Babip,
+    ExitVelocity,
+}
+
+impl MleComponent {
+    pub const ALL: [MleComponent; 5] = [MleComponent::StrikeoutRate, MleComponent::WalkRate, MleComponent::HomeRunPerFlyBall, MleComponent::Babip, MleComponent::ExitVelocity];
+
+    /// Exit velocity translates by adding the factor, the rates by multiplying
+    pub fn additive (&self) -> bool {
+        *self == MleComponent::ExitVelocity
+    }
+
+    fn identity (&self) -> f64 {
+        if self.additive() {0.0} else {1.0}
+    }
+
+    fn combine (&self, a: f64, b: f64) -> f64 {
+        if self.additive() {a + b} else {a * b}
+    }
+}
+
+/// The columns from the play by play output we need for the equivalencies.
+#[derive(Debug, Clone, Deserialize)]
+pub struct MlePitch {
+    pub game_pk: u32,
+    pub num_plate_appearance: u8,
+    pub pitch_num_game: u16,
+    pub sport_id: u32,
+    pub game_year: u16,
+    pub batter: u32,
+    pub batter_name: String,
+    pub pitcher: u32,
+    pub pitcher_name: String,
+    pub pitch_result: PitchResult,
+    pub plate_appearance_result: Option<Event>,
+    pub hit_data_trajectory: Option<Trajectory>,
+    pub hit_data_exit_velocity: Option<f32>,
+}
+
+pub struct MleData <'a> {
+    pub pitches: &'a [MlePitch],
+}
+
+/// The factor from one level to another for a season, or every season pooled when the season is None
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MleFactor {
+    pub role: Role,
+    pub season: Option<u16>,
+    pub from_sport_id: u32,
+    pub to_sport_id: u32,
+    pub component: MleComponent,
+    pub players: u32,
+    pub weight: f32,
+    pub factor: f32,
+}
+
+/// The factor from a level to MLB in a season, chained through the levels in the path
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MleTranslation {
+    pub role: Role,
+    pub season: u16,
+    pub sport_id: u32,
+    pub component: MleComponent,
+    pub factor: f32,
+    /// The sport_ids the factor was chained through, e.g. 12>11>1
+    pub path: String,
+}
+
+/// A minor league line and what it translates to in MLB
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MleLine {
+    pub role: Role,
+    pub player: u32,
+    pub player_name: String,
+    pub season: u16,
+    pub sport_id: u32,
+    pub plate_appearances: u32,
+    pub strikeout_rate: f32,
+    pub walk_rate: f32,
+    pub hr_per_fly_ball: f32,
+    pub babip: f32,
+    pub exit_velocity: Option<f32>,
+    pub avg: f32,
+    pub obp: f32,
+    pub slg: f32,
+    pub mle_strikeout_rate: f32,
+    pub mle_walk_rate: f32,
+    pub mle_hr_per_fly_ball: f32,
+    pub mle_babip: f32,
+    pub mle_exit_velocity: Option<f32>,
+    pub mle_strikeouts: f32,
+    pub mle_walks: f32,
+    pub mle_home_runs: f32,
+    pub mle_hits: f32,
+    pub mle_avg: f32,
+    pub mle_obp: f32,
+    pub mle_slg: f32,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct MleOutput {
+    pub factors: Vec<MleFactor>,
+    pub translations: Vec<MleTranslation>,
+    pub lines: Vec<MleLine>,
+}
+
+#[derive(Debug, Default, Clone)]
+struct MleTotals {
+    name: String,
+    batting: BattingTotals,
+    fly_balls: u32,
+    exit_velocity: (f64, u32),
+}
+
+impl MleTotals {
+    /// Balls in play for BABIP, without the home runs
+    fn balls_in_play (&self) -> f64 {
+        let b = &self.batting;
+        b.at_bats as f64 - b.strikeouts as f64 - b.home_runs as f64 + b.sac_flies as f64
+    }
+
+    /// (value, denominator) for a component
+    fn component (&self, component: MleComponent) -> (f64, f64) {
+        let b = &self.batting;
+        let pa = b.plate_appearances as f64;
+        match component {
+            MleComponent::StrikeoutRate => (ratio(b.strikeouts as f64, pa), pa),
+            MleComponent::WalkRate => (ratio(b.walks as f64, pa), pa),
+            MleComponent::HomeRunPerFlyBall => (ratio(b.home_runs as f64, self.fly_balls as f64), self.fly_balls as f64),
+            MleComponent::Babip => (ratio((b.hits - b.home_runs) as f64, self.balls_in_play()), self.balls_in_play()),
+            MleComponent::ExitVelocity => (ratio(self.exit_velocity.0, self.exit_velocity.1 as f64), self.exit_velocity.1 as f64),
+        }
+    }
+
+    fn exit_velocity (&self) -> Option<f64> {
+        if self.exit_velocity.1 == 0 {None} else {Some(self.exit_velocity.0 / self.exit_velocity.1 as f64)}
+    }
+}
+
+/// Sums for pooling a component over linked players
+#[derive(Debug, Default, Copy, Clone)]
+struct Link {
+    players: u32,
+    weight: f64,
+    from: f64,
+    to: f64,
+}
+
+impl Link {
+    fn add (&mut self, from: (f64, f64), to: (f64, f64)) {
+        let weight = ratio(2.0 * from.1 * to.1, from.1 + to.1);
+        if weight == 0.0 {return};
+        self.players += 1;
+        self.weight += weight;
+        self.from += weight * from.0;
+        self.to += weight * to.0;
+    }
+
+    fn combine (&mut self, other: &Link) {
+        self.players += other.players;
+        self.weight += other.weight;
+        self.from += other.from;
+        self.to += other.to;
+    }
+
+    fn factor (&self, component: MleComponent) -> f64 {
+        if component.additive() {ratio(self.to - self.from, self.weight)} else {ratio(self.to, self.from)}
+    }
+}
+
+fn level_rank (sport_id: u32) -> Option<u8> {
+    SPORTS.iter()
+        .find(|sport| sport.id == sport_id && sport.affiliation != MLB::Unaffiliated)
+        .map(|sport| sport.level_of_play_rank)
+}
+
+/// A line rebuilt from translated rates
+struct Translated {
+    strikeout_rate: f64,
+    walk_rate: f64,
+    hr_per_fly_ball: f64,
+    babip: f64,
+    strikeouts: f64,
+    walks: f64,
+    home_runs: f64,
+    hits: f64,
+    avg: f64,
+    obp: f64,
+    slg: f64,
+}
+
+/// Rebuilds a line from translated rates. The plate appearances, fly balls, hit by pitches and sac flies stay put, walks that go
+/// away become at bats, and the extra base hit mix of the hits in play stays the same.
+fn translate (b: &BattingTotals, fly_balls: u32, factors: &HashMap<MleComponent, f64>) -> Translated {
+    let factor = |component: MleComponent| factors.get(&component).copied().unwrap_or(component.identity());
+    let pa = b.plate_appearances as f64;
+
+    let strikeout_rate = ratio(b.strikeouts as f64, pa) * factor(MleComponent::StrikeoutRate);
+    let walk_rate = ratio(b.walks as f64, pa) * factor(MleComponent::WalkRate);
+    let hr_per_fly_ball = ratio(b.home_runs as f64, fly_balls as f64) * factor(MleComponent::HomeRunPerFlyBall);
+    let babip_in = ratio((b.hits - b.home_runs) as f64, b.at_bats as f64 - b.strikeouts as f64 - b.home_runs as f64 + b.sac_flies as f64);
+    let babip = babip_in * factor(MleComponent::Babip);
+
+    let strikeouts = strikeout_rate * pa;
+    let walks = walk_rate * pa;
+    let home_runs = hr_per_fly_ball * fly_balls as f64;
+    let at_bats = b.at_bats as f64 + b.walks as f64 - walks;
+    let balls_in_play = (at_bats - strikeouts - home_runs + b.sac_flies as f64).max(0.0);
+    let hits_in_play = babip * balls_in_play;
+    let hits = hits_in_play + home_runs;
+
+    let in_play = (b.hits - b.home_runs) as f64;
+    let scale = ratio(hits_in_play, in_play);
+    let bases = if in_play > 0.0 {
+        scale * (b.singles + 2 * b.doubles + 3 * b.triples) as f64
+    } else {
+        hits_in_play
+    } + 4.0 * home_runs;
+
+    Translated {
+        strikeout_rate,
+        walk_rate,
+        hr_per_fly_ball,
+        babip,
+        strikeouts,
+        walks,
+        home_runs,
+        hits,
+        avg: ratio(hits, at_bats),
+        obp: ratio(hits + walks + b.hit_by_pitch as f64, at_bats + walks + (b.hit_by_pitch + b.sac_flies) as f64),
+        slg: ratio(bases, at_bats),
+    }
+}
+
+/// Role, player, sport_id and season
+type LineKey = (Role, u32, u32, u16);
+/// Role, season, from sport_id, to sport_id and component
+type LinkKey = (Role, u16, u32, u32, MleComponent);
+/// A player's lines in a season, by sport_id
+type PlayerLevels<'a> = Vec<(u32, &'a MleTotals)>;
+
+impl <'a> From<MleData<'a>> for MleOutput {
+    fn from (data: MleData) -> MleOutput {
+
+        // The last pitch of each plate appearance is the one the result happened on
+        let mut plate_appearances: HashMap<(u32, u8), &MlePitch> = HashMap::new();
+        for pitch in data.pitches {
+            if pitch.pitch_result == PitchResult::NoPitch {continue};
+            if level_rank(pitch.sport_id).is_none() {continue};
+            let last = plate_appearances.entry((pitch.game_pk, pitch.num_plate_appearance)).or_insert(pitch);
+            if pitch.pitch_num_game > last.pitch_num_game {*last = pitch};
+        }
+
+        let mut lines: BTreeMap<LineKey, MleTotals> = BTreeMap::new();
+        for pitch in plate_appearances.into_values() {
+            let event = match pitch.plate_appearance_result {
+                Some (event) => event,
+                None => continue,
+            };
+            let players = [
+                ((Role::Batter, pitch.batter, pitch.sport_id, pitch.game_year), &pitch.batter_name),
+                ((Role::Pitcher, pitch.pitcher, pitch.sport_id, pitch.game_year), &pitch.pitcher_name),
+            ];
+            for (key, name) in players {
+                let totals = lines.entry(key).or_default();
+                if totals.name.is_empty() {totals.name = name.clone()};
+                totals.batting.add(event);
+                if pitch.hit_data_trajectory == Some(Trajectory::FlyBall) || event == Event::HomeRun {
+                    totals.fly_balls += 1;
+                }
+                if let Some(exit_velocity) = pitch.hit_data_exit_velocity {
+                    totals.exit_velocity.0 += exit_velocity as f64;
+                    totals.exit_velocity.1 += 1;
+                }
+            }
+        }
+
+        // Link each player's lines at two levels in the same season, from the lower level to the higher
+        let mut by_player: BTreeMap<(Role, u32, u16), PlayerLevels> = BTreeMap::new();
+        for ((role, player, sport_id, season), totals) in lines.iter() {
+            if totals.batting.plate_appearances < MIN_PLATE_APPEARANCES {continue};
+            by_player.entry((*role, *player, *season)).or_default().push((*sport_id, totals));
+        }
+
+        let mut links: BTreeMap<LinkKey, Link> = BTreeMap::new();
+        for ((role, _, season), levels) in by_player.iter() {
+            for (i, (a, a_totals)) in levels.iter().enumerate() {
+                for (b, b_totals) in levels.iter().skip(i + 1) {
+                    let (from, from_totals, to, to_totals) = if level_rank(*a) > level_rank(*b) {(a, a_totals, b, b_totals)} else {(b, b_totals, a, a_totals)};
+                    for component in MleComponent::ALL {
+                        links.entry((*role, *season, *from, *to, component)).or_default()
+                            .add(from_totals.component(component), to_totals.component(component));
+                    }
+                }
+            }
+        }
+
+        let mut pooled: BTreeMap<(Role, u32, u32, MleComponent), Link> = BTreeMap::new();
+        for ((role, _, from, to, component), link) in links.iter() {
+            pooled.entry((*role, *from, *to, *component)).or_default().combine(link);
+        }
+
+        let mut factors: Vec<MleFactor> = vec![];
+        for ((role, from, to, component), link) in pooled.iter() {
+            factors.push(MleFactor {
+                role: *role,
+                season: None,
+                from_sport_id: *from,
+                to_sport_id: *to,
+                component: *component,
+                players: link.players,
+                weight: link.weight as f32,
+                factor: link.factor(*component) as f32,
+            });
+        }
+        for ((role, season, from, to, component), link) in links.iter() {
+            factors.push(MleFactor {
+                role: *role,
+                season: Some(*season),
+                from_sport_id: *from,
+                to_sport_id: *to,
+                component: *component,
+                players: link.players,
+                weight: link.weight as f32,
+                factor: link.factor(*component) as f32,
+            });
+        }
+
+        // The factor for a link in a season, falling back to every season pooled
+        let link_factor = |role: Role, season: u16, from: u32, to: u32, component: MleComponent| -> Option<f64> {
+            match links.get(&(role, season, from, to, component)) {
+                Some (link) if link.players >= MIN_PLAYERS => Some(link.factor(component)),
+                _ => pooled.get(&(role, from, to, component))
+                    .filter(|link| link.players >= MIN_PLAYERS)
+                    .map(|link| link.factor(component)),
+            }
+        };
+
+        // Chain each level up to MLB through the nearest level above it with a link and a chain of its own
+        let mut levels: Vec<(u8, u32)> = SPORTS.iter()
+            .filter(|sport| sport.affiliation != MLB::Unaffiliated)
+            .map(|sport| (sport.level_of_play_rank, sport.id))
+            .collect();
+        levels.sort();
+        let seasons: std::collections::BTreeSet<u16> = lines.keys().map(|key| key.3).collect();
+
+        let mut chains: HashMap<(Role, u16, u32, MleComponent), (f64, String)> = HashMap::new();
+        let mut translations = vec![];
+        for role in [Role::Batter, Role::Pitcher] {
+            for season in seasons.iter() {
+                for component in MleComponent::ALL {
+                    chains.insert((role, *season, MLB_SPORT_ID, component), (component.identity(), MLB_SPORT_ID.to_string()));
+                    for (rank, sport_id) in levels.iter() {
+                        if *sport_id == MLB_SPORT_ID {continue};
+                        let chained = levels.iter()
+                            .filter(|(higher_rank, _)| higher_rank < rank)
+                            .rev()
+                            .find_map(|(_, higher)| {
+                                let factor = link_factor(role, *season, *sport_id, *higher, component)?;
+                                let (rest, path) = chains.get(&(role, *season, *higher, component))?;
+                                Some((component.combine(factor, *rest), format!("{}>{}", sport_id, path)))
+                            });
+                        if let Some((factor, path)) = chained {
+                            translations.push(MleTranslation {
+                                role,
+                                season: *season,
+                                sport_id: *sport_id,
+                                component,
+                                factor: factor as f32,
+                                path: path.clone(),
+                            });
+                            chains.insert((role, *season, *sport_id, component), (factor, path));
+                        }
+                    }
+                }
+            }
+        }
+
+        let mut mle_lines = vec![];
+        for ((role, player, sport_id, season), totals) in lines.iter() {
+            if *sport_id == MLB_SPORT_ID {continue};
+            let level_factors: HashMap<MleComponent, f64> = MleComponent::ALL.iter()
+                .filter_map(|component| Some((*component, chains.get(&(*role, *season, *sport_id, *component))?.0)))
+                .collect();
+            // Without a chain for the rates we can't say anything about the line
+            if !level_factors.contains_key(&MleComponent::StrikeoutRate) {continue};
+
+            let b = &totals.batting;
+            let raw = translate(b, totals.fly_balls, &HashMap::new());
+            let mle = translate(b, totals.fly_balls, &level_factors);
+            let exit_velocity = totals.exit_velocity();
+
+            mle_lines.push(MleLine {
+                role: *role,
+                player: *player,
+                player_name: totals.name.clone(),
+                season: *season,
+                sport_id: *sport_id,
+                plate_appearances: b.plate_appearances,
+                strikeout_rate: raw.strikeout_rate as f32,
+                walk_rate: raw.walk_rate as f32,
+                hr_per_fly_ball: raw.hr_per_fly_ball as f32,
+                babip: raw.babip as f32,
+                exit_velocity: exit_velocity.map(|ev| ev as f32),
+                avg: raw.avg as f32,
+                obp: raw.obp as f32,
+                slg: raw.slg as f32,
+                mle_strikeout_rate: mle.strikeout_rate as f32,
+                mle_walk_rate: mle.walk_rate as f32,
+                mle_hr_per_fly_ball: mle.hr_per_fly_ball as f32,
+                mle_babip: mle.babip as f32,
+                mle_exit_velocity: exit_velocity.map(|ev| (ev + level_factors.get(&MleComponent::ExitVelocity).copied().unwrap_or(0.0)) as f32),
+                mle_strikeouts: mle.strikeouts as f32,
+                mle_walks: mle.walks as f32,
+                mle_home_runs: mle.home_runs as f32,
+                mle_hits: mle.hits as f32,
+                mle_avg: mle.avg as f32,
+                mle_obp: mle.obp as f32,
+                mle_slg: mle.slg as f32,
+            });
+        }
+
+        MleOutput {
+            factors,
+            translations,
+            lines: mle_lines,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn identity_translation_keeps_the_line () {
+        let mut b = BattingTotals::default();
+        for event in [Event::Single, Event::Double, Event::HomeRun, Event::StrikeOut, Event::Walk, Event::FieldOut, Event::FieldOut, Event::Triple, Event::SacFly, Event::HitByPitch] {
+            b.add(event);
+        }
+        let line = translate(&b, 3, &HashMap::new());
+        assert!((line.hits - b.hits as f64).abs() < 1e-9);
+        assert!((line.avg - 4.0 / 7.0).abs() < 1e-9);
+        assert!((line.obp - 6.0 / 10.0).abs() < 1e-9);
+        assert!((line.slg - 10.0 / 7.0).abs() < 1e-9);
+
+        // Double the strikeouts, those at bats come out of the balls in play
+        let factors: HashMap<MleComponent, f64> = [(MleComponent::StrikeoutRate, 2.0)].into_iter().collect();
+        let line = translate(&b, 3, &factors);
+        assert!((line.strikeouts - 2.0).abs() < 1e-9);
+        assert!(line.hits < b.hits as f64);
+    }
+
+    /// A batter's plate appearances at a level, each against a different pitcher so no pitcher gets a line
+    fn batter_line (batter: u32, sport_id: u32, season: u16, plate_appearances: u8, strikeouts: u8) -> Vec<MlePitch> {
+        let game_pk = ((batter * 100 + sport_id) * 10) + (season % 10) as u32;
+        (0 .. plate_appearances).map(|i| {
+            let strikeout = i < strikeouts;
+            MlePitch {
+                game_pk,
+                num_plate_appearance: i,
+                pitch_num_game: i as u16,
+                sport_id,
+                game_year: season,
+                batter,
+                batter_name: format!("Batter {}", batter),
+                pitcher: game_pk * 1000 + i as u32,
+                pitcher_name: String::new(),
+                pitch_result: if strikeout {PitchResult::SwingingStrike} else {PitchResult::InPlay},
+                plate_appearance_result: Some(if strikeout {Event::StrikeOut} else {Event::FieldOut}),
+                hit_data_trajectory: None,
+                hit_data_exit_velocity: None,
+            }
+        }).collect()
+    }
+
+    fn factor (output: &MleOutput, season: Option<u16>, from: u32, to: u32) -> &MleFactor {
+        output.factors.iter()
+            .find(|f| f.role == Role::Batter && f.season == season && f.from_sport_id == from && f.to_sport_id == to && f.component == MleComponent::StrikeoutRate)
+            .unwrap()
+    }
+
+    fn translation (output: &MleOutput, season: u16, sport_id: u32) -> Option<&MleTranslation> {
+        output.translations.iter()
+            .find(|t| t.role == Role::Batter && t.season == season && t.sport_id == sport_id && t.component == MleComponent::StrikeoutRate)
+    }
+
+    #[test]
+    fn links_are_weighted_by_the_harmonic_mean () {
+        let pitches: Vec<MlePitch> = [
+            batter_line(1, 11, 2023, 100, 20),
+            batter_line(1, 1, 2023, 50, 15),
+            batter_line(2, 11, 2023, 50, 5),
+            batter_line(2, 1, 2023, 200, 40),
+            // Too few plate appearances to link
+            batter_line(3, 11, 2023, 49, 49),
+            batter_line(3, 1, 2023, 100, 0),
+        ].concat();
+        let output = MleOutput::from(MleData {pitches: &pitches});
+
+        let one = 2.0 * 100.0 * 50.0 / 150.0;
+        let two = 2.0 * 50.0 * 200.0 / 250.0;
+        let link = factor(&output, Some(2023), 11, 1);
+        assert_eq!(link.players, 2);
+        assert_eq!(link.weight, (one + two) as f32);
+        assert_eq!(link.factor, ((one * 0.3 + two * 0.2) / (one * 0.2 + two * 0.1)) as f32);
+        // The pooled factor has the one season in it
+        assert_eq!(factor(&output, None, 11, 1).factor, link.factor);
+    }
+
+    #[test]
+    fn thin_seasons_fall_back_and_chains_skip_missing_levels () {
+        let mut lines = vec![];
+        for batter in 0 .. MIN_PLAYERS {
+            // Triple-A to MLB, .2 to .3
+            lines.push(batter_line(batter, 11, 2022, 50, 10));
+            lines.push(batter_line(batter, 1, 2022, 50, 15));
+            // High-A to Triple-A without anyone in Double-A, .1 to .2
+            lines.push(batter_line(100 + batter, 13, 2022, 50, 5));
+            lines.push(batter_line(100 + batter, 11, 2022, 50, 10));
+        }
+        // One player in 2023, .2 to .4
+        lines.push(batter_line(200, 11, 2023, 50, 10));
+        lines.push(batter_line(200, 1, 2023, 50, 20));
+        let pitches = lines.concat();
+        let output = MleOutput::from(MleData {pitches: &pitches});
+
+        let season = translation(&output, 2022, 11).unwrap();
+        assert_eq!(season.factor, 1.5);
+        assert_eq!(season.path, "11>1");
+
+        // One linked player isn't enough, so 2023 uses every season pooled
+        assert_eq!(factor(&output, Some(2023), 11, 1).factor, 2.0);
+        let pooled = (50.0 * (10.0 * 0.3 + 0.4)) / (50.0 * 11.0 * 0.2);
+        assert_eq!(factor(&output, None, 11, 1).factor, pooled as f32);
+        assert_eq!(translation(&output, 2023, 11).unwrap().factor, pooled as f32);
+
+        // High-A skips Double-A and goes through Triple-A
+        let chained = translation(&output, 2022, 13).unwrap();
+        assert_eq!(chained.factor, 3.0);
+        assert_eq!(chained.path, "13>11>1");
+        assert!(translation(&output, 2022, 12).is_none());
+        assert_eq!(translation(&output, 2023, 13).unwrap().factor, (2.0 * pooled) as f32);
+    }
+}
@@ -2,7 +2,9 @@
 /// 
 
 use core::f64::consts::*;
+use serde::{Serialize, Deserialize};
 
+#[derive(Debug, Copy, Clone)]
 pub (crate) struct Data {
     pub (crate) x0: f64,
     pub (crate) y0: f64,
@@ -47,6 +49,9 @@ pub (crate) struct Nathan {
     pub (crate) ivb: Option<f64>,
     pub (crate) hb: Option<f64>,
     pub (crate) cd: Option<f64>,
+    pub (crate) cl: Option<f64>,
+    pub (crate) spin_t: Option<f64>,
+    pub (crate) spin_axis: Option<f64>,
 }
 
 impl Default for Nathan {
@@ -79,6 +84,9 @@ impl Default for Nathan {
             ivb: None,
             hb: None,
             cd: None,
+            cl: None,
+            spin_t: None,
+            spin_axis: None,
         }
     }
 }
@@ -142,6 +150,18 @@ impl From<Data> for Nathan {
         let ivb = (plate_z - zr - (vzr/vyr)*(PLATE-yr)+0.5*G*tf.powf(2.))*12.;
         let cd = ad / (K * vbar.powf(2.0));
 
+        // Lift coefficient to spin factor from the spreadsheet's fit, which blows up past CL = 1/2.32
+        let cl = at / (K * vbar.powf(2.0));
+        let spin_t = if cl < 1. / 2.32 {
+            let s = 0.4 * cl / (1. - 2.32 * cl);
+            Some(78.92 * s * vbar)
+        }
+        else {
+            None
+        };
+        // Statcast convention, the spin axis is 90 degrees clockwise of the movement, so 180 is pure backspin
+        let spin_axis = (phi_t + 90.).rem_euclid(360.);
+
         Nathan {
             xr: Some(xr),
             yr: Some(yr),
@@ -170,6 +190,347 @@ impl From<Data> for Nathan {
             ivb: Some(ivb),
             hb: Some(hb),
             cd: Some(cd),
+            cl: Some(cl),
+            spin_t,
+            spin_axis: Some(spin_axis),
+        }
+    }
+}
+
+/// Degrees of difference between the observed and the inferred spin axis before we call it seam-shifted wake
+pub const SSW_DEVIATION: f64 = 15.0;
+
+/// The spin numbers we get by comparing the movement with what Hawkeye measured directly
+#[derive(Debug, Clone, Default)]
+pub (crate) struct Spin {
+    pub (crate) transverse_spin: Option<f64>,
+    pub (crate) spin_efficiency: Option<f64>,
+    pub (crate) spin_axis_inferred: Option<f64>,
+    pub (crate) spin_axis_inferred_clock: Option<String>,
+    pub (crate) spin_axis_observed_clock: Option<String>,
+    pub (crate) spin_axis_deviation: Option<f64>,
+    pub (crate) seam_shifted_wake: Option<bool>,
+}
+
+impl Nathan {
+    /// spin_rate and spin_direction are the measured total spin and spin axis. Efficiency is left unclamped, noise in either
+    /// number can push it a little past 1.
+    pub (crate) fn spin (&self, spin_rate: Option<f64>, spin_direction: Option<f64>) -> Spin {
+        let spin_axis_deviation = match (spin_direction, self.spin_axis) {
+            (Some (observed), Some (inferred)) => Some(axis_deviation(observed, inferred)),
+            _ => None,
+        };
+        Spin {
+            transverse_spin: self.spin_t,
+            spin_efficiency: match (self.spin_t, spin_rate) {
+                (Some (spin_t), Some (spin_rate)) if spin_rate > 0. => Some(spin_t / spin_rate),
+                _ => None,
+            },
+            spin_axis_inferred: self.spin_axis,
+            spin_axis_inferred_clock: self.spin_axis.map(clock_face),
+            spin_axis_observed_clock: spin_direction.map(clock_face),
+            spin_axis_deviation,
+            seam_shifted_wake: spin_axis_deviation.map(|deviation| deviation.abs() >= SSW_DEVIATION),
+        }
+    }
+}
+
+/// Signed difference, observed minus inferred, wrapped to (-180, 180]
+pub (crate) fn axis_deviation (observed: f64, inferred: f64) -> f64 {
+    let deviation = (observed - inferred).rem_euclid(360.);
+    if deviation > 180. {deviation - 360.} else {deviation}
+}
+
+/// Spin axis in degrees to the tilt on a clock face the way Savant reports it, rounded to 15 minutes. 180 degrees is 12:00.
+pub (crate) fn clock_face (axis: f64) -> String {
+    let quarters = ((axis / 7.5).round() as i64 + 24).rem_euclid(48);
+    let hour = match quarters / 4 {
+        0 => 12,
+        hour => hour,
+    };
+    format!("{}:{:02}", hour, (quarters % 4) * 15)
+}
+
+/// Distance from the back of home plate, in feet, where the batter has to decide whether to swing
+pub const DECISION_POINT: f64 = 23.8;
+/// Front of home plate, in feet from the back
+pub const PLATE_FRONT: f64 = 17.0/12.0;
+/// Points sampled per pitch for the exported polylines
+pub const POLYLINE_POINTS: usize = 20;
+
+/// The 9-parameter fit on its own, for sampling the flight of the pitch anywhere between release and the plate. Like the spreadsheet,
+/// the acceleration is constant for the whole flight. y is the distance from the back of home plate, x is to the catcher's right and
+/// z is the height, all in feet, with time in seconds from y = y0 (50 ft).
+#[derive(Debug, Copy, Clone)]
+pub (crate) struct PitchTrajectory {
+    pub (crate) x0: f64,
+    pub (crate) y0: f64,
+    pub (crate) z0: f64,
+    pub (crate) vx0: f64,
+    pub (crate) vy0: f64,
+    pub (crate) vz0: f64,
+    pub (crate) ax: f64,
+    pub (crate) ay: f64,
+    pub (crate) az: f64,
+}
+
+#[derive(Debug, Copy, Clone, Serialize, Deserialize)]
+pub struct TrajectoryPoint {
+    pub t: f64,
+    pub x: f64,
+    pub y: f64,
+    pub z: f64,
+    pub vx: f64,
+    pub vy: f64,
+    pub vz: f64,
+}
+
+impl From<&Data> for PitchTrajectory {
+    fn from (data: &Data) -> Self {
+        PitchTrajectory {
+            x0: data.x0,
+            y0: data.y0,
+            z0: data.z0,
+            vx0: data.vx0,
+            vy0: data.vy0,
+            vz0: data.vz0,
+            ax: data.ax,
+            ay: data.ay,
+            az: data.az,
         }
     }
-}
\ No newline at end of file
+}
+
+impl PitchTrajectory {
+
+    pub (crate) fn at_time (&self, t: f64) -> TrajectoryPoint {
+        TrajectoryPoint {
+            t,
+            x: self.x0 + self.vx0 * t + 0.5 * self.ax * t * t,
+            y: self.y0 + self.vy0 * t + 0.5 * self.ay * t * t,
+            z: self.z0 + self.vz0 * t + 0.5 * self.az * t * t,
+            vx: self.vx0 + self.ax * t,
+            vy: self.vy0 + self.ay * t,
+            vz: self.vz0 + self.az * t,
+        }
+    }
+
+    /// Time the pitch reaches y, the same root the spreadsheet uses for the release and the plate. Negative before y0.
+    /// None if the pitch never gets there.
+    pub (crate) fn time_at_y (&self, y: f64) -> Option<f64> {
+        if self.ay == 0. {
+            return if self.vy0 == 0. {None} else {Some((y - self.y0) / self.vy0)};
+        }
+        let discriminant = self.vy0.powf(2.) - 2. * self.ay * (self.y0 - y);
+        if discriminant < 0. {return None};
+        Some((-self.vy0 - discriminant.sqrt()) / self.ay)
+    }
+
+    pub (crate) fn at_y (&self, y: f64) -> Option<TrajectoryPoint> {
+        self.time_at_y(y).map(|t| self.at_time(t))
+    }
+
+    /// Where the pitch is when the batter has to decide, distance is from the back of home plate
+    pub (crate) fn at_decision_point (&self, distance: f64) -> Option<TrajectoryPoint> {
+        self.at_y(distance)
+    }
+
+    pub (crate) fn at_release (&self, extension: f64) -> Option<TrajectoryPoint> {
+        self.at_y(60.5 - extension)
+    }
+
+    pub (crate) fn at_plate (&self) -> Option<TrajectoryPoint> {
+        self.at_y(PLATE_FRONT)
+    }
+
+    /// Evenly spaced points in time from the release to the front of the plate, both included
+    pub (crate) fn polyline (&self, extension: f64, points: usize) -> Vec<TrajectoryPoint> {
+        let (start, end) = match (self.time_at_y(60.5 - extension), self.time_at_y(PLATE_FRONT)) {
+            (Some (start), Some (end)) if points > 1 => (start, end),
+            _ => return vec![],
+        };
+        (0 .. points)
+            .map(|i| self.at_time(start + (end - start) * i as f64 / (points - 1) as f64))
+            .collect()
+    }
+}
+
+/// The columns from the play by play output we need to export trajectories.
+#[derive(Debug, Clone, Deserialize)]
+pub struct TrajectoryPitch {
+    pub game_pk: u32,
+    pub play_id: String,
+    pub pitcher: u32,
+    pub pitch_extension: Option<f64>,
+    pub pitch_x0: Option<f64>,
+    pub pitch_y0: Option<f64>,
+    pub pitch_z0: Option<f64>,
+    pub pitch_v_x0: Option<f64>,
+    pub pitch_v_y0: Option<f64>,
+    pub pitch_v_z0: Option<f64>,
+    pub pitch_a_x: Option<f64>,
+    pub pitch_a_y: Option<f64>,
+    pub pitch_a_z: Option<f64>,
+}
+
+impl TrajectoryPitch {
+    pub (crate) fn trajectory (&self) -> Option<PitchTrajectory> {
+        Some(PitchTrajectory {
+            x0: self.pitch_x0?,
+            y0: self.pitch_y0?,
+            z0: self.pitch_z0?,
+            vx0: self.pitch_v_x0?,
+            vy0: self.pitch_v_y0?,
+            vz0: self.pitch_v_z0?,
+            ax: self.pitch_a_x?,
+            ay: self.pitch_a_y?,
+            az: self.pitch_a_z?,
+        })
+    }
+}
+
+/// One point of a pitch's polyline, flattened for the csv
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TrajectoryRow {
+    pub game_pk: u32,
+    pub play_id: String,
+    pub pitcher: u32,
+    pub point: u16,
+    pub t: f64,
+    pub x: f64,
+    pub y: f64,
+    pub z: f64,
+    pub vx: f64,
+    pub vy: f64,
+    pub vz: f64,
+}
+
+pub struct PolylineData <'p> {
+    pub pitches: &'p [TrajectoryPitch],
+    pub points: usize,
+}
+
+impl <'p> From<PolylineData<'p>> for Vec<TrajectoryRow> {
+    fn from (data: PolylineData) -> Vec<TrajectoryRow> {
+        data.pitches.iter()
+            .filter_map(|pitch| Some((pitch, pitch.trajectory()?, pitch.pitch_extension?)))
+            .flat_map(|(pitch, trajectory, extension)| {
+                trajectory.polyline(extension, data.points).into_iter().enumerate()
+                    .map(move |(point, p)| TrajectoryRow {
+                        game_pk: pitch.game_pk,
+                        play_id: pitch.play_id.clone(),
+                        pitcher: pitch.pitcher,
+                        point: point as u16,
+                        t: p.t,
+                        x: p.x,
+                        y: p.y,
+                        z: p.z,
+                        vx: p.vx,
+                        vy: p.vy,
+                        vz: p.vz,
+                    })
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn trajectory_matches_spreadsheet () {
+        // A typical four seamer
+        let data = Data {
+            x0: -1.5, y0: 50.0, z0: 5.8,
+            vx0: 5.0, vy0: -135.0, vz0: -6.0,
+            ax: -10.0, ay: 28.0, az: -15.0,
+            extension: 6.3, plate_x: 0.0, plate_z: 0.0,
+        };
+        let trajectory = PitchTrajectory::from(&data);
+        let plate = trajectory.at_plate().unwrap();
+        let data = Data {plate_x: plate.x, plate_z: plate.z, ..data};
+        let trajectory = PitchTrajectory::from(&data);
+        let nathan: Nathan = data.into();
+
+        let release = trajectory.at_release(6.3).unwrap();
+        assert!((release.x - nathan.xr.unwrap()).abs() < 1e-9);
+        assert!((release.z - nathan.zr.unwrap()).abs() < 1e-9);
+        assert!((plate.t - release.t - nathan.tf.unwrap()).abs() < 1e-9);
+
+        let polyline = trajectory.polyline(6.3, 10);
+        assert_eq!(polyline.len(), 10);
+        assert!((polyline[9].y - PLATE_FRONT).abs() < 1e-9);
+        let decision = trajectory.at_decision_point(DECISION_POINT).unwrap();
+        assert!(decision.t > release.t && decision.t < plate.t);
+    }
+
+    #[test]
+    fn spin_axis_clock () {
+        assert_eq!(clock_face(180.), "12:00");
+        assert_eq!(clock_face(225.), "1:30");
+        assert_eq!(clock_face(0.), "6:00");
+        assert_eq!(clock_face(359.), "6:00");
+        assert!((axis_deviation(10., 350.) - 20.).abs() < 1e-9);
+        assert!((axis_deviation(350., 10.) + 20.).abs() < 1e-9);
+    }
+
+    /// Straight down the middle with no sideways or vertical velocity, so all of the movement is backspin. With az of 0 the
+    /// lift exactly cancels gravity.
+    fn backspin (az: f64) -> Nathan {
+        Data {
+            x0: 0.0, y0: 50.0, z0: 6.0,
+            vx0: 0.0, vy0: -130.0, vz0: 0.0,
+            ax: 0.0, ay: 25.0, az,
+            extension: 6.0, plate_x: 0.0, plate_z: 6.0,
+        }.into()
+    }
+
+    #[test]
+    fn pure_backspin () {
+        const K: f64 = 0.00538310;
+        const G: f64 = 32.174;
+        let nathan = backspin(0.0);
+        let vbar = nathan.vbar.unwrap();
+
+        assert_eq!((nathan.atx, nathan.aty, nathan.at), (Some(0.0), Some(0.0), Some(G)));
+        let cl = G / (K * vbar * vbar);
+        assert_eq!(nathan.cl, Some(cl));
+        assert_eq!(nathan.spin_t, Some(78.92 * (0.4 * cl / (1. - 2.32 * cl)) * vbar));
+        assert!((nathan.spin_axis.unwrap() - 180.).abs() < 1e-9);
+    }
+
+    #[test]
+    fn lift_past_the_fit_has_no_spin () {
+        // Twice the lift, which at this speed is past CL = 1/2.32
+        let nathan = backspin(32.174);
+        assert!(nathan.cl.unwrap() >= 1. / 2.32);
+        assert_eq!(nathan.spin_t, None);
+
+        let spin = nathan.spin(Some(2400.), Some(180.));
+        assert_eq!((spin.transverse_spin, spin.spin_efficiency), (None, None));
+        assert_eq!(spin.seam_shifted_wake, Some(false));
+    }
+
+    #[test]
+    fn spin_against_the_measured_spin () {
+        let nathan = Nathan {spin_t: Some(2000.), spin_axis: Some(180.), ..Nathan::default()};
+
+        let spin = nathan.spin(Some(2500.), Some(190.));
+        assert_eq!(spin.transverse_spin, Some(2000.));
+        assert_eq!(spin.spin_efficiency, Some(0.8));
+        assert_eq!(spin.spin_axis_inferred, Some(180.));
+        assert_eq!(spin.spin_axis_inferred_clock, Some("12:00".to_string()));
+        assert_eq!(spin.spin_axis_observed_clock, Some("12:15".to_string()));
+        assert_eq!((spin.spin_axis_deviation, spin.seam_shifted_wake), (Some(10.), Some(false)));
+
+        // Seam-shifted wake from SSW_DEVIATION either way
+        assert_eq!(nathan.spin(Some(2500.), Some(180. + SSW_DEVIATION)).seam_shifted_wake, Some(true));
+        assert_eq!(nathan.spin(Some(2500.), Some(180. - SSW_DEVIATION)).seam_shifted_wake, Some(true));
+
+        // Nothing measured
+        let spin = nathan.spin(Some(0.), None);
+        assert_eq!((spin.spin_efficiency, spin.spin_axis_observed_clock, spin.spin_axis_deviation, spin.seam_shifted_wake), (None, None, None, None));
+        assert_eq!(nathan.spin(None, Some(180.)).spin_efficiency, None);
+    }
+}
@@ -0,0 +1,377 @@
+//! Park factors for each venue, sport_id and season.
+//!
+//! The rate factors (runs, singles, doubles, triples and home runs) compare home and away splits. For every team that played home
+//! games at the venue, we take the rate per plate appearance in those home games, both teams batting, and compare it to the rate
+//! in that team's road games. The venue's factor is the events at the venue over the events we'd expect from the road rates.
+//! A factor of 1.10 means 10% more of the event than the same teams' road parks, so to neutralize a full season of home and road
+//! games use (1 + factor) / 2.
+//!
+//! We pool PARK_FACTOR_SEASONS seasons, the season and the ones before it, and then regress towards 1. Each event has its own
+//! amount of regression, in plate appearances, since triples take far longer to stabilize than runs.
+//!
+//! The batted ball home run factor doesn't need the road splits. It's the home runs hit at the venue over the home runs we'd
+//! expect from the league home run rate for each exit velocity and launch angle, so it measures the park and not the hitters
+//! who happened to play there.
+//!
+//! Every factor is also split by the side the batter hit from.
+
+use serde::{Serialize, Deserialize};
+use std::collections::{BTreeSet, HashMap};
+use crate::play_by_play::{Event, HalfInning, SideCode};
+
+/// The number of seasons pooled for each factor, including the season itself
+pub const PARK_FACTOR_SEASONS: u16 = 3;
+
+/// Regression towards 1, in plate appearances. A venue with this many plate appearances gets half its observed factor.
+const RUNS_REGRESSION: f64 = 5_000.0;
+const SINGLES_REGRESSION: f64 = 8_000.0;
+const DOUBLES_REGRESSION: f64 = 12_000.0;
+const TRIPLES_REGRESSION: f64 = 30_000.0;
+const HOME_RUNS_REGRESSION: f64 = 10_000.0;
+/// Regression for the batted ball home run factor, in batted balls
+const BATTED_BALL_REGRESSION: f64 = 2_000.0;
+
+/// Width of the exit velocity (mph) and launch angle (degrees) bins for the league home run rates
+const EXIT_VELOCITY_BIN: f32 = 3.0;
+const LAUNCH_ANGLE_BIN: f32 = 3.0;
+
+/// The columns from the play by play output we need for park factors.
+#[derive(Debug, Clone, Deserialize)]
+pub struct ParkFactorPitch {
+    pub game_pk: u32,
+    pub sport_id: u32,
+    pub game_year: u16,
+    pub venue_id: u32,
+    pub half_inning: HalfInning,
+    pub num_plate_appearance: u8,
+    pub pitch_num_game: u16,
+    pub batter_team_id: u32,
+    pub pitcher_team_id: u32,
+    pub batter_bats: SideCode,
+    pub runs_scored: u8,
+    pub plate_appearance_result: Option<Event>,
+    pub in_play: u8,
+    pub hit_data_exit_velocity: Option<f32>,
+    pub hit_data_launch_angle: Option<f32>,
+}
+
+pub struct ParkFactorData <'p> {
+    pub pitches: &'p [ParkFactorPitch],
+}
+
+/// Park factors for a venue, sport_id and season. Rows with no batter_bats are for all batters.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ParkFactor {
+    pub venue_id: u32,
+    pub sport_id: u32,
+    pub season: u16,
+    pub batter_bats: Option<SideCode>,
+    /// Seasons with games at the venue that went into the factors
+    pub seasons: u16,
+    pub plate_appearances: u32,
+    pub runs: f32,
+    pub singles: f32,
+    pub doubles: f32,
+    pub triples: f32,
+    pub home_runs: f32,
+    pub batted_balls: u32,
+    pub home_runs_batted_ball: Option<f32>,
+}
+
+#[derive(Debug, Default, Copy, Clone)]
+struct Totals {
+    plate_appearances: u32,
+    runs: u32,
+    singles: u32,
+    doubles: u32,
+    triples: u32,
+    home_runs: u32,
+}
+
+impl Totals {
+    fn add (&mut self, runs: u32, result: Option<Event>) {
+        self.plate_appearances += 1;
+        self.runs += runs;
+        match result {
+            Some (Event::Single) => self.singles += 1,
+            Some (Event::Double) => self.doubles += 1,
+            Some (Event::Triple) => self.triples += 1,
+            Some (Event::HomeRun) => self.home_runs += 1,
+            _ => {},
+        }
+    }
+
+    fn events (&self) -> [f64; 5] {
+        [self.runs as f64, self.singles as f64, self.doubles as f64, self.triples as f64, self.home_runs as f64]
+    }
+}
+
+const REGRESSION: [f64; 5] = [RUNS_REGRESSION, SINGLES_REGRESSION, DOUBLES_REGRESSION, TRIPLES_REGRESSION, HOME_RUNS_REGRESSION];
+
+/// Batted balls and home runs, for the league rate in a bin or the actual and expected home runs at a venue
+#[derive(Debug, Default, Copy, Clone)]
+struct BattedBalls {
+    batted_balls: u32,
+    home_runs: f64,
+    expected_home_runs: f64,
+}
+
+/// All batters (None), left and right
+fn sides (batter_bats: SideCode) -> Vec<Option<SideCode>> {
+    match batter_bats {
+        SideCode::L | SideCode::R => vec![None, Some(batter_bats)],
+        _ => vec![None],
+    }
+}
+
+fn bin (exit_velocity: f32, launch_angle: f32) -> (i16, i16) {
+    ((exit_velocity / EXIT_VELOCITY_BIN).floor() as i16, (launch_angle / LAUNCH_ANGLE_BIN).floor() as i16)
+}
+
+fn regress (observed: f64, expected: f64, n: f64, regression: f64) -> f32 {
+    if expected <= 0.0 {return 1.0};
+    (1.0 + (observed / expected - 1.0) * n / (n + regression)) as f32
+}
+
+/// (team_id, sport_id, season, batter_bats)
+type TeamKey = (u32, u32, u16, Option<SideCode>);
+/// (venue_id, sport_id, season, batter_bats)
+type VenueKey = (u32, u32, u16, Option<SideCode>);
+
+impl <'p> From<ParkFactorData<'p>> for Vec<ParkFactor> {
+    fn from (data: ParkFactorData) -> Vec<ParkFactor> {
+
+        // Runs can score on any pitch of the plate appearance, the result is on the last one
+        let mut plate_appearances: HashMap<(u32, u8), (u32, &ParkFactorPitch)> = HashMap::new();
+        for pitch in data.pitches {
+            let (runs, last) = plate_appearances.entry((pitch.game_pk, pitch.num_plate_appearance)).or_insert((0, pitch));
+            *runs += pitch.runs_scored as u32;
+            if pitch.pitch_num_game > last.pitch_num_game {*last = pitch};
+        }
+
+        let mut home: HashMap<VenueKey, HashMap<u32, Totals>> = HashMap::new();
+        let mut road: HashMap<TeamKey, Totals> = HashMap::new();
+        let mut league_bins: HashMap<(u32, u16, (i16, i16)), BattedBalls> = HashMap::new();
+
+        for (runs, pitch) in plate_appearances.values() {
+            let (home_team_id, away_team_id) = match pitch.half_inning {
+                HalfInning::Top => (pitch.pitcher_team_id, pitch.batter_team_id),
+                HalfInning::Bottom => (pitch.batter_team_id, pitch.pitcher_team_id),
+            };
+
+            for side in sides(pitch.batter_bats) {
+                home.entry((pitch.venue_id, pitch.sport_id, pitch.game_year, side)).or_default()
+                    .entry(home_team_id).or_default()
+                    .add(*runs, pitch.plate_appearance_result);
+                road.entry((away_team_id, pitch.sport_id, pitch.game_year, side)).or_default()
+                    .add(*runs, pitch.plate_appearance_result);
+            }
+
+            if let (1, Some(exit_velocity), Some(launch_angle)) = (pitch.in_play, pitch.hit_data_exit_velocity, pitch.hit_data_launch_angle) {
+                let league_bin = league_bins.entry((pitch.sport_id, pitch.game_year, bin(exit_velocity, launch_angle))).or_default();
+                league_bin.batted_balls += 1;
+                if pitch.plate_appearance_result == Some(Event::HomeRun) {league_bin.home_runs += 1.0};
+            }
+        }
+
+        let mut venue_batted_balls: HashMap<VenueKey, BattedBalls> = HashMap::new();
+        for (_, pitch) in plate_appearances.values() {
+            if let (1, Some(exit_velocity), Some(launch_angle)) = (pitch.in_play, pitch.hit_data_exit_velocity, pitch.hit_data_launch_angle) {
+                let league_bin = league_bins[&(pitch.sport_id, pitch.game_year, bin(exit_velocity, launch_angle))];
+                for side in sides(pitch.batter_bats) {
+                    let venue = venue_batted_balls.entry((pitch.venue_id, pitch.sport_id, pitch.game_year, side)).or_default();
+                    venue.batted_balls += 1;
+                    venue.expected_home_runs += league_bin.home_runs / league_bin.batted_balls as f64;
+                    if pitch.plate_appearance_result == Some(Event::HomeRun) {venue.home_runs += 1.0};
+                }
+            }
+        }
+
+        // Sorted so the output is always in the same order
+        let keys: BTreeSet<(u32, u32, u16)> = home.keys().map(|(venue_id, sport_id, season, _)| (*venue_id, *sport_id, *season)).collect();
+        let mut park_factors = vec![];
+
+        for (venue_id, sport_id, season) in keys {
+            for batter_bats in [None, Some(SideCode::L), Some(SideCode::R)] {
+
+                let window: Vec<u16> = (season.saturating_sub(PARK_FACTOR_SEASONS - 1) ..= season)
+                    .filter(|s| home.contains_key(&(venue_id, sport_id, *s, batter_bats)))
+                    .collect();
+                if window.is_empty() {continue};
+
+                let mut observed = [0f64; 5];
+                let mut expected = [0f64; 5];
+                let mut plate_appearances = 0u32;
+
+                for s in window.iter() {
+                    for (team_id, totals) in home[&(venue_id, sport_id, *s, batter_bats)].iter() {
+                        let road_totals = match road.get(&(*team_id, sport_id, *s, batter_bats)) {
+                            Some (road_totals) if road_totals.plate_appearances > 0 => road_totals,
+                            _ => continue,
+                        };
+                        plate_appearances += totals.plate_appearances;
+                        for (index, (events, road_events)) in totals.events().iter().zip(road_totals.events().iter()).enumerate() {
+                            observed[index] += events;
+                            expected[index] += totals.plate_appearances as f64 * road_events / road_totals.plate_appearances as f64;
+                        }
+                    }
+                }
+
+                let factors: Vec<f32> = (0 .. 5)
+                    .map(|index| regress(observed[index], expected[index], plate_appearances as f64, REGRESSION[index]))
+                    .collect();
+
+                let batted_balls = window.iter()
+                    .filter_map(|s| venue_batted_balls.get(&(venue_id, sport_id, *s, batter_bats)))
+                    .fold(BattedBalls::default(), |acc, b| BattedBalls {
+                        batted_balls: acc.batted_balls + b.batted_balls,
+                        home_runs: acc.home_runs + b.home_runs,
+                        expected_home_runs: acc.expected_home_runs + b.expected_home_runs,
+                    });
+
+                let home_runs_batted_ball = match batted_balls.batted_balls {
+                    0 => None,
+                    n => Some(regress(batted_balls.home_runs, batted_balls.expected_home_runs, n as f64, BATTED_BALL_REGRESSION)),
+                };
+
+                park_factors.push(
+                    ParkFactor {
+                        venue_id,
+                        sport_id,
+                        season,
+                        batter_bats,
+                        seasons: window.len() as u16,
+                        plate_appearances,
+                        runs: factors[0],
+                        singles: factors[1],
+                        doubles: factors[2],
+                        triples: factors[3],
+                        home_runs: factors[4],
+                        batted_balls: batted_balls.batted_balls,
+                        home_runs_batted_ball,
+                    }
+                );
+            }
+        }
+
+        park_factors
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A one pitch plate appearance by a right handed batter. Team 1 is at home at venue 100 and team 2 at venue 200.
+    /// Batted balls are all in the same bin.
+    fn pitch (game_pk: u32, game_year: u16, venue_id: u32, half_inning: HalfInning, num_plate_appearance: u8, result: Event) -> ParkFactorPitch {
+        let (home_team_id, away_team_id) = if venue_id == 100 {(1, 2)} else {(2, 1)};
+        let (batter_team_id, pitcher_team_id) = match half_inning {
+            HalfInning::Top => (away_team_id, home_team_id),
+            HalfInning::Bottom => (home_team_id, away_team_id),
+        };
+        let in_play = !matches!(result, Event::StrikeOut);
+        ParkFactorPitch {
+            game_pk,
+            sport_id: 1,
+            game_year,
+            venue_id,
+            half_inning,
+            num_plate_appearance,
+            pitch_num_game: num_plate_appearance as u16,
+            batter_team_id,
+            pitcher_team_id,
+            batter_bats: SideCode::R,
+            runs_scored: (result == Event::HomeRun) as u8,
+            plate_appearance_result: Some(result),
+            in_play: in_play as u8,
+            hit_data_exit_velocity: in_play.then_some(100.5),
+            hit_data_launch_angle: in_play.then_some(30.5),
+        }
+    }
+
+    /// A home run and a strikeout at venue 100. At venue 200 a home run, a fly out, a run on a wild pitch before a strikeout
+    /// and another strikeout.
+    fn season (game_year: u16) -> Vec<ParkFactorPitch> {
+        let game_pk = game_year as u32 * 10;
+        vec![
+            pitch(game_pk, game_year, 100, HalfInning::Top, 1, Event::HomeRun),
+            pitch(game_pk, game_year, 100, HalfInning::Bottom, 2, Event::StrikeOut),
+            pitch(game_pk + 1, game_year, 200, HalfInning::Top, 1, Event::HomeRun),
+            pitch(game_pk + 1, game_year, 200, HalfInning::Bottom, 2, Event::FlyOut),
+            ParkFactorPitch {runs_scored: 1, plate_appearance_result: None, pitch_num_game: 3, ..pitch(game_pk + 1, game_year, 200, HalfInning::Top, 3, Event::StrikeOut)},
+            ParkFactorPitch {pitch_num_game: 4, ..pitch(game_pk + 1, game_year, 200, HalfInning::Top, 3, Event::StrikeOut)},
+            ParkFactorPitch {pitch_num_game: 5, ..pitch(game_pk + 1, game_year, 200, HalfInning::Top, 4, Event::StrikeOut)},
+        ]
+    }
+
+    fn park_factors (pitches: &[ParkFactorPitch]) -> Vec<ParkFactor> {
+        ParkFactorData {pitches}.into()
+    }
+
+    #[test]
+    fn regression_halfway_at_the_regression () {
+        assert_eq!(regress(2.0, 1.0, RUNS_REGRESSION, RUNS_REGRESSION), 1.5);
+        assert_eq!(regress(2.0, 0.0, RUNS_REGRESSION, RUNS_REGRESSION), 1.0);
+    }
+
+    #[test]
+    fn switch_hitters_only_count_for_all_batters () {
+        assert_eq!(sides(SideCode::L), vec![None, Some(SideCode::L)]);
+        assert_eq!(sides(SideCode::S), vec![None]);
+    }
+
+    #[test]
+    fn bins_round_down () {
+        assert_eq!(bin(100.5, 30.5), (33, 10));
+        assert_eq!(bin(80.0, -1.0), (26, -1));
+    }
+
+    #[test]
+    fn one_row_per_venue_and_side () {
+        let rows: Vec<(u32, Option<SideCode>)> = park_factors(&season(2021)).iter().map(|p| (p.venue_id, p.batter_bats)).collect();
+        assert_eq!(rows, vec![(100, None), (100, Some(SideCode::R)), (200, None), (200, Some(SideCode::R))]);
+    }
+
+    #[test]
+    fn home_rates_over_road_rates () {
+        let park_factors = park_factors(&season(2021));
+
+        // Venue 100 has 1 home run in 2 plate appearances, team 1 allowed 1 in 4 on the road
+        assert_eq!(park_factors[0].plate_appearances, 2);
+        assert_eq!(park_factors[0].home_runs, (1.0 + 2.0 / (2.0 + HOME_RUNS_REGRESSION)) as f32);
+        // Venue 200 has 1 in 4, team 2 allowed 1 in 2 on the road
+        assert_eq!(park_factors[2].plate_appearances, 4);
+        assert_eq!(park_factors[2].home_runs, (1.0 - 0.5 * 4.0 / (4.0 + HOME_RUNS_REGRESSION)) as f32);
+    }
+
+    #[test]
+    fn runs_on_any_pitch_of_the_plate_appearance () {
+        // 2 runs in 4 plate appearances at venue 200 against 1 in 2 at venue 100
+        let park_factors = park_factors(&season(2021));
+        assert_eq!(park_factors[0].runs, 1.0);
+        assert_eq!(park_factors[2].runs, 1.0);
+    }
+
+    #[test]
+    fn batted_ball_home_runs_against_the_league_bin () {
+        // 2 home runs in the 3 batted balls of the bin
+        let park_factors = park_factors(&season(2021));
+
+        assert_eq!(park_factors[0].batted_balls, 1);
+        assert_eq!(park_factors[0].home_runs_batted_ball, Some((1.0 + 0.5 / (1.0 + BATTED_BALL_REGRESSION)) as f32));
+        assert_eq!(park_factors[2].batted_balls, 2);
+        assert_eq!(park_factors[2].home_runs_batted_ball, Some((1.0 - 0.25 * 2.0 / (2.0 + BATTED_BALL_REGRESSION)) as f32));
+    }
+
+    #[test]
+    fn seasons_pooled_in_the_window () {
+        let pitches: Vec<ParkFactorPitch> = [2018, 2019, 2021].into_iter().flat_map(season).collect();
+        let park_factor = park_factors(&pitches).into_iter()
+            .find(|p| p.venue_id == 100 && p.season == 2021 && p.batter_bats.is_none())
+            .unwrap();
+
+        assert_eq!(park_factor.seasons, 2);
+        assert_eq!(park_factor.plate_appearances, 4);
+    }
+}
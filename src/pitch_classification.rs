@@ -0,0 +1,561 @@
+//! Pitch classification for games with missing or inconsistent pitch types.
+//!
+//! Lower levels often come through with no pitch type at all, and even when MLB tags the pitches the tags drift from season to season.
+//! So we classify every pitch ourselves, one pitcher at a time. A pitcher's pitches are clustered with k-means on speed, induced
+//! vertical break, horizontal break, spin rate, spin direction and release point. The breaks and release point come from the nathan
+//! module, falling back to the API breaks when we couldn't run the nathan calculations.
+//!
+//! Each feature is divided by a fixed scale, roughly the spread we see within a single pitch type, instead of standardizing within the
+//! pitcher. A pitcher who only throws a fastball would otherwise have their noise blown up into a second pitch. With the scales, a pitch
+//! type is close to a unit gaussian, so we choose the number of clusters with BIC.
+//!
+//! Each cluster is mapped to a canonical pitch type. If most of the cluster was tagged by MLB, we use the most common tag, after folding
+//! the old tags into the new ones (FT into SI, FA into FF etc.). Otherwise we fall back on simple rules on the cluster's centre, relative
+//! to the pitcher's hardest pitch. The confidence is the probability the pitch came from a cluster with that type.
+//!
+//! The classifier for each pitcher is fit once over all of our data and cached, so every game uses the same arsenal model.
+
+use serde::{Serialize, Deserialize};
+use std::collections::HashMap;
+use rayon::prelude::*;
+use crate::rng::Rng;
+use crate::play_by_play::{PitchTypeCode, SideCode};
+
+/// Pitchers need at least this many pitches with the core features before we fit a classifier
+pub const MIN_PITCHES: usize = 50;
+/// The most pitch types we'll look for in a single pitcher
+const MAX_CLUSTERS: usize = 7;
+/// A cluster must hold at least this share of the pitcher's pitches, so one-off pitches don't become their own pitch type
+const MIN_CLUSTER_SHARE: f64 = 0.02;
+/// A feature is only used for a pitcher if we have it on this share of their pitches
+const MIN_FEATURE_SHARE: f64 = 0.8;
+/// A cluster takes the MLB tag if at least this share of its pitches were tagged
+const MIN_TAGGED_SHARE: f64 = 0.5;
+const KMEANS_ITERATIONS: usize = 100;
+const KMEANS_RESTARTS: usize = 3;
+const SEED: u64 = 37;
+
+/// The features we cluster on, in the order they appear in a classifier's centroids
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Feature {
+    Speed,
+    InducedVerticalBreak,
+    HorizontalBreak,
+    SpinRate,
+    SpinAxisX,
+    SpinAxisY,
+    ReleaseSide,
+    ReleaseHeight,
+}
+
+const FEATURES: [Feature; 8] = [
+    Feature::Speed, Feature::InducedVerticalBreak, Feature::HorizontalBreak, Feature::SpinRate,
+    Feature::SpinAxisX, Feature::SpinAxisY, Feature::ReleaseSide, Feature::ReleaseHeight,
+];
+
+impl Feature {
+    /// Roughly the spread of the feature within a single pitch type. Release point gets a wider scale, since it tells pitches apart
+    /// far less than it tells arm slots apart.
+    fn scale (&self) -> f64 {
+        match self {
+            Feature::Speed => 1.5,
+            Feature::InducedVerticalBreak | Feature::HorizontalBreak => 2.5,
+            Feature::SpinRate => 150.0,
+            Feature::SpinAxisX | Feature::SpinAxisY => 0.2,
+            Feature::ReleaseSide | Feature::ReleaseHeight => 0.5,
+        }
+    }
+}
+
+/// The columns from the play by play output we need to fit the classifiers.
+#[derive(Debug, Clone, Deserialize)]
+pub struct ClassificationPitch {
+    pub pitcher: u32,
+    pub pitcher_throws: SideCode,
+    pub pitch_type_code: Option<PitchTypeCode>,
+    pub pitch_speed_start: Option<f64>,
+    pub pitch_break_vertical_induced: Option<f64>,
+    pub pitch_break_horizontal: Option<f64>,
+    pub pitch_spin_rate: Option<f64>,
+    pub pitch_spin_direction: Option<f64>,
+    pub xr: Option<f64>,
+    pub zr: Option<f64>,
+    pub ivb: Option<f64>,
+    pub hb: Option<f64>,
+}
+
+/// The raw features for a single pitch. Breaks are in inches, release in feet, both from the catcher's view.
+#[derive(Debug, Copy, Clone, Default)]
+pub struct PitchFeatures {
+    pub speed: Option<f64>,
+    pub induced_vertical_break: Option<f64>,
+    pub horizontal_break: Option<f64>,
+    pub spin_rate: Option<f64>,
+    /// Degrees
+    pub spin_direction: Option<f64>,
+    pub release_side: Option<f64>,
+    pub release_height: Option<f64>,
+}
+
+impl PitchFeatures {
+    fn get (&self, feature: Feature) -> Option<f64> {
+        match feature {
+            Feature::Speed => self.speed,
+            Feature::InducedVerticalBreak => self.induced_vertical_break,
+            Feature::HorizontalBreak => self.horizontal_break,
+            Feature::SpinRate => self.spin_rate,
+            Feature::SpinAxisX => self.spin_direction.map(|d| d.to_radians().sin()),
+            Feature::SpinAxisY => self.spin_direction.map(|d| d.to_radians().cos()),
+            Feature::ReleaseSide => self.release_side,
+            Feature::ReleaseHeight => self.release_height,
+        }
+    }
+
+    /// The scaled features, or None if any are missing
+    fn vector (&self, features: &[Feature]) -> Option<Vec<f64>> {
+        features.iter().map(|f| self.get(*f).map(|v| v / f.scale())).collect()
+    }
+}
+
+impl From<&ClassificationPitch> for PitchFeatures {
+    fn from (pitch: &ClassificationPitch) -> PitchFeatures {
+        PitchFeatures {
+            speed: pitch.pitch_speed_start,
+            induced_vertical_break: pitch.ivb.or(pitch.pitch_break_vertical_induced),
+            horizontal_break: pitch.hb.or(pitch.pitch_break_horizontal),
+            spin_rate: pitch.pitch_spin_rate,
+            spin_direction: pitch.pitch_spin_direction,
+            release_side: pitch.xr,
+            release_height: pitch.zr,
+        }
+    }
+}
+
+pub struct ClassificationData <'c> {
+    pub pitches: &'c [ClassificationPitch],
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PitchCluster {
+    pub pitch_type: PitchTypeCode,
+    /// Whether the pitch type came from the MLB tags or from our rules
+    pub from_tags: bool,
+    pub pitches: u32,
+    /// Centre of the cluster in scaled units, in the order of the classifier's features
+    pub centroid: Vec<f64>,
+    /// Raw averages, for anyone reading the cache
+    pub speed: Option<f64>,
+    pub induced_vertical_break: Option<f64>,
+    pub horizontal_break: Option<f64>,
+    pub spin_rate: Option<f64>,
+}
+
+/// A pitcher's arsenal model
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PitchClassifier {
+    pub pitcher: u32,
+    pub pitcher_throws: SideCode,
+    pub pitches: u32,
+    pub features: Vec<Feature>,
+    pub clusters: Vec<PitchCluster>,
+}
+
+impl PitchClassifier {
+    /// The inferred pitch type and our confidence in it. Features the classifier uses but the pitch is missing are skipped,
+    /// but we always need the speed.
+    pub fn classify (&self, pitch: &PitchFeatures) -> Option<(PitchTypeCode, f32)> {
+        pitch.speed?;
+
+        let values: Vec<Option<f64>> = self.features.iter().map(|f| pitch.get(*f).map(|v| v / f.scale())).collect();
+        let total = self.clusters.iter().map(|c| c.pitches as f64).sum::<f64>();
+
+        // Log of the prior times the likelihood, for each cluster
+        let scores: Vec<f64> = self.clusters.iter()
+            .map(|cluster| {
+                let distance: f64 = values.iter().zip(cluster.centroid.iter())
+                    .filter_map(|(v, c)| v.map(|v| (v - c).powi(2)))
+                    .sum();
+                (cluster.pitches as f64 / total).ln() - distance / 2.0
+            })
+            .collect();
+
+        let max = scores.iter().cloned().fold(f64::NEG_INFINITY, f64::max);
+        let weights: Vec<f64> = scores.iter().map(|s| (s - max).exp()).collect();
+        let sum: f64 = weights.iter().sum();
+
+        let mut by_type: Vec<(PitchTypeCode, f64)> = vec![];
+        for (cluster, weight) in self.clusters.iter().zip(weights.iter()) {
+            match by_type.iter_mut().find(|(t, _)| *t == cluster.pitch_type) {
+                Some ((_, w)) => *w += weight,
+                None => by_type.push((cluster.pitch_type, *weight)),
+            }
+        }
+
+        by_type.into_iter()
+            .max_by(|a, b| a.1.partial_cmp(&b.1).unwrap())
+            .map(|(pitch_type, weight)| (pitch_type, (weight / sum) as f32))
+    }
+}
+
+/// Folds the old and rare MLB tags into the types we classify into. Pitchouts, intentional balls and unknowns aren't pitch types.
+pub (crate) fn canonical (code: PitchTypeCode) -> Option<PitchTypeCode> {
+    match code {
+        PitchTypeCode::FA => Some(PitchTypeCode::FF),
+        PitchTypeCode::FT => Some(PitchTypeCode::SI),
+        PitchTypeCode::FO => Some(PitchTypeCode::FS),
+        PitchTypeCode::SC => Some(PitchTypeCode::CH),
+        PitchTypeCode::IN | PitchTypeCode::PO | PitchTypeCode::UN => None,
+        _ => Some(code),
+    }
+}
+
+/// Our best guess at a pitch type from a cluster's centre, relative to the pitcher's hardest pitch. Horizontal break is flipped
+/// so that positive is always towards the pitcher's arm side.
+fn rule (cluster: &PitchCluster, fastball_speed: f64, pitcher_throws: SideCode) -> PitchTypeCode {
+    let speed = cluster.speed.unwrap_or(fastball_speed);
+    let spin_rate = cluster.spin_rate;
+
+    if speed < 60.0 {return PitchTypeCode::EP};
+    if spin_rate.map(|s| s < 600.0).unwrap_or(false) && speed < 82.0 {return PitchTypeCode::KN};
+
+    let (ivb, arm_side) = match (cluster.induced_vertical_break, cluster.horizontal_break) {
+        (Some (ivb), Some (hb)) => (ivb, if pitcher_throws == SideCode::L {hb} else {-hb}),
+        // Speed alone can only pick out the fastball
+        _ => return if fastball_speed - speed < 4.0 {PitchTypeCode::FF} else {PitchTypeCode::UN},
+    };
+
+    if fastball_speed - speed < 4.0 {
+        if arm_side < 3.0 && ivb < 12.0 {PitchTypeCode::FC}
+        else if ivb < 11.0 && arm_side > 10.0 {PitchTypeCode::SI}
+        else {PitchTypeCode::FF}
+    }
+    else if arm_side > 5.0 {
+        if spin_rate.map(|s| s < 1500.0).unwrap_or(false) && ivb < 6.0 {PitchTypeCode::FS} else {PitchTypeCode::CH}
+    }
+    else if ivb < -3.0 {PitchTypeCode::CU}
+    else if arm_side < -10.0 {PitchTypeCode::ST}
+    else if fastball_speed - speed < 7.0 && arm_side > -3.0 {PitchTypeCode::FC}
+    else {PitchTypeCode::SL}
+}
+
+fn distance (a: &[f64], b: &[f64]) -> f64 {
+    a.iter().zip(b.iter()).map(|(a, b)| (a - b).powi(2)).sum()
+}
+
+fn nearest (point: &[f64], centroids: &[Vec<f64>]) -> (usize, f64) {
+    centroids.iter()
+        .map(|c| distance(point, c))
+        .enumerate()
+        .fold((0, f64::INFINITY), |best, (index, d)| if d < best.1 {(index, d)} else {best})
+}
+
+/// Lloyd's algorithm with k-means++ starts. Returns the centroids, the assignments and the sum of squared distances.
+pub (crate) fn kmeans (points: &[Vec<f64>], k: usize, rng: &mut Rng) -> (Vec<Vec<f64>>, Vec<usize>, f64) {
+    let mut centroids: Vec<Vec<f64>> = vec![points[rng.below(points.len() as u64) as usize].clone()];
+    while centroids.len() < k {
+        let distances: Vec<f64> = points.iter().map(|p| nearest(p, &centroids).1).collect();
+        let total: f64 = distances.iter().sum();
+        if total <= 0.0 {break};
+        let mut draw = rng.next_f64() * total;
+        let index = distances.iter().position(|d| {draw -= d; draw < 0.0}).unwrap_or(points.len() - 1);
+        centroids.push(points[index].clone());
+    }
+
+    let mut assignments = vec![usize::MAX; points.len()];
+    for _ in 0 .. KMEANS_ITERATIONS {
+        let mut changed = false;
+        for (point, assignment) in points.iter().zip(assignments.iter_mut()) {
+            let (index, _) = nearest(point, &centroids);
+            if *assignment != index {*assignment = index; changed = true};
+        }
+        if !changed {break};
+
+        let dimensions = points[0].len();
+        let mut sums = vec![vec![0f64; dimensions]; centroids.len()];
+        let mut counts = vec![0usize; centroids.len()];
+        for (point, assignment) in points.iter().zip(assignments.iter()) {
+            counts[*assignment] += 1;
+            for (sum, value) in sums[*assignment].iter_mut().zip(point.iter()) {*sum += value};
+        }
+        for ((centroid, sum), count) in centroids.iter_mut().zip(sums).zip(counts.iter()) {
+            if *count > 0 {*centroid = sum.into_iter().map(|s| s / *count as f64).collect()};
+        }
+    }
+
+    let sse = points.iter().zip(assignments.iter()).map(|(p, a)| distance(p, &centroids[*a])).sum();
+    (centroids, assignments, sse)
+}
+
+fn mean (values: impl Iterator<Item = f64>) -> Option<f64> {
+    let (sum, n) = values.fold((0f64, 0usize), |(sum, n), v| (sum + v, n + 1));
+    if n == 0 {None} else {Some(sum / n as f64)}
+}
+
+fn fit (pitcher: u32, pitches: &[&ClassificationPitch]) -> Option<PitchClassifier> {
+    let pitcher_throws = pitches.first()?.pitcher_throws;
+    let raw: Vec<PitchFeatures> = pitches.iter().map(|p| (*p).into()).collect();
+
+    let features: Vec<Feature> = FEATURES.iter()
+        .filter(|f| raw.iter().filter(|p| p.get(**f).is_some()).count() as f64 >= MIN_FEATURE_SHARE * raw.len() as f64)
+        .copied()
+        .collect();
+    if !features.contains(&Feature::Speed) {return None};
+
+    let rows: Vec<(usize, Vec<f64>)> = raw.iter().enumerate()
+        .filter_map(|(index, p)| p.vector(&features).map(|v| (index, v)))
+        .collect();
+    if rows.len() < MIN_PITCHES {return None};
+    let points: Vec<Vec<f64>> = rows.iter().map(|(_, v)| v.clone()).collect();
+
+    let n = points.len() as f64;
+    let min_cluster = (MIN_CLUSTER_SHARE * n).ceil().max(5.0) as usize;
+    let mut rng = Rng::new(SEED).fork(pitcher as u64);
+
+    // BIC with unit variance: the SSE plus a penalty for every coordinate of every centroid
+    let mut best: Option<(f64, Vec<Vec<f64>>, Vec<usize>)> = None;
+    for k in 1 ..= MAX_CLUSTERS.min(points.len() / min_cluster) {
+        let (centroids, assignments, sse) = (0 .. KMEANS_RESTARTS)
+            .map(|_| kmeans(&points, k, &mut rng))
+            .min_by(|a, b| a.2.partial_cmp(&b.2).unwrap())
+            .unwrap();
+
+        let too_small = (0 .. centroids.len()).any(|c| assignments.iter().filter(|a| **a == c).count() < min_cluster);
+        if too_small {continue};
+
+        let bic = sse + (k * features.len()) as f64 * n.ln();
+        if best.as_ref().map(|b| bic < b.0).unwrap_or(true) {best = Some((bic, centroids, assignments))};
+    }
+    let (_, centroids, assignments) = best?;
+
+    let mut clusters: Vec<PitchCluster> = centroids.into_iter().enumerate()
+        .map(|(index, centroid)| {
+            let members: Vec<usize> = rows.iter().zip(assignments.iter())
+                .filter(|(_, a)| **a == index)
+                .map(|((i, _), _)| *i)
+                .collect();
+            PitchCluster {
+                pitch_type: PitchTypeCode::UN,
+                from_tags: false,
+                pitches: members.len() as u32,
+                centroid,
+                speed: mean(members.iter().filter_map(|i| raw[*i].speed)),
+                induced_vertical_break: mean(members.iter().filter_map(|i| raw[*i].induced_vertical_break)),
+                horizontal_break: mean(members.iter().filter_map(|i| raw[*i].horizontal_break)),
+                spin_rate: mean(members.iter().filter_map(|i| raw[*i].spin_rate)),
+            }
+        })
+        .collect();
+
+    let fastball_speed = clusters.iter().filter_map(|c| c.speed).fold(0f64, f64::max);
+
+    for (index, cluster) in clusters.iter_mut().enumerate() {
+        let mut tags: Vec<(PitchTypeCode, u32)> = vec![];
+        for ((i, _), _) in rows.iter().zip(assignments.iter()).filter(|(_, a)| **a == index) {
+            if let Some(code) = pitches[*i].pitch_type_code.and_then(canonical) {
+                match tags.iter_mut().find(|(t, _)| *t == code) {
+                    Some ((_, count)) => *count += 1,
+                    None => tags.push((code, 1)),
+                }
+            }
+        }
+        let tagged: u32 = tags.iter().map(|(_, count)| count).sum();
+        // Ties go to the tag we saw first, which keeps the fit deterministic
+        let majority = tags.iter().fold(None, |best: Option<(PitchTypeCode, u32)>, (t, c)| match best {
+            Some ((_, b)) if b >= *c => best,
+            _ => Some((*t, *c)),
+        });
+
+        match majority {
+            Some ((code, _)) if tagged as f64 >= MIN_TAGGED_SHARE * cluster.pitches as f64 => {
+                cluster.pitch_type = code;
+                cluster.from_tags = true;
+            },
+            _ => cluster.pitch_type = rule(cluster, fastball_speed, pitcher_throws),
+        }
+    }
+
+    Some(PitchClassifier {
+        pitcher,
+        pitcher_throws,
+        pitches: points.len() as u32,
+        features,
+        clusters,
+    })
+}
+
+impl <'c> From<ClassificationData<'c>> for Vec<PitchClassifier> {
+    fn from (data: ClassificationData) -> Vec<PitchClassifier> {
+        let mut by_pitcher: HashMap<u32, Vec<&ClassificationPitch>> = HashMap::new();
+        for pitch in data.pitches {
+            by_pitcher.entry(pitch.pitcher).or_default().push(pitch);
+        }
+
+        let mut pitchers: Vec<(u32, Vec<&ClassificationPitch>)> = by_pitcher.into_iter()
+            .filter(|(_, pitches)| pitches.len() >= MIN_PITCHES)
+            .collect();
+        pitchers.sort_by_key(|(pitcher, _)| *pitcher);
+
+        pitchers.into_par_iter()
+            .filter_map(|(pitcher, pitches)| fit(pitcher, &pitches))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cluster (pitch_type: PitchTypeCode, speed: f64, ivb: Option<f64>, hb: Option<f64>, spin_rate: f64) -> PitchCluster {
+        PitchCluster {
+            pitch_type,
+            from_tags: false,
+            pitches: 100,
+            centroid: vec![speed / Feature::Speed.scale()],
+            speed: Some(speed),
+            induced_vertical_break: ivb,
+            horizontal_break: hb,
+            spin_rate: Some(spin_rate),
+        }
+    }
+
+    /// The rule for a right hander whose hardest pitch is 95, so arm side is negative horizontal break
+    fn rule_for (speed: f64, ivb: f64, hb: f64, spin_rate: f64) -> PitchTypeCode {
+        rule(&cluster(PitchTypeCode::UN, speed, Some(ivb), Some(hb), spin_rate), 95.0, SideCode::R)
+    }
+
+    fn classifier (clusters: Vec<PitchCluster>) -> PitchClassifier {
+        PitchClassifier {pitcher: 1, pitcher_throws: SideCode::R, pitches: 200, features: vec![Feature::Speed], clusters}
+    }
+
+    fn speed (speed: f64) -> PitchFeatures {
+        PitchFeatures {speed: Some(speed), ..PitchFeatures::default()}
+    }
+
+    #[test]
+    fn old_tags_fold_into_new () {
+        assert_eq!(canonical(PitchTypeCode::FA), Some(PitchTypeCode::FF));
+        assert_eq!(canonical(PitchTypeCode::FT), Some(PitchTypeCode::SI));
+        assert_eq!(canonical(PitchTypeCode::FO), Some(PitchTypeCode::FS));
+        assert_eq!(canonical(PitchTypeCode::SC), Some(PitchTypeCode::CH));
+        assert_eq!(canonical(PitchTypeCode::SL), Some(PitchTypeCode::SL));
+        assert_eq!(canonical(PitchTypeCode::PO), None);
+    }
+
+    #[test]
+    fn rules_for_fastballs () {
+        assert_eq!(rule_for(95.0, 16.0, -8.0, 2300.0), PitchTypeCode::FF);
+        assert_eq!(rule_for(94.0, 8.0, -14.0, 2150.0), PitchTypeCode::SI);
+        assert_eq!(rule_for(93.0, 9.0, 1.0, 2400.0), PitchTypeCode::FC);
+    }
+
+    #[test]
+    fn rules_for_offspeed () {
+        assert_eq!(rule_for(86.0, 7.0, -13.0, 1800.0), PitchTypeCode::CH);
+        assert_eq!(rule_for(86.0, 3.0, -9.0, 1200.0), PitchTypeCode::FS);
+    }
+
+    #[test]
+    fn rules_for_breaking_balls () {
+        assert_eq!(rule_for(79.0, -12.0, 7.0, 2700.0), PitchTypeCode::CU);
+        assert_eq!(rule_for(82.0, 0.0, 14.0, 2600.0), PitchTypeCode::ST);
+        assert_eq!(rule_for(86.0, 2.0, 5.0, 2500.0), PitchTypeCode::SL);
+    }
+
+    #[test]
+    fn rules_for_slow_pitches () {
+        assert_eq!(rule_for(55.0, 0.0, 0.0, 1000.0), PitchTypeCode::EP);
+        assert_eq!(rule_for(76.0, 0.0, 0.0, 400.0), PitchTypeCode::KN);
+    }
+
+    #[test]
+    fn rules_without_breaks () {
+        let fastball = cluster(PitchTypeCode::UN, 93.0, None, None, 2300.0);
+        let slower = cluster(PitchTypeCode::UN, 85.0, None, None, 2300.0);
+        assert_eq!(rule(&fastball, 95.0, SideCode::R), PitchTypeCode::FF);
+        assert_eq!(rule(&slower, 95.0, SideCode::R), PitchTypeCode::UN);
+    }
+
+    #[test]
+    fn confidence_split_between_clusters () {
+        // 93 is the same distance from both centres
+        let classifier = classifier(vec![cluster(PitchTypeCode::FF, 96.0, None, None, 0.0), cluster(PitchTypeCode::SI, 90.0, None, None, 0.0)]);
+        assert_eq!(classifier.classify(&speed(93.0)).unwrap().1, 0.5);
+    }
+
+    #[test]
+    fn confidence_adds_up_by_pitch_type () {
+        let classifier = classifier(vec![cluster(PitchTypeCode::FF, 96.0, None, None, 0.0), cluster(PitchTypeCode::FF, 90.0, None, None, 0.0)]);
+        assert_eq!(classifier.classify(&speed(93.0)), Some((PitchTypeCode::FF, 1.0)));
+    }
+
+    #[test]
+    fn no_speed_no_pitch_type () {
+        let classifier = classifier(vec![cluster(PitchTypeCode::FF, 96.0, None, None, 0.0)]);
+        assert_eq!(classifier.classify(&PitchFeatures::default()), None);
+    }
+
+    #[test]
+    fn kmeans_two_groups () {
+        let points = vec![vec![0.0], vec![1.0], vec![10.0], vec![11.0]];
+        let (mut centroids, _, sse) = kmeans(&points, 2, &mut Rng::new(SEED));
+        centroids.sort_by(|a, b| a[0].partial_cmp(&b[0]).unwrap());
+
+        assert_eq!(centroids, vec![vec![0.5], vec![10.5]]);
+        assert_eq!(sse, 1.0);
+    }
+
+    fn pitch (index: usize, speed: f64, ivb: f64, hb: f64, spin_rate: f64, tag: Option<PitchTypeCode>) -> ClassificationPitch {
+        // A little deterministic noise, well inside the feature scales
+        let noise = ((index * 7919) % 11) as f64 / 10.0 - 0.5;
+        ClassificationPitch {
+            pitcher: 1,
+            pitcher_throws: SideCode::R,
+            pitch_type_code: tag,
+            pitch_speed_start: Some(speed + noise),
+            pitch_break_vertical_induced: None,
+            pitch_break_horizontal: None,
+            pitch_spin_rate: Some(spin_rate + noise * 50.0),
+            pitch_spin_direction: None,
+            xr: Some(-2.0),
+            zr: Some(6.0),
+            ivb: Some(ivb + noise),
+            hb: Some(hb - noise),
+        }
+    }
+
+    /// 120 four seamers and 60 curveballs from a right hander
+    fn fastball_curveball (tag: Option<PitchTypeCode>) -> Vec<ClassificationPitch> {
+        let mut pitches: Vec<ClassificationPitch> = (0 .. 120).map(|i| pitch(i, 95.0, 16.0, -8.0, 2300.0, tag)).collect();
+        pitches.extend((120 .. 180).map(|i| pitch(i, 79.0, -12.0, 7.0, 2700.0, None)));
+        pitches
+    }
+
+    #[test]
+    fn fit_finds_the_arsenal () {
+        let pitches = fastball_curveball(None);
+        let classifiers: Vec<PitchClassifier> = ClassificationData {pitches: &pitches}.into();
+        let clusters: Vec<(PitchTypeCode, u32)> = classifiers[0].clusters.iter().map(|c| (c.pitch_type, c.pitches)).collect();
+
+        assert_eq!(clusters.len(), 2);
+        assert!(clusters.contains(&(PitchTypeCode::FF, 120)));
+        assert!(clusters.contains(&(PitchTypeCode::CU, 60)));
+    }
+
+    #[test]
+    fn tags_win_over_rules () {
+        // MLB calls the hard one a two seamer
+        let pitches = fastball_curveball(Some(PitchTypeCode::FT));
+        let classifiers: Vec<PitchClassifier> = ClassificationData {pitches: &pitches}.into();
+        let fastball = classifiers[0].clusters.iter().find(|c| c.from_tags).unwrap();
+
+        assert_eq!(fastball.pitch_type, PitchTypeCode::SI);
+        assert_eq!(fastball.pitches, 120);
+    }
+
+    #[test]
+    fn too_few_pitches () {
+        let pitches: Vec<ClassificationPitch> = (0 .. MIN_PITCHES - 1).map(|i| pitch(i, 95.0, 16.0, -8.0, 2300.0, None)).collect();
+        let classifiers: Vec<PitchClassifier> = ClassificationData {pitches: &pitches}.into();
+        assert!(classifiers.is_empty());
+    }
+}
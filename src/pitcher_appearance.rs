@@ -0,0 +1,416 @@
+//! Pitcher appearances, with one row per pitcher per game. This is the pitcher's game line, built up as we walk through the game
+//! in the game module, along with the context of when they entered and left the game.
+//!
+//! A few notes on how we assign credit:
+//!   * Pitches, batters faced and plate appearance results are credited to the pitcher in the matchup for that plate appearance,
+//!     the same as the pitcher on each Pitch row.
+//!   * Runs are charged to the pitcher who put the runner on base. We don't apply the fielder's choice rule that moves
+//!     responsibility for an inherited runner onto the batter who replaced them.
+//!   * Days of rest come from the pitcher appearances table, filled in with the boxscores of games we haven't walked yet, so they're
+//!     only as complete as the years we've pulled.
+//!   * A save situation is checked when the reliever enters: the pitching team is ahead and either the lead is 3 runs or less, or
+//!     the tying run is on base, at bat or on deck. We don't know yet if they'll go 3+ innings, so that part of the rule is ignored.
+//!     A save situation is also a hold situation, they're only different in how the appearance ends.
+
+use serde::{Serialize, Deserialize};
+use std::collections::HashMap;
+use crate::game::{PitchResult, PitcherSPRP};
+use crate::events::is_automatic_call;
+use crate::play_by_play::{Code, Event, HalfInning, RunnerData};
+use crate::schedule::GameDate;
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct PitcherAppearance {
+    pub game_pk: u32,
+    pub game_date: String,
+    pub sport_id: u32,
+
+    pub pitcher: u32,
+    pub pitcher_name: String,
+    pub pitcher_team_id: u32,
+    pub pitcher_sp_rp: PitcherSPRP,
+    /// The order the pitcher entered the game for their team, the starter is 1
+    pub pitcher_num_appearance_game: u8,
+    pub days_rest: Option<u16>,
+
+    pub entry_half_inning: HalfInning,
+    pub entry_inning: u8,
+    pub entry_outs: u8,
+    pub entry_base_value: u8,
+    /// Runs the pitcher's team was leading by when they entered, negative if trailing
+    pub entry_lead: i16,
+    pub exit_inning: u8,
+    pub exit_outs: u8,
+
+    pub inherited_runners: u8,
+    pub inherited_runners_scored: u8,
+    pub bequeathed_runners: u8,
+    pub bequeathed_runners_scored: u8,
+
+    pub pitches: u16,
+    pub strikes: u16,
+    pub batters_faced: u16,
+    pub outs_recorded: u16,
+    pub hits: u16,
+    pub home_runs: u16,
+    pub walks: u16,
+    pub strikeouts: u16,
+    pub runs: u16,
+    pub earned_runs: u16,
+
+    pub save_situation: bool,
+    pub finished_game: bool,
+}
+
+/// The columns from the pitcher appearances table we need for days of rest. The date comes from the schedule.
+#[derive(Debug, Clone, Deserialize)]
+pub struct AppearanceGame {
+    pub game_pk: u32,
+    pub pitcher: u32,
+}
+
+/// The game state at the start of a plate appearance, used to open a new appearance when the pitcher changes.
+pub (crate) struct PlateAppearanceStart {
+    pub (crate) pitcher: u32,
+    pub (crate) pitcher_name: String,
+    pub (crate) pitcher_team_id: u32,
+    pub (crate) half_inning: HalfInning,
+    pub (crate) num_inning: u8,
+    pub (crate) outs: u8,
+    pub (crate) base_value: u8,
+    pub (crate) runners_on: Vec<u32>,
+    pub (crate) lead: i16,
+    pub (crate) result: Option<Event>,
+}
+
+pub (crate) struct AppearanceTracker <'t> {
+    game_pk: u32,
+    game_date: GameDate,
+    sport_id: u32,
+    appearance_dates: &'t HashMap<u32, Vec<(GameDate, u32)>>,
+    appearances: Vec<PitcherAppearance>,
+    // Index into appearances for the pitcher currently in the game for the (home, away) team
+    current: (Option<usize>, Option<usize>),
+    // Index into appearances for the pitcher responsible for each runner on base
+    responsible: HashMap<u32, usize>,
+}
+
+impl <'t> AppearanceTracker <'t> {
+
+    pub (crate) fn new (game_pk: u32, game_date: GameDate, sport_id: u32, appearance_dates: &'t HashMap<u32, Vec<(GameDate, u32)>>) -> Self {
+        AppearanceTracker {
+            game_pk,
+            game_date,
+            sport_id,
+            appearance_dates,
+            appearances: vec![],
+            current: (None, None),
+            responsible: HashMap::new(),
+        }
+    }
+
+    // The home team is in the field in the top of the inning
+    fn current (&mut self, half_inning: HalfInning) -> &mut Option<usize> {
+        match half_inning {
+            HalfInning::Top => &mut self.current.0,
+            HalfInning::Bottom => &mut self.current.1,
+        }
+    }
+
+    fn current_appearance (&mut self, half_inning: HalfInning) -> Option<&mut PitcherAppearance> {
+        match *self.current(half_inning) {
+            Some (index) => self.appearances.get_mut(index),
+            None => None,
+        }
+    }
+
+    pub (crate) fn new_half_inning (&mut self) {
+        self.responsible.clear();
+    }
+
+    /// Credits the plate appearance to the pitcher, opening a new appearance if they just came into the game.
+    pub (crate) fn start_plate_appearance (&mut self, pa: PlateAppearanceStart) {
+
+        let previous = *self.current(pa.half_inning);
+
+        let index = match previous {
+            Some (index) if self.appearances[index].pitcher == pa.pitcher => index,
+            _ => {
+                let pitcher_num_appearance_game = match previous {
+                    Some (index) => {
+                        let bequeathed_runners = pa.runners_on.iter()
+                            .filter(|runner| self.responsible.get(runner) == Some(&index))
+                            .count() as u8;
+                        self.appearances[index].bequeathed_runners = bequeathed_runners;
+                        self.appearances[index].pitcher_num_appearance_game + 1
+                    },
+                    None => 1,
+                };
+
+                let pitcher_sp_rp = if pitcher_num_appearance_game == 1 {PitcherSPRP::SP} else {PitcherSPRP::RP};
+                let runners_on = pa.runners_on.len() as u8;
+
+                let save_situation = match pitcher_sp_rp {
+                    PitcherSPRP::SP => false,
+                    PitcherSPRP::RP => pa.lead > 0 && (pa.lead <= 3 || pa.lead <= runners_on as i16 + 2),
+                };
+
+                self.appearances.push(
+                    PitcherAppearance {
+                        game_pk: self.game_pk,
+                        game_date: self.game_date.to_string(),
+                        sport_id: self.sport_id,
+                        pitcher: pa.pitcher,
+                        pitcher_name: pa.pitcher_name,
+                        pitcher_team_id: pa.pitcher_team_id,
+                        pitcher_sp_rp,
+                        pitcher_num_appearance_game,
+                        days_rest: days_rest(self.appearance_dates.get(&pa.pitcher), self.game_date, self.game_pk),
+                        entry_half_inning: pa.half_inning,
+                        entry_inning: pa.num_inning,
+                        entry_outs: pa.outs,
+                        entry_base_value: pa.base_value,
+                        entry_lead: pa.lead,
+                        exit_inning: pa.num_inning,
+                        exit_outs: pa.outs,
+                        inherited_runners: runners_on,
+                        inherited_runners_scored: 0,
+                        bequeathed_runners: 0,
+                        bequeathed_runners_scored: 0,
+                        pitches: 0,
+                        strikes: 0,
+                        batters_faced: 0,
+                        outs_recorded: 0,
+                        hits: 0,
+                        home_runs: 0,
+                        walks: 0,
+                        strikeouts: 0,
+                        runs: 0,
+                        earned_runs: 0,
+                        save_situation,
+                        finished_game: false,
+                    }
+                );
+
+                let index = self.appearances.len() - 1;
+                *self.current(pa.half_inning) = Some(index);
+                index
+            }
+        };
+
+        let appearance = &mut self.appearances[index];
+        appearance.batters_faced += 1;
+
+        match pa.result {
+            Some(Event::Single) | Some(Event::Double) | Some(Event::Triple) => {appearance.hits += 1;},
+            Some(Event::HomeRun) => {appearance.hits += 1; appearance.home_runs += 1;},
+            Some(Event::Walk) | Some(Event::IntentionalWalk) => {appearance.walks += 1;},
+            Some(Event::StrikeOut) => {appearance.strikeouts += 1;},
+            _ => {},
+        }
+    }
+
+    /// Counts the pitch. No pitches, pickoffs and pitch clock automatic balls and strikes aren't pitches, and anything that isn't
+    /// a ball counts as a strike.
+    pub (crate) fn pitch (&mut self, half_inning: HalfInning, code: Code, pitch_result: PitchResult) {
+        if pitch_result == PitchResult::NoPitch || is_automatic_call(code) {return};
+        if let Some(appearance) = self.current_appearance(half_inning) {
+            appearance.pitches += 1;
+            if !matches!(pitch_result, PitchResult::Ball | PitchResult::HitByPitch) {appearance.strikes += 1};
+        }
+    }
+
+    /// Charges outs and runs from the runner data to the responsible pitchers. Any runner we haven't seen before is the
+    /// responsibility of the pitcher currently in the game.
+    pub (crate) fn runners <'r> (&mut self, half_inning: HalfInning, runner_data: impl Iterator<Item = &'r RunnerData>, num_inning: u8, outs: u8) {
+
+        let current = match *self.current(half_inning) {
+            Some (index) => index,
+            None => return,
+        };
+
+        for runner in runner_data {
+            let responsible = *self.responsible.entry(runner.runner_id).or_insert(current);
+
+            self.appearances[current].outs_recorded += runner.outs as u16;
+
+            if runner.runs > 0 {
+                self.appearances[responsible].runs += runner.runs as u16;
+                if runner.earned {self.appearances[responsible].earned_runs += runner.runs as u16};
+                if responsible != current {
+                    self.appearances[current].inherited_runners_scored += runner.runs;
+                    self.appearances[responsible].bequeathed_runners_scored += runner.runs;
+                }
+            }
+
+            // Once a runner is off the bases, they'll be a new runner the next time they reach
+            if runner.end_base_value == 0 {
+                self.responsible.remove(&runner.runner_id);
+            }
+        }
+
+        self.appearances[current].exit_inning = num_inning;
+        self.appearances[current].exit_outs = outs;
+    }
+
+    pub (crate) fn finish (mut self) -> Vec<PitcherAppearance> {
+        for index in [self.current.0, self.current.1].into_iter().flatten() {
+            self.appearances[index].finished_game = true;
+        }
+        self.appearances
+    }
+}
+
+/// Days between the pitcher's most recent appearance before this game and this game, less one. Pitching on back to back days
+/// is 0 days of rest, as is the second game of a doubleheader. Games on the same day are ordered by game_pk, since we don't have
+/// start times for every game in the schedule.
+fn days_rest (appearance_dates: Option<&Vec<(GameDate, u32)>>, game_date: GameDate, game_pk: u32) -> Option<u16> {
+    let (previous, _) = appearance_dates?.iter()
+        .filter(|appearance| **appearance < (game_date, game_pk))
+        .max()?
+        ;
+
+    Some((game_date.days_since_epoch() - previous.days_since_epoch() - 1).max(0) as u16)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn date (day: u8) -> GameDate {
+        GameDate {year: 2021, month: 4, day}
+    }
+
+    #[test]
+    fn back_to_back_is_no_rest () {
+        assert_eq!(days_rest(Some(&vec![(date(9), 1)]), date(10), 2), Some(0));
+    }
+
+    #[test]
+    fn days_rest_from_the_latest_earlier_game () {
+        let appearances = vec![(date(2), 1), (date(5), 2), (date(12), 4)];
+        assert_eq!(days_rest(Some(&appearances), date(10), 3), Some(4));
+    }
+
+    #[test]
+    fn doubleheaders_ordered_by_game_pk () {
+        let appearances = vec![(date(10), 5)];
+        assert_eq!(days_rest(Some(&appearances), date(10), 6), Some(0));
+        assert_eq!(days_rest(Some(&appearances), date(10), 4), None);
+    }
+
+    #[test]
+    fn no_appearances_no_rest () {
+        assert_eq!(days_rest(None, date(10), 1), None);
+        assert_eq!(days_rest(Some(&vec![]), date(10), 1), None);
+    }
+
+    /// A plate appearance in the top of the first, with the home team pitching.
+    fn plate_appearance (pitcher: u32, lead: i16, runners_on: Vec<u32>, result: Event) -> PlateAppearanceStart {
+        PlateAppearanceStart {
+            pitcher,
+            pitcher_name: String::new(),
+            pitcher_team_id: 1,
+            half_inning: HalfInning::Top,
+            num_inning: 1,
+            outs: 0,
+            base_value: 0,
+            runners_on,
+            lead,
+            result: Some(result),
+        }
+    }
+
+    fn runner (runner_id: u32, end_base_value: u8, runs: u8) -> RunnerData {
+        RunnerData {
+            runner_id,
+            start_base_value: 0,
+            end_base_value,
+            runs,
+            event: Event::Single,
+            rbi: false,
+            earned: true,
+            play_index: 0,
+            outs: 0,
+            fielded_by_pos: None,
+            fielded_by_id: None,
+        }
+    }
+
+    #[test]
+    fn pitches_and_strikes () {
+        let appearance_dates = HashMap::new();
+        let mut tracker = AppearanceTracker::new(1, date(10), 1, &appearance_dates);
+        tracker.start_plate_appearance(plate_appearance(10, 0, vec![], Event::Walk));
+        let pitches = [
+            (Code::B, PitchResult::Ball),
+            (Code::C, PitchResult::CalledStrike),
+            (Code::F, PitchResult::Foul),
+            (Code::N, PitchResult::NoPitch),
+            (Code::H, PitchResult::HitByPitch),
+        ];
+        for (code, pitch_result) in pitches {
+            tracker.pitch(HalfInning::Top, code, pitch_result);
+        }
+        let appearances = tracker.finish();
+
+        assert_eq!(appearances[0].pitches, 4);
+        assert_eq!(appearances[0].strikes, 2);
+        assert_eq!(appearances[0].walks, 1);
+    }
+
+    #[test]
+    fn automatic_calls_are_not_pitches () {
+        let appearance_dates = HashMap::new();
+        let mut tracker = AppearanceTracker::new(1, date(10), 1, &appearance_dates);
+        tracker.start_plate_appearance(plate_appearance(10, 0, vec![], Event::StrikeOut));
+        tracker.pitch(HalfInning::Top, Code::V, PitchResult::Ball);
+        tracker.pitch(HalfInning::Top, Code::A, PitchResult::CalledStrike);
+        tracker.pitch(HalfInning::Top, Code::S, PitchResult::SwingingStrike);
+        let appearances = tracker.finish();
+
+        assert_eq!(appearances[0].pitches, 1);
+        assert_eq!(appearances[0].strikes, 1);
+    }
+
+    #[test]
+    fn save_situation_on_entry () {
+        let appearance_dates = HashMap::new();
+        let mut tracker = AppearanceTracker::new(1, date(10), 1, &appearance_dates);
+        tracker.start_plate_appearance(plate_appearance(10, 3, vec![], Event::FlyOut));
+        tracker.start_plate_appearance(plate_appearance(11, 3, vec![], Event::FlyOut));
+        tracker.start_plate_appearance(plate_appearance(12, 5, vec![100, 101], Event::FlyOut));
+        tracker.start_plate_appearance(plate_appearance(13, 5, vec![100, 101, 102], Event::FlyOut));
+        tracker.start_plate_appearance(plate_appearance(14, 0, vec![100, 101, 102], Event::FlyOut));
+        let save_situations: Vec<bool> = tracker.finish().iter().map(|appearance| appearance.save_situation).collect();
+
+        assert_eq!(save_situations, vec![false, true, false, true, false]);
+    }
+
+    #[test]
+    fn inherited_runner_charged_to_the_starter () {
+        let appearance_dates = HashMap::new();
+        let mut tracker = AppearanceTracker::new(1, date(10), 1, &appearance_dates);
+
+        tracker.start_plate_appearance(plate_appearance(10, 2, vec![], Event::Single));
+        tracker.runners(HalfInning::Top, [runner(100, 1, 0)].iter(), 1, 0);
+        tracker.start_plate_appearance(plate_appearance(11, 2, vec![100], Event::HomeRun));
+        tracker.runners(HalfInning::Top, [runner(100, 0, 1), runner(101, 0, 1)].iter(), 1, 0);
+        let appearances = tracker.finish();
+
+        let starter = &appearances[0];
+        assert!(matches!(starter.pitcher_sp_rp, PitcherSPRP::SP));
+        assert_eq!(starter.hits, 1);
+        assert_eq!((starter.runs, starter.earned_runs), (1, 1));
+        assert_eq!((starter.bequeathed_runners, starter.bequeathed_runners_scored), (1, 1));
+        assert!(!starter.finished_game);
+
+        let reliever = &appearances[1];
+        assert!(matches!(reliever.pitcher_sp_rp, PitcherSPRP::RP));
+        assert_eq!(reliever.pitcher_num_appearance_game, 2);
+        assert_eq!((reliever.hits, reliever.home_runs), (1, 1));
+        assert_eq!((reliever.runs, reliever.earned_runs), (1, 1));
+        assert_eq!((reliever.inherited_runners, reliever.inherited_runners_scored), (1, 1));
+        assert!(reliever.finished_game);
+    }
+}
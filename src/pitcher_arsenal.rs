@@ -0,0 +1,595 @@
+//! Pitcher arsenals: one row per pitcher, sport_id, season and pitch type, built from the play by play output.
+//!
+//! The pitch type is the MLB tag, folded into the canonical types, or our inferred type when the pitch wasn't tagged. Usage is
+//! the share of the pitcher's pitches in each split, so the usage columns for a split add up to 1 across the pitcher's rows.
+//! Movement and release are from the nathan module, with the API breaks as a fallback, in inches and feet from the catcher's view.
+//!
+//! The rates:
+//!   * Whiff rate is swings and misses over swings.
+//!   * Chase rate is swings at pitches out of the zone over pitches out of the zone.
+//!   * Called strike rate is called strikes over all pitches, zone rate is pitches in the zone over pitches with a location.
+//!
+//! Change detection compares two windows of a pitcher's arsenal and flags the shifts that are both big enough to matter and
+//! statistically significant. By default the windows are consecutive seasons at the same level, but detect_changes will take any
+//! two arsenals, so you can build arsenals from the first and second half of a season, or the last month, and compare those.
+
+use serde::{Serialize, Deserialize};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use crate::game::PitchResult;
+use crate::linear_weights::ratio;
+use crate::pitch_classification::canonical;
+use crate::play_by_play::{PitchTypeCode, SideCode};
+
+/// Half the plate plus the radius of the ball, in feet. A pitch that clips the edge is in the zone.
+pub const ZONE_HALF_WIDTH: f64 = 17.0 / 24.0 + BALL_RADIUS;
+pub const BALL_RADIUS: f64 = 1.45 / 12.0;
+
+/// A pitch type needs this many pitches in both windows before we compare its shape
+const MIN_CHANGE_PITCHES: u32 = 30;
+/// Shifts are only flagged if they're at least this many standard errors
+const MIN_Z_SCORE: f64 = 3.0;
+/// The smallest shifts we care about, even when they're significant
+const MIN_VELOCITY_CHANGE: f64 = 1.0;
+const MIN_SPIN_RATE_CHANGE: f64 = 100.0;
+const MIN_RELEASE_CHANGE: f64 = 0.25;
+/// A pitch is new if it's at least this share of the pitches after, and under NEW_PITCH_MAX_USAGE before
+const NEW_PITCH_MIN_USAGE: f64 = 0.05;
+const NEW_PITCH_MAX_USAGE: f64 = 0.01;
+
+pub (crate) fn in_zone (p_x: f64, p_z: f64, strike_zone_top: f64, strike_zone_bottom: f64) -> bool {
+    p_x.abs() <= ZONE_HALF_WIDTH && p_z <= strike_zone_top + BALL_RADIUS && p_z >= strike_zone_bottom - BALL_RADIUS
+}
+
+/// The columns from the play by play output we need for arsenals.
+#[derive(Debug, Clone, Deserialize)]
+pub struct ArsenalPitch {
+    pub sport_id: u32,
+    pub game_year: u16,
+    pub pitcher: u32,
+    pub pitcher_name: String,
+    pub pitcher_throws: SideCode,
+    pub batter_bats: SideCode,
+    pub balls_start: u8,
+    pub strikes_start: u8,
+    pub pitch_result: PitchResult,
+    pub swing: u8,
+    pub swing_and_miss: Option<u8>,
+    pub strike_zone_top: f32,
+    pub strike_zone_bottom: f32,
+    pub pitch_p_x: Option<f64>,
+    pub pitch_p_z: Option<f64>,
+    pub pitch_speed_start: Option<f64>,
+    pub pitch_spin_rate: Option<f64>,
+    pub pitch_break_vertical_induced: Option<f64>,
+    pub pitch_break_horizontal: Option<f64>,
+    pub pitch_extension: Option<f64>,
+    pub pitch_type_code: Option<PitchTypeCode>,
+    pub pitch_type_inferred: Option<PitchTypeCode>,
+    pub xr: Option<f64>,
+    pub zr: Option<f64>,
+    pub ivb: Option<f64>,
+    pub hb: Option<f64>,
+}
+
+pub struct ArsenalData <'a> {
+    pub pitches: &'a [ArsenalPitch],
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PitcherArsenal {
+    pub pitcher: u32,
+    pub pitcher_name: String,
+    pub pitcher_throws: SideCode,
+    pub sport_id: u32,
+    pub season: u16,
+    pub pitch_type: PitchTypeCode,
+    pub pitches: u32,
+
+    pub usage: f32,
+    pub usage_vs_left: f32,
+    pub usage_vs_right: f32,
+    pub usage_first_pitch: f32,
+    /// Pitcher ahead in the count, more strikes than balls
+    pub usage_ahead: f32,
+    pub usage_even: f32,
+    pub usage_behind: f32,
+    pub usage_two_strikes: f32,
+
+    pub speed: Option<f32>,
+    pub speed_sd: Option<f32>,
+    pub speed_p10: Option<f32>,
+    pub speed_p50: Option<f32>,
+    pub speed_p90: Option<f32>,
+    pub speed_max: Option<f32>,
+    /// Pitches with a speed, the n for the speed columns
+    pub speed_pitches: u32,
+    pub spin_rate: Option<f32>,
+    pub spin_rate_sd: Option<f32>,
+    pub spin_rate_pitches: u32,
+    pub induced_vertical_break: Option<f32>,
+    pub horizontal_break: Option<f32>,
+    pub release_side: Option<f32>,
+    pub release_side_sd: Option<f32>,
+    pub release_height: Option<f32>,
+    pub release_height_sd: Option<f32>,
+    pub release_pitches: u32,
+    pub extension: Option<f32>,
+
+    pub whiff_rate: f32,
+    pub chase_rate: f32,
+    pub called_strike_rate: f32,
+    pub zone_rate: f32,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ArsenalChangeType {
+    NewPitch,
+    DroppedPitch,
+    VelocityDrop,
+    VelocityGain,
+    SpinRateDrop,
+    ArmSlotChange,
+}
+
+/// A flagged shift between two windows. For arm slot changes, before and after are the distance the release point moved, in feet.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ArsenalChange {
+    pub pitcher: u32,
+    pub pitcher_name: String,
+    pub sport_id: u32,
+    pub season_before: u16,
+    pub season_after: u16,
+    pub pitch_type: PitchTypeCode,
+    pub change: ArsenalChangeType,
+    pub before: Option<f32>,
+    pub after: Option<f32>,
+    pub z_score: f32,
+}
+
+/// Running count, sum and sum of squares, so we can get the mean and standard deviation in one pass
+#[derive(Debug, Default, Copy, Clone)]
+struct Moments {
+    n: u32,
+    sum: f64,
+    sum_squares: f64,
+}
+
+impl Moments {
+    fn add (&mut self, value: Option<f64>) {
+        if let Some(value) = value {
+            self.n += 1;
+            self.sum += value;
+            self.sum_squares += value * value;
+        }
+    }
+
+    fn mean (&self) -> Option<f32> {
+        if self.n == 0 {None} else {Some((self.sum / self.n as f64) as f32)}
+    }
+
+    fn sd (&self) -> Option<f32> {
+        if self.n < 2 {return None};
+        let mean = self.sum / self.n as f64;
+        Some((((self.sum_squares - self.n as f64 * mean * mean) / (self.n - 1) as f64).max(0.0)).sqrt() as f32)
+    }
+}
+
+/// Pitch counts for the usage splits
+#[derive(Debug, Default, Copy, Clone)]
+struct Splits {
+    all: u32,
+    vs_left: u32,
+    vs_right: u32,
+    first_pitch: u32,
+    ahead: u32,
+    even: u32,
+    behind: u32,
+    two_strikes: u32,
+}
+
+impl Splits {
+    fn add (&mut self, pitch: &ArsenalPitch) {
+        self.all += 1;
+        match pitch.batter_bats {
+            SideCode::L => self.vs_left += 1,
+            SideCode::R => self.vs_right += 1,
+            _ => {},
+        }
+        if pitch.balls_start == 0 && pitch.strikes_start == 0 {self.first_pitch += 1};
+        match pitch.strikes_start.cmp(&pitch.balls_start) {
+            std::cmp::Ordering::Greater => self.ahead += 1,
+            std::cmp::Ordering::Equal => self.even += 1,
+            std::cmp::Ordering::Less => self.behind += 1,
+        }
+        if pitch.strikes_start == 2 {self.two_strikes += 1};
+    }
+}
+
+#[derive(Debug, Default, Clone)]
+struct PitchTypeTotals {
+    splits: Splits,
+    speeds: Vec<f64>,
+    speed: Moments,
+    spin_rate: Moments,
+    induced_vertical_break: Moments,
+    horizontal_break: Moments,
+    release_side: Moments,
+    release_height: Moments,
+    extension: Moments,
+    swings: u32,
+    whiffs: u32,
+    out_of_zone: u32,
+    chases: u32,
+    located: u32,
+    in_zone: u32,
+    called_strikes: u32,
+}
+
+/// Linear interpolation between the closest ranks of a sorted slice
+fn percentile (sorted: &[f64], p: f64) -> Option<f32> {
+    if sorted.is_empty() {return None};
+    let rank = p * (sorted.len() - 1) as f64;
+    let (low, high) = (rank.floor() as usize, rank.ceil() as usize);
+    Some((sorted[low] + (sorted[high] - sorted[low]) * (rank - low as f64)) as f32)
+}
+
+/// The pitch type we aggregate on: the MLB tag if there is one, then ours
+fn pitch_type (pitch: &ArsenalPitch) -> Option<PitchTypeCode> {
+    pitch.pitch_type_code.and_then(canonical).or(pitch.pitch_type_inferred)
+}
+
+type PitcherKey = (u32, u32, u16);
+
+impl <'a> From<ArsenalData<'a>> for Vec<PitcherArsenal> {
+    fn from (data: ArsenalData) -> Vec<PitcherArsenal> {
+
+        let mut pitchers: HashMap<PitcherKey, (&ArsenalPitch, Splits)> = HashMap::new();
+        let mut totals: HashMap<(PitcherKey, PitchTypeCode), PitchTypeTotals> = HashMap::new();
+
+        for pitch in data.pitches {
+            if pitch.pitch_result == PitchResult::NoPitch {continue};
+            let pitch_type = match pitch_type(pitch) {
+                Some (pitch_type) => pitch_type,
+                None => continue,
+            };
+
+            let key = (pitch.pitcher, pitch.sport_id, pitch.game_year);
+            pitchers.entry(key).or_insert((pitch, Splits::default())).1.add(pitch);
+
+            let t = totals.entry((key, pitch_type)).or_default();
+            t.splits.add(pitch);
+            if let Some(speed) = pitch.pitch_speed_start {t.speeds.push(speed)};
+            t.speed.add(pitch.pitch_speed_start);
+            t.spin_rate.add(pitch.pitch_spin_rate);
+            t.induced_vertical_break.add(pitch.ivb.or(pitch.pitch_break_vertical_induced));
+            t.horizontal_break.add(pitch.hb.or(pitch.pitch_break_horizontal));
+            t.release_side.add(pitch.xr);
+            t.release_height.add(pitch.zr);
+            t.extension.add(pitch.pitch_extension);
+
+            if pitch.swing == 1 {
+                t.swings += 1;
+                if pitch.swing_and_miss == Some(1) {t.whiffs += 1};
+            }
+            if pitch.pitch_result == PitchResult::CalledStrike {t.called_strikes += 1};
+            if let (Some (p_x), Some (p_z)) = (pitch.pitch_p_x, pitch.pitch_p_z) {
+                t.located += 1;
+                if in_zone(p_x, p_z, pitch.strike_zone_top as f64, pitch.strike_zone_bottom as f64) {
+                    t.in_zone += 1;
+                }
+                else {
+                    t.out_of_zone += 1;
+                    if pitch.swing == 1 {t.chases += 1};
+                }
+            }
+        }
+
+        // Sorted so the output is always in the same order
+        let totals: BTreeMap<(PitcherKey, String), (PitchTypeCode, PitchTypeTotals)> = totals.into_iter()
+            .map(|((key, pitch_type), t)| ((key, format!("{:?}", pitch_type)), (pitch_type, t)))
+            .collect();
+
+        totals.into_iter()
+            .map(|(((pitcher, sport_id, season), _), (pitch_type, mut t))| {
+                let (first, all) = pitchers[&(pitcher, sport_id, season)];
+                let usage = |n: u32, d: u32| ratio(n as f64, d as f64) as f32;
+                t.speeds.sort_by(|a, b| a.partial_cmp(b).unwrap());
+
+                PitcherArsenal {
+                    pitcher,
+                    pitcher_name: first.pitcher_name.clone(),
+                    pitcher_throws: first.pitcher_throws,
+                    sport_id,
+                    season,
+                    pitch_type,
+                    pitches: t.splits.all,
+                    usage: usage(t.splits.all, all.all),
+                    usage_vs_left: usage(t.splits.vs_left, all.vs_left),
+                    usage_vs_right: usage(t.splits.vs_right, all.vs_right),
+                    usage_first_pitch: usage(t.splits.first_pitch, all.first_pitch),
+                    usage_ahead: usage(t.splits.ahead, all.ahead),
+                    usage_even: usage(t.splits.even, all.even),
+                    usage_behind: usage(t.splits.behind, all.behind),
+                    usage_two_strikes: usage(t.splits.two_strikes, all.two_strikes),
+                    speed: t.speed.mean(),
+                    speed_sd: t.speed.sd(),
+                    speed_p10: percentile(&t.speeds, 0.1),
+                    speed_p50: percentile(&t.speeds, 0.5),
+                    speed_p90: percentile(&t.speeds, 0.9),
+                    speed_max: t.speeds.last().map(|s| *s as f32),
+                    speed_pitches: t.speed.n,
+                    spin_rate: t.spin_rate.mean(),
+                    spin_rate_sd: t.spin_rate.sd(),
+                    spin_rate_pitches: t.spin_rate.n,
+                    induced_vertical_break: t.induced_vertical_break.mean(),
+                    horizontal_break: t.horizontal_break.mean(),
+                    release_side: t.release_side.mean(),
+                    release_side_sd: t.release_side.sd(),
+                    release_height: t.release_height.mean(),
+                    release_height_sd: t.release_height.sd(),
+                    release_pitches: t.release_side.n.min(t.release_height.n),
+                    extension: t.extension.mean(),
+                    whiff_rate: usage(t.whiffs, t.swings),
+                    chase_rate: usage(t.chases, t.out_of_zone),
+                    called_strike_rate: usage(t.called_strikes, t.splits.all),
+                    zone_rate: usage(t.in_zone, t.located),
+                }
+            })
+            .collect()
+    }
+}
+
+/// Welch's z for the difference in two means. None if either side is missing or has no spread.
+fn welch (before: (Option<f32>, Option<f32>, u32), after: (Option<f32>, Option<f32>, u32)) -> Option<(f64, f64, f64)> {
+    let (m1, s1, n1) = (before.0? as f64, before.1? as f64, before.2 as f64);
+    let (m2, s2, n2) = (after.0? as f64, after.1? as f64, after.2 as f64);
+    if n1 < MIN_CHANGE_PITCHES as f64 || n2 < MIN_CHANGE_PITCHES as f64 {return None};
+    let se = (s1 * s1 / n1 + s2 * s2 / n2).sqrt();
+    if se <= 0.0 {return None};
+    Some((m1, m2, (m2 - m1) / se))
+}
+
+/// Flags the shifts between two windows of one pitcher's arsenal at one level. Usage is compared with a two proportion z-test,
+/// velocity, spin and release with Welch's z.
+pub fn detect_changes (before: &[&PitcherArsenal], after: &[&PitcherArsenal]) -> Vec<ArsenalChange> {
+    let (first, last) = match (before.first(), after.first()) {
+        (Some (first), Some (last)) => (first, last),
+        _ => return vec![],
+    };
+    let before_pitches: u32 = before.iter().map(|a| a.pitches).sum();
+    let after_pitches: u32 = after.iter().map(|a| a.pitches).sum();
+
+    let mut changes = vec![];
+    let mut flag = |pitch_type: PitchTypeCode, change: ArsenalChangeType, before: Option<f64>, after: Option<f64>, z_score: f64| {
+        changes.push(ArsenalChange {
+            pitcher: last.pitcher,
+            pitcher_name: last.pitcher_name.clone(),
+            sport_id: last.sport_id,
+            season_before: first.season,
+            season_after: last.season,
+            pitch_type,
+            change,
+            before: before.map(|b| b as f32),
+            after: after.map(|a| a as f32),
+            z_score: z_score as f32,
+        });
+    };
+
+    let pitch_types: BTreeSet<PitchTypeCode> = before.iter().chain(after.iter()).map(|a| a.pitch_type).collect();
+
+    for pitch_type in pitch_types {
+        let b = before.iter().find(|a| a.pitch_type == pitch_type);
+        let a = after.iter().find(|a| a.pitch_type == pitch_type);
+
+        // Usage
+        let n1 = b.map(|b| b.pitches).unwrap_or(0) as f64;
+        let n2 = a.map(|a| a.pitches).unwrap_or(0) as f64;
+        let (p1, p2) = (ratio(n1, before_pitches as f64), ratio(n2, after_pitches as f64));
+        let pooled = ratio(n1 + n2, (before_pitches + after_pitches) as f64);
+        let se = (pooled * (1.0 - pooled) * (1.0 / before_pitches as f64 + 1.0 / after_pitches as f64)).sqrt();
+        if se > 0.0 {
+            let z = (p2 - p1) / se;
+            if p2 >= NEW_PITCH_MIN_USAGE && p1 < NEW_PITCH_MAX_USAGE && z >= MIN_Z_SCORE {
+                flag(pitch_type, ArsenalChangeType::NewPitch, Some(p1), Some(p2), z);
+            }
+            if p1 >= NEW_PITCH_MIN_USAGE && p2 < NEW_PITCH_MAX_USAGE && z <= -MIN_Z_SCORE {
+                flag(pitch_type, ArsenalChangeType::DroppedPitch, Some(p1), Some(p2), z);
+            }
+        }
+
+        let (b, a) = match (b, a) {
+            (Some (b), Some (a)) => (b, a),
+            _ => continue,
+        };
+
+        if let Some((m1, m2, z)) = welch((b.speed, b.speed_sd, b.speed_pitches), (a.speed, a.speed_sd, a.speed_pitches)) {
+            if m1 - m2 >= MIN_VELOCITY_CHANGE && z <= -MIN_Z_SCORE {
+                flag(pitch_type, ArsenalChangeType::VelocityDrop, Some(m1), Some(m2), z);
+            }
+            if m2 - m1 >= MIN_VELOCITY_CHANGE && z >= MIN_Z_SCORE {
+                flag(pitch_type, ArsenalChangeType::VelocityGain, Some(m1), Some(m2), z);
+            }
+        }
+
+        if let Some((m1, m2, z)) = welch((b.spin_rate, b.spin_rate_sd, b.spin_rate_pitches), (a.spin_rate, a.spin_rate_sd, a.spin_rate_pitches)) {
+            if m1 - m2 >= MIN_SPIN_RATE_CHANGE && z <= -MIN_Z_SCORE {
+                flag(pitch_type, ArsenalChangeType::SpinRateDrop, Some(m1), Some(m2), z);
+            }
+        }
+
+        // The arm slot moved if the release point moved far enough, and significantly on either axis
+        let side = welch((b.release_side, b.release_side_sd, b.release_pitches), (a.release_side, a.release_side_sd, a.release_pitches));
+        let height = welch((b.release_height, b.release_height_sd, b.release_pitches), (a.release_height, a.release_height_sd, a.release_pitches));
+        if let (Some ((x1, x2, zx)), Some ((z1, z2, zz))) = (side, height) {
+            let moved = ((x2 - x1).powi(2) + (z2 - z1).powi(2)).sqrt();
+            let z = if zx.abs() > zz.abs() {zx} else {zz};
+            if moved >= MIN_RELEASE_CHANGE && z.abs() >= MIN_Z_SCORE {
+                flag(pitch_type, ArsenalChangeType::ArmSlotChange, Some(0.0), Some(moved), z);
+            }
+        }
+    }
+
+    changes
+}
+
+/// Season over season changes, for every pitcher who pitched at the same level in consecutive seasons
+pub fn season_changes (arsenals: &[PitcherArsenal]) -> Vec<ArsenalChange> {
+    let mut by_season: BTreeMap<PitcherKey, Vec<&PitcherArsenal>> = BTreeMap::new();
+    for arsenal in arsenals {
+        by_season.entry((arsenal.pitcher, arsenal.sport_id, arsenal.season)).or_default().push(arsenal);
+    }
+
+    by_season.iter()
+        .filter_map(|((pitcher, sport_id, season), after)| {
+            by_season.get(&(*pitcher, *sport_id, season.checked_sub(1)?)).map(|before| detect_changes(before, after))
+        })
+        .flatten()
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn arsenal (season: u16, pitch_type: PitchTypeCode, pitches: u32, speed: f32, release_height: f32) -> PitcherArsenal {
+        PitcherArsenal {
+            pitcher: 1,
+            pitcher_name: "".to_string(),
+            pitcher_throws: SideCode::R,
+            sport_id: 1,
+            season,
+            pitch_type,
+            pitches,
+            usage: 0.0,
+            usage_vs_left: 0.0,
+            usage_vs_right: 0.0,
+            usage_first_pitch: 0.0,
+            usage_ahead: 0.0,
+            usage_even: 0.0,
+            usage_behind: 0.0,
+            usage_two_strikes: 0.0,
+            speed: Some(speed),
+            speed_sd: Some(1.0),
+            speed_p10: None,
+            speed_p50: None,
+            speed_p90: None,
+            speed_max: None,
+            speed_pitches: pitches,
+            spin_rate: Some(2300.0),
+            spin_rate_sd: Some(80.0),
+            spin_rate_pitches: pitches,
+            induced_vertical_break: None,
+            horizontal_break: None,
+            release_side: Some(-2.0),
+            release_side_sd: Some(0.15),
+            release_height: Some(release_height),
+            release_height_sd: Some(0.15),
+            release_pitches: pitches,
+            extension: None,
+            whiff_rate: 0.0,
+            chase_rate: 0.0,
+            called_strike_rate: 0.0,
+            zone_rate: 0.0,
+        }
+    }
+
+    #[test]
+    fn velo_drop_new_pitch_arm_slot () {
+        let arsenals = vec![
+            arsenal(2021, PitchTypeCode::FF, 1000, 95.0, 6.0),
+            arsenal(2021, PitchTypeCode::SL, 500, 86.0, 6.0),
+            arsenal(2022, PitchTypeCode::FF, 900, 93.5, 5.6),
+            arsenal(2022, PitchTypeCode::SL, 400, 86.1, 5.6),
+            arsenal(2022, PitchTypeCode::CH, 200, 86.0, 5.6),
+        ];
+        let changes = season_changes(&arsenals);
+        let has = |pitch_type: PitchTypeCode, change: ArsenalChangeType| changes.iter().any(|c| c.pitch_type == pitch_type && c.change == change);
+
+        assert!(has(PitchTypeCode::FF, ArsenalChangeType::VelocityDrop));
+        assert!(!has(PitchTypeCode::SL, ArsenalChangeType::VelocityDrop));
+        assert!(has(PitchTypeCode::CH, ArsenalChangeType::NewPitch));
+        assert!(has(PitchTypeCode::FF, ArsenalChangeType::ArmSlotChange));
+        assert!(changes.iter().all(|c| c.season_before == 2021 && c.season_after == 2022));
+    }
+
+    /// A tagged four-seamer to a right handed batter, taken for a ball way outside on 0-0
+    fn pitch (speed: f64) -> ArsenalPitch {
+        ArsenalPitch {
+            sport_id: 1,
+            game_year: 2023,
+            pitcher: 1,
+            pitcher_name: "".to_string(),
+            pitcher_throws: SideCode::R,
+            batter_bats: SideCode::R,
+            balls_start: 0,
+            strikes_start: 0,
+            pitch_result: PitchResult::Ball,
+            swing: 0,
+            swing_and_miss: None,
+            strike_zone_top: 3.5,
+            strike_zone_bottom: 1.5,
+            pitch_p_x: Some(2.0),
+            pitch_p_z: Some(2.5),
+            pitch_speed_start: Some(speed),
+            pitch_spin_rate: None,
+            pitch_break_vertical_induced: None,
+            pitch_break_horizontal: None,
+            pitch_extension: None,
+            pitch_type_code: Some(PitchTypeCode::FF),
+            pitch_type_inferred: None,
+            xr: None,
+            zr: None,
+            ivb: None,
+            hb: None,
+        }
+    }
+
+    #[test]
+    fn arsenal_from_pitches () {
+        let pitches = vec![
+            pitch(90.0),
+            // A four-seamer under its old tag, missed on 0-2 by a lefty
+            ArsenalPitch {
+                pitch_type_code: Some(PitchTypeCode::FA), batter_bats: SideCode::L, strikes_start: 2, pitch_result: PitchResult::SwingingStrike,
+                swing: 1, swing_and_miss: Some(1), pitch_p_x: Some(0.0), ..pitch(92.0)
+            },
+            ArsenalPitch {balls_start: 1, pitch_result: PitchResult::CalledStrike, pitch_p_x: Some(0.0), ..pitch(94.0)},
+            // Chased and fouled off on 1-1
+            ArsenalPitch {balls_start: 1, strikes_start: 1, pitch_result: PitchResult::Foul, swing: 1, swing_and_miss: Some(0), ..pitch(96.0)},
+            // Untagged, so our slider
+            ArsenalPitch {pitch_type_code: None, pitch_type_inferred: Some(PitchTypeCode::SL), batter_bats: SideCode::L, ..pitch(85.0)},
+            // MLB's slider over our four-seamer, on 2-2 with no location
+            ArsenalPitch {
+                pitch_type_code: Some(PitchTypeCode::SL), pitch_type_inferred: Some(PitchTypeCode::FF), balls_start: 2, strikes_start: 2,
+                pitch_result: PitchResult::CalledStrike, pitch_p_x: None, pitch_p_z: None, ..pitch(84.0)
+            },
+            // Neither a pitch nor a pitch type
+            ArsenalPitch {pitch_result: PitchResult::NoPitch, ..pitch(99.0)},
+            ArsenalPitch {pitch_type_code: Some(PitchTypeCode::UN), ..pitch(99.0)},
+        ];
+        let arsenals: Vec<PitcherArsenal> = ArsenalData {pitches: &pitches}.into();
+        let row = |pitch_type: PitchTypeCode| arsenals.iter().find(|a| a.pitch_type == pitch_type).unwrap();
+        let (ff, sl) = (row(PitchTypeCode::FF), row(PitchTypeCode::SL));
+
+        assert_eq!(arsenals.len(), 2);
+        assert_eq!((ff.pitches, sl.pitches), (4, 2));
+
+        let splits = |a: &PitcherArsenal| [
+            a.usage, a.usage_vs_left, a.usage_vs_right, a.usage_first_pitch, a.usage_ahead, a.usage_even, a.usage_behind, a.usage_two_strikes,
+        ];
+        assert_eq!(splits(ff), [(4.0 / 6.0) as f32, 0.5, 0.75, 0.5, 1.0, 0.5, 1.0, 0.5]);
+        assert_eq!(splits(sl), [(2.0 / 6.0) as f32, 0.5, 0.25, 0.5, 0.0, 0.5, 0.0, 0.5]);
+        assert!(splits(ff).iter().zip(splits(sl).iter()).all(|(ff, sl)| (ff + sl - 1.0).abs() < 1e-6));
+
+        assert_eq!(ff.speed, Some(93.0));
+        assert_eq!(ff.speed_max, Some(96.0));
+        assert_eq!(ff.speed_p10, Some((90.0 + 2.0 * (0.1 * 3.0)) as f32));
+        assert_eq!(ff.speed_p50, Some(93.0));
+        assert_eq!(ff.speed_p90, Some((94.0 + 2.0 * (0.9 * 3.0 - 2.0)) as f32));
+
+        // One whiff and one chase in two swings, two of four in the zone and one called strike
+        assert_eq!((ff.whiff_rate, ff.chase_rate, ff.zone_rate, ff.called_strike_rate), (0.5, 0.5, 0.5, 0.25));
+        // The slider was never swung at and the one located was out of the zone
+        assert_eq!((sl.whiff_rate, sl.chase_rate, sl.zone_rate, sl.called_strike_rate), (0.0, 0.0, 0.0, 0.5));
+    }
+}
@@ -24,9 +24,12 @@ pub (crate) struct AllPlays {
   //contains pitches, subs, pickoffs, etc.
   #[serde(rename="playEvents")]
   pub (crate) play_events: Vec<PlayEvent>,
+  // Reviews of the play as a whole, such as a manager challenge on a close play at first
+  #[serde(rename="reviewDetails", default)]
+  pub (crate) review_details: Option<ReviewDetails>,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, Copy, Clone, PartialEq, Eq)]
 #[serde(rename_all="camelCase")]
 pub (crate) enum PlayEventType  {
     Action,
@@ -103,7 +106,7 @@ pub (crate) struct HitData {
     pub (crate) total_distance: Option<f32>,
 }
 
-#[derive(Debug, Deserialize, Serialize, Copy, Clone, PartialEq)]
+#[derive(Debug, Deserialize, Serialize, Copy, Clone, PartialEq, Eq, Hash)]
 pub enum Trajectory {
     #[serde(alias = "line_drive")]
     LineDrive,
@@ -123,7 +126,7 @@ pub enum Trajectory {
     Unknown,
 }
 
-#[derive(Debug, Deserialize, Serialize, Copy, Clone, PartialEq)]
+#[derive(Debug, Deserialize, Serialize, Copy, Clone, PartialEq, Eq, Hash)]
 #[serde(rename_all="camelCase")]
 pub enum Hardness {
     Soft,
@@ -138,7 +141,7 @@ pub struct PitchType {
   pub description: Option<PitchTypeDescription>,
 }
 
-#[derive(Debug, Deserialize, Serialize)]
+#[derive(Debug, Deserialize, Serialize, Copy, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
 pub enum PitchTypeCode {
     CH,
     CU,
@@ -215,12 +218,25 @@ pub (crate) struct PlayEvent {
     pub (crate) position: Option<crate::boxscore::Position>,
     pub (crate) start_time: Option<String>,
     pub (crate) play_id: Option<String>,
+    #[serde(default)]
+    pub (crate) review_details: Option<ReviewDetails>,
+}
+
+/// Replay reviews and challenges, attached to either a single play event or the whole plate appearance.
+#[derive(Debug, Deserialize, Clone)]
+#[serde(rename_all="camelCase")]
+pub (crate) struct ReviewDetails {
+    pub (crate) is_overturned: Option<bool>,
+    pub (crate) review_type: Option<String>,
+    pub (crate) challenge_team_id: Option<u32>,
 }
 
 #[derive(Debug, Deserialize)]
 pub (crate) struct Count {
     pub (crate) balls: Option<u8>,
     pub (crate) strikes: Option<u8>,
+    #[serde(default)]
+    pub (crate) outs: Option<u8>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -228,7 +244,7 @@ pub (crate) struct PlayerID {
   pub (crate) id: u32,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, Serialize, Copy, Clone, PartialEq, Eq)]
 pub enum Code {
   ///Pickoff Attempt
   #[serde(alias="1", alias="2", alias="3", alias="+1", alias="+2", alias="+3")]
@@ -301,8 +317,9 @@ pub (crate) struct PlayEventDetails {
   pub (crate) description: Option<String>,
 }
 
-///Result captures plate appearance level details. We ignore the "rbi", "awayscore" and "homescore" fields, as we'll be manually tracking game state,
-///including RE24/288, Win Probability and other metadata such as previous pitch. We are ignoring the plate appearance description for performance reasons. 
+///Result captures plate appearance level details. We ignore the "rbi" field, as we'll be manually tracking game state,
+///including RE24/288, Win Probability and other metadata such as previous pitch. "homeScore" and "awayScore" are the score at the end of
+///the plate appearance, which becomes the score going into the next one. We are ignoring the plate appearance description for performance reasons. 
 /// All the relevant data are captured in other data fields.
 #[derive(Debug, Deserialize)]
 pub(crate) struct PlateAppearanceData {
@@ -314,9 +331,13 @@ pub(crate) struct PlateAppearanceData {
     pub(crate) plate_appearance_result_type: Option<EventType>,
     #[serde(rename="description")]
     pub(crate) plate_appearance_result_description: Option<String>,
+    #[serde(rename="homeScore", default)]
+    pub(crate) home_score: Option<u8>,
+    #[serde(rename="awayScore", default)]
+    pub(crate) away_score: Option<u8>,
 }
 
-#[derive(Debug, Deserialize, Serialize, Copy, Clone, Eq, PartialEq)]
+#[derive(Debug, Deserialize, Serialize, Copy, Clone, Eq, PartialEq, Hash)]
 #[serde(rename_all="camelCase")]
 pub enum HalfInning {
     Top,
@@ -369,7 +390,7 @@ pub(crate) struct Side {
     description: Option<SideDescription>,
 }
 
-#[derive(Debug, Deserialize, Serialize, Copy, Clone, PartialEq)]
+#[derive(Debug, Deserialize, Serialize, Copy, Clone, PartialEq, Eq, Hash)]
 pub enum SideCode {
     L,
     R,
@@ -636,7 +657,7 @@ pub (crate) enum EventType {
 /// Event stores all the possible events. Wherever possible, we'll convert text
 /// into enums, avoiding lifetime issues and increasing memory efficiency. Serde does all the heavy lifting in the
 /// background. TODO: use this for both the "event" and "eventType" fields to see where there are differences
-#[derive(Debug, Deserialize, PartialEq, Eq, Serialize, Copy, Clone)]
+#[derive(Debug, Deserialize, PartialEq, Eq, Hash, Serialize, Copy, Clone)]
 pub enum Event {
     #[serde(alias = "Game Advisory")]
     GameAdvisory,
@@ -0,0 +1,483 @@
+//! Marcel style projections, from our own plate appearances.
+//!
+//! For every player, level and season we project the next season from the three seasons before it:
+//!   * Each event rate (K, BB, HBP, SF, 1B, 2B, 3B, HR and the rest) is the sum of the events over the sum of the plate appearances,
+//!     with the seasons weighted 5/4/3 from the most recent.
+//!   * The rates are regressed to the league rates at the player's level, the same seasons weighted the same way by the player's
+//!     plate appearances, by adding 1200 plate appearances of league average for batters and 600 batters faced for pitchers.
+//!   * The age adjustment comes from the aging curves at the level, the change from last year's age to this year's for K%, BB% and
+//!     ISO. Rates without a curve get Marcel's own: better by 0.6% a year under 29, worse by 0.3% a year over it.
+//!   * Playing time is half of last season's plate appearances, a tenth of the season before that, and a base of 200 for batters or
+//!     100 batters faced for pitchers.
+//!
+//! A player who played at two levels gets a projection at each. Pitchers are projected with what batters did against them.
+//!
+//! Every season in the data is projected from the seasons before it, so the backtest can score the projections against what
+//! happened. Each stat is scored with the RMSE and mean absolute error against players with at least MIN_BACKTEST_PLATE_APPEARANCES
+//! in the season, weighted by those plate appearances, next to the naive projection of the player's most recent season at the
+//! level. The projections after the last season in the data are the ones for the upcoming season. The aging curves are built from
+//! every season, so the backtest gets a little help from the future on the age adjustment.
+
+use serde::{Serialize, Deserialize};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use crate::aging::{AgingCurves, AgingMetric, Role, SurvivorCorrection};
+use crate::game::PitchResult;
+use crate::linear_weights::{BattingTotals, ratio};
+use crate::play_by_play::Event;
+
+/// Season weights, most recent first
+const SEASON_WEIGHTS: [f64; 3] = [5.0, 4.0, 3.0];
+const BATTER_REGRESSION: f64 = 1200.0;
+const PITCHER_REGRESSION: f64 = 600.0;
+const BATTER_BASE_PLAYING_TIME: f64 = 200.0;
+const PITCHER_BASE_PLAYING_TIME: f64 = 100.0;
+const PEAK_AGE: f64 = 29.0;
+const YOUNG_AGING: f64 = 0.006;
+const OLD_AGING: f64 = 0.003;
+pub const MIN_BACKTEST_PLATE_APPEARANCES: u32 = 100;
+/// The aging curves we adjust with
+pub const AGING_CORRECTION: SurvivorCorrection = SurvivorCorrection::Phantom;
+
+/// The events we project a rate for, per plate appearance. Other is everything that isn't an at bat, walk, hit by pitch or sac
+/// fly: intentional walks, sac bunts and interference.
+const STRIKEOUTS: usize = 0;
+const WALKS: usize = 1;
+const HIT_BY_PITCH: usize = 2;
+const SAC_FLIES: usize = 3;
+const SINGLES: usize = 4;
+const DOUBLES: usize = 5;
+const TRIPLES: usize = 6;
+const HOME_RUNS: usize = 7;
+const OTHER: usize = 8;
+const EVENTS: usize = 9;
+
+type Rates = [f64; EVENTS];
+
+/// The columns from the play by play output we need for projections.
+#[derive(Debug, Clone, Deserialize)]
+pub struct ProjectionPitch {
+    pub game_pk: u32,
+    pub num_plate_appearance: u8,
+    pub pitch_num_game: u16,
+    pub sport_id: u32,
+    pub game_year: u16,
+    pub batter: u32,
+    pub batter_name: String,
+    pub batter_age: Option<f32>,
+    pub pitcher: u32,
+    pub pitcher_name: String,
+    pub pitcher_age: Option<f32>,
+    pub pitch_result: PitchResult,
+    pub plate_appearance_result: Option<Event>,
+}
+
+pub struct ProjectionData <'a> {
+    pub pitches: &'a [ProjectionPitch],
+    pub aging_curves: &'a AgingCurves,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Projection {
+    pub role: Role,
+    pub player: u32,
+    pub player_name: String,
+    pub season: u16,
+    pub sport_id: u32,
+    pub age: Option<u8>,
+    /// How much of the projection is the player rather than the league
+    pub reliability: f32,
+    pub plate_appearances: f32,
+    pub strikeout_rate: f32,
+    pub walk_rate: f32,
+    pub home_run_rate: f32,
+    pub strikeouts: f32,
+    pub walks: f32,
+    pub singles: f32,
+    pub doubles: f32,
+    pub triples: f32,
+    pub home_runs: f32,
+    pub avg: f32,
+    pub obp: f32,
+    pub slg: f32,
+    pub iso: f32,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum ProjectedStat {
+    PlateAppearances,
+    StrikeoutRate,
+    WalkRate,
+    HomeRunRate,
+    Avg,
+    Obp,
+    Slg,
+}
+
+impl ProjectedStat {
+    pub const ALL: [ProjectedStat; 7] = [ProjectedStat::PlateAppearances, ProjectedStat::StrikeoutRate, ProjectedStat::WalkRate, ProjectedStat::HomeRunRate, ProjectedStat::Avg, ProjectedStat::Obp, ProjectedStat::Slg];
+}
+
+/// How well the projections for a season did against what happened
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProjectionScore {
+    pub role: Role,
+    pub season: u16,
+    pub sport_id: u32,
+    pub stat: ProjectedStat,
+    pub players: u32,
+    pub rmse: f32,
+    pub mae: f32,
+    /// The player's most recent season at the level as the projection
+    pub naive_rmse: f32,
+    pub naive_mae: f32,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct ProjectionOutput {
+    pub projections: Vec<Projection>,
+    pub backtest: Vec<ProjectionScore>,
+}
+
+#[derive(Debug, Default, Clone)]
+struct SeasonTotals {
+    name: String,
+    age_sum: f64,
+    age_n: u32,
+    batting: BattingTotals,
+}
+
+impl SeasonTotals {
+    fn age (&self) -> Option<u8> {
+        if self.age_n == 0 {return None};
+        Some((self.age_sum / self.age_n as f64).floor() as u8)
+    }
+}
+
+fn events (b: &BattingTotals) -> Rates {
+    let mut events = [0.0; EVENTS];
+    events[STRIKEOUTS] = b.strikeouts as f64;
+    events[WALKS] = b.walks as f64;
+    events[HIT_BY_PITCH] = b.hit_by_pitch as f64;
+    events[SAC_FLIES] = b.sac_flies as f64;
+    events[SINGLES] = b.singles as f64;
+    events[DOUBLES] = b.doubles as f64;
+    events[TRIPLES] = b.triples as f64;
+    events[HOME_RUNS] = b.home_runs as f64;
+    events[OTHER] = (b.plate_appearances - b.at_bats - b.walks - b.hit_by_pitch - b.sac_flies) as f64;
+    events
+}
+
+fn rates (b: &BattingTotals) -> Rates {
+    events(b).map(|e| ratio(e, b.plate_appearances as f64))
+}
+
+/// At bats, hits and total bases per plate appearance
+fn at_bats (rates: &Rates) -> f64 {
+    1.0 - rates[WALKS] - rates[HIT_BY_PITCH] - rates[SAC_FLIES] - rates[OTHER]
+}
+
+fn hits (rates: &Rates) -> f64 {
+    rates[SINGLES] + rates[DOUBLES] + rates[TRIPLES] + rates[HOME_RUNS]
+}
+
+fn total_bases (rates: &Rates) -> f64 {
+    rates[SINGLES] + 2.0 * rates[DOUBLES] + 3.0 * rates[TRIPLES] + 4.0 * rates[HOME_RUNS]
+}
+
+fn avg (rates: &Rates) -> f64 {
+    ratio(hits(rates), at_bats(rates))
+}
+
+fn obp (rates: &Rates) -> f64 {
+    ratio(hits(rates) + rates[WALKS] + rates[HIT_BY_PITCH], at_bats(rates) + rates[WALKS] + rates[HIT_BY_PITCH] + rates[SAC_FLIES])
+}
+
+fn slg (rates: &Rates) -> f64 {
+    ratio(total_bases(rates), at_bats(rates))
+}
+
+fn stat (stat: ProjectedStat, rates: &Rates, plate_appearances: f64) -> f64 {
+    match stat {
+        ProjectedStat::PlateAppearances => plate_appearances,
+        ProjectedStat::StrikeoutRate => rates[STRIKEOUTS],
+        ProjectedStat::WalkRate => rates[WALKS],
+        ProjectedStat::HomeRunRate => rates[HOME_RUNS],
+        ProjectedStat::Avg => avg(rates),
+        ProjectedStat::Obp => obp(rates),
+        ProjectedStat::Slg => slg(rates),
+    }
+}
+
+/// Marcel's age multiplier, for rates where more is better for the player
+pub (crate) fn marcel_age_factor (age: u8) -> f64 {
+    let age = age as f64;
+    if age < PEAK_AGE {1.0 + (PEAK_AGE - age) * YOUNG_AGING} else {1.0 - (age - PEAK_AGE) * OLD_AGING}
+}
+
+/// Adjusts projected rates from last year's age to this year's. The aging curves move K%, BB% and ISO, and whatever doesn't have a
+/// curve gets Marcel's multiplier, the other way around for the pitcher. The outs in play are whatever's left of the at bats, so
+/// they soak up the changes.
+fn age_adjust (mut rates: Rates, role: Role, sport_id: u32, age: u8, curves: &AgingCurves) -> Rates {
+    let change = |metric: AgingMetric| curves.change(role, metric, sport_id, age.saturating_sub(1), age);
+    let factor = marcel_age_factor(age);
+    let (good, bad) = match role {
+        Role::Batter => (factor, 1.0 / factor),
+        Role::Pitcher => (1.0 / factor, factor),
+    };
+
+    match change(AgingMetric::StrikeoutRate) {
+        Some (delta) => rates[STRIKEOUTS] = (rates[STRIKEOUTS] + delta).max(0.0),
+        None => rates[STRIKEOUTS] *= bad,
+    }
+    match change(AgingMetric::WalkRate) {
+        Some (delta) => rates[WALKS] = (rates[WALKS] + delta).max(0.0),
+        None => rates[WALKS] *= good,
+    }
+
+    rates[SINGLES] *= good;
+    let iso = ratio(total_bases(&rates) - hits(&rates), at_bats(&rates));
+    let extra_bases = match (role, change(AgingMetric::Iso)) {
+        (Role::Batter, Some (delta)) if iso > 0.0 => ((iso + delta) / iso).max(0.0),
+        _ => good,
+    };
+    for event in [DOUBLES, TRIPLES, HOME_RUNS] {
+        rates[event] *= extra_bases;
+    }
+    rates
+}
+
+/// Role, player, sport_id and season
+type SeasonKey = (Role, u32, u32, u16);
+
+/// A projection with its rates, and the player's most recent season at the level for the naive projection
+struct Projected {
+    projection: Projection,
+    rates: Rates,
+    naive: Rates,
+    naive_plate_appearances: f64,
+}
+
+/// Projects one player at one level for a season from the seasons before it. None if they didn't play at the level in any of the
+/// three seasons before.
+fn project (key: (Role, u32, u32), season: u16, seasons: &BTreeMap<SeasonKey, SeasonTotals>, leagues: &HashMap<(u32, u16), BattingTotals>, curves: &AgingCurves) -> Option<Projected> {
+    let (role, player, sport_id) = key;
+    let mut weighted_events = [0.0; EVENTS];
+    let mut weighted_league = [0.0; EVENTS];
+    let mut weighted_plate_appearances = 0.0;
+    let mut recent: Option<(u16, &SeasonTotals)> = None;
+    let mut playing_time = [0.0; 2];
+
+    for (i, weight) in SEASON_WEIGHTS.iter().enumerate() {
+        let past = season.checked_sub(i as u16 + 1)?;
+        let totals = match seasons.get(&(role, player, sport_id, past)) {
+            Some (totals) => totals,
+            None => continue,
+        };
+        if recent.is_none() {recent = Some((past, totals))};
+        if i < 2 {playing_time[i] = totals.batting.plate_appearances as f64};
+
+        let pa = totals.batting.plate_appearances as f64;
+        let league = leagues.get(&(sport_id, past)).map(rates).unwrap_or_default();
+        for (event, count) in events(&totals.batting).iter().enumerate() {
+            weighted_events[event] += weight * count;
+            weighted_league[event] += weight * pa * league[event];
+        }
+        weighted_plate_appearances += weight * pa;
+    }
+
+    let (recent_season, recent_totals) = recent?;
+    if weighted_plate_appearances == 0.0 {return None};
+
+    let regression = match role {
+        Role::Batter => BATTER_REGRESSION,
+        Role::Pitcher => PITCHER_REGRESSION,
+    };
+    let mut projected = [0.0; EVENTS];
+    for event in 0 .. EVENTS {
+        let league = weighted_league[event] / weighted_plate_appearances;
+        projected[event] = (weighted_events[event] + regression * league) / (weighted_plate_appearances + regression);
+    }
+
+    let age = recent_totals.age().map(|age| age + (season - recent_season) as u8);
+    if let Some(age) = age {
+        projected = age_adjust(projected, role, sport_id, age, curves);
+    }
+
+    let base = match role {
+        Role::Batter => BATTER_BASE_PLAYING_TIME,
+        Role::Pitcher => PITCHER_BASE_PLAYING_TIME,
+    };
+    let plate_appearances = 0.5 * playing_time[0] + 0.1 * playing_time[1] + base;
+    let count = |event: usize| (projected[event] * plate_appearances) as f32;
+
+    let projection = Projection {
+        role,
+        player,
+        player_name: recent_totals.name.clone(),
+        season,
+        sport_id,
+        age,
+        reliability: (weighted_plate_appearances / (weighted_plate_appearances + regression)) as f32,
+        plate_appearances: plate_appearances as f32,
+        strikeout_rate: projected[STRIKEOUTS] as f32,
+        walk_rate: projected[WALKS] as f32,
+        home_run_rate: projected[HOME_RUNS] as f32,
+        strikeouts: count(STRIKEOUTS),
+        walks: count(WALKS),
+        singles: count(SINGLES),
+        doubles: count(DOUBLES),
+        triples: count(TRIPLES),
+        home_runs: count(HOME_RUNS),
+        avg: avg(&projected) as f32,
+        obp: obp(&projected) as f32,
+        slg: slg(&projected) as f32,
+        iso: (slg(&projected) - avg(&projected)) as f32,
+    };
+    Some(Projected {
+        projection,
+        rates: projected,
+        naive: rates(&recent_totals.batting),
+        naive_plate_appearances: recent_totals.batting.plate_appearances as f64,
+    })
+}
+
+/// Squared and absolute errors, weighted, for the projection and the naive projection
+#[derive(Debug, Default, Copy, Clone)]
+struct Errors {
+    players: u32,
+    weight: f64,
+    squared: f64,
+    absolute: f64,
+    naive_squared: f64,
+    naive_absolute: f64,
+}
+
+impl <'a> From<ProjectionData<'a>> for ProjectionOutput {
+    fn from (data: ProjectionData) -> ProjectionOutput {
+
+        // The last pitch of each plate appearance is the one the result happened on
+        let mut plate_appearances: HashMap<(u32, u8), &ProjectionPitch> = HashMap::new();
+        let mut seasons: BTreeMap<SeasonKey, SeasonTotals> = BTreeMap::new();
+        for pitch in data.pitches {
+            if pitch.pitch_result == PitchResult::NoPitch {continue};
+            let last = plate_appearances.entry((pitch.game_pk, pitch.num_plate_appearance)).or_insert(pitch);
+            if pitch.pitch_num_game > last.pitch_num_game {*last = pitch};
+
+            let players = [
+                ((Role::Batter, pitch.batter, pitch.sport_id, pitch.game_year), &pitch.batter_name, pitch.batter_age),
+                ((Role::Pitcher, pitch.pitcher, pitch.sport_id, pitch.game_year), &pitch.pitcher_name, pitch.pitcher_age),
+            ];
+            for (key, name, age) in players {
+                let totals = seasons.entry(key).or_default();
+                if totals.name.is_empty() {totals.name = name.clone()};
+                if let Some(age) = age {
+                    totals.age_sum += age as f64;
+                    totals.age_n += 1;
+                }
+            }
+        }
+
+        let mut leagues: HashMap<(u32, u16), BattingTotals> = HashMap::new();
+        for pitch in plate_appearances.into_values() {
+            let event = match pitch.plate_appearance_result {
+                Some (event) => event,
+                None => continue,
+            };
+            leagues.entry((pitch.sport_id, pitch.game_year)).or_default().add(event);
+            for key in [(Role::Batter, pitch.batter, pitch.sport_id, pitch.game_year), (Role::Pitcher, pitch.pitcher, pitch.sport_id, pitch.game_year)] {
+                seasons.entry(key).or_default().batting.add(event);
+            }
+        }
+
+        let all_seasons: BTreeSet<u16> = seasons.keys().map(|key| key.3).collect();
+        let (first, last) = match (all_seasons.first(), all_seasons.last()) {
+            (Some (first), Some (last)) => (*first, *last),
+            _ => return ProjectionOutput::default(),
+        };
+        let players: BTreeSet<(Role, u32, u32)> = seasons.keys()
+            .map(|(role, player, sport_id, _)| (*role, *player, *sport_id))
+            .collect();
+
+        let mut projections = vec![];
+        let mut errors: BTreeMap<(Role, u16, u32, ProjectedStat), Errors> = BTreeMap::new();
+
+        for season in first + 1 ..= last + 1 {
+            for key in players.iter() {
+                let projected = match project(*key, season, &seasons, &leagues, data.aging_curves) {
+                    Some (projected) => projected,
+                    None => continue,
+                };
+
+                let (role, player, sport_id) = *key;
+                if let Some(actual) = seasons.get(&(role, player, sport_id, season)) {
+                    let pa = actual.batting.plate_appearances;
+                    if pa >= MIN_BACKTEST_PLATE_APPEARANCES {
+                        let actual_rates = rates(&actual.batting);
+                        for projected_stat in ProjectedStat::ALL {
+                            let truth = stat(projected_stat, &actual_rates, pa as f64);
+                            let error = stat(projected_stat, &projected.rates, projected.projection.plate_appearances as f64) - truth;
+                            let naive_error = stat(projected_stat, &projected.naive, projected.naive_plate_appearances) - truth;
+                            let e = errors.entry((role, season, sport_id, projected_stat)).or_default();
+                            e.players += 1;
+                            e.weight += pa as f64;
+                            e.squared += pa as f64 * error * error;
+                            e.absolute += pa as f64 * error.abs();
+                            e.naive_squared += pa as f64 * naive_error * naive_error;
+                            e.naive_absolute += pa as f64 * naive_error.abs();
+                        }
+                    }
+                }
+
+                projections.push(projected.projection);
+            }
+        }
+
+        let backtest = errors.into_iter()
+            .map(|((role, season, sport_id, stat), e)| ProjectionScore {
+                role,
+                season,
+                sport_id,
+                stat,
+                players: e.players,
+                rmse: ratio(e.squared, e.weight).sqrt() as f32,
+                mae: ratio(e.absolute, e.weight) as f32,
+                naive_rmse: ratio(e.naive_squared, e.weight).sqrt() as f32,
+                naive_mae: ratio(e.naive_absolute, e.weight) as f32,
+            })
+            .collect();
+
+        ProjectionOutput {
+            projections,
+            backtest,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn regresses_toward_the_league () {
+        // A batter who struck out in half their plate appearances for three seasons, in a league that strikes out a fifth of the time
+        let mut player = BattingTotals::default();
+        let mut league = BattingTotals::default();
+        for i in 0 .. 500 {
+            player.add(if i % 2 == 0 {Event::StrikeOut} else {Event::Single});
+            league.add(if i % 5 == 0 {Event::StrikeOut} else {Event::Single});
+        }
+        let mut seasons = BTreeMap::new();
+        let mut leagues = HashMap::new();
+        for season in 2021 .. 2024 {
+            seasons.insert((Role::Batter, 1, 1, season), SeasonTotals {name: "A".to_string(), age_sum: 0.0, age_n: 0, batting: player});
+            leagues.insert((1, season), league);
+        }
+
+        let projected = project((Role::Batter, 1, 1), 2024, &seasons, &leagues, &AgingCurves::default()).unwrap();
+        // 6000 weighted plate appearances and 1200 of league average
+        let expected = (6000.0 * 0.5 + 1200.0 * 0.2) / 7200.0;
+        assert!((projected.rates[STRIKEOUTS] - expected).abs() < 1e-9);
+        assert!((projected.projection.plate_appearances - 500.0).abs() < 1e-3);
+        assert!((marcel_age_factor(26) - 1.018).abs() < 1e-9);
+    }
+}
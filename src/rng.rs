@@ -0,0 +1,38 @@
+//! A small seeded random number generator for our simulations. Like the date module, we roll our own rather than bring in an extra
+//! dependency. We only need reproducible uniform draws, not cryptographic quality, so SplitMix64 is more than enough.
+
+#[derive(Debug, Clone)]
+pub struct Rng {
+    state: u64,
+}
+
+impl Rng {
+    pub fn new (seed: u64) -> Rng {
+        Rng {state: seed}
+    }
+
+    pub fn next_u64 (&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+
+    /// Uniform draw in [0, 1)
+    pub fn next_f64 (&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+
+    /// Uniform draw in [0, n)
+    pub fn below (&mut self, n: u64) -> u64 {
+        ((self.next_u64() as u128 * n as u128) >> 64) as u64
+    }
+
+    /// Derives an independent generator, so parallel simulations stay reproducible no matter which thread runs them.
+    pub fn fork (&self, stream: u64) -> Rng {
+        let mut rng = Rng::new(self.state ^ stream.wrapping_mul(0xD1B5_4A32_D192_ED03));
+        rng.next_u64();
+        rng
+    }
+}
@@ -2,15 +2,25 @@
 /// 
 /// ## Method 1: Use averages
 ///     1) For each venue, season and sport_id, calculate the average number of runs scored for after reaching a specific 288 state. <br/>
-///     2) Then we make sure that for items that have sample size < 30, we take the average of the two neighbours. Neighbours will be classified
-///     statically.
+///     2) Then every state is shrunk towards the same state of the table above it, with RE288_MIN_SAMPLE pseudo-counts. Venue tables shrink
+///     towards the sport_id and season table, which shrinks towards the default table.
 /// 
 /// ## Methos 2: Simulation
 ///     1) For each venue, season and sport_id, calculate the probability of each high-level event type (ball, strike, in-play)
 ///     2) The in-play probability (specifically single/double/triple) is dependent on the ball-strike-out state.
 ///     3) Simulate seasons based on the above probabilities.
+/// 
+/// Method 1 is implemented below. The tables are built from our own play by play output with `RE288Data`, persisted in the cache and
+/// looked up by the Pitch builder for each game's sport_id and season (and venue, if venue tables were built). Any context we don't
+/// have a table for falls back to `RE288_DEFAULT`.
+/// 
+/// Method 2 lives in the markov module.
  
 
+use serde::{Serialize, Deserialize};
+use std::collections::{BTreeMap, HashMap};
+use crate::play_by_play::HalfInning;
+
 /// RE288: The run expectancy for all 288 possible base, out, ball, strike states
 #[derive(Debug)]
 pub struct RE288 {
@@ -311,4 +321,307 @@ pub const RE288_DEFAULT: [RE288; 288] = [
     RE288 {balls: 0, strikes:  1, base_value: 0, outs: 2, run_expectancy: 0.086},
     RE288 {balls: 1, strikes:  2, base_value: 0, outs: 2, run_expectancy: 0.069},
     RE288 {balls: 0, strikes:  2, base_value: 0, outs: 2, run_expectancy: 0.06},
-];
\ No newline at end of file
+];
+
+/// Pseudo-counts of the table above when shrinking a cell. Cells with fewer observations than this are mostly the table above.
+pub const RE288_MIN_SAMPLE: u32 = 30;
+
+/// The columns from the play by play output we need to build run expectancy tables. Any other columns in the file are ignored.
+#[derive(Debug, Clone, Deserialize)]
+pub struct RE288Pitch {
+    pub game_pk: u32,
+    pub sport_id: u32,
+    pub game_year: u16,
+    pub venue_id: u32,
+    pub half_inning: HalfInning,
+    pub num_inning: u8,
+    pub pitch_num_game: u16,
+    pub balls_start: u8,
+    pub strikes_start: u8,
+    pub base_value_start: u8,
+    pub outs_start: u8,
+    pub outs_end: u8,
+    pub runs_scored: u8,
+}
+
+/// One cell of a generated RE288 table. Venue tables have a venue_id, league tables don't.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RE288Entry {
+    pub sport_id: u32,
+    pub season: u16,
+    pub venue_id: Option<u32>,
+    pub balls: u8,
+    pub strikes: u8,
+    pub base_value: u8,
+    pub outs: u8,
+    pub run_expectancy: f32,
+    pub sample_size: u32,
+    /// Did the cell have less than RE288_MIN_SAMPLE observations, so it's mostly the table above it?
+    pub smoothed: bool,
+}
+
+pub struct RE288Data <'r> {
+    pub pitches: &'r [RE288Pitch],
+    /// Also build a table for each venue, on top of the league tables
+    pub by_venue: bool,
+}
+
+/// (balls, strikes, base_value, outs)
+pub (crate) type State = (u8, u8, u8, u8);
+type Context = (u32, u16, Option<u32>);
+
+/// Sum of runs to the end of the inning and the number of times we saw the state
+#[derive(Debug, Default, Copy, Clone)]
+struct Cell {
+    runs: f64,
+    n: u32,
+}
+
+/// Method 1: the run expectancy for a state is the average number of runs scored from that pitch until the end of the inning.
+impl <'r> From<RE288Data<'r>> for Vec<RE288Entry> {
+    fn from (data: RE288Data) -> Vec<RE288Entry> {
+
+        // BTreeMap so the output is always in the same order
+        let mut cells: BTreeMap<Context, HashMap<State, Cell>> = BTreeMap::new();
+
+        for pitches in complete_half_innings(data.pitches) {
+
+            let mut runs_to_end = 0u32;
+
+            for pitch in pitches.iter().rev() {
+                runs_to_end += pitch.runs_scored as u32;
+
+                let state = (pitch.balls_start, pitch.strikes_start, pitch.base_value_start, pitch.outs_start);
+                if !valid_state(state) {continue};
+
+                let mut contexts = vec![(pitch.sport_id, pitch.game_year, None)];
+                if data.by_venue {contexts.push((pitch.sport_id, pitch.game_year, Some(pitch.venue_id)))};
+
+                for context in contexts {
+                    let cell = cells.entry(context).or_default().entry(state).or_default();
+                    cell.runs += runs_to_end as f64;
+                    cell.n += 1;
+                }
+            }
+        }
+
+        let default: HashMap<State, f32> = RE288_DEFAULT.iter()
+            .map(|re| ((re.balls, re.strikes, re.base_value, re.outs), re.run_expectancy))
+            .collect();
+
+        let mut entries: Vec<RE288Entry> = Vec::with_capacity(cells.len() * 288);
+        let mut league: HashMap<(u32, u16), HashMap<State, f32>> = HashMap::new();
+        let k = RE288_MIN_SAMPLE as f64;
+
+        // The BTreeMap puts the sport_id and season table (no venue) ahead of its venue tables
+        for ((sport_id, season, venue_id), context_cells) in cells {
+            let parent = match venue_id {
+                None => &default,
+                Some (_) => &league[&(sport_id, season)],
+            };
+            let mut table: HashMap<State, f32> = HashMap::with_capacity(288);
+
+            for state in all_states() {
+                let cell = context_cells.get(&state).copied().unwrap_or_default();
+                let (balls, strikes, base_value, outs) = state;
+
+                let prior = *parent.get(&state).unwrap_or(&0f32) as f64;
+                let run_expectancy = ((cell.runs + k * prior) / (cell.n as f64 + k)) as f32;
+                let smoothed = cell.n < RE288_MIN_SAMPLE;
+                table.insert(state, run_expectancy);
+
+                entries.push(
+                    RE288Entry {
+                        sport_id,
+                        season,
+                        venue_id,
+                        balls,
+                        strikes,
+                        base_value,
+                        outs,
+                        run_expectancy,
+                        sample_size: cell.n,
+                        smoothed,
+                    }
+                );
+            }
+
+            if venue_id.is_none() {league.insert((sport_id, season), table);};
+        }
+
+        entries
+    }
+}
+
+/// Groups the pitches into half-innings, in the order they were thrown. The third out can come between pitches, on a pickoff or a
+/// caught stealing, so a half-inning is complete if its last pitch made the third out or the game went on to another half-inning.
+/// Walk-offs and shortened games never finish the last inning, so we don't know how many runs would have scored and those
+/// half-innings are dropped.
+pub (crate) fn complete_half_innings (pitches: &[RE288Pitch]) -> Vec<Vec<&RE288Pitch>> {
+
+    // (num_inning, 0 for the top and 1 for the bottom) puts the half-innings in order
+    let order = |num_inning: u8, half_inning: HalfInning| (num_inning, (half_inning == HalfInning::Bottom) as u8);
+
+    let mut half_innings: HashMap<(u32, u8, HalfInning), Vec<&RE288Pitch>> = HashMap::new();
+    let mut last_half_inning: HashMap<u32, (u8, u8)> = HashMap::new();
+    for pitch in pitches {
+        half_innings.entry((pitch.game_pk, pitch.num_inning, pitch.half_inning)).or_default().push(pitch);
+        let last = last_half_inning.entry(pitch.game_pk).or_insert((0, 0));
+        *last = (*last).max(order(pitch.num_inning, pitch.half_inning));
+    }
+
+    half_innings.into_iter()
+        .map(|(key, mut pitches)| {pitches.sort_by_key(|pitch| pitch.pitch_num_game); (key, pitches)})
+        .filter(|((game_pk, num_inning, half_inning), pitches)|
+            pitches.last().map(|pitch| pitch.outs_end).unwrap_or(0) >= 3 || order(*num_inning, *half_inning) < last_half_inning[game_pk]
+        )
+        .map(|(_, pitches)| pitches)
+        .collect()
+}
+
+pub (crate) fn valid_state (state: State) -> bool {
+    let (balls, strikes, base_value, outs) = state;
+    balls <= 3 && strikes <= 2 && base_value <= 7 && outs <= 2
+}
+
+pub (crate) fn all_states () -> Vec<State> {
+    let mut states = Vec::with_capacity(288);
+    for outs in 0 ..= 2 {
+        for base_value in 0 ..= 7 {
+            for balls in 0 ..= 3 {
+                for strikes in 0 ..= 2 {
+                    states.push((balls, strikes, base_value, outs));
+                }
+            }
+        }
+    }
+    states
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A pitch with the bases empty in the top of the first
+    fn pitch (game_pk: u32, pitch_num_game: u16, balls: u8, strikes: u8, outs_start: u8, outs_end: u8, runs_scored: u8) -> RE288Pitch {
+        RE288Pitch {
+            game_pk,
+            sport_id: 1,
+            game_year: 2023,
+            venue_id: 15,
+            half_inning: HalfInning::Top,
+            num_inning: 1,
+            pitch_num_game,
+            balls_start: balls,
+            strikes_start: strikes,
+            base_value_start: 0,
+            outs_start,
+            outs_end,
+            runs_scored,
+        }
+    }
+
+    fn entry (entries: &[RE288Entry], balls: u8, strikes: u8, outs: u8) -> &RE288Entry {
+        entries.iter().find(|e| e.venue_id.is_none() && e.balls == balls && e.strikes == strikes && e.base_value == 0 && e.outs == outs).unwrap()
+    }
+
+    /// The default table's value for a bases empty state
+    fn default (balls: u8, strikes: u8, outs: u8) -> f64 {
+        RE288_DEFAULT.iter().find(|re| re.balls == balls && re.strikes == strikes && re.base_value == 0 && re.outs == outs).unwrap().run_expectancy as f64
+    }
+
+    const K: f64 = RE288_MIN_SAMPLE as f64;
+
+    /// A solo home run on the first pitch, then three straight first pitch outs
+    fn solo_home_run () -> Vec<RE288Pitch> {
+        vec![pitch(1, 1, 0, 0, 0, 0, 1), pitch(1, 2, 0, 0, 0, 1, 0), pitch(1, 3, 0, 0, 1, 2, 0), pitch(1, 4, 0, 0, 2, 3, 0)]
+    }
+
+    #[test]
+    fn runs_counted_to_end_of_inning () {
+        let entries: Vec<RE288Entry> = RE288Data {pitches: &solo_home_run(), by_venue: false}.into();
+
+        // 0-0 with nobody out was seen twice, with 1 and 0 runs still to come
+        assert_eq!(entry(&entries, 0, 0, 0).sample_size, 2);
+        assert_eq!(entry(&entries, 0, 0, 0).run_expectancy, ((1.0 + K * default(0, 0, 0)) / (2.0 + K)) as f32);
+        assert_eq!(entry(&entries, 0, 0, 1).run_expectancy, (K * default(0, 0, 1) / (1.0 + K)) as f32);
+    }
+
+    #[test]
+    fn one_entry_per_state () {
+        let entries: Vec<RE288Entry> = RE288Data {pitches: &solo_home_run(), by_venue: false}.into();
+        assert_eq!(entries.len(), 288);
+    }
+
+    #[test]
+    fn one_table_per_venue () {
+        let entries: Vec<RE288Entry> = RE288Data {pitches: &solo_home_run(), by_venue: true}.into();
+        assert_eq!(entries.iter().filter(|e| e.venue_id.is_none()).count(), 288);
+        assert_eq!(entries.iter().filter(|e| e.venue_id == Some(15)).count(), 288);
+    }
+
+    #[test]
+    fn unfinished_innings_are_dropped () {
+        // A walk-off single with one out
+        let pitches = vec![pitch(1, 1, 0, 0, 1, 1, 1)];
+        let entries: Vec<RE288Entry> = RE288Data {pitches: &pitches, by_venue: false}.into();
+        assert!(entries.is_empty());
+    }
+
+    #[test]
+    fn innings_ending_between_pitches_are_kept () {
+        // Picked off for the third out after a 0-0 pitch with two out, then the bottom of the inning is cut short
+        let pitches = vec![pitch(1, 1, 0, 0, 2, 2, 0), RE288Pitch {half_inning: HalfInning::Bottom, ..pitch(1, 2, 0, 0, 0, 0, 0)}];
+        let entries: Vec<RE288Entry> = RE288Data {pitches: &pitches, by_venue: false}.into();
+
+        assert_eq!(entry(&entries, 0, 0, 2).sample_size, 1);
+        assert_eq!(entry(&entries, 0, 0, 0).sample_size, 0);
+    }
+
+    #[test]
+    fn full_cells_are_not_smoothed () {
+        let pitches: Vec<RE288Pitch> = (0 .. RE288_MIN_SAMPLE).map(|game| pitch(game, 1, 0, 0, 2, 3, 0)).collect();
+        let entries: Vec<RE288Entry> = RE288Data {pitches: &pitches, by_venue: false}.into();
+
+        assert_eq!(entry(&entries, 0, 0, 2).sample_size, RE288_MIN_SAMPLE);
+        assert!(!entry(&entries, 0, 0, 2).smoothed);
+    }
+
+    #[test]
+    fn full_cells_are_shrunk_halfway () {
+        // RE288_MIN_SAMPLE scoreless innings ending on 0-0 with two out
+        let pitches: Vec<RE288Pitch> = (0 .. RE288_MIN_SAMPLE).map(|game| pitch(game, 1, 0, 0, 2, 3, 0)).collect();
+        let entries: Vec<RE288Entry> = RE288Data {pitches: &pitches, by_venue: false}.into();
+
+        assert_eq!(entry(&entries, 0, 0, 2).run_expectancy, (K * default(0, 0, 2) / (2.0 * K)) as f32);
+    }
+
+    #[test]
+    fn venue_cells_shrink_towards_the_league () {
+        // Another game at a second venue, so the league table differs from either venue's
+        let mut pitches = solo_home_run();
+        pitches.push(RE288Pitch {venue_id: 16, ..pitch(2, 1, 0, 0, 2, 3, 0)});
+        let entries: Vec<RE288Entry> = RE288Data {pitches: &pitches, by_venue: true}.into();
+
+        let league = entry(&entries, 0, 0, 2).run_expectancy as f64;
+        let venue = |venue_id: u32, balls: u8, strikes: u8| entries.iter()
+            .find(|e| e.venue_id == Some(venue_id) && e.balls == balls && e.strikes == strikes && e.base_value == 0 && e.outs == 2)
+            .unwrap()
+            .run_expectancy;
+
+        assert_eq!(league, (K * default(0, 0, 2) / (2.0 + K)) as f32 as f64);
+        assert_eq!(venue(15, 0, 0), (K * league / (1.0 + K)) as f32);
+        // Nothing at either level, so the league value is the default
+        assert_eq!(venue(16, 1, 0), default(1, 0, 2) as f32);
+    }
+
+    #[test]
+    fn empty_cells_use_the_default_table () {
+        let entries: Vec<RE288Entry> = RE288Data {pitches: &solo_home_run(), by_venue: false}.into();
+        let loaded = entries.iter().find(|e| e.balls == 3 && e.strikes == 2 && e.base_value == 7 && e.outs == 2).unwrap();
+
+        assert!(loaded.smoothed);
+        assert_eq!(loaded.sample_size, 0);
+        assert_eq!(loaded.run_expectancy, 0.992);
+    }
+}
@@ -269,6 +269,20 @@ impl From <crate::date::Date> for GameDate {
     }
 }
 
+impl GameDate {
+    /// Number of days since 1970-01-01, for when we need exact day counts such as days of rest. Uses the days_from_civil
+    /// algorithm from Howard Hinnant's date library.
+    pub fn days_since_epoch (&self) -> i32 {
+        let year = self.year as i32 - if self.month <= 2 {1} else {0};
+        let era = year.div_euclid(400);
+        let year_of_era = year - era * 400;
+        let month = self.month as i32;
+        let day_of_year = (153 * (month + if month > 2 {-3} else {9}) + 2) / 5 + self.day as i32 - 1;
+        let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+        era * 146_097 + day_of_era - 719_468
+    }
+}
+
 impl ToString for GameDate {
     fn to_string(&self) -> String {
         format!("{}-{}-{}", self.year, self.month, self.day)
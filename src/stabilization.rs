@@ -0,0 +1,670 @@
+//! How much to trust a player's rate stat, and what it should be after shrinking it toward the league.
+//!
+//! For each metric, level and season we measure reliability two ways:
+//!   * Split half: every group's trials are split into odd and even, in the order of the play by play output, and we correlate
+//!     the two halves across groups with at least MIN_SPLIT_TRIALS. Spearman-Brown steps the correlation up to the full sample,
+//!     which is the reliability at the mean number of trials.
+//!   * KR-21: for the yes/no metrics, the first KR-21 trials of every group with that many. KR-21 needs every group to have the
+//!     same number of trials, so we don't use the rest.
+//!
+//! A reliability of r at n trials means the stabilization point, where reliability is one half, is M = n (1 - r) / r. M is how
+//! many trials of league average the shrinkage adds:
+//!   * Yes/no metrics (K%, whiff%, barrel%, called strike%) get a beta-binomial, a Beta(p M, (1 - p) M) prior around the league
+//!     rate p. The posterior is a beta too, and the interval is its 2.5th and 97.5th percentiles.
+//!   * xwOBA gets a normal-normal. The spread of single plate appearances is the pooled variance within groups, and the prior's
+//!     variance is that over M.
+//!
+//! M comes from KR-21 when we have it and split half when we don't. Metrics, levels and seasons where neither gives a positive
+//! reliability aren't shrunk.
+//!
+//! Groups are batters, pitchers, catchers and home plate umpires. Barrels follow Statcast: 98 mph at 26-30 degrees, the window
+//! opening up with every mph to 8-50 degrees at 116 mph. Called strike% leaves out pitch clock automatic calls, pitchouts and
+//! intentional balls, since nobody decided to take those.
+
+use serde::{Serialize, Deserialize};
+use std::collections::{BTreeMap, HashMap};
+use crate::game::PitchResult;
+use crate::linear_weights::{WobaWeights, DEFAULT_WOBA_WEIGHTS, ratio};
+use crate::play_by_play::{Event, PitchTypeCode};
+
+/// Groups need this many trials before they go into the split half correlation
+pub const MIN_SPLIT_TRIALS: u32 = 20;
+const INTERVAL: f64 = 0.95;
+const Z_95: f64 = 1.96;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum Grouping {
+    Batter,
+    Pitcher,
+    Catcher,
+    Umpire,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum StabilizedMetric {
+    /// Per plate appearance
+    StrikeoutRate,
+    /// Per swing
+    WhiffRate,
+    /// Per batted ball with an exit velocity and launch angle
+    BarrelRate,
+    /// Per pitch taken
+    CalledStrikeRate,
+    /// Per plate appearance, in the wOBA denominator
+    Xwoba,
+}
+
+impl StabilizedMetric {
+    pub fn binomial (&self) -> bool {
+        *self != StabilizedMetric::Xwoba
+    }
+
+    /// The trials for KR-21
+    fn kr21_trials (&self) -> u32 {
+        match self {
+            StabilizedMetric::StrikeoutRate | StabilizedMetric::Xwoba => 100,
+            StabilizedMetric::WhiffRate => 150,
+            StabilizedMetric::BarrelRate => 50,
+            StabilizedMetric::CalledStrikeRate => 300,
+        }
+    }
+}
+
+/// The columns from the play by play output we need for stabilization.
+#[derive(Debug, Clone, Deserialize)]
+pub struct StabilizationPitch {
+    pub game_pk: u32,
+    pub sport_id: u32,
+    pub game_year: u16,
+    pub num_plate_appearance: u8,
+    pub pitch_num_game: u16,
+    pub batter: u32,
+    pub batter_name: String,
+    pub pitcher: u32,
+    pub pitcher_name: String,
+    pub catcher_id: Option<u32>,
+    pub catcher_name: Option<String>,
+    pub hp_umpire_id: Option<u32>,
+    pub hp_umpire_name: Option<String>,
+    pub pitch_result: PitchResult,
+    pub in_play: u8,
+    pub swing: u8,
+    pub swing_and_miss: Option<u8>,
+    pub description: String,
+    pub pitch_type_code: Option<PitchTypeCode>,
+    pub plate_appearance_result: Option<Event>,
+    pub hit_data_exit_velocity: Option<f32>,
+    pub hit_data_launch_angle: Option<f32>,
+    pub hit_data_xwoba: Option<f32>,
+}
+
+pub struct StabilizationData <'a> {
+    pub pitches: &'a [StabilizationPitch],
+    pub woba_weights: &'a HashMap<(u32, u16), WobaWeights>,
+}
+
+/// Reliability and the prior for a metric at a level in a season
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Stabilization {
+    pub grouping: Grouping,
+    pub metric: StabilizedMetric,
+    pub sport_id: u32,
+    pub season: u16,
+    pub groups: u32,
+    pub league_mean: f32,
+    /// Variance of a single trial, pooled within groups
+    pub trial_variance: f32,
+    pub split_half_groups: u32,
+    pub split_half_trials: Option<f32>,
+    pub split_half: Option<f32>,
+    pub split_half_stabilization: Option<f32>,
+    pub kr21_groups: u32,
+    pub kr21: Option<f32>,
+    pub kr21_stabilization: Option<f32>,
+    /// The trials of league average the shrinkage adds
+    pub stabilization: Option<f32>,
+}
+
+/// A group's rate, as observed and shrunk, with the posterior interval
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ShrunkRate {
+    pub grouping: Grouping,
+    pub id: u32,
+    pub name: String,
+    pub metric: StabilizedMetric,
+    pub sport_id: u32,
+    pub season: u16,
+    pub trials: u32,
+    pub observed: f32,
+    pub reliability: f32,
+    pub shrunk: f32,
+    pub low: f32,
+    pub high: f32,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct StabilizationOutput {
+    pub stabilization: Vec<Stabilization>,
+    pub rates: Vec<ShrunkRate>,
+}
+
+#[derive(Debug, Copy, Clone)]
+pub struct Posterior {
+    pub mean: f64,
+    pub low: f64,
+    pub high: f64,
+}
+
+/// Beta-binomial shrinkage for a rate with a yes/no outcome
+#[derive(Debug, Copy, Clone)]
+pub struct BetaBinomial {
+    pub alpha: f64,
+    pub beta: f64,
+}
+
+impl BetaBinomial {
+    /// A prior worth `stabilization` trials at the league rate
+    pub fn new (league_rate: f64, stabilization: f64) -> Self {
+        BetaBinomial {
+            alpha: league_rate * stabilization,
+            beta: (1.0 - league_rate) * stabilization,
+        }
+    }
+
+    pub fn posterior (&self, successes: f64, trials: f64) -> Posterior {
+        let (a, b) = (self.alpha + successes, self.beta + trials - successes);
+        let tail = (1.0 - INTERVAL) / 2.0;
+        Posterior {
+            mean: a / (a + b),
+            low: beta_quantile(tail, a, b),
+            high: beta_quantile(1.0 - tail, a, b),
+        }
+    }
+}
+
+/// Normal-normal shrinkage for a rate that's a mean of continuous values
+#[derive(Debug, Copy, Clone)]
+pub struct NormalNormal {
+    pub mean: f64,
+    pub prior_variance: f64,
+    pub trial_variance: f64,
+}
+
+impl NormalNormal {
+    pub fn new (league_mean: f64, trial_variance: f64, stabilization: f64) -> Self {
+        NormalNormal {
+            mean: league_mean,
+            prior_variance: trial_variance / stabilization,
+            trial_variance,
+        }
+    }
+
+    pub fn posterior (&self, sum: f64, trials: f64) -> Posterior {
+        let precision = 1.0 / self.prior_variance + trials / self.trial_variance;
+        let mean = (self.mean / self.prior_variance + sum / self.trial_variance) / precision;
+        let sd = (1.0 / precision).sqrt();
+        Posterior {
+            mean,
+            low: mean - Z_95 * sd,
+            high: mean + Z_95 * sd,
+        }
+    }
+}
+
+/// Lanczos approximation, g = 7
+fn ln_gamma (x: f64) -> f64 {
+    const COEFFICIENTS: [f64; 9] = [
+        0.999_999_999_999_809_9, 676.520_368_121_885_1, -1_259.139_216_722_402_8, 771.323_428_777_653_1,
+        -176.615_029_162_140_6, 12.507_343_278_686_905, -0.138_571_095_265_720_12, 9.984_369_578_019_572e-6,
+        1.505_632_735_149_311_6e-7,
+    ];
+    if x < 0.5 {
+        return (std::f64::consts::PI / (std::f64::consts::PI * x).sin()).ln() - ln_gamma(1.0 - x);
+    }
+    let x = x - 1.0;
+    let t = x + 7.5;
+    let series = COEFFICIENTS.iter().skip(1).enumerate()
+        .fold(COEFFICIENTS[0], |sum, (i, c)| sum + c / (x + i as f64 + 1.0));
+    0.5 * (2.0 * std::f64::consts::PI).ln() + (x + 0.5) * t.ln() - t + series.ln()
+}
+
+/// Continued fraction for the incomplete beta function, by the modified Lentz method
+fn beta_continued_fraction (x: f64, a: f64, b: f64) -> f64 {
+    const TINY: f64 = 1e-300;
+    let mut c = 1.0;
+    let mut d = 1.0 - (a + b) * x / (a + 1.0);
+    if d.abs() < TINY {d = TINY};
+    d = 1.0 / d;
+    let mut h = d;
+    for m in 1 .. 300 {
+        let m = m as f64;
+        for numerator in [
+            m * (b - m) * x / ((a + 2.0 * m - 1.0) * (a + 2.0 * m)),
+            -(a + m) * (a + b + m) * x / ((a + 2.0 * m) * (a + 2.0 * m + 1.0)),
+        ] {
+            d = 1.0 + numerator * d;
+            if d.abs() < TINY {d = TINY};
+            c = 1.0 + numerator / c;
+            if c.abs() < TINY {c = TINY};
+            d = 1.0 / d;
+            h *= d * c;
+        }
+        if (d * c - 1.0).abs() < 1e-12 {break};
+    }
+    h
+}
+
+/// The regularized incomplete beta function, the beta CDF
+pub (crate) fn beta_cdf (x: f64, a: f64, b: f64) -> f64 {
+    if x <= 0.0 {return 0.0};
+    if x >= 1.0 {return 1.0};
+    let front = (ln_gamma(a + b) - ln_gamma(a) - ln_gamma(b) + a * x.ln() + b * (1.0 - x).ln()).exp();
+    if x < (a + 1.0) / (a + b + 2.0) {
+        front * beta_continued_fraction(x, a, b) / a
+    } else {
+        1.0 - front * beta_continued_fraction(1.0 - x, b, a) / b
+    }
+}
+
+/// Bisection on the CDF
+pub (crate) fn beta_quantile (p: f64, a: f64, b: f64) -> f64 {
+    let (mut low, mut high) = (0.0, 1.0);
+    for _ in 0 .. 60 {
+        let mid = (low + high) / 2.0;
+        if beta_cdf(mid, a, b) < p {low = mid} else {high = mid};
+    }
+    (low + high) / 2.0
+}
+
+/// Statcast's barrel window
+pub (crate) fn barrel (exit_velocity: f32, launch_angle: f32) -> bool {
+    if exit_velocity < 98.0 {return false};
+    let over = (exit_velocity - 98.0).min(18.0);
+    let low = 26.0 - over;
+    let high = if over < 2.0 {30.0 + over} else {33.0 + (over - 2.0) * 17.0 / 16.0};
+    (low ..= high).contains(&launch_angle)
+}
+
+/// Balls and called strikes the batter took. Automatic calls only show up in the pitch description.
+fn taken (pitch: &StabilizationPitch) -> bool {
+    matches!(pitch.pitch_result, PitchResult::Ball | PitchResult::CalledStrike)
+        && !matches!(pitch.pitch_type_code, Some (PitchTypeCode::PO) | Some (PitchTypeCode::IN))
+        && !pitch.description.starts_with("Automatic")
+}
+
+/// Running sums of a group's trials
+#[derive(Debug, Default, Copy, Clone)]
+struct Trials {
+    n: u32,
+    sum: f64,
+    sum_squares: f64,
+    halves: [(f64, u32); 2],
+    first: (f64, u32),
+}
+
+impl Trials {
+    fn add (&mut self, value: f64, kr21_trials: u32) {
+        let half = &mut self.halves[(self.n % 2) as usize];
+        half.0 += value;
+        half.1 += 1;
+        if self.n < kr21_trials {
+            self.first.0 += value;
+            self.first.1 += 1;
+        }
+        self.n += 1;
+        self.sum += value;
+        self.sum_squares += value * value;
+    }
+
+    fn mean (&self) -> f64 {
+        ratio(self.sum, self.n as f64)
+    }
+}
+
+fn pearson (pairs: &[(f64, f64)]) -> Option<f64> {
+    let n = pairs.len() as f64;
+    if n < 3.0 {return None};
+    let (mean_x, mean_y) = (pairs.iter().map(|p| p.0).sum::<f64>() / n, pairs.iter().map(|p| p.1).sum::<f64>() / n);
+    let (mut xy, mut xx, mut yy) = (0.0, 0.0, 0.0);
+    for (x, y) in pairs {
+        xy += (x - mean_x) * (y - mean_y);
+        xx += (x - mean_x).powi(2);
+        yy += (y - mean_y).powi(2);
+    }
+    if xx == 0.0 || yy == 0.0 {return None};
+    Some(xy / (xx * yy).sqrt())
+}
+
+/// The stabilization point from a reliability at n trials
+fn stabilization_point (reliability: f64, trials: f64) -> Option<f64> {
+    if reliability <= 0.0 || reliability >= 1.0 {return None};
+    Some(trials * (1.0 - reliability) / reliability)
+}
+
+/// Grouping, id, metric, sport_id and season
+type GroupKey = (Grouping, u32, StabilizedMetric, u32, u16);
+/// Grouping, metric, sport_id and season
+type ContextKey = (Grouping, StabilizedMetric, u32, u16);
+
+impl <'a> From<StabilizationData<'a>> for StabilizationOutput {
+    fn from (data: StabilizationData) -> StabilizationOutput {
+
+        let mut last_pitches: HashMap<(u32, u8), u16> = HashMap::new();
+        for pitch in data.pitches {
+            if pitch.pitch_result == PitchResult::NoPitch {continue};
+            let last = last_pitches.entry((pitch.game_pk, pitch.num_plate_appearance)).or_insert(pitch.pitch_num_game);
+            *last = (*last).max(pitch.pitch_num_game);
+        }
+
+        let mut trials: BTreeMap<GroupKey, Trials> = BTreeMap::new();
+        let mut names: HashMap<(Grouping, u32), String> = HashMap::new();
+
+        for pitch in data.pitches {
+            if pitch.pitch_result == PitchResult::NoPitch {continue};
+            let last = last_pitches.get(&(pitch.game_pk, pitch.num_plate_appearance)) == Some(&pitch.pitch_num_game);
+
+            let mut values: Vec<(StabilizedMetric, f64)> = vec![];
+            if pitch.swing == 1 {
+                if let Some(whiff) = pitch.swing_and_miss {values.push((StabilizedMetric::WhiffRate, whiff as f64))};
+            }
+            if taken(pitch) {
+                values.push((StabilizedMetric::CalledStrikeRate, (pitch.pitch_result == PitchResult::CalledStrike) as u8 as f64));
+            }
+            if let (1, Some (exit_velocity), Some (launch_angle)) = (pitch.in_play, pitch.hit_data_exit_velocity, pitch.hit_data_launch_angle) {
+                values.push((StabilizedMetric::BarrelRate, barrel(exit_velocity, launch_angle) as u8 as f64));
+            }
+            if let (true, Some (event)) = (last, pitch.plate_appearance_result) {
+                values.push((StabilizedMetric::StrikeoutRate, (event == Event::StrikeOut) as u8 as f64));
+                let w = data.woba_weights.get(&(pitch.sport_id, pitch.game_year)).unwrap_or(&DEFAULT_WOBA_WEIGHTS);
+                let xwoba = match event {
+                    Event::IntentionalWalk | Event::SacBunt | Event::SacrificeBuntDoublePlay | Event::CatcherInterference => None,
+                    Event::Walk => Some(w.walk as f64),
+                    Event::HitByPitch => Some(w.hit_by_pitch as f64),
+                    _ if pitch.in_play == 1 => pitch.hit_data_xwoba.map(|x| x as f64),
+                    _ => Some(0.0),
+                };
+                if let Some(xwoba) = xwoba {values.push((StabilizedMetric::Xwoba, xwoba))};
+            }
+            if values.is_empty() {continue};
+
+            let groups = [
+                (Grouping::Batter, Some(pitch.batter), Some(&pitch.batter_name)),
+                (Grouping::Pitcher, Some(pitch.pitcher), Some(&pitch.pitcher_name)),
+                (Grouping::Catcher, pitch.catcher_id, pitch.catcher_name.as_ref()),
+                (Grouping::Umpire, pitch.hp_umpire_id, pitch.hp_umpire_name.as_ref()),
+            ];
+            for (grouping, id, name) in groups {
+                let id = match id {
+                    Some (id) => id,
+                    None => continue,
+                };
+                if let Some(name) = name {
+                    names.entry((grouping, id)).or_insert_with(|| name.clone());
+                }
+                for (metric, value) in values.iter() {
+                    trials.entry((grouping, id, *metric, pitch.sport_id, pitch.game_year)).or_default().add(*value, metric.kr21_trials());
+                }
+            }
+        }
+
+        let mut contexts: BTreeMap<ContextKey, Vec<&Trials>> = BTreeMap::new();
+        for ((grouping, _, metric, sport_id, season), t) in trials.iter() {
+            contexts.entry((*grouping, *metric, *sport_id, *season)).or_default().push(t);
+        }
+
+        let mut stabilization = vec![];
+        let mut priors: HashMap<ContextKey, (Option<BetaBinomial>, Option<NormalNormal>)> = HashMap::new();
+
+        for ((grouping, metric, sport_id, season), groups) in contexts {
+            let n: f64 = groups.iter().map(|t| t.n as f64).sum();
+            let league_mean = ratio(groups.iter().map(|t| t.sum).sum(), n);
+            let within: f64 = groups.iter().map(|t| t.sum_squares - ratio(t.sum * t.sum, t.n as f64)).sum();
+            let degrees: f64 = groups.iter().map(|t| t.n.saturating_sub(1) as f64).sum();
+            let trial_variance = ratio(within, degrees);
+
+            let halves: Vec<(f64, f64)> = groups.iter()
+                .filter(|t| t.n >= MIN_SPLIT_TRIALS)
+                .map(|t| (ratio(t.halves[0].0, t.halves[0].1 as f64), ratio(t.halves[1].0, t.halves[1].1 as f64)))
+                .collect();
+            let split_half_trials = ratio(groups.iter().filter(|t| t.n >= MIN_SPLIT_TRIALS).map(|t| t.n as f64).sum(), halves.len() as f64);
+            let split_half = pearson(&halves).map(|r| 2.0 * r / (1.0 + r));
+            let split_half_stabilization = split_half.and_then(|r| stabilization_point(r, split_half_trials));
+
+            let k = metric.kr21_trials() as f64;
+            let firsts: Vec<f64> = groups.iter().filter(|t| t.first.1 == metric.kr21_trials()).map(|t| t.first.0).collect();
+            let kr21 = if metric.binomial() && firsts.len() >= 3 {
+                let mean = firsts.iter().sum::<f64>() / firsts.len() as f64;
+                let variance = firsts.iter().map(|x| (x - mean).powi(2)).sum::<f64>() / firsts.len() as f64;
+                if variance > 0.0 {Some(k / (k - 1.0) * (1.0 - mean * (k - mean) / (k * variance)))} else {None}
+            } else {
+                None
+            };
+            let kr21_stabilization = kr21.and_then(|r| stabilization_point(r, k));
+
+            let m = kr21_stabilization.or(split_half_stabilization);
+            let prior = match m {
+                Some (m) if metric.binomial() => (Some(BetaBinomial::new(league_mean, m)), None),
+                Some (m) if trial_variance > 0.0 => (None, Some(NormalNormal::new(league_mean, trial_variance, m))),
+                _ => (None, None),
+            };
+            priors.insert((grouping, metric, sport_id, season), prior);
+
+            stabilization.push(Stabilization {
+                grouping,
+                metric,
+                sport_id,
+                season,
+                groups: groups.len() as u32,
+                league_mean: league_mean as f32,
+                trial_variance: trial_variance as f32,
+                split_half_groups: halves.len() as u32,
+                split_half_trials: if halves.is_empty() {None} else {Some(split_half_trials as f32)},
+                split_half: split_half.map(|r| r as f32),
+                split_half_stabilization: split_half_stabilization.map(|m| m as f32),
+                kr21_groups: firsts.len() as u32,
+                kr21: kr21.map(|r| r as f32),
+                kr21_stabilization: kr21_stabilization.map(|m| m as f32),
+                stabilization: m.map(|m| m as f32),
+            });
+        }
+
+        let mut rates = vec![];
+        for ((grouping, id, metric, sport_id, season), t) in trials.iter() {
+            let (posterior, reliability) = match priors.get(&(*grouping, *metric, *sport_id, *season)) {
+                Some ((Some (prior), _)) => (prior.posterior(t.sum, t.n as f64), t.n as f64 / (t.n as f64 + prior.alpha + prior.beta)),
+                Some ((_, Some (prior))) => {
+                    let m = prior.trial_variance / prior.prior_variance;
+                    (prior.posterior(t.sum, t.n as f64), t.n as f64 / (t.n as f64 + m))
+                },
+                _ => continue,
+            };
+            rates.push(ShrunkRate {
+                grouping: *grouping,
+                id: *id,
+                name: names.get(&(*grouping, *id)).cloned().unwrap_or_default(),
+                metric: *metric,
+                sport_id: *sport_id,
+                season: *season,
+                trials: t.n,
+                observed: t.mean() as f32,
+                reliability: reliability as f32,
+                shrunk: posterior.mean as f32,
+                low: posterior.low as f32,
+                high: posterior.high as f32,
+            });
+        }
+
+        StabilizationOutput {
+            stabilization,
+            rates,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn beta_binomial_posterior () {
+        assert!((beta_cdf(0.3, 1.0, 1.0) - 0.3).abs() < 1e-9);
+        assert!((beta_quantile(0.5, 4.0, 4.0) - 0.5).abs() < 1e-9);
+        // Beta(2, 3) CDF is 1 - (1 - x)^3 (1 + 3x)
+        assert!((beta_cdf(0.4, 2.0, 3.0) - (1.0 - 0.6f64.powi(3) * 2.2)).abs() < 1e-9);
+
+        // 20 of 50 against a .220 league worth 70 trials
+        let prior = BetaBinomial::new(0.22, 70.0);
+        let posterior = prior.posterior(20.0, 50.0);
+        assert!((posterior.mean - (15.4 + 20.0) / 120.0).abs() < 1e-9);
+        assert!(posterior.low < posterior.mean && posterior.mean < posterior.high);
+
+        assert!(barrel(98.0, 28.0) && !barrel(98.0, 31.0) && barrel(116.0, 8.0) && barrel(116.0, 50.0) && !barrel(97.9, 28.0));
+    }
+
+    /// A one pitch plate appearance for a batter, in play unless it's a strikeout
+    fn plate_appearance (batter: u32, index: u8, event: Event, xwoba: Option<f32>) -> StabilizationPitch {
+        let in_play = (event != Event::StrikeOut) as u8;
+        StabilizationPitch {
+            game_pk: batter,
+            sport_id: 1,
+            game_year: 2024,
+            num_plate_appearance: index,
+            pitch_num_game: index as u16,
+            batter,
+            batter_name: format!("Batter {}", batter),
+            pitcher: 1,
+            pitcher_name: String::new(),
+            catcher_id: None,
+            catcher_name: None,
+            hp_umpire_id: None,
+            hp_umpire_name: None,
+            pitch_result: if in_play == 1 {PitchResult::InPlay} else {PitchResult::SwingingStrike},
+            in_play,
+            swing: 1,
+            swing_and_miss: Some(1 - in_play),
+            description: String::new(),
+            pitch_type_code: Some(PitchTypeCode::FF),
+            plate_appearance_result: Some(event),
+            hit_data_exit_velocity: None,
+            hit_data_launch_angle: None,
+            hit_data_xwoba: xwoba,
+        }
+    }
+
+    /// `strikeouts` of the even and odd plate appearances, out of `trials` in all
+    fn strikeouts (batter: u32, trials: u8, strikeouts: (u8, u8)) -> Vec<StabilizationPitch> {
+        (0 .. trials).map(|i| {
+            let half = if i % 2 == 0 {strikeouts.0} else {strikeouts.1};
+            plate_appearance(batter, i, if i / 2 < half {Event::StrikeOut} else {Event::FieldOut}, None)
+        }).collect()
+    }
+
+    fn stabilize (pitches: &[StabilizationPitch]) -> StabilizationOutput {
+        StabilizationOutput::from(StabilizationData {pitches, woba_weights: &HashMap::new()})
+    }
+
+    fn batters (output: &StabilizationOutput, metric: StabilizedMetric) -> &Stabilization {
+        output.stabilization.iter().find(|s| s.grouping == Grouping::Batter && s.metric == metric).unwrap()
+    }
+
+    #[test]
+    fn kr21_over_the_first_trials () {
+        // 10, 20, 30 and 40 strikeouts in the first 100, the first batter striking out in all 20 after that, and a batter short of 100
+        let pitches: Vec<StabilizationPitch> = [
+            strikeouts(1, 100, (5, 5)),
+            (100 .. 120).map(|i| plate_appearance(1, i, Event::StrikeOut, None)).collect(),
+            strikeouts(2, 100, (10, 10)),
+            strikeouts(3, 100, (15, 15)),
+            strikeouts(4, 100, (20, 20)),
+            strikeouts(5, 99, (50, 49)),
+        ].concat();
+        let output = stabilize(&pitches);
+        let k = batters(&output, StabilizedMetric::StrikeoutRate);
+
+        let kr21 = 100.0 / 99.0 * (1.0 - 25.0 * 75.0 / (100.0 * 125.0));
+        assert_eq!(k.groups, 5);
+        assert_eq!(k.kr21_groups, 4);
+        assert_eq!(k.kr21, Some(kr21 as f32));
+        assert_eq!(k.kr21_stabilization, Some((100.0 * (1.0 - kr21) / kr21) as f32));
+        // KR-21 wins over split half
+        assert!(k.split_half.is_some());
+        assert_eq!(k.stabilization, k.kr21_stabilization);
+    }
+
+    #[test]
+    fn split_half_steps_up_with_spearman_brown () {
+        // Halves of .1 and .2, .3 and .2, .5 and .6 correlate at sqrt(3) / 2, and a batter short of MIN_SPLIT_TRIALS
+        let pitches: Vec<StabilizationPitch> = [
+            strikeouts(1, 20, (1, 2)),
+            strikeouts(2, 20, (3, 2)),
+            strikeouts(3, 20, (5, 6)),
+            strikeouts(4, 19, (10, 9)),
+        ].concat();
+        let output = stabilize(&pitches);
+        let k = batters(&output, StabilizedMetric::StrikeoutRate);
+
+        let r = 3f64.sqrt() / 2.0;
+        let split_half = 2.0 * r / (1.0 + r);
+        assert_eq!(k.split_half_groups, 3);
+        assert_eq!(k.split_half_trials, Some(20.0));
+        assert!((k.split_half.unwrap() as f64 - split_half).abs() < 1e-6);
+        assert!((k.split_half_stabilization.unwrap() as f64 - 20.0 * (1.0 - split_half) / split_half).abs() < 1e-4);
+        assert_eq!(k.kr21, None);
+        assert_eq!(k.stabilization, k.split_half_stabilization);
+    }
+
+    #[test]
+    fn xwoba_shrinks_normal_normal () {
+        // Every other plate appearance at one of the values, with the same halves as the split half test
+        let halves = [(0.1, 0.2), (0.3, 0.2), (0.5, 0.6)];
+        let pitches: Vec<StabilizationPitch> = halves.iter().enumerate().flat_map(|(batter, (even, odd))| {
+            (0 .. 20).map(move |i| plate_appearance(batter as u32, i, Event::FieldOut, Some(if i % 2 == 0 {*even} else {*odd})))
+        }).collect();
+        let output = stabilize(&pitches);
+        let x = batters(&output, StabilizedMetric::Xwoba);
+
+        // Ten plate appearances at each value, so a group's squares within are 5 (even - odd)^2
+        let league_mean = (0.15 + 0.25 + 0.55) / 3.0;
+        let trial_variance = 5.0 * (0.01 + 0.01 + 0.01) / 57.0;
+        assert!((x.league_mean as f64 - league_mean).abs() < 1e-6);
+        assert!((x.trial_variance as f64 - trial_variance).abs() < 1e-9);
+        assert_eq!(x.kr21, None);
+        let m = x.stabilization.unwrap() as f64;
+        assert_eq!(x.stabilization, x.split_half_stabilization);
+
+        let rate = output.rates.iter().find(|r| r.grouping == Grouping::Batter && r.id == 0 && r.metric == StabilizedMetric::Xwoba).unwrap();
+        assert_eq!(rate.trials, 20);
+        assert!((rate.observed as f64 - 0.15).abs() < 1e-6);
+        assert!((rate.reliability as f64 - 20.0 / (20.0 + m)).abs() < 1e-6);
+        assert!((rate.shrunk as f64 - (20.0 * 0.15 + m * league_mean) / (20.0 + m)).abs() < 1e-6);
+        assert!((rate.high as f64 - rate.shrunk as f64 - Z_95 * (trial_variance / (20.0 + m)).sqrt()).abs() < 1e-6);
+        assert!((rate.shrunk as f64 - rate.low as f64 - Z_95 * (trial_variance / (20.0 + m)).sqrt()).abs() < 1e-6);
+    }
+
+    #[test]
+    fn automatic_calls_and_pitchouts_are_not_taken () {
+        let pitch = |index: u8, pitch_result: PitchResult, description: &str, pitch_type_code: Option<PitchTypeCode>| StabilizationPitch {
+            pitch_num_game: index as u16,
+            pitch_result,
+            in_play: 0,
+            swing: 0,
+            swing_and_miss: None,
+            description: description.to_string(),
+            pitch_type_code,
+            plate_appearance_result: None,
+            ..plate_appearance(1, 0, Event::FieldOut, None)
+        };
+        let pitches = [
+            pitch(0, PitchResult::Ball, "Ball", Some(PitchTypeCode::SL)),
+            pitch(1, PitchResult::CalledStrike, "Called Strike", Some(PitchTypeCode::FF)),
+            pitch(2, PitchResult::Ball, "Automatic Ball - Pitcher Pitch Timer Violation", None),
+            pitch(3, PitchResult::CalledStrike, "Automatic Strike - Batter Timer Violation", None),
+            pitch(4, PitchResult::Ball, "Pitchout", Some(PitchTypeCode::PO)),
+            pitch(5, PitchResult::Ball, "Intent Ball", Some(PitchTypeCode::IN)),
+        ];
+        let output = stabilize(&pitches);
+        let called = batters(&output, StabilizedMetric::CalledStrikeRate);
+        assert_eq!(called.league_mean, 0.5);
+        assert_eq!(called.trial_variance, 0.5);
+    }
+}
@@ -0,0 +1,365 @@
+//! Pitch tunneling: how alike two consecutive pitches look out of the hand and how far apart they end up.
+//!
+//! Every pair of consecutive pitches from the same pitcher in a plate appearance is compared at three points, using the trajectory
+//! from the nathan module:
+//!   * Release, each pitch at its own release point.
+//!   * The tunnel point, the decision point 23.8 ft from the plate, where the batter has to commit.
+//!   * The front of the plate.
+//!
+//! Distances are in inches. Post tunnel break is how far apart the pitches move after the tunnel point, beyond the straight lines
+//! they were on at the tunnel point. The break to tunnel ratio is that over the tunnel distance, so a high ratio is two pitches that
+//! look the same at the decision point and then split apart.
+//!
+//! The aggregates are per pitcher, sport_id, season and ordered pitch type pair, so a slider after a fastball is a different row
+//! from a fastball after a slider. The ratio in the aggregate is the mean post tunnel break over the mean tunnel distance, a pair
+//! with a tiny tunnel distance would blow up a mean of ratios.
+
+use serde::{Serialize, Deserialize};
+use std::collections::BTreeMap;
+use crate::game::PitchResult;
+use crate::linear_weights::ratio;
+use crate::nathan::{PitchTrajectory, TrajectoryPoint, DECISION_POINT};
+use crate::pitch_classification::canonical;
+use crate::play_by_play::PitchTypeCode;
+
+/// The columns from the play by play output we need for tunneling.
+#[derive(Debug, Clone, Deserialize)]
+pub struct TunnelPitch {
+    pub game_pk: u32,
+    pub sport_id: u32,
+    pub game_year: u16,
+    pub num_plate_appearance: u8,
+    pub pitch_num_plate_appearance: u8,
+    pub pitcher: u32,
+    pub pitcher_name: String,
+    pub pitch_result: PitchResult,
+    pub pitch_type_code: Option<PitchTypeCode>,
+    pub pitch_type_inferred: Option<PitchTypeCode>,
+    pub pitch_extension: Option<f64>,
+    pub pitch_x0: Option<f64>,
+    pub pitch_y0: Option<f64>,
+    pub pitch_z0: Option<f64>,
+    pub pitch_v_x0: Option<f64>,
+    pub pitch_v_y0: Option<f64>,
+    pub pitch_v_z0: Option<f64>,
+    pub pitch_a_x: Option<f64>,
+    pub pitch_a_y: Option<f64>,
+    pub pitch_a_z: Option<f64>,
+}
+
+pub struct TunnelingData <'a> {
+    pub pitches: &'a [TunnelPitch],
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TunnelPair {
+    pub game_pk: u32,
+    pub num_plate_appearance: u8,
+    /// The second pitch of the pair
+    pub pitch_num_plate_appearance: u8,
+    pub pitcher: u32,
+    pub pitcher_name: String,
+    pub sport_id: u32,
+    pub season: u16,
+    pub first_pitch_type: PitchTypeCode,
+    pub second_pitch_type: PitchTypeCode,
+    pub release_distance: f32,
+    pub tunnel_distance: f32,
+    pub plate_distance: f32,
+    pub post_tunnel_break: f32,
+    pub break_tunnel_ratio: f32,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PitcherTunnel {
+    pub pitcher: u32,
+    pub pitcher_name: String,
+    pub sport_id: u32,
+    pub season: u16,
+    pub first_pitch_type: PitchTypeCode,
+    pub second_pitch_type: PitchTypeCode,
+    pub pairs: u32,
+    pub release_distance: f32,
+    pub tunnel_distance: f32,
+    pub plate_distance: f32,
+    pub post_tunnel_break: f32,
+    pub break_tunnel_ratio: f32,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct TunnelingOutput {
+    pub pairs: Vec<TunnelPair>,
+    pub pitchers: Vec<PitcherTunnel>,
+}
+
+/// Where one pitch is at each of the points we compare, plus where it would have crossed the plate had it kept going straight
+/// from the tunnel point.
+#[derive(Debug, Copy, Clone)]
+pub (crate) struct TunnelPoints {
+    release: TrajectoryPoint,
+    tunnel: TrajectoryPoint,
+    plate: TrajectoryPoint,
+    straight: (f64, f64),
+}
+
+impl TunnelPoints {
+    pub (crate) fn new (trajectory: &PitchTrajectory, extension: f64) -> Option<Self> {
+        let release = trajectory.at_release(extension)?;
+        let tunnel = trajectory.at_decision_point(DECISION_POINT)?;
+        let plate = trajectory.at_plate()?;
+        let t = plate.t - tunnel.t;
+        Some(TunnelPoints {
+            release,
+            tunnel,
+            plate,
+            straight: (tunnel.x + tunnel.vx * t, tunnel.z + tunnel.vz * t),
+        })
+    }
+
+    fn post_tunnel_break (&self) -> (f64, f64) {
+        (self.plate.x - self.straight.0, self.plate.z - self.straight.1)
+    }
+}
+
+/// Distance between two points in the x-z plane, feet in, inches out
+fn distance (a: (f64, f64), b: (f64, f64)) -> f64 {
+    ((a.0 - b.0).powi(2) + (a.1 - b.1).powi(2)).sqrt() * 12.0
+}
+
+fn xz (point: &TrajectoryPoint) -> (f64, f64) {
+    (point.x, point.z)
+}
+
+/// release, tunnel, plate, post tunnel break
+pub (crate) fn compare (first: &TunnelPoints, second: &TunnelPoints) -> (f64, f64, f64, f64) {
+    (
+        distance(xz(&first.release), xz(&second.release)),
+        distance(xz(&first.tunnel), xz(&second.tunnel)),
+        distance(xz(&first.plate), xz(&second.plate)),
+        distance(first.post_tunnel_break(), second.post_tunnel_break()),
+    )
+}
+
+impl TunnelPitch {
+    fn pitch_type (&self) -> Option<PitchTypeCode> {
+        self.pitch_type_code.and_then(canonical).or(self.pitch_type_inferred)
+    }
+
+    fn points (&self) -> Option<TunnelPoints> {
+        let trajectory = PitchTrajectory {
+            x0: self.pitch_x0?,
+            y0: self.pitch_y0?,
+            z0: self.pitch_z0?,
+            vx0: self.pitch_v_x0?,
+            vy0: self.pitch_v_y0?,
+            vz0: self.pitch_v_z0?,
+            ax: self.pitch_a_x?,
+            ay: self.pitch_a_y?,
+            az: self.pitch_a_z?,
+        };
+        TunnelPoints::new(&trajectory, self.pitch_extension?)
+    }
+}
+
+#[derive(Debug, Default, Copy, Clone)]
+struct PairTotals {
+    pairs: u32,
+    release_distance: f64,
+    tunnel_distance: f64,
+    plate_distance: f64,
+    post_tunnel_break: f64,
+}
+
+type PairKey = (u32, u32, u16, String, String);
+
+impl <'a> From<TunnelingData<'a>> for TunnelingOutput {
+    fn from (data: TunnelingData) -> TunnelingOutput {
+
+        // Plate appearances, with the pitches in order
+        let mut plate_appearances: BTreeMap<(u32, u8), Vec<&TunnelPitch>> = BTreeMap::new();
+        for pitch in data.pitches {
+            if pitch.pitch_result == PitchResult::NoPitch {continue};
+            plate_appearances.entry((pitch.game_pk, pitch.num_plate_appearance)).or_default().push(pitch);
+        }
+
+        let mut pairs = vec![];
+        let mut totals: BTreeMap<PairKey, (&TunnelPitch, PitchTypeCode, PitchTypeCode, PairTotals)> = BTreeMap::new();
+
+        for pitches in plate_appearances.values_mut() {
+            pitches.sort_by_key(|pitch| pitch.pitch_num_plate_appearance);
+
+            for window in pitches.windows(2) {
+                let (first, second) = (window[0], window[1]);
+                if first.pitcher != second.pitcher {continue};
+                let (first_type, second_type) = match (first.pitch_type(), second.pitch_type()) {
+                    (Some (first_type), Some (second_type)) => (first_type, second_type),
+                    _ => continue,
+                };
+                let (first_points, second_points) = match (first.points(), second.points()) {
+                    (Some (first_points), Some (second_points)) => (first_points, second_points),
+                    _ => continue,
+                };
+
+                let (release, tunnel, plate, post_tunnel_break) = compare(&first_points, &second_points);
+
+                pairs.push(TunnelPair {
+                    game_pk: second.game_pk,
+                    num_plate_appearance: second.num_plate_appearance,
+                    pitch_num_plate_appearance: second.pitch_num_plate_appearance,
+                    pitcher: second.pitcher,
+                    pitcher_name: second.pitcher_name.clone(),
+                    sport_id: second.sport_id,
+                    season: second.game_year,
+                    first_pitch_type: first_type,
+                    second_pitch_type: second_type,
+                    release_distance: release as f32,
+                    tunnel_distance: tunnel as f32,
+                    plate_distance: plate as f32,
+                    post_tunnel_break: post_tunnel_break as f32,
+                    break_tunnel_ratio: ratio(post_tunnel_break, tunnel) as f32,
+                });
+
+                let key = (second.pitcher, second.sport_id, second.game_year, format!("{:?}", first_type), format!("{:?}", second_type));
+                let t = &mut totals.entry(key).or_insert((second, first_type, second_type, PairTotals::default())).3;
+                t.pairs += 1;
+                t.release_distance += release;
+                t.tunnel_distance += tunnel;
+                t.plate_distance += plate;
+                t.post_tunnel_break += post_tunnel_break;
+            }
+        }
+
+        let pitchers = totals.into_values()
+            .map(|(pitch, first_pitch_type, second_pitch_type, t)| {
+                let mean = |sum: f64| ratio(sum, t.pairs as f64) as f32;
+                PitcherTunnel {
+                    pitcher: pitch.pitcher,
+                    pitcher_name: pitch.pitcher_name.clone(),
+                    sport_id: pitch.sport_id,
+                    season: pitch.game_year,
+                    first_pitch_type,
+                    second_pitch_type,
+                    pairs: t.pairs,
+                    release_distance: mean(t.release_distance),
+                    tunnel_distance: mean(t.tunnel_distance),
+                    plate_distance: mean(t.plate_distance),
+                    post_tunnel_break: mean(t.post_tunnel_break),
+                    break_tunnel_ratio: ratio(t.post_tunnel_break, t.tunnel_distance) as f32,
+                }
+            })
+            .collect();
+
+        TunnelingOutput {
+            pairs,
+            pitchers,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fastball_slider_tunnel () {
+        let fastball = PitchTrajectory {
+            x0: -1.5, y0: 50.0, z0: 5.8,
+            vx0: 5.0, vy0: -135.0, vz0: -6.0,
+            ax: -10.0, ay: 28.0, az: -15.0,
+        };
+        // Same release and flight at first, the slider breaks the other way and drops
+        let slider = PitchTrajectory {vy0: -125.0, ax: 8.0, az: -35.0, ..fastball};
+        let fastball = TunnelPoints::new(&fastball, 6.3).unwrap();
+        let slider = TunnelPoints::new(&slider, 6.3).unwrap();
+
+        let (release, tunnel, plate, post_tunnel_break) = compare(&fastball, &fastball);
+        assert!(release.abs() < 1e-9 && tunnel.abs() < 1e-9 && plate.abs() < 1e-9 && post_tunnel_break.abs() < 1e-9);
+
+        let (release, tunnel, plate, post_tunnel_break) = compare(&fastball, &slider);
+        assert!(release < tunnel);
+        assert!(tunnel < plate);
+        assert!(post_tunnel_break > 0.0);
+    }
+
+    const FASTBALL: PitchTrajectory = PitchTrajectory {
+        x0: -1.5, y0: 50.0, z0: 5.8,
+        vx0: 5.0, vy0: -135.0, vz0: -6.0,
+        ax: -10.0, ay: 28.0, az: -15.0,
+    };
+    const SLIDER: PitchTrajectory = PitchTrajectory {vy0: -125.0, ax: 8.0, az: -35.0, ..FASTBALL};
+    const SWEEPER: PitchTrajectory = PitchTrajectory {vy0: -120.0, ax: 16.0, az: -30.0, ..FASTBALL};
+
+    fn pitch (num_plate_appearance: u8, pitch_num_plate_appearance: u8, pitcher: u32, pitch_type: PitchTypeCode, trajectory: PitchTrajectory) -> TunnelPitch {
+        TunnelPitch {
+            game_pk: 1,
+            sport_id: 1,
+            game_year: 2023,
+            num_plate_appearance,
+            pitch_num_plate_appearance,
+            pitcher,
+            pitcher_name: "".to_string(),
+            pitch_result: PitchResult::Ball,
+            pitch_type_code: Some(pitch_type),
+            pitch_type_inferred: None,
+            pitch_extension: Some(6.3),
+            pitch_x0: Some(trajectory.x0),
+            pitch_y0: Some(trajectory.y0),
+            pitch_z0: Some(trajectory.z0),
+            pitch_v_x0: Some(trajectory.vx0),
+            pitch_v_y0: Some(trajectory.vy0),
+            pitch_v_z0: Some(trajectory.vz0),
+            pitch_a_x: Some(trajectory.ax),
+            pitch_a_y: Some(trajectory.ay),
+            pitch_a_z: Some(trajectory.az),
+        }
+    }
+
+    fn compared (first: &PitchTrajectory, second: &PitchTrajectory) -> (f64, f64, f64, f64) {
+        compare(&TunnelPoints::new(first, 6.3).unwrap(), &TunnelPoints::new(second, 6.3).unwrap())
+    }
+
+    #[test]
+    fn pairs_and_pitcher_totals () {
+        let pitches = vec![
+            // Out of order, with a no pitch between the slider and the last fastball
+            pitch(1, 2, 1, PitchTypeCode::SL, SLIDER),
+            pitch(1, 1, 1, PitchTypeCode::FF, FASTBALL),
+            TunnelPitch {pitch_result: PitchResult::NoPitch, ..pitch(1, 3, 1, PitchTypeCode::CH, SWEEPER)},
+            pitch(1, 4, 1, PitchTypeCode::FF, FASTBALL),
+            // The last pitch is from a new pitcher, so it isn't paired with anything
+            pitch(2, 1, 1, PitchTypeCode::FF, FASTBALL),
+            pitch(2, 2, 1, PitchTypeCode::SL, SWEEPER),
+            pitch(2, 3, 2, PitchTypeCode::SL, SLIDER),
+        ];
+        let output: TunnelingOutput = TunnelingData {pitches: &pitches}.into();
+
+        let pairs: Vec<(u8, u8, PitchTypeCode, PitchTypeCode)> = output.pairs.iter()
+            .map(|p| (p.num_plate_appearance, p.pitch_num_plate_appearance, p.first_pitch_type, p.second_pitch_type))
+            .collect();
+        assert_eq!(pairs, vec![
+            (1, 2, PitchTypeCode::FF, PitchTypeCode::SL),
+            (1, 4, PitchTypeCode::SL, PitchTypeCode::FF),
+            (2, 2, PitchTypeCode::FF, PitchTypeCode::SL),
+        ]);
+
+        let (_, slider_tunnel, _, slider_break) = compared(&FASTBALL, &SLIDER);
+        let (_, sweeper_tunnel, _, sweeper_break) = compared(&FASTBALL, &SWEEPER);
+        assert_eq!(output.pairs[0].tunnel_distance, slider_tunnel as f32);
+        assert_eq!(output.pairs[0].break_tunnel_ratio, (slider_break / slider_tunnel) as f32);
+        assert_eq!(output.pairs[2].break_tunnel_ratio, (sweeper_break / sweeper_tunnel) as f32);
+
+        let total = |first: PitchTypeCode, second: PitchTypeCode| output.pitchers.iter()
+            .find(|t| t.first_pitch_type == first && t.second_pitch_type == second)
+            .unwrap();
+        assert_eq!(output.pitchers.len(), 2);
+        assert_eq!(total(PitchTypeCode::SL, PitchTypeCode::FF).pairs, 1);
+
+        let fastball_slider = total(PitchTypeCode::FF, PitchTypeCode::SL);
+        assert_eq!(fastball_slider.pairs, 2);
+        assert_eq!(fastball_slider.tunnel_distance, ((slider_tunnel + sweeper_tunnel) / 2.0) as f32);
+        assert_eq!(fastball_slider.post_tunnel_break, ((slider_break + sweeper_break) / 2.0) as f32);
+        // The ratio of the means, not the mean of the ratios
+        assert_eq!(fastball_slider.break_tunnel_ratio, ((slider_break + sweeper_break) / (slider_tunnel + sweeper_tunnel)) as f32);
+        assert_ne!(fastball_slider.break_tunnel_ratio, ((slider_break / slider_tunnel + sweeper_break / sweeper_tunnel) / 2.0) as f32);
+    }
+}
@@ -28,6 +28,8 @@ impl From<VenueDe> for Venue {
                     .unwrap_or("".to_string())
                     ;
         
+        let venue_elevation = v.location.elevation;
+
         let (venue_latitude, venue_longitude) = match v.location.default_coordinates {
             Some (loc) => (Some(loc.latitude), Some(loc.longitude)),
             _ => (None, None)
@@ -54,6 +56,7 @@ impl From<VenueDe> for Venue {
             venue_right: v.field_info.right,
             venue_latitude,
             venue_longitude,
+            venue_elevation,
         }
     }
 }
@@ -103,6 +106,8 @@ pub struct Venue {
     pub venue_retrosheet_id: String,
     pub venue_latitude: Option<f32>,
     pub venue_longitude: Option<f32>,
+    /// Feet above sea level
+    pub venue_elevation: Option<f32>,
 }
 
 impl Default for Venue {
@@ -129,6 +134,7 @@ impl Default for Venue {
             venue_retrosheet_id: "".to_string(),
             venue_latitude: None,
             venue_longitude: None,
+            venue_elevation: None,
         }
     }
 }
@@ -152,6 +158,7 @@ pub(crate) struct Location {
     pub(crate) state: Option<String>,
     pub(crate) state_abbrev: Option<String>,
     pub(crate) default_coordinates: Option<Coords>,
+    pub(crate) elevation: Option<f32>,
 }
 
 #[derive(Deserialize, Debug, Clone)]
@@ -299,6 +306,36 @@ pub enum RoofType {
     Indoor,
 }
 
+/// Every <polyline> in the venue's svg, in pixels, in the order they're drawn. Empty if there's no svg for the venue, None if
+/// the request failed and we don't know either way.
+pub fn get_svg_polylines (id: u32) -> Option<Vec<Vec<(f32, f32)>>> {
+
+    let link = format!("http://mlb.mlb.com/images/gameday/fields/svg/{}.svg", id);
+    let svg_data = get(link).and_then(|response| response.text()).ok()?;
+
+    if svg_data.contains("Page Not Found") {return Some(vec![])};
+
+    Some(parse_polylines(&svg_data))
+}
+
+pub (crate) fn parse_polylines (svg_data: &str) -> Vec<Vec<(f32, f32)>> {
+    svg_data
+        .split("<polyline").skip(1)
+        .map(|polyline| {
+            let points = polyline.split("points=").nth(1).unwrap_or("");
+            let points = points.trim_start_matches(['"', '\'']);
+            let end = points.find(['"', '\'']).unwrap_or(points.len());
+            points[.. end]
+                .split_whitespace()
+                .filter_map(|point| {
+                    let (x, y) = point.split_once(',')?;
+                    Some((x.parse::<f32>().ok()?, y.parse::<f32>().ok()?))
+                })
+                .collect()
+        })
+        .collect()
+}
+
 pub fn get_svg (id: u32) -> (Option<f32>, Option<f32>) {
 
     let link = format!("http://mlb.mlb.com/images/gameday/fields/svg/{}.svg", id);
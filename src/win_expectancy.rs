@@ -0,0 +1,695 @@
+//! Win expectancy (WE), win probability added (WPA) and leverage index (LI) for every pitch.
+//!
+//! Win expectancy is always the home team's chance of winning, from the state of the game: inning, half-inning, outs, base state,
+//! count and the home team's lead. We build a table for each sport_id and season from two pieces:
+//!   * A model. For each RE288 state we take the distribution of runs scored to the end of the half-inning from our own complete
+//!     half-innings, the same way as Method 1 in the run_expectancy module. Assuming both teams score from the same distributions,
+//!     we can work backwards from the end of the 9th to get the win expectancy at the start of every half-inning, and from there
+//!     the win expectancy of any state. Extra innings start from whatever base state most extra innings started from in that
+//!     league and season, which picks up the automatic runner on second.
+//!   * What actually happened. Every pitch in a game with a winner is a sample of its state. The observed home win rate is shrunk
+//!     towards the model, with the model counting as RE288_MIN_SAMPLE games. Sparse states lean on the model, states we've seen
+//!     thousands of times are almost all data.
+//!
+//! WPA is from the batting team's point of view, so a home run is always positive. Leverage index is Tom Tango's: the average
+//! swing in win expectancy over the possible results of a plate appearance from the base/out/inning/score state, divided by the
+//! average swing over every plate appearance in the league and season. The count isn't part of the leverage index, so every pitch
+//! in a plate appearance has the same LI unless the runners move.
+//!
+//! We assume every game is scheduled for 9 innings. Seven inning games are only picked up by the observed win rates.
+
+use serde::{Serialize, Deserialize};
+use std::collections::{BTreeMap, HashMap};
+use crate::play_by_play::HalfInning;
+use crate::run_expectancy::{RE288_MIN_SAMPLE, State, all_states, valid_state};
+
+/// Runs scored in the rest of a half-inning are capped here, anything more is treated as this many
+const MAX_RUNS: usize = 10;
+/// Leads bigger than this are treated as this lead
+const MAX_LEAD: i16 = 15;
+const REGULATION_INNINGS: u8 = 9;
+/// Every extra inning shares the same cells
+const EXTRA_INNINGS: u8 = REGULATION_INNINGS + 1;
+
+/// The columns from the play by play output we need to build win expectancy tables.
+#[derive(Debug, Clone, Deserialize)]
+pub struct WinExpectancyPitch {
+    pub game_pk: u32,
+    pub sport_id: u32,
+    pub game_year: u16,
+    pub half_inning: HalfInning,
+    pub num_inning: u8,
+    pub num_plate_appearance: u8,
+    pub pitch_num_game: u16,
+    pub balls_start: u8,
+    pub strikes_start: u8,
+    pub base_value_start: u8,
+    pub outs_start: u8,
+    pub base_value_end: u8,
+    pub outs_end: u8,
+    pub runs_scored: u8,
+    pub home_score_start: u8,
+    pub away_score_start: u8,
+}
+
+pub struct WinExpectancyData <'w> {
+    pub pitches: &'w [WinExpectancyPitch],
+}
+
+/// The state of the game. Three outs is the end of the half-inning.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct WinExpectancyState {
+    pub num_inning: u8,
+    pub half_inning: HalfInning,
+    pub balls: u8,
+    pub strikes: u8,
+    pub base_value: u8,
+    pub outs: u8,
+    pub home_lead: i16,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RunDistribution {
+    pub balls: u8,
+    pub strikes: u8,
+    pub base_value: u8,
+    pub outs: u8,
+    /// Probability of scoring 0, 1, 2 ... MAX_RUNS or more runs before the end of the half-inning
+    pub probabilities: Vec<f32>,
+    pub sample_size: u32,
+}
+
+/// How often a plate appearance starting in a base/out state ends in another, along with the runs that scored on the play.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PlateAppearanceOutcome {
+    pub base_value: u8,
+    pub outs: u8,
+    pub base_value_end: u8,
+    pub outs_end: u8,
+    pub runs: u8,
+    pub probability: f32,
+}
+
+/// Every time we saw a state, and how many of those games the home team won. Extra innings are all inning EXTRA_INNINGS.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WinExpectancyCell {
+    pub inning: u8,
+    pub half_inning: HalfInning,
+    pub balls: u8,
+    pub strikes: u8,
+    pub base_value: u8,
+    pub outs: u8,
+    pub home_lead: i16,
+    pub sample_size: u32,
+    pub home_wins: u32,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WinExpectancyTable {
+    pub sport_id: u32,
+    pub season: u16,
+    pub extra_inning_base_value: u8,
+    pub run_distributions: Vec<RunDistribution>,
+    pub plate_appearance_outcomes: Vec<PlateAppearanceOutcome>,
+    pub cells: Vec<WinExpectancyCell>,
+    /// The average swing in win expectancy over every plate appearance in the league and season, which is an LI of 1
+    pub average_swing: f32,
+}
+
+type CellKey = (u8, HalfInning, u8, u8, u8, u8, i16);
+/// (base_value_end, outs_end, runs, probability) for each (base_value, outs) at the start of a plate appearance
+type Outcomes = HashMap<(u8, u8), Vec<(u8, u8, u8, f64)>>;
+
+/// A win expectancy table ready for lookups, built from a WinExpectancyTable.
+#[derive(Debug, Clone)]
+pub struct WinExpectancy {
+    extra_inning_base_value: u8,
+    run_distributions: HashMap<State, Vec<f64>>,
+    plate_appearance_outcomes: Outcomes,
+    cells: HashMap<CellKey, (u32, u32)>,
+    /// The model's home win expectancy at the start of each half-inning, by (inning, half_inning, home_lead)
+    half_inning_start: HashMap<(u8, HalfInning, i16), f64>,
+    average_swing: f64,
+}
+
+impl From<&WinExpectancyTable> for WinExpectancy {
+    fn from (table: &WinExpectancyTable) -> WinExpectancy {
+
+        let mut win_expectancy = WinExpectancy {
+            extra_inning_base_value: table.extra_inning_base_value,
+            run_distributions: table.run_distributions.iter()
+                .map(|d| ((d.balls, d.strikes, d.base_value, d.outs), d.probabilities.iter().map(|p| *p as f64).collect()))
+                .collect(),
+            plate_appearance_outcomes: HashMap::new(),
+            cells: table.cells.iter()
+                .map(|c| ((c.inning, c.half_inning, c.balls, c.strikes, c.base_value, c.outs, c.home_lead), (c.sample_size, c.home_wins)))
+                .collect(),
+            half_inning_start: HashMap::new(),
+            average_swing: table.average_swing as f64,
+        };
+
+        for outcome in table.plate_appearance_outcomes.iter() {
+            win_expectancy.plate_appearance_outcomes.entry((outcome.base_value, outcome.outs)).or_default()
+                .push((outcome.base_value_end, outcome.outs_end, outcome.runs, outcome.probability as f64));
+        }
+
+        win_expectancy.solve_half_innings();
+        win_expectancy
+    }
+}
+
+impl WinExpectancy {
+
+    fn distribution (&self, state: State) -> Vec<f64> {
+        match self.run_distributions.get(&state) {
+            Some (distribution) => distribution.clone(),
+            None => {let mut scoreless = vec![0f64; MAX_RUNS + 1]; scoreless[0] = 1.0; scoreless},
+        }
+    }
+
+    fn start_distribution (&self, inning: u8) -> Vec<f64> {
+        let base_value = if inning > REGULATION_INNINGS {self.extra_inning_base_value} else {0};
+        self.distribution((0, 0, base_value, 0))
+    }
+
+    /// The model's home win expectancy once a half-inning ends with the home team leading by `home_lead`.
+    fn end_of_half_inning (&self, inning: u8, half_inning: HalfInning, home_lead: i16) -> f64 {
+        let inning = inning.min(EXTRA_INNINGS);
+        let home_lead = home_lead.clamp(-MAX_LEAD, MAX_LEAD);
+
+        match half_inning {
+            HalfInning::Top if inning >= REGULATION_INNINGS && home_lead > 0 => 1.0,
+            HalfInning::Top => self.half_inning_start[&(inning, HalfInning::Bottom, home_lead)],
+            HalfInning::Bottom if inning >= REGULATION_INNINGS && home_lead != 0 => if home_lead > 0 {1.0} else {0.0},
+            HalfInning::Bottom => self.half_inning_start[&((inning + 1).min(EXTRA_INNINGS), HalfInning::Top, home_lead)],
+        }
+    }
+
+    /// Works backwards from the end of the game to the start of each half-inning.
+    fn solve_half_innings (&mut self) {
+
+        // A tied extra inning repeats until someone scores more, so the home team wins it with chance P(bottom > top) / (1 - P(tie))
+        let extra = self.start_distribution(EXTRA_INNINGS);
+        let (mut home_ahead, mut tied) = (0f64, 0f64);
+        for (top, p_top) in extra.iter().enumerate() {
+            for (bottom, p_bottom) in extra.iter().enumerate() {
+                if bottom > top {home_ahead += p_top * p_bottom};
+                if bottom == top {tied += p_top * p_bottom};
+            }
+        }
+        let tied_extra_inning = if tied < 1.0 {home_ahead / (1.0 - tied)} else {0.5};
+        self.half_inning_start.insert((EXTRA_INNINGS, HalfInning::Top, 0), tied_extra_inning);
+
+        for inning in (1 ..= EXTRA_INNINGS).rev() {
+            let distribution = self.start_distribution(inning);
+
+            for half_inning in [HalfInning::Bottom, HalfInning::Top] {
+                for home_lead in -MAX_LEAD ..= MAX_LEAD {
+                    if inning == EXTRA_INNINGS && half_inning == HalfInning::Top && home_lead == 0 {continue};
+
+                    let win_expectancy = distribution.iter().enumerate()
+                        .map(|(runs, p)| p * self.end_of_half_inning(inning, half_inning, lead_after(half_inning, home_lead, runs as i16)))
+                        .sum();
+                    self.half_inning_start.insert((inning, half_inning, home_lead), win_expectancy);
+                }
+            }
+        }
+    }
+
+    /// The model's home win expectancy for a state
+    fn model (&self, state: WinExpectancyState) -> f64 {
+        self.distribution((state.balls, state.strikes, state.base_value, state.outs)).iter().enumerate()
+            .map(|(runs, p)| p * self.end_of_half_inning(state.num_inning, state.half_inning, lead_after(state.half_inning, state.home_lead, runs as i16)))
+            .sum()
+    }
+
+    /// The home team's win expectancy for the state.
+    pub fn win_expectancy (&self, state: WinExpectancyState) -> f32 {
+        let state = match next_state(state, self.extra_inning_base_value) {
+            Ok (state) => state,
+            Err (final_win_expectancy) => return final_win_expectancy as f32,
+        };
+
+        let home_lead = state.home_lead.clamp(-MAX_LEAD, MAX_LEAD);
+        let key = (state.num_inning.min(EXTRA_INNINGS), state.half_inning, state.balls, state.strikes, state.base_value, state.outs, home_lead);
+        let (sample_size, home_wins) = self.cells.get(&key).copied().unwrap_or((0, 0));
+
+        let prior = RE288_MIN_SAMPLE as f64;
+        ((home_wins as f64 + prior * self.model(state)) / (sample_size as f64 + prior)) as f32
+    }
+
+    /// The average absolute change in win expectancy over the results of a plate appearance from the state, ignoring the count.
+    fn swing (&self, state: WinExpectancyState) -> f64 {
+        let start = WinExpectancyState {balls: 0, strikes: 0, ..state};
+        let win_expectancy_start = self.win_expectancy(start);
+
+        let outcomes = match self.plate_appearance_outcomes.get(&(state.base_value, state.outs)) {
+            Some (outcomes) => outcomes,
+            None => return 0.0,
+        };
+
+        outcomes.iter()
+            .map(|(base_value_end, outs_end, runs, p)| {
+                let end = WinExpectancyState {
+                    base_value: *base_value_end,
+                    outs: *outs_end,
+                    home_lead: lead_after(state.half_inning, state.home_lead, *runs as i16),
+                    ..start
+                };
+                p * (self.win_expectancy(end) - win_expectancy_start).abs() as f64
+            })
+            .sum()
+    }
+
+    pub fn leverage_index (&self, state: WinExpectancyState) -> f32 {
+        match self.average_swing > 0.0 {
+            true => (self.swing(state) / self.average_swing) as f32,
+            false => 1.0,
+        }
+    }
+}
+
+fn lead_after (half_inning: HalfInning, home_lead: i16, runs: i16) -> i16 {
+    match half_inning {
+        HalfInning::Top => home_lead - runs,
+        HalfInning::Bottom => home_lead + runs,
+    }
+}
+
+/// Moves a state with three outs on to the start of the next half-inning. If the game is over, we get back the home team's
+/// final win expectancy, 1 or 0, instead.
+fn next_state (state: WinExpectancyState, extra_inning_base_value: u8) -> Result<WinExpectancyState, f64> {
+    let last_inning = state.num_inning >= REGULATION_INNINGS;
+
+    match (state.half_inning, state.outs >= 3) {
+        // Walk-off
+        (HalfInning::Bottom, _) if last_inning && state.home_lead > 0 => Err(1.0),
+        (HalfInning::Top, true) if last_inning && state.home_lead > 0 => Err(1.0),
+        (HalfInning::Bottom, true) if last_inning && state.home_lead < 0 => Err(0.0),
+        (_, false) => Ok(state),
+        (HalfInning::Top, true) => Ok(WinExpectancyState {half_inning: HalfInning::Bottom, ..start_of_inning(state, state.num_inning, extra_inning_base_value)}),
+        (HalfInning::Bottom, true) => Ok(WinExpectancyState {half_inning: HalfInning::Top, ..start_of_inning(state, state.num_inning + 1, extra_inning_base_value)}),
+    }
+}
+
+fn start_of_inning (state: WinExpectancyState, num_inning: u8, extra_inning_base_value: u8) -> WinExpectancyState {
+    WinExpectancyState {
+        num_inning,
+        half_inning: state.half_inning,
+        balls: 0,
+        strikes: 0,
+        base_value: if num_inning > REGULATION_INNINGS {extra_inning_base_value} else {0},
+        outs: 0,
+        home_lead: state.home_lead,
+    }
+}
+
+fn state (pitch: &WinExpectancyPitch) -> WinExpectancyState {
+    WinExpectancyState {
+        num_inning: pitch.num_inning,
+        half_inning: pitch.half_inning,
+        balls: pitch.balls_start,
+        strikes: pitch.strikes_start,
+        base_value: pitch.base_value_start,
+        outs: pitch.outs_start,
+        home_lead: pitch.home_score_start as i16 - pitch.away_score_start as i16,
+    }
+}
+
+type Context = (u32, u16);
+
+/// Counts of runs to the end of the half-inning for each state
+type RunCounts = HashMap<State, Vec<u32>>;
+
+fn add_runs (counts: &mut RunCounts, state: State, runs: usize) {
+    counts.entry(state).or_insert(vec![0; MAX_RUNS + 1])[runs.min(MAX_RUNS)] += 1;
+}
+
+/// All the counts of runs for the base/out state, no matter the count
+fn base_out_runs (counts: &RunCounts, state: State) -> Vec<u32> {
+    let (_, _, base_value, outs) = state;
+    counts.iter()
+        .filter(|((_, _, b, o), _)| *b == base_value && *o == outs)
+        .fold(vec![0; MAX_RUNS + 1], |acc, (_, c)| acc.iter().zip(c.iter()).map(|(a, b)| a + b).collect())
+}
+
+/// Uses the season's counts for the state if there are enough of them, then the season's counts for the base/out state, then
+/// the same two from all of our data.
+fn run_distribution (season: &RunCounts, all: &RunCounts, state: State) -> RunDistribution {
+    let sample_size = season.get(&state).map(|c| c.iter().sum()).unwrap_or(0u32);
+
+    let candidates = [
+        season.get(&state).cloned().unwrap_or_default(),
+        base_out_runs(season, state),
+        all.get(&state).cloned().unwrap_or_default(),
+        base_out_runs(all, state),
+    ];
+
+    let counts = candidates.iter()
+        .find(|c| c.iter().sum::<u32>() >= RE288_MIN_SAMPLE)
+        .or(candidates.iter().rev().find(|c| c.iter().sum::<u32>() > 0));
+
+    let probabilities = match counts {
+        Some (counts) => {
+            let total: u32 = counts.iter().sum();
+            counts.iter().map(|c| *c as f32 / total as f32).collect()
+        },
+        None => {let mut scoreless = vec![0f32; MAX_RUNS + 1]; scoreless[0] = 1.0; scoreless},
+    };
+
+    let (balls, strikes, base_value, outs) = state;
+    RunDistribution {balls, strikes, base_value, outs, probabilities, sample_size}
+}
+
+type OutcomeCounts = HashMap<(u8, u8), HashMap<(u8, u8, u8), u32>>;
+
+fn plate_appearance_outcomes (season: &OutcomeCounts, all: &OutcomeCounts) -> Vec<PlateAppearanceOutcome> {
+    let mut outcomes = vec![];
+
+    for outs in 0 ..= 2 {
+        for base_value in 0 ..= 7 {
+            let season_counts = season.get(&(base_value, outs));
+            let season_total: u32 = season_counts.map(|c| c.values().sum()).unwrap_or(0);
+
+            let counts = match season_total >= RE288_MIN_SAMPLE {
+                true => season_counts,
+                false => all.get(&(base_value, outs)).or(season_counts),
+            };
+            let counts = match counts {
+                Some (counts) => counts,
+                None => continue,
+            };
+
+            let total: u32 = counts.values().sum();
+            // Sorted so the output is always in the same order
+            let counts: BTreeMap<&(u8, u8, u8), &u32> = counts.iter().collect();
+            for ((base_value_end, outs_end, runs), count) in counts {
+                outcomes.push(
+                    PlateAppearanceOutcome {
+                        base_value,
+                        outs,
+                        base_value_end: *base_value_end,
+                        outs_end: *outs_end,
+                        runs: *runs,
+                        probability: *count as f32 / total as f32,
+                    }
+                );
+            }
+        }
+    }
+
+    outcomes
+}
+
+/// Group the pitches by key, each group sorted in the order they were thrown
+fn group_by <'p, K, F> (pitches: &[&'p WinExpectancyPitch], key: F) -> Vec<Vec<&'p WinExpectancyPitch>>
+where K: std::hash::Hash + Eq, F: Fn(&WinExpectancyPitch) -> K,
+{
+    let mut groups: HashMap<K, Vec<&WinExpectancyPitch>> = HashMap::new();
+    for pitch in pitches {
+        groups.entry(key(pitch)).or_default().push(pitch);
+    }
+    groups.into_values()
+        .map(|mut group| {group.sort_by_key(|pitch| pitch.pitch_num_game); group})
+        .collect()
+}
+
+impl <'w> From<WinExpectancyData<'w>> for Vec<WinExpectancyTable> {
+    fn from (data: WinExpectancyData) -> Vec<WinExpectancyTable> {
+
+        let all_pitches: Vec<&WinExpectancyPitch> = data.pitches.iter().collect();
+        let games = group_by(&all_pitches, |pitch| pitch.game_pk);
+
+        let mut season_runs: BTreeMap<Context, RunCounts> = BTreeMap::new();
+        let mut all_runs: RunCounts = HashMap::new();
+        let mut season_outcomes: HashMap<Context, OutcomeCounts> = HashMap::new();
+        let mut all_outcomes: OutcomeCounts = HashMap::new();
+        let mut extra_inning_starts: HashMap<Context, HashMap<u8, u32>> = HashMap::new();
+        let mut cells: HashMap<Context, HashMap<CellKey, (u32, u32)>> = HashMap::new();
+        let mut plate_appearance_starts: HashMap<Context, Vec<WinExpectancyState>> = HashMap::new();
+
+        for game in games {
+            let context = (game[0].sport_id, game[0].game_year);
+
+            for half_inning in group_by(&game, |pitch| (pitch.num_inning, pitch.half_inning)) {
+                if half_inning[0].num_inning > REGULATION_INNINGS {
+                    *extra_inning_starts.entry(context).or_default().entry(half_inning[0].base_value_start).or_insert(0) += 1;
+                }
+
+                // Walk-offs and shortened games never finish the inning, so we don't know how many runs would have scored
+                if half_inning.last().map(|pitch| pitch.outs_end).unwrap_or(0) < 3 {continue};
+
+                let mut runs_to_end = 0usize;
+                for pitch in half_inning.iter().rev() {
+                    runs_to_end += pitch.runs_scored as usize;
+                    let state = (pitch.balls_start, pitch.strikes_start, pitch.base_value_start, pitch.outs_start);
+                    if !valid_state(state) {continue};
+                    add_runs(season_runs.entry(context).or_default(), state, runs_to_end);
+                    add_runs(&mut all_runs, state, runs_to_end);
+                }
+            }
+
+            for plate_appearance in group_by(&game, |pitch| pitch.num_plate_appearance) {
+                let first = plate_appearance[0];
+                let last = plate_appearance[plate_appearance.len() - 1];
+                if valid_state((0, 0, first.base_value_start, first.outs_start)) {
+                    plate_appearance_starts.entry(context).or_default().push(state(first));
+                }
+
+                if !valid_state((0, 0, last.base_value_start, last.outs_start)) || last.base_value_end > 7 || last.outs_end > 3 {continue};
+                let base_value_end = if last.outs_end == 3 {0} else {last.base_value_end};
+                let outcome = (base_value_end, last.outs_end, last.runs_scored);
+                for counts in [season_outcomes.entry(context).or_default(), &mut all_outcomes] {
+                    *counts.entry((last.base_value_start, last.outs_start)).or_default().entry(outcome).or_insert(0) += 1;
+                }
+            }
+
+            // The final score is the score going into the last pitch plus anything that scored on it
+            let last = game[game.len() - 1];
+            let (mut home, mut away) = (last.home_score_start as u16, last.away_score_start as u16);
+            match last.half_inning {
+                HalfInning::Top => away += last.runs_scored as u16,
+                HalfInning::Bottom => home += last.runs_scored as u16,
+            }
+            // Suspended games that never finished
+            if home == away {continue};
+            let home_win = (home > away) as u32;
+
+            for pitch in game.iter() {
+                let state = state(pitch);
+                if !valid_state((state.balls, state.strikes, state.base_value, state.outs)) {continue};
+                let key = (state.num_inning.min(EXTRA_INNINGS), state.half_inning, state.balls, state.strikes, state.base_value, state.outs, state.home_lead.clamp(-MAX_LEAD, MAX_LEAD));
+                let cell = cells.entry(context).or_default().entry(key).or_insert((0, 0));
+                cell.0 += 1;
+                cell.1 += home_win;
+            }
+        }
+
+        season_runs.into_iter()
+            .map(|((sport_id, season), runs)| {
+                let context = (sport_id, season);
+
+                // Most extra innings start with the automatic runner on second these days, but not in every league and season
+                let extra_inning_base_value = extra_inning_starts.get(&context)
+                    .and_then(|starts| starts.iter().max_by_key(|(base_value, n)| (**n, std::cmp::Reverse(**base_value))).map(|(base_value, _)| *base_value))
+                    .filter(|base_value| *base_value <= 7)
+                    .unwrap_or(0);
+
+                let mut table = WinExpectancyTable {
+                    sport_id,
+                    season,
+                    extra_inning_base_value,
+                    run_distributions: all_states().into_iter().map(|state| run_distribution(&runs, &all_runs, state)).collect(),
+                    plate_appearance_outcomes: plate_appearance_outcomes(season_outcomes.get(&context).unwrap_or(&HashMap::new()), &all_outcomes),
+                    cells: cells.remove(&context).unwrap_or_default().into_iter()
+                        .map(|((inning, half_inning, balls, strikes, base_value, outs, home_lead), (sample_size, home_wins))| WinExpectancyCell {
+                            inning, half_inning, balls, strikes, base_value, outs, home_lead, sample_size, home_wins,
+                        })
+                        .collect(),
+                    average_swing: 0.0,
+                };
+
+                let win_expectancy = WinExpectancy::from(&table);
+                let starts = plate_appearance_starts.remove(&context).unwrap_or_default();
+                let total_swing: f64 = starts.iter().map(|state| win_expectancy.swing(*state)).sum();
+                table.average_swing = if starts.is_empty() {0.0} else {(total_swing / starts.len() as f64) as f32};
+
+                table
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Every half-inning scores 0 or 1 runs with even odds, no matter the state
+    fn coin_flip_table () -> WinExpectancyTable {
+        WinExpectancyTable {
+            sport_id: 1,
+            season: 2023,
+            extra_inning_base_value: 2,
+            run_distributions: all_states().into_iter().map(|(balls, strikes, base_value, outs)| {
+                let mut probabilities = vec![0f32; MAX_RUNS + 1];
+                probabilities[0] = 0.5;
+                probabilities[1] = 0.5;
+                RunDistribution {balls, strikes, base_value, outs, probabilities, sample_size: 100}
+            }).collect(),
+            plate_appearance_outcomes: vec![],
+            cells: vec![],
+            average_swing: 0.0,
+        }
+    }
+
+    fn state (num_inning: u8, half_inning: HalfInning, outs: u8, home_lead: i16) -> WinExpectancyState {
+        WinExpectancyState {num_inning, half_inning, balls: 0, strikes: 0, base_value: 0, outs, home_lead}
+    }
+
+    #[test]
+    fn coin_flip_innings () {
+        let we = WinExpectancy::from(&coin_flip_table());
+
+        // Both teams score the same way, so a tied game is a coin flip
+        assert!((we.win_expectancy(state(1, HalfInning::Top, 0, 0)) - 0.5).abs() < 1e-6);
+        // Tied in the bottom of the 9th: half the time the home team walks it off, otherwise it's a coin flip in extras
+        assert!((we.win_expectancy(state(9, HalfInning::Bottom, 0, 0)) - 0.75).abs() < 1e-6);
+        // Down 2 in the bottom of the 9th, and no inning scores more than 1
+        assert!(we.win_expectancy(state(9, HalfInning::Bottom, 0, -2)).abs() < 1e-6);
+        // Three outs in the top of the 9th with the home team ahead is the end of the game
+        assert_eq!(we.win_expectancy(state(9, HalfInning::Top, 3, 1)), 1.0);
+    }
+
+    #[test]
+    fn end_of_half_innings () {
+        // Walk-off, whatever the outs
+        assert_eq!(next_state(state(9, HalfInning::Bottom, 1, 1), 2), Err(1.0));
+        assert_eq!(next_state(state(12, HalfInning::Bottom, 0, 2), 2), Err(1.0));
+        // No need for the bottom of the 9th
+        assert_eq!(next_state(state(9, HalfInning::Top, 3, 1), 2), Err(1.0));
+        assert_eq!(next_state(state(9, HalfInning::Bottom, 3, -1), 2), Err(0.0));
+        // Leading in the bottom of an early inning isn't the end of anything
+        assert_eq!(next_state(state(5, HalfInning::Bottom, 1, 3), 2), Ok(state(5, HalfInning::Bottom, 1, 3)));
+        assert_eq!(next_state(state(5, HalfInning::Top, 3, -1), 2), Ok(state(5, HalfInning::Bottom, 0, -1)));
+        assert_eq!(next_state(state(5, HalfInning::Bottom, 3, 1), 2), Ok(state(6, HalfInning::Top, 0, 1)));
+        // Tied after 9 goes to extras, with the automatic runner
+        assert_eq!(
+            next_state(state(9, HalfInning::Bottom, 3, 0), 2),
+            Ok(WinExpectancyState {base_value: 2, ..state(10, HalfInning::Top, 0, 0)}),
+        );
+    }
+
+    #[test]
+    fn extra_inning_runner () {
+        let runner = WinExpectancyState {base_value: 3, balls: 2, strikes: 1, ..state(8, HalfInning::Top, 3, 0)};
+        assert_eq!(start_of_inning(runner, 9, 2), state(9, HalfInning::Top, 0, 0));
+        assert_eq!(start_of_inning(runner, 10, 2), WinExpectancyState {base_value: 2, ..state(10, HalfInning::Top, 0, 0)});
+        assert_eq!(start_of_inning(runner, 10, 0), state(10, HalfInning::Top, 0, 0));
+    }
+
+    #[test]
+    fn observed_cells_shrink_towards_the_model () {
+        // The model has the bottom of the 9th tied at 0.75, and we saw the home team lose all 30 times
+        let table = WinExpectancyTable {
+            cells: vec![
+                WinExpectancyCell {inning: 9, half_inning: HalfInning::Bottom, balls: 0, strikes: 0, base_value: 0, outs: 0, home_lead: 0, sample_size: 30, home_wins: 0},
+            ],
+            ..coin_flip_table()
+        };
+        let we = WinExpectancy::from(&table);
+
+        assert_eq!(we.win_expectancy(state(9, HalfInning::Bottom, 0, 0)), 0.375);
+        // Other states are all model
+        assert_eq!(we.win_expectancy(state(9, HalfInning::Bottom, 2, 0)), 0.75);
+    }
+
+    /// With two outs, the batter either hits a home run or makes the last out
+    fn home_run_or_out () -> Vec<PlateAppearanceOutcome> {
+        vec![
+            PlateAppearanceOutcome {base_value: 0, outs: 2, base_value_end: 0, outs_end: 2, runs: 1, probability: 0.5},
+            PlateAppearanceOutcome {base_value: 0, outs: 2, base_value_end: 0, outs_end: 3, runs: 0, probability: 0.5},
+        ]
+    }
+
+    #[test]
+    fn leverage_index () {
+        let table = WinExpectancyTable {plate_appearance_outcomes: home_run_or_out(), average_swing: 0.125, ..coin_flip_table()};
+        let we = WinExpectancy::from(&table);
+
+        // Tied with two outs in the bottom of the 9th is 0.75. A home run wins it, an out goes to extras at 0.5.
+        let state = state(9, HalfInning::Bottom, 2, 0);
+        assert_eq!(we.swing(state), 0.25);
+        // The count doesn't matter
+        assert_eq!(we.swing(WinExpectancyState {balls: 3, strikes: 2, ..state}), 0.25);
+        assert_eq!(we.leverage_index(state), 2.0);
+        // No outcomes from this base/out state
+        assert_eq!(we.swing(WinExpectancyState {outs: 1, ..state}), 0.0);
+
+        let table = WinExpectancyTable {average_swing: 0.0, ..table};
+        assert_eq!(WinExpectancy::from(&table).leverage_index(state), 1.0);
+    }
+
+    fn pitch (pitch_num_game: u16, num_plate_appearance: u8, num_inning: u8, half_inning: HalfInning) -> WinExpectancyPitch {
+        WinExpectancyPitch {
+            game_pk: 1,
+            sport_id: 1,
+            game_year: 2023,
+            half_inning,
+            num_inning,
+            num_plate_appearance,
+            pitch_num_game,
+            balls_start: 0,
+            strikes_start: 0,
+            base_value_start: 0,
+            outs_start: 2,
+            base_value_end: 0,
+            outs_end: 3,
+            runs_scored: 0,
+            home_score_start: 0,
+            away_score_start: 0,
+        }
+    }
+
+    #[test]
+    fn tables_from_pitches () {
+        // A one inning game the home team wins on a home run
+        let pitches = vec![
+            pitch(1, 1, 1, HalfInning::Top),
+            WinExpectancyPitch {outs_end: 2, runs_scored: 1, ..pitch(2, 2, 1, HalfInning::Bottom)},
+            WinExpectancyPitch {home_score_start: 1, ..pitch(3, 3, 1, HalfInning::Bottom)},
+        ];
+        let tables: Vec<WinExpectancyTable> = WinExpectancyData {pitches: &pitches}.into();
+        assert_eq!(tables.len(), 1);
+        let table = &tables[0];
+
+        assert_eq!(table.extra_inning_base_value, 0);
+
+        let mut cells: Vec<(HalfInning, i16, u32, u32)> = table.cells.iter()
+            .map(|c| (c.half_inning, c.home_lead, c.sample_size, c.home_wins))
+            .collect();
+        cells.sort_by_key(|(half_inning, home_lead, _, _)| (*half_inning == HalfInning::Bottom, *home_lead));
+        assert_eq!(cells, vec![(HalfInning::Top, 0, 1, 1), (HalfInning::Bottom, 0, 1, 1), (HalfInning::Bottom, 1, 1, 1)]);
+
+        // Twice no more runs with two outs, once a run
+        let two_outs = table.run_distributions.iter().find(|d| (d.balls, d.strikes, d.base_value, d.outs) == (0, 0, 0, 2)).unwrap();
+        assert_eq!(two_outs.sample_size, 3);
+        assert_eq!(two_outs.probabilities[.. 3], [2.0 / 3.0, 1.0 / 3.0, 0.0]);
+
+        let outcomes: Vec<(u8, u8, u8, f32)> = table.plate_appearance_outcomes.iter()
+            .map(|o| (o.base_value_end, o.outs_end, o.runs, o.probability))
+            .collect();
+        assert_eq!(outcomes, vec![(0, 2, 1, 1.0 / 3.0), (0, 3, 0, 2.0 / 3.0)]);
+        assert!(table.average_swing > 0.0);
+    }
+
+    #[test]
+    fn most_common_extra_inning_start () {
+        let pitches = vec![
+            WinExpectancyPitch {base_value_start: 2, ..pitch(1, 1, 10, HalfInning::Top)},
+            WinExpectancyPitch {base_value_start: 2, runs_scored: 1, ..pitch(2, 2, 10, HalfInning::Bottom)},
+            WinExpectancyPitch {game_pk: 2, ..pitch(1, 1, 10, HalfInning::Top)},
+        ];
+        let tables: Vec<WinExpectancyTable> = WinExpectancyData {pitches: &pitches}.into();
+        assert_eq!(tables[0].extra_inning_base_value, 2);
+    }
+}